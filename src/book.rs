@@ -0,0 +1,225 @@
+//Book
+//A book project: a title, an author and the sections of the book in reading order,
+//with the metadata an ebook needs (see epub.rs).
+//A book is split into parts, or straight into chapters when it has no parts, and a
+//chapter into scenes. Any section can have a file of text, usually the scenes or the
+//chapters that aren't split into scenes. The manifest, book.toml, lists them all and
+//is read and written by iomod.
+//
+//Sections can be left out of the compiled book (see compile.rs), which is set up by
+//the book's compile options, and laid out on pages by its pdf options (see pdf.rs).
+//
+//Sections are found by their path, the index among its siblings of each section on
+//the way down, e.g. [1, 0, 2] is the third scene of the first chapter of the second part.
+
+use std::path::{Path, PathBuf};
+
+use crate::compile::CompileOptions;
+use crate::pdf::PdfOptions;
+
+//name of the manifest in the folder of a book
+pub const MANIFEST : &str = "book.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Part,
+    Chapter,
+    Scene
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Book {
+    pub title: String,
+    pub author: String,
+    pub language: Option<String>,   //a language tag such as "en-GB", English when not given
+    pub identifier: Option<String>, //e.g. an ISBN or "urn:uuid:...", one is made up from the title and author when not given
+    pub cover: Option<PathBuf>,     //an image, resolved against the folder of the manifest
+    pub compile: CompileOptions,
+    pub pdf: PdfOptions,
+    pub sections: Vec<Section>  //parts, or chapters when there are no parts
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub level: Level,
+    pub title: String,
+    pub file: Option<PathBuf>,  //where its text is, resolved against the folder of the manifest
+    pub include: bool,          //false to leave it and what is in it out of the compiled book
+    pub sections: Vec<Section>  //chapters of a part, scenes of a chapter
+}
+
+impl Level {
+    //what a section at this level is split into
+    pub fn child(self) -> Option<Level> {
+        match self {
+            Level::Part => Some(Level::Chapter),
+            Level::Chapter => Some(Level::Scene),
+            Level::Scene => None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Part => "part",
+            Level::Chapter => "chapter",
+            Level::Scene => "scene"
+        }
+    }
+}
+
+impl Section {
+    pub fn new(level : Level, title : &str, file : Option<PathBuf>) -> Self {
+        Self {
+            level,
+            title: title.to_string(),
+            file,
+            include: true,
+            sections: Vec::new()
+        }
+    }
+
+    //the title, or the name of the file for a section without one
+    pub fn label(&self) -> String {
+        match (self.title.is_empty(), &self.file) {
+            (false, _) => self.title.clone(),
+            (true, Some(file)) => file.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned()),
+            (true, None) => format!("Untitled {}", self.level.name())
+        }
+    }
+}
+
+impl Book {
+    pub fn new(title : &str, author : &str) -> Self {
+        Self {
+            title: title.to_string(),
+            author: author.to_string(),
+            language: None,
+            identifier: None,
+            cover: None,
+            compile: CompileOptions::default(),
+            pdf: PdfOptions::default(),
+            sections: Vec::new()
+        }
+    }
+
+    //every section in reading order with its path, a part or chapter before what is in it
+    pub fn rows(&self) -> Vec<(Vec<usize>, &Section)> {
+        fn walk<'a>(sections : &'a [Section], path : &mut Vec<usize>, rows : &mut Vec<(Vec<usize>, &'a Section)>) {
+            for (i, section) in sections.iter().enumerate() {
+                path.push(i);
+                rows.push((path.clone(), section));
+                walk(&section.sections, path, rows);
+                path.pop();
+            }
+        }
+
+        let mut rows = Vec::new();
+        walk(&self.sections, &mut Vec::new(), &mut rows);
+        rows
+    }
+
+    //the files of the book in reading order
+    pub fn files(&self) -> Vec<&Path> {
+        self.rows().into_iter().filter_map(|(_, section)| section.file.as_deref()).collect()
+    }
+
+    pub fn section(&self, path : &[usize]) -> Option<&Section> {
+        let (last, parent) = path.split_last()?;
+        self.children(parent)?.get(*last)
+    }
+
+    //include a section in the compiled book, or leave it out. Returns whether it is now included.
+    pub fn toggle_include(&mut self, path : &[usize]) -> Option<bool> {
+        let (last, parent) = path.split_last()?;
+        self.children(parent)?.get(*last)?;
+        let section = &mut self.children_mut(parent)[*last];
+        section.include = !section.include;
+        Some(section.include)
+    }
+
+    //move a section up past the one before it. The first section of a chapter or part moves
+    //to the end of the one before its parent, e.g. the first scene of chapter 2 becomes the
+    //last scene of chapter 1. Returns its new path, None when it can't go any further.
+    pub fn move_up(&mut self, path : &[usize]) -> Option<Vec<usize>> {
+        let (last, parent) = path.split_last()?;
+        match (*last, parent.split_last()) {
+            (0, Some((0, _)) | None) => None,
+            (0, Some((parent_last, grandparent))) => {
+                let previous = [grandparent, &[parent_last - 1]].concat();
+                let end = self.children(&previous)?.len();
+                Some(self.move_section(path, &previous, end))
+            },
+            (i, _) => Some(self.move_section(path, parent, i - 1))
+        }
+    }
+
+    //move a section down past the one after it, into the start of the next part or chapter from the last place
+    pub fn move_down(&mut self, path : &[usize]) -> Option<Vec<usize>> {
+        let (last, parent) = path.split_last()?;
+        let siblings = self.children(parent)?.len();
+        match (*last + 1 < siblings, parent.split_last()) {
+            (true, _) => Some(self.move_section(path, parent, last + 2)),
+            (false, None) => None,
+            (false, Some((parent_last, grandparent))) => {
+                let next = [grandparent, &[parent_last + 1]].concat();
+                self.children(&next)?;
+                Some(self.move_section(path, &next, 0))
+            }
+        }
+    }
+
+    //move a section dropped onto another, e.g. by dragging it in the sidebar. Dropped on a section
+    //of its level it takes that section's place, going after it when dragged down and before it
+    //when dragged up. Dropped on a section of the level above, it becomes its first section.
+    pub fn drop_onto(&mut self, from : &[usize], onto : &[usize]) -> Result<Vec<usize>, String> {
+        let (moved, target) = match (self.section(from), self.section(onto)) {
+            (Some(moved), Some(target)) => (moved.level, target.level),
+            _ => return Err(String::from("There is no such section"))
+        };
+        if onto.starts_with(from) {
+            return Err(format!("A {} can't be moved into itself", moved.name()))
+        }
+
+        let (last, parent) = onto.split_last().expect("a section has a path");
+        match (target == moved, target.child() == Some(moved), from < onto) {
+            (true, _, true) => Ok(self.move_section(from, parent, last + 1)),
+            (true, _, false) => Ok(self.move_section(from, parent, *last)),
+            (false, true, _) => Ok(self.move_section(from, onto, 0)),
+            (false, false, _) => Err(format!("A {} can only be moved among {}s or into a {}", moved.name(), moved.name(), match moved {
+                Level::Part => "book",
+                Level::Chapter => "part",
+                Level::Scene => "chapter"
+            }))
+        }
+    }
+
+    //take a section out and put it back in at index among the sections of parent, counted before
+    //it was taken out. Returns its new path.
+    fn move_section(&mut self, from : &[usize], parent : &[usize], index : usize) -> Vec<usize> {
+        let (last, from_parent) = from.split_last().expect("a section has a path");
+        let section = self.children_mut(from_parent).remove(*last);
+
+        //taking it out moves the sections after it up one, including any on the way to parent
+        let mut parent = parent.to_vec();
+        let mut index = index;
+        match parent.len() == from_parent.len() && parent.starts_with(from_parent) {
+            true if index > *last => index -= 1,
+            true => {},
+            false => if parent.len() > from_parent.len() && parent.starts_with(from_parent) && parent[from_parent.len()] > *last {
+                parent[from_parent.len()] -= 1;
+            }
+        }
+
+        self.children_mut(&parent).insert(index, section);
+        [parent.as_slice(), &[index]].concat()
+    }
+
+    //the sections in the section at path, the book's own for an empty path
+    fn children(&self, path : &[usize]) -> Option<&Vec<Section>> {
+        path.iter().try_fold(&self.sections, |sections, i| sections.get(*i).map(|section| &section.sections))
+    }
+
+    fn children_mut(&mut self, path : &[usize]) -> &mut Vec<Section> {
+        path.iter().fold(&mut self.sections, |sections, i| &mut sections[*i].sections)
+    }
+}
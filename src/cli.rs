@@ -0,0 +1,260 @@
+//Command line
+//Subcommands that work on a file without opening a window, for scripts:
+//
+//  garbanzo_book stats FILE                        word, char and paragraph counts
+//  garbanzo_book export --format html FILE         writes FILE.html, or --output PATH
+//  garbanzo_book compile --format txt BOOK         writes the book in book.toml, or the folder
+//                                                  it is in, to book.txt next to it or --output PATH
+//  garbanzo_book lint FILE                         see lint.rs
+//  garbanzo_book convert --eol lf --encoding utf-8 FILE
+//
+//Each prints one line of JSON on stdout, {"error": "..."} when it fails. The exit
+//code is 0 on success, 1 when a file could not be read or written, 2 for bad
+//arguments and 3 when lint found problems.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde_json::{json, Value};
+
+use crate::book::{Level, MANIFEST};
+use crate::compile::compile;
+use crate::doc_handler::DocHandler;
+use crate::export::{self, Block, Document, Format};
+use crate::iomod::{self, Encoding, LineEnding};
+use crate::lint::lint;
+use crate::status::count_words;
+
+pub const EXIT_OK : i32 = 0;
+pub const EXIT_FAILED : i32 = 1;
+pub const EXIT_USAGE : i32 = 2;
+pub const EXIT_PROBLEMS : i32 = 3;
+
+const USAGE : &str = "usage: garbanzo_book stats FILE | export --format txt|html|epub|pdf [--output PATH] FILE | compile [--format txt|html|epub|pdf] [--output PATH] BOOK | lint FILE | convert [--eol lf|crlf] [--encoding ENCODING] FILE";
+
+enum CliError {
+    Usage(String),
+    Failed(String)
+}
+
+//the file and --options given to a subcommand
+struct Args {
+    file: PathBuf,
+    options: HashMap<String, String>
+}
+
+//run the subcommand in args (without the program name), print its JSON and return
+//the exit code, None when the first arg is not a subcommand so the editor should open
+pub fn run(args : &[String]) -> Option<i32> {
+    let (output, code) = execute(args)?;
+
+    if code == EXIT_USAGE {
+        eprintln!("{}", USAGE);
+    }
+    println!("{}", output);
+    Some(code)
+}
+
+//as run, but the JSON is returned instead of printed
+pub fn execute(args : &[String]) -> Option<(Value, i32)> {
+    let (name, rest) = args.split_first()?;
+
+    let result = match name.as_str() {
+        "stats" => parse(rest, &[]).and_then(|args| stats(&args)),
+        "export" => parse(rest, &["format", "output"]).and_then(|args| export(&args)),
+        "compile" => parse(rest, &["format", "output"]).and_then(|args| compile_book(&args)),
+        "lint" => parse(rest, &[]).and_then(|args| check(&args)),
+        "convert" => parse(rest, &["eol", "encoding"]).and_then(|args| convert(&args)),
+        _ => return None
+    };
+
+    Some(match result {
+        Ok(done) => done,
+        Err(CliError::Usage(e)) => (json!({ "error": e }), EXIT_USAGE),
+        Err(CliError::Failed(e)) => (json!({ "error": e }), EXIT_FAILED)
+    })
+}
+
+//one FILE and any of the allowed --name VALUE (or --name=VALUE) options
+fn parse(args : &[String], allowed : &[&str]) -> Result<Args, CliError> {
+    let mut file = None;
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let option = match arg.strip_prefix("--") {
+            Some(option) => option,
+            None => match file.replace(PathBuf::from(arg)) {
+                None => continue,
+                Some(_) => return Err(CliError::Usage(format!("unexpected argument \"{}\", only one file can be given", arg)))
+            }
+        };
+
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (option, args.next().cloned())
+        };
+        if !allowed.contains(&name) {
+            return Err(CliError::Usage(format!("unknown option \"--{}\"", name)))
+        }
+        match value {
+            Some(value) => options.insert(name.to_string(), value),
+            None => return Err(CliError::Usage(format!("--{} needs a value", name)))
+        };
+    }
+
+    match file {
+        Some(file) => Ok(Args { file, options }),
+        None => Err(CliError::Usage(String::from("no file given")))
+    }
+}
+
+fn open(path : &PathBuf) -> Result<DocHandler, CliError> {
+    DocHandler::open(path).map_err(|e| CliError::Failed(format!("could not read {}: {}", path.display(), e)))
+}
+
+//counts for the whole file, paragraphs are separated by blank lines
+fn stats(args : &Args) -> Result<(Value, i32), CliError> {
+    let doc = open(&args.file)?;
+
+    let paragraphs = doc.text.iter()
+        .enumerate()
+        .filter(|(y, line)| !line.trim().is_empty() && (*y == 0 || doc.text[y - 1].trim().is_empty()))
+        .count();
+    let words : usize = doc.text.iter().map(|line| count_words(line)).sum();
+    let chars : usize = doc.text.iter().map(|line| line.chars().count()).sum();
+    let spaces : usize = doc.text.iter().map(|line| line.chars().filter(|c| c.is_whitespace()).count()).sum();
+
+    Ok((json!({
+        "file": args.file,
+        "lines": doc.text.len(),
+        "paragraphs": paragraphs,
+        "words": words,
+        "chars": chars,
+        "chars_without_spaces": chars - spaces,
+        "line_ending": doc.format.line_ending.to_string(),
+        "encoding": doc.format.encoding.to_string()
+    }), EXIT_OK))
+}
+
+//write the file in another format, next to it unless --output is given
+fn export(args : &Args) -> Result<(Value, i32), CliError> {
+    let format = match args.options.get("format") {
+        Some(format) => format.parse::<Format>().map_err(CliError::Usage)?,
+        None => return Err(CliError::Usage(String::from("--format is needed")))
+    };
+    let output = match args.options.get("output") {
+        Some(output) => PathBuf::from(output),
+        None => args.file.with_extension(format.extension())
+    };
+    if output == args.file {
+        return Err(CliError::Usage(format!("{} would be overwritten, give another --output", output.display())))
+    }
+
+    let doc = open(&args.file)?;
+    let title = args.file.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let bytes = export::export(&Document::from_text(&doc.text, &title), format);
+
+    std::fs::write(&output, &bytes).map_err(|e| CliError::Failed(format!("could not write {}: {}", output.display(), e)))?;
+
+    Ok((json!({
+        "file": args.file,
+        "format": format.to_string(),
+        "output": output,
+        "bytes": bytes.len()
+    }), EXIT_OK))
+}
+
+//compile a book into one file, plain text unless --format is given
+fn compile_book(args : &Args) -> Result<(Value, i32), CliError> {
+    let format = args.options.get("format").map(|format| format.parse::<Format>()).transpose().map_err(CliError::Usage)?.unwrap_or(Format::Text);
+    let manifest = match args.file.is_dir() {
+        true => args.file.join(MANIFEST),
+        false => args.file.clone()
+    };
+    let output = match args.options.get("output") {
+        Some(output) => PathBuf::from(output),
+        None => manifest.with_extension(format.extension())
+    };
+    if output == manifest {
+        return Err(CliError::Usage(format!("{} would be overwritten, give another --output", output.display())))
+    }
+
+    let book = iomod::load_manifest(&manifest).map_err(|e| CliError::Failed(format!("{}: {}", manifest.display(), e)))?;
+    let document = compile(&book).map_err(|e| CliError::Failed(e.to_string()))?;
+    let bytes = export::export(&document, format);
+
+    std::fs::write(&output, &bytes).map_err(|e| CliError::Failed(format!("could not write {}: {}", output.display(), e)))?;
+
+    let chapters = document.blocks.iter().filter(|block| matches!(block, Block::Heading(Level::Chapter, _))).count();
+    let words : usize = document.blocks.iter()
+        .filter_map(|block| match block {
+            Block::Paragraph(lines) => Some(lines.iter().map(|line| count_words(line)).sum::<usize>()),
+            _ => None
+        })
+        .sum();
+
+    Ok((json!({
+        "book": manifest,
+        "format": format.to_string(),
+        "output": output,
+        "bytes": bytes.len(),
+        "chapters": chapters,
+        "words": words
+    }), EXIT_OK))
+}
+
+//lines and columns in the output count from 1, as in the status bar
+fn check(args : &Args) -> Result<(Value, i32), CliError> {
+    let doc = open(&args.file)?;
+    let problems = lint(&doc.text);
+
+    let listed : Vec<Value> = problems.iter()
+        .map(|problem| json!({
+            "line": problem.line + 1,
+            "column": problem.column + 1,
+            "rule": problem.rule,
+            "message": problem.message
+        }))
+        .collect();
+    let code = match problems.is_empty() {
+        true => EXIT_OK,
+        false => EXIT_PROBLEMS
+    };
+
+    Ok((json!({ "file": args.file, "problems": listed }), code))
+}
+
+//rewrite the file with another line ending and/or encoding. Refuses to save in an
+//encoding that would lose chars.
+fn convert(args : &Args) -> Result<(Value, i32), CliError> {
+    let line_ending = args.options.get("eol").map(|eol| eol.parse::<LineEnding>()).transpose().map_err(CliError::Usage)?;
+    let encoding = args.options.get("encoding").map(|encoding| encoding.parse::<Encoding>()).transpose().map_err(CliError::Usage)?;
+    if line_ending.is_none() && encoding.is_none() {
+        return Err(CliError::Usage(String::from("--eol or --encoding is needed")))
+    }
+
+    let mut doc = open(&args.file)?;
+    let from = doc.format;
+    doc.format.line_ending = line_ending.unwrap_or(from.line_ending);
+    doc.format.encoding = encoding.unwrap_or(from.encoding);
+
+    let lost = doc.text.iter().flat_map(|line| line.chars()).filter(|c| !doc.format.encoding.can_encode(*c)).count();
+    match lost {
+        0 => {},
+        1 => return Err(CliError::Failed(format!("1 char cannot be stored in {}", doc.format.encoding))),
+        n => return Err(CliError::Failed(format!("{} chars cannot be stored in {}", n, doc.format.encoding)))
+    };
+
+    let changed = doc.format != from;
+    if changed {
+        doc.save().map_err(|e| CliError::Failed(format!("could not write {}: {}", args.file.display(), e)))?;
+    }
+
+    Ok((json!({
+        "file": args.file,
+        "line_ending": doc.format.line_ending.to_string(),
+        "encoding": doc.format.encoding.to_string(),
+        "changed": changed
+    }), EXIT_OK))
+}
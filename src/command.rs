@@ -0,0 +1,479 @@
+//Editor commands
+//Everything the user can do to a doc is described by an EditorCommand, so input
+//handling (see keymap.rs) only decides *which* command to run and dispatch() is
+//the one place that knows how to apply a command to a DocHandler.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::doc_handler::DocHandler;
+use crate::search::SearchOption;
+
+//Where a cursor movement (or the range of a deletion) ends up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordLeft,
+    WordRight,
+    SentenceBack,
+    SentenceForward,
+    ParagraphUp,
+    ParagraphDown,
+    LineStart,
+    LineEnd,
+    DocStart,
+    DocEnd
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorCommand {
+    InsertChar(char),
+    InsertNewline,
+    Move(Motion),
+    Delete(Motion), //delete from the cursor to wherever the motion would land
+    SetMark,        //start a selection at the cursor
+    Cancel,         //drop the selection
+    //kill ring commands, used by the emacs profile (see emacs.rs)
+    Kill(Motion),
+    KillLine,
+    KillRegion,
+    CopyRegion,
+    Yank,
+    YankPop,
+    //keyboard macros, see macros.rs. A None name plays the last recorded macro.
+    RecordMacro,    //start recording, or stop if already recording
+    PlayMacro(Option<String>, Repeat),
+    SaveMacro(String), //keep the last recorded macro under a name
+    //the find bar, see find.rs
+    Find,
+    FindNext,
+    FindPrevious,
+    CloseFind,
+    ToggleSearchOption(SearchOption),
+    FindInProject,  //open the find bar searching every file in the project
+    Replace,        //open the find bar with the replace field
+    ReplaceNext,    //replace the match at the cursor and go to the next one
+    ReplaceAll,
+    SwitchFindField, //type into the other field of the find bar
+    //display settings, see settings.rs
+    CycleLineNumbers, //line numbers off, absolute, relative
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    //open docs, see tabs.rs
+    NewTab,
+    NextTab,
+    PreviousTab,
+    CloseTab,
+    CloseAll,
+    SaveAll,
+    //split panes, see panes.rs. Horizontal puts the new pane below, vertical beside
+    SplitHorizontal,
+    SplitVertical,
+    NextPane,
+    ClosePane,
+    //the project sidebar, see sidebar.rs
+    ToggleSidebar,
+    FocusSidebar,   //move the keyboard to the sidebar, or back to the text
+    OpenSection,    //open the file of the section picked in the sidebar
+    MoveSectionUp,
+    MoveSectionDown,
+    ToggleInclude,  //leave the section out of the compiled book, or put it back
+    Save,
+    ExportPdf,      //write the doc, or the book when there is one, as a pdf next to it for printing
+    Undo,
+    Redo,
+    Quit
+}
+
+//How many times a macro is played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Times(usize),
+    UntilFailure    //until a command fails or has nothing left to do
+}
+
+//What happened when a command was dispatched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Applied,        //the text or the cursor changed
+    Unchanged,      //nothing to do, e.g. moving left at the start of the doc
+    Quit,           //the editor should close
+    Open(PathBuf, (usize, usize)), //the editor should open a file at a (line, column)
+    Notice(String), //the command was carried out, with something to tell the user, e.g. where a macro was saved
+    Failed(String)  //the command could not be carried out
+}
+
+impl Motion {
+    pub const ALL : [Motion; 14] = [
+        Motion::Left,
+        Motion::Right,
+        Motion::Up,
+        Motion::Down,
+        Motion::WordLeft,
+        Motion::WordRight,
+        Motion::SentenceBack,
+        Motion::SentenceForward,
+        Motion::ParagraphUp,
+        Motion::ParagraphDown,
+        Motion::LineStart,
+        Motion::LineEnd,
+        Motion::DocStart,
+        Motion::DocEnd
+    ];
+
+    //name used for the motion in config files, e.g. "word_left"
+    pub fn name(self) -> &'static str {
+        match self {
+            Motion::Left => "left",
+            Motion::Right => "right",
+            Motion::Up => "up",
+            Motion::Down => "down",
+            Motion::WordLeft => "word_left",
+            Motion::WordRight => "word_right",
+            Motion::SentenceBack => "sentence_back",
+            Motion::SentenceForward => "sentence_forward",
+            Motion::ParagraphUp => "paragraph_up",
+            Motion::ParagraphDown => "paragraph_down",
+            Motion::LineStart => "line_start",
+            Motion::LineEnd => "line_end",
+            Motion::DocStart => "doc_start",
+            Motion::DocEnd => "doc_end"
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<Motion> {
+        Motion::ALL.into_iter().find(|motion| motion.name() == name)
+    }
+
+    //(line, column) the cursor would land on if the motion was applied to doc
+    pub fn target(self, doc : &DocHandler) -> (usize, usize) {
+        let last_line = doc.text.len() - 1;
+
+        match self {
+            Motion::Left => doc.left_pos(),
+            Motion::Right => doc.right_pos(),
+            Motion::Up => doc.up_pos(),
+            Motion::Down => doc.down_pos(),
+            Motion::WordLeft => doc.word_left_pos(),
+            Motion::WordRight => doc.word_right_pos(),
+            Motion::SentenceBack => doc.sentence_back_pos(),
+            Motion::SentenceForward => doc.sentence_forward_pos(),
+            Motion::ParagraphUp => doc.paragraph_up_pos(),
+            Motion::ParagraphDown => doc.paragraph_down_pos(),
+            Motion::LineStart => (doc.ptr_y, 0),
+            Motion::LineEnd => (doc.ptr_y, doc.line_len(doc.ptr_y)),
+            Motion::DocStart => (0, 0),
+            Motion::DocEnd => (last_line, doc.line_len(last_line))
+        }
+    }
+}
+
+//Commands are named in config files as "save", "undo", "move_word_left",
+//"delete_left", "insert:—", "macro:tidy:3" and so on
+impl fmt::Display for EditorCommand {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EditorCommand::InsertChar(c) => write!(f, "insert:{}", c),
+            EditorCommand::InsertNewline => write!(f, "insert_newline"),
+            EditorCommand::Move(motion) => write!(f, "move_{}", motion.name()),
+            EditorCommand::Delete(motion) => write!(f, "delete_{}", motion.name()),
+            EditorCommand::SetMark => write!(f, "set_mark"),
+            EditorCommand::Cancel => write!(f, "cancel"),
+            EditorCommand::Kill(motion) => write!(f, "kill_{}", motion.name()),
+            EditorCommand::KillLine => write!(f, "kill_line"),
+            EditorCommand::KillRegion => write!(f, "kill_region"),
+            EditorCommand::CopyRegion => write!(f, "copy_region"),
+            EditorCommand::Yank => write!(f, "yank"),
+            EditorCommand::YankPop => write!(f, "yank_pop"),
+            EditorCommand::RecordMacro => write!(f, "record_macro"),
+            EditorCommand::PlayMacro(name, repeat) => {
+                match name {
+                    Some(name) => write!(f, "macro:{}", name)?,
+                    None => write!(f, "play_macro")?
+                };
+                match repeat {
+                    Repeat::Times(1) => Ok(()),
+                    Repeat::Times(n) => write!(f, ":{}", n),
+                    Repeat::UntilFailure => write!(f, ":until_fail")
+                }
+            },
+            EditorCommand::SaveMacro(name) => write!(f, "save_macro:{}", name),
+            EditorCommand::Find => write!(f, "find"),
+            EditorCommand::FindNext => write!(f, "find_next"),
+            EditorCommand::FindPrevious => write!(f, "find_previous"),
+            EditorCommand::CloseFind => write!(f, "close_find"),
+            EditorCommand::ToggleSearchOption(option) => write!(f, "toggle_{}", option.name()),
+            EditorCommand::FindInProject => write!(f, "find_in_project"),
+            EditorCommand::Replace => write!(f, "replace"),
+            EditorCommand::ReplaceNext => write!(f, "replace_next"),
+            EditorCommand::ReplaceAll => write!(f, "replace_all"),
+            EditorCommand::SwitchFindField => write!(f, "switch_find_field"),
+            EditorCommand::CycleLineNumbers => write!(f, "cycle_line_numbers"),
+            EditorCommand::ZoomIn => write!(f, "zoom_in"),
+            EditorCommand::ZoomOut => write!(f, "zoom_out"),
+            EditorCommand::ZoomReset => write!(f, "zoom_reset"),
+            EditorCommand::NewTab => write!(f, "new_tab"),
+            EditorCommand::NextTab => write!(f, "next_tab"),
+            EditorCommand::PreviousTab => write!(f, "previous_tab"),
+            EditorCommand::CloseTab => write!(f, "close_tab"),
+            EditorCommand::CloseAll => write!(f, "close_all"),
+            EditorCommand::SaveAll => write!(f, "save_all"),
+            EditorCommand::SplitHorizontal => write!(f, "split_horizontal"),
+            EditorCommand::SplitVertical => write!(f, "split_vertical"),
+            EditorCommand::NextPane => write!(f, "next_pane"),
+            EditorCommand::ClosePane => write!(f, "close_pane"),
+            EditorCommand::ToggleSidebar => write!(f, "toggle_sidebar"),
+            EditorCommand::FocusSidebar => write!(f, "focus_sidebar"),
+            EditorCommand::OpenSection => write!(f, "open_section"),
+            EditorCommand::MoveSectionUp => write!(f, "move_section_up"),
+            EditorCommand::MoveSectionDown => write!(f, "move_section_down"),
+            EditorCommand::ToggleInclude => write!(f, "toggle_include"),
+            EditorCommand::ExportPdf => write!(f, "export_pdf"),
+            EditorCommand::Save => write!(f, "save"),
+            EditorCommand::Undo => write!(f, "undo"),
+            EditorCommand::Redo => write!(f, "redo"),
+            EditorCommand::Quit => write!(f, "quit")
+        }
+    }
+}
+
+impl FromStr for EditorCommand {
+    type Err = String;
+
+    fn from_str(name : &str) -> Result<Self, Self::Err> {
+        let command = match name {
+            "insert_newline" => Some(EditorCommand::InsertNewline),
+            "set_mark" => Some(EditorCommand::SetMark),
+            "cancel" => Some(EditorCommand::Cancel),
+            "kill_line" => Some(EditorCommand::KillLine),
+            "kill_region" => Some(EditorCommand::KillRegion),
+            "copy_region" => Some(EditorCommand::CopyRegion),
+            "yank" => Some(EditorCommand::Yank),
+            "yank_pop" => Some(EditorCommand::YankPop),
+            "record_macro" => Some(EditorCommand::RecordMacro),
+            "play_macro" => Some(EditorCommand::PlayMacro(None, Repeat::Times(1))),
+            "find" => Some(EditorCommand::Find),
+            "find_next" => Some(EditorCommand::FindNext),
+            "find_previous" => Some(EditorCommand::FindPrevious),
+            "close_find" => Some(EditorCommand::CloseFind),
+            "find_in_project" => Some(EditorCommand::FindInProject),
+            "replace" => Some(EditorCommand::Replace),
+            "replace_next" => Some(EditorCommand::ReplaceNext),
+            "replace_all" => Some(EditorCommand::ReplaceAll),
+            "switch_find_field" => Some(EditorCommand::SwitchFindField),
+            "cycle_line_numbers" => Some(EditorCommand::CycleLineNumbers),
+            "zoom_in" => Some(EditorCommand::ZoomIn),
+            "zoom_out" => Some(EditorCommand::ZoomOut),
+            "zoom_reset" => Some(EditorCommand::ZoomReset),
+            "new_tab" => Some(EditorCommand::NewTab),
+            "next_tab" => Some(EditorCommand::NextTab),
+            "previous_tab" => Some(EditorCommand::PreviousTab),
+            "close_tab" => Some(EditorCommand::CloseTab),
+            "close_all" => Some(EditorCommand::CloseAll),
+            "save_all" => Some(EditorCommand::SaveAll),
+            "split_horizontal" => Some(EditorCommand::SplitHorizontal),
+            "split_vertical" => Some(EditorCommand::SplitVertical),
+            "next_pane" => Some(EditorCommand::NextPane),
+            "close_pane" => Some(EditorCommand::ClosePane),
+            "toggle_sidebar" => Some(EditorCommand::ToggleSidebar),
+            "focus_sidebar" => Some(EditorCommand::FocusSidebar),
+            "open_section" => Some(EditorCommand::OpenSection),
+            "move_section_up" => Some(EditorCommand::MoveSectionUp),
+            "move_section_down" => Some(EditorCommand::MoveSectionDown),
+            "toggle_include" => Some(EditorCommand::ToggleInclude),
+            "export_pdf" => Some(EditorCommand::ExportPdf),
+            "save" => Some(EditorCommand::Save),
+            "undo" => Some(EditorCommand::Undo),
+            "redo" => Some(EditorCommand::Redo),
+            "quit" => Some(EditorCommand::Quit),
+            _ => None
+        };
+
+        if let Some(command) = command {
+            return Ok(command)
+        }
+
+        if let Some(text) = name.strip_prefix("insert:") {
+            let mut chars = text.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(EditorCommand::InsertChar(c)),
+                _ => Err(format!("\"{}\" must insert exactly one char", name))
+            }
+        }
+
+        if let Some(repeat) = name.strip_prefix("play_macro:") {
+            return Ok(EditorCommand::PlayMacro(None, parse_repeat(repeat)?))
+        }
+
+        if let Some(text) = name.strip_prefix("macro:") {
+            let (macro_name, repeat) = match text.split_once(':') {
+                Some((macro_name, repeat)) => (macro_name, parse_repeat(repeat)?),
+                None => (text, Repeat::Times(1))
+            };
+            return Ok(EditorCommand::PlayMacro(Some(check_macro_name(macro_name)?), repeat))
+        }
+
+        if let Some(macro_name) = name.strip_prefix("save_macro:") {
+            return Ok(EditorCommand::SaveMacro(check_macro_name(macro_name)?))
+        }
+
+        let motion = name.strip_prefix("move_").and_then(Motion::from_name).map(EditorCommand::Move);
+        let delete = name.strip_prefix("delete_").and_then(Motion::from_name).map(EditorCommand::Delete);
+        let kill = name.strip_prefix("kill_").and_then(Motion::from_name).map(EditorCommand::Kill);
+        let toggle = name.strip_prefix("toggle_").and_then(SearchOption::from_name).map(EditorCommand::ToggleSearchOption);
+
+        motion.or(delete).or(kill).or(toggle).ok_or_else(|| format!("unknown command \"{}\"", name))
+    }
+}
+
+//"3" or "until_fail" after the macro name in "macro:tidy:3"
+fn parse_repeat(text : &str) -> Result<Repeat, String> {
+    match text {
+        "until_fail" => Ok(Repeat::UntilFailure),
+        _ => match text.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(Repeat::Times(n)),
+            _ => Err(format!("\"{}\" is not a repeat count, use a number or until_fail", text))
+        }
+    }
+}
+
+//macro names end up in command names, so keep them to letters, digits, _ and -
+pub fn check_macro_name(name : &str) -> Result<String, String> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+
+    match valid {
+        true => Ok(name.to_string()),
+        false => Err(format!("\"{}\" is not a valid macro name, use letters, digits, _ and -", name))
+    }
+}
+
+//every command name accepted by from_str, except the insert:<char>, macro:<name>
+//and save_macro:<name> families
+pub fn command_names() -> Vec<String> {
+    let mut names : Vec<String> = [
+        "insert_newline", "set_mark", "cancel", "kill_line", "kill_region", "copy_region",
+        "yank", "yank_pop", "record_macro", "play_macro", "play_macro:until_fail",
+        "find", "find_next", "find_previous", "close_find", "find_in_project", "replace", "replace_next", "replace_all",
+        "switch_find_field", "cycle_line_numbers", "zoom_in", "zoom_out", "zoom_reset",
+        "new_tab", "next_tab", "previous_tab", "close_tab", "close_all", "save_all",
+        "split_horizontal", "split_vertical", "next_pane", "close_pane",
+        "toggle_sidebar", "focus_sidebar", "open_section", "move_section_up", "move_section_down", "toggle_include", "save", "export_pdf", "undo", "redo", "quit"
+    ].iter().map(|name| name.to_string()).collect();
+
+    for option in SearchOption::ALL {
+        names.push(EditorCommand::ToggleSearchOption(option).to_string());
+    }
+
+    for motion in Motion::ALL {
+        names.push(EditorCommand::Move(motion).to_string());
+        names.push(EditorCommand::Delete(motion).to_string());
+        names.push(EditorCommand::Kill(motion).to_string());
+    }
+
+    names
+}
+
+//apply a command to the doc
+pub fn dispatch(doc : &mut DocHandler, command : &EditorCommand) -> Outcome {
+    let before = (doc.revision, doc.ptr_y, doc.ptr_x);
+
+    match command {
+        EditorCommand::InsertChar(c) => {
+            //typed chars share an undo step until the end of the word
+            doc.checkpoint(true);
+            doc.update(*c);
+            if c.is_whitespace() {
+                doc.break_coalescing();
+            }
+        },
+
+        EditorCommand::InsertNewline => {
+            doc.checkpoint(false);
+            doc.newline();
+        },
+
+        EditorCommand::Move(motion) => {
+            let (y, x) = motion.target(doc);
+            doc.set_cursor(y, x);
+            doc.break_coalescing();
+        },
+
+        EditorCommand::Delete(motion) => {
+            let target = motion.target(doc);
+            if target != (doc.ptr_y, doc.ptr_x) {
+                doc.checkpoint(false);
+                doc.delete_range((doc.ptr_y, doc.ptr_x), target);
+            }
+        },
+
+        EditorCommand::SetMark => doc.anchor = Some((doc.ptr_y, doc.ptr_x)),
+
+        EditorCommand::Cancel => {
+            return match doc.anchor.take() {
+                Some(_) => Outcome::Applied,
+                None => Outcome::Unchanged
+            }
+        },
+
+        EditorCommand::Kill(_) | EditorCommand::KillLine | EditorCommand::KillRegion |
+        EditorCommand::CopyRegion | EditorCommand::Yank | EditorCommand::YankPop => {
+            return Outcome::Failed(format!("{} needs the kill ring of the emacs profile", command))
+        },
+
+        EditorCommand::RecordMacro | EditorCommand::PlayMacro(..) | EditorCommand::SaveMacro(_) => {
+            return Outcome::Failed(format!("{} needs the macro recorder", command))
+        },
+
+        EditorCommand::Find | EditorCommand::FindNext | EditorCommand::FindPrevious |
+        EditorCommand::CloseFind | EditorCommand::ToggleSearchOption(_) | EditorCommand::FindInProject | EditorCommand::Replace |
+        EditorCommand::ReplaceNext | EditorCommand::ReplaceAll | EditorCommand::SwitchFindField => {
+            return Outcome::Failed(format!("{} needs the find bar", command))
+        },
+
+        EditorCommand::CycleLineNumbers | EditorCommand::ZoomIn | EditorCommand::ZoomOut | EditorCommand::ZoomReset => {
+            return Outcome::Failed(format!("{} needs the settings", command))
+        },
+
+        EditorCommand::NewTab | EditorCommand::NextTab | EditorCommand::PreviousTab |
+        EditorCommand::CloseTab | EditorCommand::CloseAll | EditorCommand::SaveAll => {
+            return Outcome::Failed(format!("{} needs the tabs", command))
+        },
+
+        EditorCommand::SplitHorizontal | EditorCommand::SplitVertical | EditorCommand::NextPane | EditorCommand::ClosePane => {
+            return Outcome::Failed(format!("{} needs the panes", command))
+        },
+
+        EditorCommand::ToggleSidebar | EditorCommand::FocusSidebar | EditorCommand::OpenSection |
+        EditorCommand::MoveSectionUp | EditorCommand::MoveSectionDown | EditorCommand::ToggleInclude | EditorCommand::ExportPdf => {
+            return Outcome::Failed(format!("{} needs the sidebar", command))
+        },
+
+        EditorCommand::Save => {
+            return match doc.save() {
+                Ok(_) => Outcome::Notice(format!("Saved {}", doc.name())),
+                Err(e) => Outcome::Failed(format!("Save failed: {}", e))
+            }
+        },
+
+        EditorCommand::Undo => { doc.undo(); },
+
+        EditorCommand::Redo => { doc.redo(); },
+
+        EditorCommand::Quit => return Outcome::Quit
+    };
+
+    //the selection does not survive an edit
+    if doc.revision != before.0 {
+        doc.anchor = None;
+    }
+
+    match before == (doc.revision, doc.ptr_y, doc.ptr_x) && *command != EditorCommand::SetMark {
+        true => Outcome::Unchanged,
+        false => Outcome::Applied
+    }
+}
@@ -0,0 +1,211 @@
+//Compile
+//Turns a book (see book.rs) into one document for export.rs to write out: the front
+//matter, then the text of every section in manifest order with a heading for each
+//part and chapter and a scene separator between the scenes of a chapter. Sections
+//that aren't included are left out along with everything in them, and aren't counted
+//when numbering the headings. The book's cover image is read in too.
+//
+//The options are the [compile] table of book.toml, every one is optional, e.g.
+//
+//  [compile]
+//  part_heading = "Part {roman}: {title}"  # {number}, {roman} and {title} are filled in
+//  chapter_heading = "Chapter {number}"    # an empty heading leaves it out
+//  scene_separator = "#"                   # empty for a rule
+//  title_page = true
+//  dedication = "For Ann"                  # \n starts a new line
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::book::{Book, Level, Section};
+use crate::epub;
+use crate::export::{self, Block, Cover, Document};
+use crate::iomod;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompileOptions {
+    pub part_heading: String,
+    pub chapter_heading: String,
+    pub scene_separator: String,
+    pub title_page: bool,
+    pub dedication: Option<String>
+}
+
+//a file of the book that could not be read, or a cover that isn't an image
+#[derive(Debug)]
+pub struct CompileError {
+    pub path: PathBuf,
+    pub error: io::Error
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not read {}: {}", self.path.display(), self.error)
+    }
+}
+
+impl Error for CompileError {}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            part_heading: String::from("Part {number}: {title}"),
+            chapter_heading: String::from("Chapter {number}: {title}"),
+            scene_separator: String::from("#"),
+            title_page: true,
+            dedication: None
+        }
+    }
+}
+
+impl CompileOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+//the blocks compiled so far and how many parts and chapters have been headed
+struct Compiler<'a> {
+    options: &'a CompileOptions,
+    blocks: Vec<Block>,
+    parts: usize,
+    chapters: usize
+}
+
+//the book's included sections in manifest order, reading their files from disk
+pub fn compile(book : &Book) -> Result<Document, CompileError> {
+    let options = &book.compile;
+    let mut compiler = Compiler { options, blocks: Vec::new(), parts: 0, chapters: 0 };
+
+    if options.title_page {
+        compiler.blocks.push(Block::TitlePage);
+    }
+    if let Some(dedication) = options.dedication.as_ref().filter(|dedication| !dedication.trim().is_empty()) {
+        compiler.blocks.push(Block::Dedication(dedication.lines().map(|line| line.trim().to_string()).collect()));
+    }
+
+    for section in &book.sections {
+        compiler.section(section)?;
+    }
+
+    Ok(Document {
+        title: book.title.clone(),
+        author: book.author.clone(),
+        language: book.language.clone().filter(|language| !language.trim().is_empty()).unwrap_or_else(|| String::from("en")),
+        identifier: book.identifier.clone().filter(|identifier| !identifier.trim().is_empty()).unwrap_or_else(|| export::identifier(&book.title, &book.author)),
+        cover: book.cover.as_deref().map(cover).transpose()?,
+        blocks: compiler.blocks,
+        scene_break: Some(options.scene_separator.clone()).filter(|separator| !separator.is_empty()),
+        pdf: book.pdf.clone()
+    })
+}
+
+impl Compiler<'_> {
+    //a section's heading, its own text then the sections in it
+    fn section(&mut self, section : &Section) -> Result<(), CompileError> {
+        if !section.include {
+            return Ok(())
+        }
+
+        match section.level {
+            Level::Part => {
+                self.parts += 1;
+                self.heading(Level::Part, &self.options.part_heading, self.parts, &section.title);
+            },
+            Level::Chapter => {
+                self.chapters += 1;
+                self.heading(Level::Chapter, &self.options.chapter_heading, self.chapters, &section.title);
+            },
+            Level::Scene => {}
+        }
+
+        if let Some(file) = &section.file {
+            self.text(file)?;
+        }
+        for inner in &section.sections {
+            self.section(inner)?;
+        }
+        Ok(())
+    }
+
+    fn heading(&mut self, level : Level, format : &str, number : usize, title : &str) {
+        let format = match title.trim().is_empty() {
+            true => untitled(format),
+            false => format.to_string()
+        };
+        let heading = format
+            .replace("{number}", &number.to_string())
+            .replace("{roman}", &roman(number))
+            .replace("{title}", title);
+
+        if !heading.trim().is_empty() {
+            self.blocks.push(Block::Heading(level, heading));
+        }
+    }
+
+    //the paragraphs of a file, after a scene separator when they follow other text without a heading between
+    fn text(&mut self, file : &Path) -> Result<(), CompileError> {
+        let (text, _) = iomod::load_file_txt(&file.to_path_buf()).map_err(|error| CompileError { path: file.to_path_buf(), error })?;
+        let blocks = export::blocks(&text);
+
+        let follows_text = matches!(self.blocks.last(), Some(Block::Paragraph(_)));
+        let starts_with_break = matches!(blocks.first(), Some(Block::SceneBreak));
+        if follows_text && !starts_with_break && !blocks.is_empty() {
+            self.blocks.push(Block::SceneBreak);
+        }
+
+        self.blocks.extend(blocks);
+        Ok(())
+    }
+}
+
+//a heading format for a section without a title, without what separates the title from
+//the number, e.g. "Chapter {number}: {title}" becomes "Chapter {number}{title}" and
+//"{title} - Part {roman}" becomes "{title}Part {roman}"
+fn untitled(format : &str) -> String {
+    const TITLE : &str = "{title}";
+    const NUMBERS : [&str; 2] = ["{number}", "{roman}"];
+
+    let title = match format.find(TITLE) {
+        Some(title) => title,
+        None => return format.to_string()
+    };
+    let after = title + TITLE.len();
+
+    match NUMBERS.iter().filter_map(|number| format[..title].rfind(number).map(|i| i + number.len())).max() {
+        Some(number) => format!("{}{}", &format[..number], &format[title..]),
+        None => format!("{}{}", &format[..after], format[after..].trim_start_matches([' ', ':', '-', '.', ',']))
+    }
+}
+
+fn cover(path : &Path) -> Result<Cover, CompileError> {
+    let (extension, media_type) = epub::image_type(path).ok_or_else(|| CompileError {
+        path: path.to_path_buf(),
+        error: io::Error::new(io::ErrorKind::InvalidInput, "a cover has to be a jpeg, png, gif, svg or webp image")
+    })?;
+    let data = std::fs::read(path).map_err(|error| CompileError { path: path.to_path_buf(), error })?;
+    Ok(Cover { extension, media_type, data })
+}
+
+//a number in upper case roman numerals, e.g. XIV
+pub fn roman(number : usize) -> String {
+    const NUMERALS : [(usize, &str); 13] = [
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+        (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I")
+    ];
+
+    let mut number = number;
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+    roman
+}
@@ -0,0 +1,231 @@
+//CPU renderer
+//Draws frames in software into a tiny-skia pixmap, for machines without a usable
+//graphics adapter, and copies them to the window with softbuffer. Glyphs are
+//rasterised from the same fonts and positions as on the GPU, so the two look
+//alike apart from antialiasing.
+
+use std::num::{NonZeroIsize, NonZeroU32};
+use std::ptr::NonNull;
+
+use raw_window_handle::{
+    AppKitDisplayHandle, AppKitWindowHandle, DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle,
+    RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle, Win32WindowHandle,
+    WindowHandle, WindowsDisplayHandle, XcbDisplayHandle, XcbWindowHandle, XlibDisplayHandle, XlibWindowHandle
+};
+use raw_window_handle_05 as rwh_05;
+use rwh_05::{HasRawDisplayHandle, HasRawWindowHandle};
+use softbuffer::{Context, Surface};
+use tiny_skia::{Paint, Pixmap, Transform};
+use wgpu_glyph::ab_glyph::{point, Font, FontArc, GlyphId, ScaleFont};
+use winit::dpi::PhysicalSize;
+use winit::window::Window;
+
+use crate::fonts::FontChain;
+use crate::renderer::{Frame, RenderError, Renderer, TextLine};
+use crate::theme::Color;
+
+//the window's handles in the form softbuffer takes them
+#[derive(Clone, Copy)]
+struct Handles {
+    window: RawWindowHandle,
+    display: RawDisplayHandle
+}
+
+enum Target {
+    Window(Surface<Handles, Handles>),
+    Offscreen
+}
+
+pub struct CpuRenderer {
+    target: Target,
+    pixmap: Pixmap,
+    fonts: Vec<FontArc>
+}
+
+impl CpuRenderer {
+    //None when the window system is not one softbuffer can draw to
+    pub fn new(window : &Window, fonts : &FontChain) -> Option<Self> {
+        let handles = Handles::of(window)?;
+        let context = Context::new(handles).ok()?;
+        let surface = Surface::new(&context, handles).ok()?;
+
+        let size = window.inner_size();
+        Some(Self {
+            target: Target::Window(surface),
+            pixmap: Pixmap::new(size.width.max(1), size.height.max(1))?,
+            fonts: fonts.fonts.clone()
+        })
+    }
+
+    //draw into a pixmap without a window, for tests
+    pub fn headless(size : PhysicalSize<u32>, fonts : &FontChain) -> Self {
+        Self {
+            target: Target::Offscreen,
+            pixmap: Pixmap::new(size.width.max(1), size.height.max(1)).expect("pixmap size is not zero"),
+            fonts: fonts.fonts.clone()
+        }
+    }
+
+    //blend a glyph's coverage of each pixel into the pixmap, inside the line's clip
+    fn draw_text(&mut self, line : &TextLine) {
+        let width = self.pixmap.width() as i32;
+        let (left, top, right, bottom) = match line.clip {
+            Some(clip) => clip.pixels(self.pixmap.width(), self.pixmap.height()),
+            None => (0, 0, self.pixmap.width(), self.pixmap.height())
+        };
+        let (left, top, right, bottom) = (left as i32, top as i32, right as i32, bottom as i32);
+        let (scale, color) = (line.scale, line.color);
+        let mut x = line.x;
+
+        for (run, font) in &line.runs {
+            let font = &self.fonts[*font];
+            let scaled = font.as_scaled(scale);
+            let baseline = line.y + scaled.ascent();
+            let mut previous : Option<GlyphId> = None;
+
+            for c in run.chars() {
+                let id = scaled.glyph_id(c);
+                if let Some(previous) = previous {
+                    x += scaled.kern(previous, id);
+                }
+                previous = Some(id);
+
+                let glyph = id.with_scale_and_position(scale, point(x, baseline));
+                x += scaled.h_advance(id);
+
+                let outlined = match font.outline_glyph(glyph) {
+                    Some(outlined) => outlined,
+                    None => continue
+                };
+                let bounds = outlined.px_bounds();
+                let data = self.pixmap.data_mut();
+
+                outlined.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x as i32 + gx as i32;
+                    let py = bounds.min.y as i32 + gy as i32;
+                    if px < left || py < top || px >= right || py >= bottom {
+                        return
+                    }
+
+                    let i = (py * width + px) as usize * 4;
+                    let alpha = coverage.min(1.0) * color[3];
+                    for channel in 0..3 {
+                        let dst = data[i + channel] as f32;
+                        data[i + channel] = (dst + (color[channel] * 255.0 - dst) * alpha).round() as u8;
+                    }
+                    let dst = data[i + 3] as f32;
+                    data[i + 3] = (dst + (255.0 - dst) * alpha).round() as u8;
+                });
+            }
+        }
+    }
+}
+
+impl Renderer for CpuRenderer {
+    fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.pixmap.width(), self.pixmap.height())
+    }
+
+    fn resize(&mut self, size : PhysicalSize<u32>) {
+        if let Some(pixmap) = Pixmap::new(size.width, size.height) {
+            self.pixmap = pixmap;
+        }
+    }
+
+    fn render(&mut self, frame : &Frame) -> Result<(), RenderError> {
+        self.pixmap.fill(skia_color(frame.background));
+
+        let mut paint = Paint::default();
+        for rect in &frame.rects {
+            if let Some(area) = tiny_skia::Rect::from_xywh(rect.x, rect.y, rect.w, rect.h) {
+                paint.set_color(skia_color(rect.color));
+                self.pixmap.fill_rect(area, &paint, Transform::identity(), None);
+            }
+        }
+
+        for line in &frame.lines {
+            self.draw_text(line);
+        }
+
+        //softbuffer takes 0RGB pixels
+        if let Target::Window(surface) = &mut self.target {
+            let error = |e : softbuffer::SoftBufferError| RenderError::Other(e.to_string());
+            let (width, height) = match (NonZeroU32::new(self.pixmap.width()), NonZeroU32::new(self.pixmap.height())) {
+                (Some(width), Some(height)) => (width, height),
+                _ => return Ok(())
+            };
+            surface.resize(width, height).map_err(error)?;
+
+            let mut buffer = surface.buffer_mut().map_err(error)?;
+            for (pixel, rgba) in buffer.iter_mut().zip(self.pixmap.data().chunks_exact(4)) {
+                *pixel = (rgba[0] as u32) << 16 | (rgba[1] as u32) << 8 | rgba[2] as u32;
+            }
+            buffer.present().map_err(error)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_pixels(&self) -> Option<Vec<u8>> {
+        match self.target {
+            Target::Offscreen => Some(self.pixmap.data().to_vec()),
+            Target::Window(_) => None
+        }
+    }
+}
+
+impl Handles {
+    //winit 0.27 gives 0.5 handles, the window systems softbuffer supports are converted
+    fn of(window : &Window) -> Option<Self> {
+        let window_handle = match window.raw_window_handle() {
+            rwh_05::RawWindowHandle::Xlib(handle) => {
+                let mut converted = XlibWindowHandle::new(handle.window);
+                converted.visual_id = handle.visual_id;
+                RawWindowHandle::Xlib(converted)
+            },
+            rwh_05::RawWindowHandle::Xcb(handle) => {
+                let mut converted = XcbWindowHandle::new(NonZeroU32::new(handle.window)?);
+                converted.visual_id = NonZeroU32::new(handle.visual_id);
+                RawWindowHandle::Xcb(converted)
+            },
+            rwh_05::RawWindowHandle::Wayland(handle) => RawWindowHandle::Wayland(WaylandWindowHandle::new(NonNull::new(handle.surface)?)),
+            rwh_05::RawWindowHandle::Win32(handle) => {
+                let mut converted = Win32WindowHandle::new(NonZeroIsize::new(handle.hwnd as isize)?);
+                converted.hinstance = NonZeroIsize::new(handle.hinstance as isize);
+                RawWindowHandle::Win32(converted)
+            },
+            rwh_05::RawWindowHandle::AppKit(handle) => RawWindowHandle::AppKit(AppKitWindowHandle::new(NonNull::new(handle.ns_view)?)),
+            _ => return None
+        };
+
+        let display_handle = match window.raw_display_handle() {
+            rwh_05::RawDisplayHandle::Xlib(handle) => RawDisplayHandle::Xlib(XlibDisplayHandle::new(NonNull::new(handle.display), handle.screen)),
+            rwh_05::RawDisplayHandle::Xcb(handle) => RawDisplayHandle::Xcb(XcbDisplayHandle::new(NonNull::new(handle.connection), handle.screen)),
+            rwh_05::RawDisplayHandle::Wayland(handle) => RawDisplayHandle::Wayland(WaylandDisplayHandle::new(NonNull::new(handle.display)?)),
+            rwh_05::RawDisplayHandle::Windows(_) => RawDisplayHandle::Windows(WindowsDisplayHandle::new()),
+            rwh_05::RawDisplayHandle::AppKit(_) => RawDisplayHandle::AppKit(AppKitDisplayHandle::new()),
+            _ => return None
+        };
+
+        Some(Self { window: window_handle, display: display_handle })
+    }
+}
+
+//# Safety
+//The handles are of the window Screen owns, which drops the renderer before the window
+impl HasWindowHandle for Handles {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        Ok(unsafe { WindowHandle::borrow_raw(self.window) })
+    }
+}
+
+impl HasDisplayHandle for Handles {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        Ok(unsafe { DisplayHandle::borrow_raw(self.display) })
+    }
+}
+
+fn skia_color(color : Color) -> tiny_skia::Color {
+    let [r, g, b, a] = color.map(|channel| channel.clamp(0.0, 1.0));
+    tiny_skia::Color::from_rgba(r, g, b, a).unwrap_or(tiny_skia::Color::BLACK)
+}
@@ -1,87 +1,246 @@
-use std::env;
-use std::path::Path;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 //modules
-use crate::iomod;
+use crate::iomod::{self, FileFormat};
+
+//max number of undo steps kept, each one is a full copy of the text
+const UNDO_LIMIT : usize = 200;
+
+//id of the next doc created
+static NEXT_ID : AtomicU64 = AtomicU64::new(0);
 
 pub struct DocHandler {
     pub text: Vec<String>,     //text contents of doc
     pub ptr_x: usize, //increases left going to right
-    pub ptr_y: usize  //increases top going to bottom
+    pub ptr_y: usize, //increases top going to bottom
+    pub path: Option<PathBuf>, //where the doc is saved, None for a new unsaved doc
+    pub id: u64,        //different for every doc, so with the revision it tells which text something was worked out from
+    pub revision: u64,  //bumped on every edit so callers can tell when the text changed
+    pub format: FileFormat, //line ending and encoding the doc is saved with
+    pub anchor: Option<(usize, usize)>, //other end of the selection from the cursor, None when nothing is selected
+    saved_revision: u64,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
+    coalescing: bool    //true while consecutive typed chars share one undo step
+}
+
+//copy of the doc taken before an edit so it can be restored by undo/redo
+struct Snapshot {
+    text: Vec<String>,
+    ptr_x: usize,
+    ptr_y: usize
 }
 
 impl DocHandler {
     //for initializing a completely new doc
     pub fn new() -> Self {
-        let mut text : Vec<String> = vec![String::new()];
-        let mut ptr_x : usize = 0;
-        let mut ptr_y : usize = 0;
+        let text : Vec<String> = vec![String::new()];
+        let ptr_x : usize = 0;
+        let ptr_y : usize = 0;
 
         Self {
             text,
             ptr_x,
-            ptr_y
+            ptr_y,
+            path: None,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            revision: 0,
+            format: FileFormat::default(),
+            anchor: None,
+            saved_revision: 0,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            coalescing: false
         }
     }//end new def
 
     //for loading in data from an existing .txt
     pub fn load(path : &PathBuf) -> Self {
-        //read in text
-        let mut text : Vec<String> = match iomod::load_file_txt(&path) {
-            Ok(text) => text,
+        match Self::open(path) {
+            Ok(doc) => doc,
             Err(e) => panic!("Failed to load {:?} due to {:?}", path, e)
-        };
+        }
+    }//end load def
+
+    //as load, but an unreadable file is an error rather than a panic
+    pub fn open(path : &PathBuf) -> Result<Self, io::Error> {
+        //read in text
+        let (text, format) = iomod::load_file_txt(path)?;
 
         let num_lines = text.len() - 1;
 
         //default cursor to last position in doc
-        let mut ptr_x = text[num_lines].chars().count(); //cannot use String.len() as it returns number of bytes 
-        let mut ptr_y = num_lines;
+        let ptr_x = text[num_lines].chars().count(); //cannot use String.len() as it returns number of bytes
+        let ptr_y = num_lines;
 
-        Self {
+        Ok(Self {
             text,
             ptr_x,
-            ptr_y
+            ptr_y,
+            path: Some(path.clone()),
+            format,
+            ..Self::new()
+        })
+
+    }//end open def
+
+    //as open, but a file that isn't there yet is a new empty doc that saves to it
+    pub fn open_or_new(path : &PathBuf) -> Result<Self, io::Error> {
+        match Self::open(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self {
+                path: Some(path.clone()),
+                ..Self::new()
+            }),
+            result => result
         }
+    }//end open_or_new def
 
-    }//end load def
+    //for building a doc from a string, lines are split on \n
+    pub fn from_text(text : &str) -> Self {
+        Self {
+            text: text.split('\n').map(String::from).collect(),
+            ..Self::new()
+        }
+    }//end from_text def
+
+    //write the doc back to the path it was loaded from
+    pub fn save(&mut self) -> Result<(), io::Error> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "document has no path to save to"))
+        };
+
+        iomod::save_file_txt(path, &self.text, self.format)?;
+        self.saved_revision = self.revision;
+        Ok(())
+    }//end save def
+
+    //the file name, untitled if there is none
+    pub fn name(&self) -> String {
+        self.path.as_deref().and_then(Path::file_name).map_or(String::from("untitled"), |name| name.to_string_lossy().into_owned())
+    }
+
+    //true if there are edits that have not been saved
+    pub fn is_dirty(&self) -> bool {
+        self.revision != self.saved_revision
+    }
+
+    //record the current state as an undo step before an edit.
+    //With coalesce set, runs of calls (e.g. typing a word) share a single step.
+    pub fn checkpoint(&mut self, coalesce : bool) {
+        if coalesce && self.coalescing {
+            return
+        }
+
+        self.undo_stack.push(self.snapshot());
+        if self.undo_stack.len() > UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.redo_stack.clear();
+        self.coalescing = coalesce;
+    }
+
+    //stop the current run of coalesced edits, called when the cursor moves
+    pub fn break_coalescing(&mut self) {
+        self.coalescing = false;
+    }
+
+    //returns false if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.redo_stack.push(current);
+                self.restore(snapshot);
+                true
+            },
+            None => false
+        }
+    }
+
+    //returns false if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(snapshot) => {
+                let current = self.snapshot();
+                self.undo_stack.push(current);
+                self.restore(snapshot);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            text: self.text.clone(),
+            ptr_x: self.ptr_x,
+            ptr_y: self.ptr_y
+        }
+    }
+
+    fn restore(&mut self, snapshot : Snapshot) {
+        self.text = snapshot.text;
+        self.ptr_x = snapshot.ptr_x;
+        self.ptr_y = snapshot.ptr_y;
+        self.coalescing = false;
+        self.revision += 1;
+    }
 
     pub fn backspace(&mut self) {
         let line = self.text[self.ptr_y].clone();
         let line = remove_char(&line, self.ptr_x);
-        
+
         match (self.ptr_x, self.ptr_y) {
             (0, 0) => {}, //do nothing
 
             (0, _) => {   //append to line above
                 self.ptr_y -= 1;
                 self.ptr_x = self.text[self.ptr_y].chars().count();
-                self.text[self.ptr_y].push_str(&line); 
+                self.text[self.ptr_y].push_str(&line);
                 self.text.remove(self.ptr_y + 1);
-            }, 
+                self.revision += 1;
+            },
 
             (_, _) => {   //update line
                 self.ptr_x -= 1;
-                self.text[self.ptr_y] = String::from(line);
-            } 
+                self.text[self.ptr_y] = line;
+                self.revision += 1;
+            }
         };
 
     }//end backspace
 
+    //forward delete, the mirror of backspace
+    pub fn delete(&mut self) {
+        let end = match self.ptr_x < self.line_len(self.ptr_y) {
+            true => (self.ptr_y, self.ptr_x + 1),
+            false if self.ptr_y + 1 < self.text.len() => (self.ptr_y + 1, 0),
+            false => return //end of doc, do nothing
+        };
+
+        self.delete_range((self.ptr_y, self.ptr_x), end);
+    }//end delete
+
     pub fn update(&mut self, x : char) {
         let line = self.text[self.ptr_y].clone();
         let line = add_char(&line, self.ptr_x, x);
         self.text[self.ptr_y] = line;
         self.ptr_x += 1;
+        self.revision += 1;
     }
 
     pub fn newline(&mut self) {
-        let line = &self.text[self.ptr_y][self.ptr_x..];
-        self.text.insert(self.ptr_y + 1, String::from(line) );
-        self.text[self.ptr_y].truncate(self.ptr_x);
+        let split = byte_index(&self.text[self.ptr_y], self.ptr_x);
+        let line = self.text[self.ptr_y].split_off(split);
+        self.text.insert(self.ptr_y + 1, line);
         self.ptr_y += 1;
         self.ptr_x = 0;
+        self.revision += 1;
     }
 
     pub fn update_cursor(&mut self, code : &str) {//todo add functionality to keep pressing right or left onto a new line above or below
@@ -97,28 +256,373 @@ impl DocHandler {
         };
 
         if self.ptr_y > self.text.len() - 1 {
-            self.ptr_y = self.text.len() - 1 
+            self.ptr_y = self.text.len() - 1
         };
 
         if self.ptr_x > self.text[self.ptr_y].chars().count() {
             self.ptr_x = self.text[self.ptr_y].chars().count()
         };
     }
+
+    //one char to the left, wrapping onto the end of the line above
+    pub fn left_pos(&self) -> (usize, usize) {
+        match (self.ptr_x, self.ptr_y) {
+            (0, 0) => (0, 0),
+            (0, y) => (y - 1, self.line_len(y - 1)),
+            (x, y) => (y, x - 1)
+        }
+    }
+
+    //one char to the right, wrapping onto the start of the line below
+    pub fn right_pos(&self) -> (usize, usize) {
+        let (y, x) = (self.ptr_y, self.ptr_x);
+
+        match x < self.line_len(y) {
+            true => (y, x + 1),
+            false if y + 1 < self.text.len() => (y + 1, 0),
+            false => (y, x)
+        }
+    }
+
+    //same column on the line above, clamped to its length
+    pub fn up_pos(&self) -> (usize, usize) {
+        match self.ptr_y {
+            0 => (0, self.ptr_x),
+            y => (y - 1, self.ptr_x.min(self.line_len(y - 1)))
+        }
+    }
+
+    //same column on the line below, clamped to its length
+    pub fn down_pos(&self) -> (usize, usize) {
+        let y = (self.ptr_y + 1).min(self.text.len() - 1);
+        (y, self.ptr_x.min(self.line_len(y)))
+    }
+
+    //Word, sentence and paragraph motions
+    //Each motion has a *_pos() fn returning the (line, column) it would land on
+    //so the same target can be used for both moving and deleting.
+
+    //ctrl + left
+    pub fn word_left(&mut self) {
+        let (y, x) = self.word_left_pos();
+        self.set_cursor(y, x);
+    }
+
+    //ctrl + right
+    pub fn word_right(&mut self) {
+        let (y, x) = self.word_right_pos();
+        self.set_cursor(y, x);
+    }
+
+    //ctrl + backspace
+    pub fn delete_word_back(&mut self) {
+        let start = self.word_left_pos();
+        self.delete_range(start, (self.ptr_y, self.ptr_x));
+    }
+
+    //ctrl + delete
+    pub fn delete_word_forward(&mut self) {
+        let end = self.word_right_pos();
+        self.delete_range((self.ptr_y, self.ptr_x), end);
+    }
+
+    //alt + left
+    pub fn sentence_back(&mut self) {
+        let (y, x) = self.sentence_back_pos();
+        self.set_cursor(y, x);
+    }
+
+    //alt + right
+    pub fn sentence_forward(&mut self) {
+        let (y, x) = self.sentence_forward_pos();
+        self.set_cursor(y, x);
+    }
+
+    //alt + up
+    pub fn paragraph_up(&mut self) {
+        let (y, x) = self.paragraph_up_pos();
+        self.set_cursor(y, x);
+    }
+
+    //alt + down
+    pub fn paragraph_down(&mut self) {
+        let (y, x) = self.paragraph_down_pos();
+        self.set_cursor(y, x);
+    }
+
+    //start of the nearest word before the cursor, falling back to the start of
+    //the line and then to the end of the line above
+    pub fn word_left_pos(&self) -> (usize, usize) {
+        let (y, x) = (self.ptr_y, self.ptr_x);
+
+        match word_spans(&self.text[y]).iter().rev().find(|(start, _)| *start < x) {
+            Some((start, _)) => (y, *start),
+            None if x > 0 => (y, 0),
+            None if y > 0 => (y - 1, self.line_len(y - 1)),
+            None => (y, x)
+        }
+    }
+
+    //end of the nearest word after the cursor, falling back to the end of
+    //the line and then to the start of the line below
+    pub fn word_right_pos(&self) -> (usize, usize) {
+        let (y, x) = (self.ptr_y, self.ptr_x);
+        let len = self.line_len(y);
+
+        match word_spans(&self.text[y]).iter().find(|(_, end)| *end > x) {
+            Some((_, end)) => (y, *end),
+            None if x < len => (y, len),
+            None if y + 1 < self.text.len() => (y + 1, 0),
+            None => (y, x)
+        }
+    }
+
+    //start of the current sentence, or of the previous one if already there
+    pub fn sentence_back_pos(&self) -> (usize, usize) {
+        let (y, x) = (self.ptr_y, self.ptr_x);
+
+        match sentence_starts(&self.text[y]).into_iter().rev().find(|start| *start < x) {
+            Some(start) => (y, start),
+            None if y > 0 => (y - 1, sentence_starts(&self.text[y - 1]).pop().unwrap_or(0)),
+            None => (y, 0)
+        }
+    }
+
+    //start of the next sentence
+    pub fn sentence_forward_pos(&self) -> (usize, usize) {
+        let (y, x) = (self.ptr_y, self.ptr_x);
+
+        match sentence_starts(&self.text[y]).into_iter().find(|start| *start > x) {
+            Some(start) => (y, start),
+            None if y + 1 < self.text.len() => (y + 1, 0),
+            None => (y, self.line_len(y))
+        }
+    }
+
+    //first line of the current paragraph, or of the previous one if already there
+    pub fn paragraph_up_pos(&self) -> (usize, usize) {
+        let mut i = self.ptr_y;
+        let at_start = self.ptr_x == 0 && (i == 0 || self.is_blank(i - 1));
+
+        // step back over the blank lines separating us from the paragraph above
+        if at_start || self.is_blank(i) {
+            while i > 0 && (i == self.ptr_y || self.is_blank(i)) {
+                i -= 1;
+            }
+        }
+
+        while i > 0 && !self.is_blank(i - 1) {
+            i -= 1;
+        }
+
+        (i, 0)
+    }
+
+    //first line of the next paragraph, or the end of the doc if there is none
+    pub fn paragraph_down_pos(&self) -> (usize, usize) {
+        let num_lines = self.text.len();
+        let mut i = self.ptr_y;
+
+        while i < num_lines && !self.is_blank(i) {
+            i += 1;
+        }
+        while i < num_lines && self.is_blank(i) {
+            i += 1;
+        }
+
+        match i < num_lines {
+            true => (i, 0),
+            false => (num_lines - 1, self.line_len(num_lines - 1))
+        }
+    }
+
+    //the word, run of spaces or punctuation mark at a position, as selected by a double click
+    pub fn word_range_at(&self, pos : (usize, usize)) -> ((usize, usize), (usize, usize)) {
+        let (y, x) = pos;
+        let mut start = 0;
+
+        for segment in self.text[y].split_word_bounds() {
+            let end = start + segment.chars().count();
+            if x < end {
+                return ((y, start), (y, end))
+            }
+            start = end;
+        }
+
+        //past the end of the line the last segment is picked
+        let last = self.text[y].split_word_bounds().next_back().map_or(0, |segment| segment.chars().count());
+        ((y, start - last), (y, start))
+    }
+
+    //a whole line including its line break, as selected by a triple click
+    pub fn line_range_at(&self, y : usize) -> ((usize, usize), (usize, usize)) {
+        self.lines_range(y, y)
+    }
+
+    //the lines of the paragraph a line is in, a blank line is its own paragraph
+    pub fn paragraph_range_at(&self, y : usize) -> ((usize, usize), (usize, usize)) {
+        let (mut first, mut last) = (y, y);
+
+        if !self.is_blank(y) {
+            while first > 0 && !self.is_blank(first - 1) {
+                first -= 1;
+            }
+            while last + 1 < self.text.len() && !self.is_blank(last + 1) {
+                last += 1;
+            }
+        }
+
+        self.lines_range(first, last)
+    }
+
+    fn lines_range(&self, first : usize, last : usize) -> ((usize, usize), (usize, usize)) {
+        match last + 1 < self.text.len() {
+            true => ((first, 0), (last + 1, 0)),
+            false => ((first, 0), (last, self.line_len(last)))
+        }
+    }
+
+    //remove the text between two (line, column) positions, in either order,
+    //joining lines where the range spans more than one
+    pub fn delete_range(&mut self, a : (usize, usize), b : (usize, usize)) {
+        let ((start_y, start_x), (end_y, end_x)) = if a <= b { (a, b) } else { (b, a) };
+        if a == b {
+            return
+        }
+
+        let head : String = self.text[start_y].chars().take(start_x).collect();
+        let tail : String = self.text[end_y].chars().skip(end_x).collect();
+
+        self.text.splice(start_y..=end_y, std::iter::once(head + &tail));
+        self.set_cursor(start_y, start_x);
+        self.revision += 1;
+    }
+
+    //text between two (line, column) positions, in either order, lines joined with \n
+    pub fn get_range(&self, a : (usize, usize), b : (usize, usize)) -> String {
+        let ((start_y, start_x), (end_y, end_x)) = if a <= b { (a, b) } else { (b, a) };
+
+        if start_y == end_y {
+            return self.text[start_y].chars().skip(start_x).take(end_x.saturating_sub(start_x)).collect()
+        }
+
+        let mut result : String = self.text[start_y].chars().skip(start_x).collect();
+        for line in &self.text[start_y + 1..end_y] {
+            result.push('\n');
+            result.push_str(line);
+        }
+        result.push('\n');
+        result.extend(self.text[end_y].chars().take(end_x));
+        result
+    }
+
+    //insert text at the cursor, which may contain \n, leaving the cursor after it
+    pub fn insert_text(&mut self, text : &str) {
+        let split = byte_index(&self.text[self.ptr_y], self.ptr_x);
+        let tail = self.text[self.ptr_y].split_off(split);
+
+        let mut lines = text.split('\n');
+        self.text[self.ptr_y].push_str(lines.next().unwrap_or(""));
+
+        for line in lines {
+            self.ptr_y += 1;
+            self.text.insert(self.ptr_y, String::from(line));
+        }
+
+        self.ptr_x = self.line_len(self.ptr_y);
+        self.text[self.ptr_y].push_str(&tail);
+        self.revision += 1;
+    }
+
+    //insert whole lines before line y
+    pub fn insert_lines(&mut self, y : usize, lines : &[String]) {
+        self.text.splice(y..y, lines.iter().cloned());
+        self.revision += 1;
+    }
+
+    //remove lines first..=last, a doc always keeps at least one (empty) line
+    pub fn delete_lines(&mut self, first : usize, last : usize) {
+        self.text.drain(first..=last.min(self.text.len() - 1));
+        if self.text.is_empty() {
+            self.text.push(String::new());
+        }
+        self.set_cursor(first, self.ptr_x);
+        self.revision += 1;
+    }
+
+    //start and end of the selection in document order
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        let cursor = (self.ptr_y, self.ptr_x);
+
+        self.anchor.map(|anchor| match anchor <= cursor {
+            true => (anchor, cursor),
+            false => (cursor, anchor)
+        })
+    }
+
+    pub fn set_cursor(&mut self, y : usize, x : usize) {
+        self.ptr_y = y.min(self.text.len() - 1);
+        self.ptr_x = x.min(self.line_len(self.ptr_y));
+    }
+
+    //length of a line in chars
+    pub fn line_len(&self, y : usize) -> usize {
+        self.text[y].chars().count()
+    }
+
+    fn is_blank(&self, y : usize) -> bool {
+        self.text[y].trim().is_empty()
+    }
+}
+
+impl Default for DocHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//convert a char index into the byte index String methods expect
+fn byte_index(line : &str, index : usize) -> usize {
+    line.char_indices().nth(index).map_or(line.len(), |(i, _)| i)
+}
+
+//char spans (start, end) of the words in a line using unicode word segmentation.
+//Segments with no alphanumeric char (spaces, punctuation, em dashes) are not words,
+//while contractions such as "don't" or "don’t" are kept as a single word.
+fn word_spans(line : &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    for segment in line.split_word_bounds() {
+        let end = start + segment.chars().count();
+        if segment.chars().any(char::is_alphanumeric) {
+            spans.push((start, end));
+        }
+        start = end;
+    }
+
+    spans
+}
+
+//char index of the start of every sentence in a line
+fn sentence_starts(line : &str) -> Vec<usize> {
+    line.split_sentence_bound_indices()
+        .map(|(i, _)| line[..i].chars().count())
+        .collect()
 }
 
 fn remove_char(line : &str, index : usize) -> String {
     match index {
         0 => line.to_string(),
         _ => {
-            let (p1, p2) = line.split_at(index);
-            let (p3, p4) = p1.split_at(index - 1);
+            let (p1, p2) = line.split_at(byte_index(line, index));
+            let (p3, _) = p1.split_at(byte_index(p1, index - 1));
             format!("{}{}", p3, p2)
         }
     }
 }
 
 fn add_char(line : &str, index : usize, x : char) -> String {
-    let (p1, p2) = line.split_at(index);
-    format!("{}{}{}", p1, x.to_string(), p2)
+    let (p1, p2) = line.split_at(byte_index(line, index));
+    format!("{}{}{}", p1, x, p2)
 }
-
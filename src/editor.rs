@@ -0,0 +1,264 @@
+//Editor
+//The open docs, the panes showing them and everything that turns key presses into
+//changes to them: the keymap, the vim and emacs layers, macros, the find bar, the
+//project sidebar and settings. The window and the terminal frontend (tui.rs) both
+//feed it keys and draw what it holds.
+
+use std::path::Path;
+
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::book::MANIFEST;
+use crate::command::*;
+use crate::doc_handler::DocHandler;
+use crate::emacs::Emacs;
+use crate::find::FindBar;
+use crate::keymap::*;
+use crate::macros::Macros;
+use crate::panes::Panes;
+use crate::settings::Settings;
+use crate::sidebar::Sidebar;
+use crate::status::Status;
+use crate::tabs::Tabs;
+use crate::vim::*;
+
+pub struct Editor {
+    pub tabs: Tabs,
+    pub panes: Panes,
+    pub keymap: Keymap,
+    pub vim: Option<Vim>,           //modal editing layer, only used with the vim profile
+    pub emacs: Option<Emacs>,       //kill ring and mark, only used with the emacs profile
+    pub macros: Macros,
+    pub find_bar: FindBar,
+    pub sidebar: Sidebar,
+    pub settings: Settings,
+    pub status: Status,
+    pub message: Option<String>,    //why the last command failed or a notice from it, shown until the next one
+    pub quit: bool                  //a quit command was given
+}
+
+impl Editor {
+    pub fn new(doc : DocHandler, keymap : Keymap, settings : Settings, macros : Macros) -> Self {
+        let vim = match keymap.profile {
+            Profile::Vim => Some(Vim::new()),
+            Profile::Default | Profile::Emacs => None
+        };
+        let emacs = match keymap.profile {
+            Profile::Emacs => Some(Emacs::new()),
+            Profile::Default | Profile::Vim => None
+        };
+
+        let mut editor = Self {
+            panes: Panes::new(&doc),
+            tabs: Tabs::new(doc),
+            keymap,
+            vim,
+            emacs,
+            macros,
+            find_bar: FindBar::new(),
+            sidebar: Sidebar::new(),
+            settings,
+            status: Status::new(),
+            message: None,
+            quit: false
+        };
+        editor.keymap.mode = editor.keymap_mode();
+        editor
+    }
+
+    //the book in book.toml in the working directory opened at its first section, or text.txt
+    //when there is no book, with keymap.toml, settings.toml and macros.toml.
+    //Config files that are missing or invalid are replaced by the defaults.
+    pub fn load(wkdir : &Path) -> Self {
+        let book_path = wkdir.join(MANIFEST);
+        let sidebar = Sidebar::load(&book_path).unwrap_or_else(|e| {
+            eprintln!("Ignoring {:?}, not opening it as a book: {}", book_path, e);
+            Sidebar::new()
+        });
+
+        let first = sidebar.book().and_then(|book| book.files().first().map(|file| file.to_path_buf()));
+        let doc = match first {
+            Some(path) => DocHandler::open_or_new(&path).unwrap_or_else(|e| {
+                eprintln!("Could not open {:?}, starting a new doc: {}", path, e);
+                DocHandler::new()
+            }),
+            None => DocHandler::load(&wkdir.join("text.txt")) //later prompt user to provide path
+        };
+
+        let keymap_path = wkdir.join("keymap.toml");
+        let keymap = Keymap::load(&keymap_path).unwrap_or_else(|e| {
+            eprintln!("Ignoring {:?}, using the default keymap: {}", keymap_path, e);
+            Keymap::default()
+        });
+
+        let settings_path = wkdir.join("settings.toml");
+        let settings = Settings::load(&settings_path).unwrap_or_else(|e| {
+            eprintln!("Ignoring {:?}, using the default settings: {}", settings_path, e);
+            Settings::default()
+        });
+
+        let macros_path = wkdir.join("macros.toml");
+        let macros = Macros::load(&macros_path).unwrap_or_else(|e| {
+            eprintln!("Ignoring {:?}, no saved macros: {}", macros_path, e);
+            Macros::new()
+        });
+
+        Self {
+            sidebar,
+            ..Self::new(doc, keymap, settings, macros)
+        }
+    }
+
+    //the doc being edited
+    pub fn doc(&self) -> &DocHandler {
+        self.tabs.doc()
+    }
+
+    pub fn doc_mut(&mut self) -> &mut DocHandler {
+        self.tabs.doc_mut()
+    }
+
+    //switch the focused pane to another tab, e.g. when the tab is clicked
+    pub fn select_tab(&mut self, i : usize) {
+        self.tabs.select(i);
+        self.panes.sync(&self.tabs);
+    }
+
+    //focus another pane, e.g. when it is clicked, which gives the keyboard back to the text
+    pub fn focus_pane(&mut self, i : usize) {
+        self.panes.focus(&mut self.tabs, i);
+        self.sidebar.focused = false;
+        self.keymap.mode = self.keymap_mode();
+    }
+
+    //open the section in a row of the sidebar, e.g. when it is clicked
+    pub fn open_section(&mut self, row : usize) {
+        self.message = None;
+        let outcome = self.sidebar.open_section(&mut self.tabs, row);
+        self.handle_outcome(outcome);
+        self.keymap.mode = self.keymap_mode();
+    }
+
+    //move a section dragged from one row of the sidebar onto another
+    pub fn drop_section(&mut self, from : usize, onto : usize) {
+        self.message = None;
+        let outcome = self.sidebar.drop_section(from, onto);
+        self.handle_outcome(outcome);
+    }
+
+    //a key press. Printable keys are also typed as chars, see char()
+    pub fn key(&mut self, key : VirtualKeyCode, modifiers : ModifiersState) {
+        match (self.vim.as_mut(), key_code_vim_key(key, modifiers)) {
+            (Some(vim), Some(vim_key)) if !self.find_bar.open && !self.sidebar.focused => {
+                self.message = None;
+                vim.unsaved = self.tabs.unsaved_elsewhere();
+                let outcome = vim.feed(self.tabs.doc_mut(), vim_key);
+                self.handle_outcome(outcome);
+            },
+
+            _ => {
+                if let Some(command) = self.keymap.translate_key(key, modifiers) {
+                    self.run_command(&command);
+                }
+            }
+        }
+        self.keymap.mode = self.keymap_mode();
+    }
+
+    //typed text. With the vim profile typed chars are vim keys, even in insert mode,
+    //unless they are being typed into the find bar or the sidebar has the keyboard
+    pub fn char(&mut self, c : char, modifiers : ModifiersState) {
+        match (self.keymap.translate_char(c, modifiers), self.vim.as_mut()) {
+            (Some(EditorCommand::InsertChar(c)), Some(vim)) if !self.find_bar.open && !self.sidebar.focused => {
+                self.message = None;
+                vim.unsaved = self.tabs.unsaved_elsewhere();
+                let outcome = vim.feed(self.tabs.doc_mut(), VimKey::Char(c));
+                self.handle_outcome(outcome);
+            },
+
+            (Some(command), _) => self.run_command(&command),
+
+            (None, _) => {}
+        }
+        self.keymap.mode = self.keymap_mode();
+    }
+
+    // Apply a command to the doc and act on the outcome.
+    // Macro commands are handled by the recorder, pane and tab commands by the panes and tabs, display
+    // settings by the settings, find commands by the find bar and sidebar commands by the sidebar.
+    // With the emacs profile the rest go through the kill ring first.
+    pub fn run_command(&mut self, command : &EditorCommand) {
+        let panes = &mut self.panes;
+        let tabs = &mut self.tabs;
+        let settings = &mut self.settings;
+        let find_bar = &mut self.find_bar;
+        let sidebar = &mut self.sidebar;
+        let mut emacs = self.emacs.as_mut();
+
+        let mut apply = |command : &EditorCommand| match panes.run(tabs, command).or_else(|| tabs.run(command)).or_else(|| settings.run(command)) {
+            Some(outcome) => outcome,
+            None => match find_bar.run(tabs, command).or_else(|| sidebar.run(tabs, command)) {
+                Some(outcome) => outcome,
+                None => {
+                    let doc = tabs.doc_mut();
+                    match emacs.as_deref_mut() {
+                        Some(emacs) => emacs.run(doc, command),
+                        None => dispatch(doc, command)
+                    }
+                }
+            }
+        };
+
+        self.message = None;
+        let outcome = self.macros.run(command, &mut apply);
+        self.handle_outcome(outcome);
+    }
+
+    fn handle_outcome(&mut self, outcome : Outcome) {
+        match outcome {
+            Outcome::Quit => self.quit = true,
+
+            // A project search result in another file, switch to it or open it in a new tab
+            Outcome::Open(path, (y, x)) => match self.tabs.open(&path) {
+                Ok(_) => self.tabs.doc_mut().set_cursor(y, x),
+                Err(e) => self.message = Some(format!("Could not open {}: {}", path.display(), e))
+            },
+
+            Outcome::Failed(e) | Outcome::Notice(e) => self.message = Some(e),
+
+            Outcome::Applied | Outcome::Unchanged => {}
+        }
+
+        // The focused pane follows the tabs, e.g. to a doc just opened
+        self.panes.sync(&self.tabs);
+    }
+
+    //search the doc and count its words again if it changed or another tab was picked, before drawing it
+    pub fn refresh(&mut self) {
+        self.find_bar.refresh(self.tabs.doc());
+        self.status.refresh(self.tabs.doc());
+    }
+
+    // The vim mode, or the first chords of a sequence such as "ctrl+x ctrl+s"
+    pub fn mode_indicator(&self) -> String {
+        let mode_indicator = match &self.vim {
+            Some(vim) => vim.mode_indicator(),
+            None => self.keymap.pending_keys()
+        };
+
+        match self.macros.is_recording() {
+            true => format!("{} [recording macro]", mode_indicator).trim_start().to_string(),
+            false => mode_indicator
+        }
+    }
+
+    // Keymap section in use, the find bar takes over the keyboard while it is open and so does the sidebar while focused
+    fn keymap_mode(&self) -> Mode {
+        match (self.find_bar.open, self.sidebar.focused, &self.vim) {
+            (true, _, _) => Mode::Find,
+            (false, true, _) => Mode::Sidebar,
+            (false, false, Some(vim)) => vim.keymap_mode(),
+            (false, false, None) => Mode::Insert
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//Emacs
+//Kill ring and mark for the emacs profile, turned on with profile = "emacs" in
+//keymap.toml. The key bindings themselves are ordinary keymap bindings (see
+//Keymap::emacs), this only handles the commands that need state carried over
+//from one command to the next:
+//
+//  kill_*, kill_line    remove text onto the kill ring, consecutive kills are joined
+//  kill_region          C-w, kill between the mark and the cursor
+//  copy_region          M-w, like kill_region but leaves the text in place
+//  yank                 C-y, insert the last kill
+//  yank_pop             M-y, straight after a yank, swap it for the kill before
+//
+//Everything else is handed to dispatch().
+
+use crate::command::{dispatch, EditorCommand, Outcome};
+use crate::doc_handler::DocHandler;
+
+//oldest kills are dropped past this
+const KILL_RING_MAX : usize = 60;
+
+pub struct Emacs {
+    kill_ring: Vec<String>, //most recent kill last
+    last_was_kill: bool,    //the previous command killed text, so the next kill joins it
+    yank: Option<Yank>      //the previous command was a yank
+}
+
+//text put in by the last yank, so yank_pop can replace it
+struct Yank {
+    start: (usize, usize),
+    end: (usize, usize),
+    index: usize //position in the kill ring counted back from the most recent
+}
+
+impl Emacs {
+    pub fn new() -> Self {
+        Self {
+            kill_ring: Vec::new(),
+            last_was_kill: false,
+            yank: None
+        }
+    }
+
+    //the most recent kill
+    pub fn current_kill(&self) -> Option<&str> {
+        self.kill_ring.last().map(|kill| kill.as_str())
+    }
+
+    //apply a command to the doc, keeping track of kills and yanks
+    pub fn run(&mut self, doc : &mut DocHandler, command : &EditorCommand) -> Outcome {
+        let append = std::mem::take(&mut self.last_was_kill);
+        let yank = self.yank.take();
+        let cursor = (doc.ptr_y, doc.ptr_x);
+
+        match command {
+            EditorCommand::Kill(motion) => {
+                let target = motion.target(doc);
+                self.kill(doc, cursor, target, append)
+            },
+
+            EditorCommand::KillLine => {
+                let target = kill_line_target(doc);
+                self.kill(doc, cursor, target, append)
+            },
+
+            EditorCommand::KillRegion => match doc.anchor.take() {
+                Some(mark) => self.kill(doc, mark, cursor, append),
+                None => Outcome::Failed(String::from("The mark is not set now, so there is no region"))
+            },
+
+            EditorCommand::CopyRegion => match doc.anchor.take() {
+                Some(mark) => {
+                    let text = doc.get_range(mark, cursor);
+                    self.push_kill(text, append, mark > cursor);
+                    self.last_was_kill = true;
+                    Outcome::Applied
+                },
+                None => Outcome::Failed(String::from("The mark is not set now, so there is no region"))
+            },
+
+            EditorCommand::Yank => match self.kill_ring.len() {
+                0 => Outcome::Failed(String::from("Kill ring is empty")),
+                _ => {
+                    doc.checkpoint(false);
+                    self.insert_yank(doc, 0);
+                    Outcome::Applied
+                }
+            },
+
+            EditorCommand::YankPop => match yank {
+                Some(yank) => {
+                    doc.checkpoint(false);
+                    doc.delete_range(yank.start, yank.end);
+                    self.insert_yank(doc, (yank.index + 1) % self.kill_ring.len());
+                    Outcome::Applied
+                },
+                None => Outcome::Failed(String::from("Previous command was not a yank"))
+            },
+
+            _ => dispatch(doc, command)
+        }
+    }
+
+    //remove the text between two positions and put it on the kill ring
+    fn kill(&mut self, doc : &mut DocHandler, from : (usize, usize), to : (usize, usize), append : bool) -> Outcome {
+        //a failed kill still counts for joining, like C-k at the end of the doc
+        self.last_was_kill = true;
+        doc.anchor = None;
+
+        if from == to {
+            return Outcome::Unchanged
+        }
+
+        let text = doc.get_range(from, to);
+        doc.checkpoint(false);
+        doc.delete_range(from, to);
+        self.push_kill(text, append, to < from);
+        Outcome::Applied
+    }
+
+    //add a kill to the ring, or join it to the last one.
+    //Killing backwards (e.g. M-backspace) puts the text in front.
+    fn push_kill(&mut self, text : String, append : bool, backwards : bool) {
+        match (append, self.kill_ring.last_mut()) {
+            (true, Some(last)) => match backwards {
+                true => last.insert_str(0, &text),
+                false => last.push_str(&text)
+            },
+            _ => {
+                self.kill_ring.push(text);
+                if self.kill_ring.len() > KILL_RING_MAX {
+                    self.kill_ring.remove(0);
+                }
+            }
+        }
+    }
+
+    //insert the kill index places back from the most recent at the cursor
+    fn insert_yank(&mut self, doc : &mut DocHandler, index : usize) {
+        let text = self.kill_ring[self.kill_ring.len() - 1 - index].clone();
+        let start = (doc.ptr_y, doc.ptr_x);
+
+        doc.insert_text(&text);
+        doc.anchor = None;
+
+        self.yank = Some(Yank { start, end: (doc.ptr_y, doc.ptr_x), index });
+    }
+}
+
+impl Default for Emacs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//C-k kills to the end of the line, or the line break itself when only
+//whitespace is left on the line
+fn kill_line_target(doc : &DocHandler) -> (usize, usize) {
+    let rest : String = doc.text[doc.ptr_y].chars().skip(doc.ptr_x).collect();
+
+    match rest.trim().is_empty() && doc.ptr_y + 1 < doc.text.len() {
+        true => (doc.ptr_y + 1, 0),
+        false => (doc.ptr_y, doc.line_len(doc.ptr_y))
+    }
+}
@@ -0,0 +1,276 @@
+//EPUB
+//Writes a document (see export.rs) as an EPUB 3 ebook, a zip archive of xhtml pages
+//with the package document (package.opf) listing them and the book's metadata, and a
+//nav page with its contents. The archive starts with the uncompressed mimetype file as
+//readers expect, then:
+//
+//  META-INF/container.xml      where the package document is
+//  EPUB/package.opf            metadata, every file and the reading order
+//  EPUB/nav.xhtml              the parts and chapters
+//  EPUB/style.css
+//  EPUB/cover.xhtml            and EPUB/images/cover.jpg, for a book with a cover
+//  EPUB/section-001.xhtml      the title page, the dedication, then a page per part and chapter
+//
+//Nothing else is needed to build one, the archive is written by the zip crate.
+
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::book::Level;
+use crate::export::{self, escape, Block, Document};
+
+pub const MIMETYPE : &str = "application/epub+zip";
+
+const CONTAINER : &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"EPUB/package.opf\" media-type=\"application/oebps-package+xml\" />
+</rootfiles>
+</container>
+";
+
+const STYLE : &str = "body { margin: 0 5%; }
+p { margin: 0; text-indent: 1.5em; text-align: justify; }
+h1, h2 { margin: 3em 0 2em; text-align: center; }
+h1 + p, h2 + p, p.scene-break + p, hr + p { text-indent: 0; }
+h1.title { margin-top: 30%; }
+p.author, p.dedication, p.scene-break { text-align: center; text-indent: 0; }
+p.author { margin-top: 1em; }
+p.dedication { margin-top: 30%; font-style: italic; }
+p.scene-break { margin: 1em 0; }
+hr { margin: 1em 30%; border: 0; border-top: 1px solid; }
+.cover { margin: 0; padding: 0; text-align: center; }
+.cover img { max-width: 100%; max-height: 100%; }
+";
+
+//a chapter, or a part with the chapters in it, as listed in the nav
+struct Entry {
+    title: String,
+    href: String,
+    part: bool,
+    chapters: Vec<Entry>
+}
+
+//the bytes of an epub file holding the document
+pub fn epub(document : &Document) -> Vec<u8> {
+    let pages = pages(&document.blocks);
+    let mut files : Vec<(String, Vec<u8>)> = vec![
+        (String::from("META-INF/container.xml"), CONTAINER.as_bytes().to_vec()),
+        (String::from("EPUB/package.opf"), package(document, &pages).into_bytes()),
+        (String::from("EPUB/nav.xhtml"), nav(document, &pages).into_bytes()),
+        (String::from("EPUB/style.css"), STYLE.as_bytes().to_vec())
+    ];
+    if let Some(cover) = &document.cover {
+        let body = format!("<section class=\"cover\" epub:type=\"cover\">\n<img src=\"images/cover.{}\" alt=\"{}\" />\n</section>\n", cover.extension, escape(&title(document)));
+        files.push((String::from("EPUB/cover.xhtml"), xhtml(document, "Cover", &body).into_bytes()));
+        files.push((format!("EPUB/images/cover.{}", cover.extension), cover.data.clone()));
+    }
+    for (i, page) in pages.iter().enumerate() {
+        let body = match kind(page) {
+            Some(kind) => format!("<section epub:type=\"{}\">\n{}</section>\n", kind, export::html_body(document, page)),
+            None => export::html_body(document, page)
+        };
+        files.push((format!("EPUB/{}", href(i)), xhtml(document, &page_title(document, page), &body).into_bytes()));
+    }
+
+    //the mimetype goes first and isn't compressed so it can be read at a fixed place in the file
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file("mimetype", stored).expect("writing to memory");
+    zip.write_all(MIMETYPE.as_bytes()).expect("writing to memory");
+    for (name, data) in files {
+        zip.start_file(name, deflated).expect("writing to memory");
+        zip.write_all(&data).expect("writing to memory");
+    }
+    zip.finish().expect("writing to memory").into_inner()
+}
+
+//the file extension and media type of an image that can be a cover, from its name
+pub fn image_type(path : &Path) -> Option<(&'static str, &'static str)> {
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "jpg" | "jpeg" => Some(("jpg", "image/jpeg")),
+        "png" => Some(("png", "image/png")),
+        "gif" => Some(("gif", "image/gif")),
+        "svg" => Some(("svg", "image/svg+xml")),
+        "webp" => Some(("webp", "image/webp")),
+        _ => None
+    }
+}
+
+//the blocks split into pages. The title page and the dedication get a page each and every
+//heading starts a page. There is always a page, an empty one for an empty document.
+fn pages(blocks : &[Block]) -> Vec<&[Block]> {
+    let mut starts = vec![0];
+    for (i, block) in blocks.iter().enumerate().skip(1) {
+        let starts_page = matches!(block, Block::TitlePage | Block::Dedication(_) | Block::Heading(..));
+        let after_front_matter = matches!(blocks[starts[starts.len() - 1]], Block::TitlePage | Block::Dedication(_));
+        if starts_page || after_front_matter {
+            starts.push(i);
+        }
+    }
+
+    starts.iter()
+        .zip(starts.iter().skip(1).chain([&blocks.len()]))
+        .map(|(start, end)| &blocks[*start..*end])
+        .collect()
+}
+
+//the epub:type of a page's section, None for text that isn't in a chapter
+fn kind(page : &[Block]) -> Option<&'static str> {
+    match page.first()? {
+        Block::TitlePage => Some("titlepage"),
+        Block::Dedication(_) => Some("dedication"),
+        Block::Heading(Level::Part, _) => Some("part"),
+        Block::Heading(..) => Some("chapter"),
+        _ => None
+    }
+}
+
+fn page_title(document : &Document, page : &[Block]) -> String {
+    match page.first() {
+        Some(Block::Heading(_, heading)) => heading.clone(),
+        Some(Block::Dedication(_)) => String::from("Dedication"),
+        _ => title(document)
+    }
+}
+
+fn href(page : usize) -> String {
+    format!("section-{:03}.xhtml", page + 1)
+}
+
+//a book has to have a title
+fn title(document : &Document) -> String {
+    match document.title.trim().is_empty() {
+        true => String::from("Untitled"),
+        false => document.title.clone()
+    }
+}
+
+//a content page around the body
+fn xhtml(document : &Document, title : &str, body : &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+        <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{}\" lang=\"{}\">\n\
+        <head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\" />\n</head>\n\
+        <body>\n{}</body>\n</html>\n",
+        escape(&document.language),
+        escape(&document.language),
+        escape(title),
+        body
+    )
+}
+
+//the package document: the metadata, every file but the mimetype and container, and the reading order
+fn package(document : &Document, pages : &[&[Block]]) -> String {
+    let mut metadata = format!("<dc:identifier id=\"book-id\">{}</dc:identifier>\n", escape(&document.identifier));
+    metadata.push_str(&format!("<dc:title>{}</dc:title>\n", escape(&title(document))));
+    if !document.author.is_empty() {
+        metadata.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(&document.author)));
+    }
+    metadata.push_str(&format!("<dc:language>{}</dc:language>\n", escape(&document.language)));
+    metadata.push_str(&format!("<meta property=\"dcterms:modified\">{}</meta>\n", modified()));
+
+    let mut manifest = String::from("<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />\n");
+    manifest.push_str("<item id=\"style\" href=\"style.css\" media-type=\"text/css\" />\n");
+    let mut spine = String::new();
+
+    if let Some(cover) = &document.cover {
+        //for readers that only know EPUB 2
+        metadata.push_str("<meta name=\"cover\" content=\"cover-image\" />\n");
+        manifest.push_str(&format!("<item id=\"cover-image\" href=\"images/cover.{}\" media-type=\"{}\" properties=\"cover-image\" />\n", cover.extension, cover.media_type));
+        manifest.push_str("<item id=\"cover\" href=\"cover.xhtml\" media-type=\"application/xhtml+xml\" />\n");
+        spine.push_str("<itemref idref=\"cover\" />\n");
+    }
+    for i in 0..pages.len() {
+        manifest.push_str(&format!("<item id=\"section-{:03}\" href=\"{}\" media-type=\"application/xhtml+xml\" />\n", i + 1, href(i)));
+        spine.push_str(&format!("<itemref idref=\"section-{:03}\" />\n", i + 1));
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{}\">\n\
+        <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{}</metadata>\n\
+        <manifest>\n{}</manifest>\n\
+        <spine>\n{}</spine>\n\
+        </package>\n",
+        escape(&document.language),
+        metadata,
+        manifest,
+        spine
+    )
+}
+
+//the table of contents, the parts with their chapters in them, or every page for a document without headings
+fn nav(document : &Document, pages : &[&[Block]]) -> String {
+    let mut entries : Vec<Entry> = Vec::new();
+    for (i, page) in pages.iter().enumerate() {
+        let (level, heading) = match page.first() {
+            Some(Block::Heading(level, heading)) => (*level, heading),
+            _ => continue
+        };
+        let entry = Entry { title: heading.clone(), href: href(i), part: level == Level::Part, chapters: Vec::new() };
+        match entries.last_mut() {
+            Some(last) if last.part && !entry.part => last.chapters.push(entry),
+            _ => entries.push(entry)
+        }
+    }
+    if entries.is_empty() {
+        entries = pages.iter()
+            .enumerate()
+            .map(|(i, page)| Entry { title: page_title(document, page), href: href(i), part: false, chapters: Vec::new() })
+            .collect();
+    }
+
+    fn list(entries : &[Entry]) -> String {
+        let items : Vec<String> = entries.iter()
+            .map(|entry| match entry.chapters.is_empty() {
+                true => format!("<li><a href=\"{}\">{}</a></li>\n", entry.href, escape(&entry.title)),
+                false => format!("<li><a href=\"{}\">{}</a>\n{}</li>\n", entry.href, escape(&entry.title), list(&entry.chapters))
+            })
+            .collect();
+        format!("<ol>\n{}</ol>\n", items.concat())
+    }
+
+    let mut landmarks = String::new();
+    if document.cover.is_some() {
+        landmarks.push_str("<li><a epub:type=\"cover\" href=\"cover.xhtml\">Cover</a></li>\n");
+    }
+    landmarks.push_str("<li><a epub:type=\"toc\" href=\"nav.xhtml#toc\">Contents</a></li>\n");
+    if let Some(start) = pages.iter().position(|page| !matches!(page.first(), Some(Block::TitlePage | Block::Dedication(_)))) {
+        landmarks.push_str(&format!("<li><a epub:type=\"bodymatter\" href=\"{}\">Start</a></li>\n", href(start)));
+    }
+
+    let body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>Contents</h1>\n{}</nav>\n<nav epub:type=\"landmarks\" hidden=\"\">\n<ol>\n{}</ol>\n</nav>\n",
+        list(&entries),
+        landmarks
+    );
+    xhtml(document, "Contents", &body)
+}
+
+//now as an xml date and time in UTC, e.g. 2024-03-01T12:00:00Z
+fn modified() -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_secs()) as i64;
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    //from days since 1970-01-01 to the date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = match mp < 10 {
+        true => mp + 3,
+        false => mp - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
@@ -0,0 +1,228 @@
+//Export
+//Writes a doc, or a book compiled into one document (see compile.rs), out in a
+//format for publishing. Paragraphs are separated by blank lines as in the editor,
+//a line break inside a paragraph is kept as a line break, and a line that is only
+//a scene break (#, *** or * * *) becomes a rule, or the book's scene separator.
+//EPUB is written by epub.rs and PDF by pdf.rs.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::book::Level;
+use crate::epub;
+use crate::pdf::{self, PdfOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Html,
+    Epub,
+    Pdf
+}
+
+//what is exported, a doc has only paragraphs and scene breaks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    pub title: String,
+    pub author: String,
+    pub language: String,               //a language tag, e.g. "en-GB"
+    pub identifier: String,             //unique to the book, e.g. an ISBN
+    pub cover: Option<Cover>,
+    pub blocks: Vec<Block>,
+    pub scene_break: Option<String>,    //written for a scene break, None for a rule
+    pub pdf: PdfOptions                 //how it is laid out on pages
+}
+
+//an image for the front of the book
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cover {
+    pub extension: &'static str,        //without the dot, e.g. "jpg"
+    pub media_type: &'static str,       //e.g. "image/jpeg"
+    pub data: Vec<u8>
+}
+
+//a paragraph or other part of the document, as exported
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Paragraph(Vec<String>),     //its lines, trimmed
+    SceneBreak,
+    Heading(Level, String),     //starts a part or chapter of a book
+    TitlePage,                  //the title and author
+    Dedication(Vec<String>)
+}
+
+impl Format {
+    //usual file extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "txt",
+            Format::Html => "html",
+            Format::Epub => "epub",
+            Format::Pdf => "pdf"
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+//as written on the command line, e.g. "html"
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(text : &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "txt" | "text" => Ok(Format::Text),
+            "html" | "htm" => Ok(Format::Html),
+            "epub" => Ok(Format::Epub),
+            "pdf" => Ok(Format::Pdf),
+            _ => Err(format!("unknown export format \"{}\", expected txt, html, epub or pdf", text))
+        }
+    }
+}
+
+impl Document {
+    //a doc's text, titled title
+    pub fn from_text(text : &[String], title : &str) -> Self {
+        Self {
+            title: title.to_string(),
+            author: String::new(),
+            language: String::from("en"),
+            identifier: identifier(title, ""),
+            cover: None,
+            blocks: blocks(text),
+            scene_break: None,
+            pdf: PdfOptions::default()
+        }
+    }
+}
+
+//an identifier for a book that wasn't given one, the same every time the book is exported
+//so readers see a new export as the same book
+pub fn identifier(title : &str, author : &str) -> String {
+    //FNV-1a
+    let hash = title.bytes().chain([0]).chain(author.bytes()).fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("urn:garbanzo_book:{:016x}", hash)
+}
+
+//the bytes of a file holding the document
+pub fn export(document : &Document, format : Format) -> Vec<u8> {
+    match format {
+        Format::Text => text(document).into_bytes(),
+        Format::Html => html(document).into_bytes(),
+        Format::Epub => epub::epub(document),
+        Format::Pdf => pdf::pdf(document)
+    }
+}
+
+//plain text as it would be written in the editor, with a blank line between paragraphs
+//and two before a heading or after the front matter
+pub fn text(document : &Document) -> String {
+    let mut text = String::new();
+    for (i, block) in document.blocks.iter().enumerate() {
+        let gap = match (i, block, i.checked_sub(1).map(|previous| &document.blocks[previous])) {
+            (0, _, _) => "",
+            (_, Block::Heading(..), _) | (_, _, Some(Block::TitlePage | Block::Dedication(_))) => "\n\n\n",
+            _ => "\n\n"
+        };
+        text.push_str(gap);
+
+        match block {
+            Block::Paragraph(lines) | Block::Dedication(lines) => text.push_str(&lines.join("\n")),
+            Block::SceneBreak => text.push_str(document.scene_break.as_deref().unwrap_or("* * *")),
+            Block::Heading(_, heading) => text.push_str(heading),
+            Block::TitlePage => match document.author.is_empty() {
+                true => text.push_str(&document.title),
+                false => text.push_str(&format!("{}\n\nby {}", document.title, document.author))
+            }
+        }
+    }
+    text.push('\n');
+    text
+}
+
+//a standalone html page
+pub fn html(document : &Document) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(&document.language),
+        escape(&document.title),
+        html_body(document, &document.blocks)
+    )
+}
+
+//some of the document's blocks as html elements, written as xhtml so they can go in an epub too
+pub fn html_body(document : &Document, blocks : &[Block]) -> String {
+    let paragraph = |class : &str, lines : &[String]| {
+        let lines : Vec<String> = lines.iter().map(|line| escape(line)).collect();
+        format!("<p{}>{}</p>\n", class, lines.join("<br />\n"))
+    };
+
+    let mut body = String::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph(lines) => body.push_str(&paragraph("", lines)),
+            Block::SceneBreak => match &document.scene_break {
+                Some(separator) => body.push_str(&format!("<p class=\"scene-break\">{}</p>\n", escape(separator))),
+                None => body.push_str("<hr />\n")
+            },
+            Block::Heading(Level::Part, heading) => body.push_str(&format!("<h1>{}</h1>\n", escape(heading))),
+            Block::Heading(_, heading) => body.push_str(&format!("<h2>{}</h2>\n", escape(heading))),
+            Block::TitlePage => {
+                body.push_str(&format!("<h1 class=\"title\">{}</h1>\n", escape(&document.title)));
+                if !document.author.is_empty() {
+                    body.push_str(&format!("<p class=\"author\">{}</p>\n", escape(&document.author)));
+                }
+            },
+            Block::Dedication(lines) => body.push_str(&paragraph(" class=\"dedication\"", lines))
+        }
+    }
+    body
+}
+
+//the paragraphs of the text, blank lines between them are dropped.
+//A scene break ends a paragraph whether or not there is a blank line before it.
+pub fn blocks(text : &[String]) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut lines : Vec<String> = Vec::new();
+
+    for line in text.iter().map(|line| line.trim()).chain([""]) {
+        let scene_break = is_scene_break(line);
+        if (line.is_empty() || scene_break) && !lines.is_empty() {
+            blocks.push(Block::Paragraph(std::mem::take(&mut lines)));
+        }
+
+        match (scene_break, line.is_empty()) {
+            (true, _) => blocks.push(Block::SceneBreak),
+            (false, false) => lines.push(line.to_string()),
+            (false, true) => {}
+        }
+    }
+
+    blocks
+}
+
+//a line marking a break between scenes
+pub fn is_scene_break(line : &str) -> bool {
+    matches!(line.trim(), "#" | "***" | "* * *")
+}
+
+//text with the chars that mean something in html and xml escaped
+pub fn escape(text : &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c)
+        }
+    }
+    escaped
+}
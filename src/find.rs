@@ -0,0 +1,553 @@
+//Find
+//State of the find bar. While the bar is open typed chars and backspace edit the
+//focused field (the query, or the replacement when replacing) instead of the doc,
+//and the doc is searched again after every change to the query, moving the cursor
+//to the first match after where the search started.
+//The matches are highlighted by the view.
+//
+//Replace all is a single undo step. With the in_selection option only the
+//selection there was when the bar was opened is searched and replaced in.
+//
+//Opened with find_in_project the bar also searches every file in the project
+//(see project_search.rs). Find next/previous then step through the results,
+//asking for the file to be opened when it is not the doc, and replace all
+//only goes ahead when given twice, the first time shows what it would change.
+
+use std::env;
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::command::{EditorCommand, Motion, Outcome};
+use crate::doc_handler::DocHandler;
+use crate::project_search::{same_file, ProjectSearch};
+use crate::search::{build_regex, find_all, replace_all, replacement, Match, SearchOption, SearchOptions};
+use crate::tabs::Tabs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindField {
+    Query,
+    Replacement
+}
+
+type Range = ((usize, usize), (usize, usize));
+
+//most project search results listed above the bar at once
+const PANEL_ROWS : usize = 8;
+
+pub struct FindBar {
+    pub open: bool,
+    pub replacing: bool,        //the replace field is shown
+    pub focus: FindField,
+    pub query: String,
+    pub replacement: String,
+    pub options: SearchOptions,
+    matches: Vec<Match>,
+    regex: Option<Regex>,
+    current: Option<usize>,     //index into matches of the match at the cursor
+    error: Option<String>,      //why the query could not be searched for, e.g. a bad regex
+    notice: Option<String>,     //shown instead of the match count, e.g. after replace all
+    searched: Option<(u64, u64)>, //doc id and revision the matches were found in, None to search again
+    origin: (usize, usize),     //cursor position when the bar was opened
+    selection: Option<Range>,   //selection when the bar was opened, for in_selection
+    pub project: Option<ProjectSearch>, //set while searching the whole project
+    confirm_replace: bool       //replace all in the project was asked for once
+}
+
+impl FindBar {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            replacing: false,
+            focus: FindField::Query,
+            query: String::new(),
+            replacement: String::new(),
+            options: SearchOptions::default(),
+            matches: Vec::new(),
+            regex: None,
+            current: None,
+            error: None,
+            notice: None,
+            searched: None,
+            origin: (0, 0),
+            selection: None,
+            project: None,
+            confirm_replace: false
+        }
+    }
+
+    //matches in doc order
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    //the match the cursor was last moved to
+    pub fn current(&self) -> Option<Match> {
+        self.current.map(|i| self.matches[i])
+    }
+
+    //text shown next to the query, e.g. "3 of 17"
+    pub fn status(&self) -> String {
+        if let (None, None, Some(project)) = (&self.error, &self.notice, &self.project) {
+            return match (self.query.is_empty(), project.selected) {
+                (true, _) => String::new(),
+                (false, Some(i)) => format!("{} of {}", i + 1, project.status()),
+                (false, None) => project.status()
+            }
+        }
+
+        match (&self.error, &self.notice, self.current, self.matches.len()) {
+            (Some(e), _, _, _) => e.lines().last().unwrap_or_default().trim().to_string(),
+            (None, Some(notice), _, _) => notice.clone(),
+            _ if self.query.is_empty() => String::new(),
+            (None, None, _, 0) => String::from("No results"),
+            (None, None, Some(i), total) => format!("{} of {}", i + 1, total),
+            (None, None, None, total) => format!("{} results", total)
+        }
+    }
+
+    //text shown next to the replacement: how many matches replace all would
+    //change and what the current match would become, e.g. "17 to replace, Anna → Bella"
+    pub fn replace_preview(&self, doc : &DocHandler) -> String {
+        let (regex, total) = match (&self.regex, self.matches.len()) {
+            (Some(regex), total) if total > 0 => (regex, total),
+            _ => return String::new()
+        };
+
+        match self.current() {
+            Some(m) => {
+                let line = &doc.text[m.line];
+                let before : String = line.chars().skip(m.start).take(m.end - m.start).collect();
+                let after = replacement(line, &m, regex, &self.replacement, self.options);
+                format!("{} to replace, {} → {}", total, before, after)
+            },
+            None => format!("{} to replace", total)
+        }
+    }
+
+    //lines listed above the find bar when searching the project, with the index
+    //of the selected one: the results, or what replace all would do
+    pub fn panel(&self) -> (Vec<String>, Option<usize>) {
+        let project = match &self.project {
+            Some(project) => project,
+            None => return (Vec::new(), None)
+        };
+
+        match (&self.regex, self.confirm_replace) {
+            (Some(regex), true) => (project.replace_preview(regex, &self.replacement, self.options), None),
+            _ => {
+                let lines = project.results().iter()
+                    .map(|result| format!("{}:{}: {}", project.display_path(&result.path), result.found.line + 1, result.text.trim()))
+                    .collect();
+                (lines, project.selected)
+            }
+        }
+    }
+
+    //lines of the bar as drawn, empty while it is closed. The replace field goes under
+    //the query and project search results are listed above, scrolled to the selected one.
+    pub fn lines(&self, doc : &DocHandler) -> Vec<String> {
+        let mut lines = Vec::new();
+        if !self.open {
+            return lines
+        }
+
+        let (panel, selected) = self.panel();
+        let first = selected.map_or(0, |i| (i + 1).saturating_sub(PANEL_ROWS));
+        for (i, line) in panel.iter().enumerate().skip(first).take(PANEL_ROWS) {
+            let marker = match selected == Some(i) {
+                true => ">",
+                false => " "
+            };
+            lines.push(format!("{}{}", marker, line));
+        }
+
+        let options = [
+            (self.options.case_sensitive, "[match case]"),
+            (self.options.whole_word, "[whole word]"),
+            (self.options.regex, "[regex]"),
+            (self.options.preserve_case, "[preserve case]"),
+            (self.options.in_selection, "[in selection]")
+        ];
+        let flags : Vec<&str> = options.iter().filter(|(on, _)| *on).map(|(_, name)| *name).collect();
+
+        //the field being typed in is marked with a >
+        let marker = |field : FindField| match self.focus == field && self.replacing {
+            true => ">",
+            false => " "
+        };
+
+        lines.push(format!("{}Find: {}   {}   {}", marker(FindField::Query), self.query, self.status(), flags.join(" ")));
+        if self.replacing {
+            lines.push(format!("{}Replace: {}   {}", marker(FindField::Replacement), self.replacement, self.replace_preview(doc)));
+        }
+        lines
+    }
+
+    //collect results from a project search, true if there were new ones
+    pub fn poll(&mut self) -> bool {
+        self.project.as_mut().is_some_and(|project| project.poll())
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.project.as_ref().is_some_and(|project| project.is_searching())
+    }
+
+    //handle a command meant for the find bar, None if it is not one
+    pub fn run(&mut self, tabs : &mut Tabs, command : &EditorCommand) -> Option<Outcome> {
+        //replace all in the project has to be given twice in a row
+        let pending = std::mem::take(&mut self.confirm_replace);
+        let confirmed = pending && *command == EditorCommand::ReplaceAll;
+        if pending && !confirmed {
+            self.notice = None;
+        }
+
+        let doc = tabs.doc_mut();
+        let outcome = match command {
+            EditorCommand::FindInProject => {
+                if self.project.is_some() && self.open && !self.query.is_empty() {
+                    return Some(self.step(doc, true))
+                }
+
+                //the project is the directory the doc is in
+                let root = doc.path.as_ref()
+                    .and_then(|path| path.parent().map(PathBuf::from))
+                    .filter(|root| !root.as_os_str().is_empty())
+                    .or_else(|| env::current_dir().ok())
+                    .unwrap_or_default();
+
+                self.project = Some(ProjectSearch::new(&root));
+                self.open_bar(doc, self.replacing);
+                Outcome::Applied
+            },
+
+            EditorCommand::Find | EditorCommand::Replace => {
+                let replacing = *command == EditorCommand::Replace;
+                if self.open && self.replacing == replacing && !self.query.is_empty() {
+                    return Some(self.step(doc, true))
+                }
+
+                self.project = None;
+                self.open_bar(doc, replacing);
+                Outcome::Applied
+            },
+
+            EditorCommand::FindNext => self.step(doc, true),
+
+            EditorCommand::FindPrevious => self.step(doc, false),
+
+            EditorCommand::CloseFind => {
+                self.project = None;
+                match std::mem::take(&mut self.open) {
+                    true => Outcome::Applied,
+                    false => Outcome::Unchanged
+                }
+            },
+
+            EditorCommand::ToggleSearchOption(SearchOption::InSelection) if !self.options.in_selection && self.selection.is_none() => {
+                Outcome::Failed(String::from("There was no selection when the find bar was opened"))
+            },
+
+            EditorCommand::ToggleSearchOption(option) => {
+                self.options.toggle(*option);
+                self.query_changed(doc);
+                Outcome::Applied
+            },
+
+            EditorCommand::SwitchFindField => {
+                self.replacing = true;
+                self.focus = match self.focus {
+                    FindField::Query => FindField::Replacement,
+                    FindField::Replacement => FindField::Query
+                };
+                Outcome::Applied
+            },
+
+            EditorCommand::ReplaceNext => self.replace_next(doc),
+
+            EditorCommand::ReplaceAll if self.project.is_some() => self.replace_in_project(tabs, confirmed),
+
+            EditorCommand::ReplaceAll => self.replace_all(doc),
+
+            //with the bar open, typing edits the focused field
+            EditorCommand::InsertChar(c) if self.open => {
+                match self.focus {
+                    FindField::Query => {
+                        self.query.push(*c);
+                        self.query_changed(doc);
+                    },
+                    FindField::Replacement => self.replacement.push(*c)
+                };
+                Outcome::Applied
+            },
+
+            EditorCommand::Delete(Motion::Left) if self.open => {
+                let removed = match self.focus {
+                    FindField::Query => self.query.pop(),
+                    FindField::Replacement => self.replacement.pop()
+                };
+
+                match (removed, self.focus) {
+                    (None, _) => return Some(Outcome::Unchanged),
+                    (Some(_), FindField::Query) => self.query_changed(doc),
+                    (Some(_), FindField::Replacement) => {}
+                };
+                Outcome::Applied
+            },
+
+            _ => return None
+        };
+
+        Some(outcome)
+    }
+
+    fn open_bar(&mut self, doc : &mut DocHandler, replacing : bool) {
+        if !self.open {
+            self.origin = (doc.ptr_y, doc.ptr_x);
+            self.selection = doc.selection();
+        }
+        self.open = true;
+        self.replacing = replacing;
+        self.focus = match replacing && !self.query.is_empty() {
+            true => FindField::Replacement,
+            false => FindField::Query
+        };
+        self.query_changed(doc);
+    }
+
+    //search again for a new query or new options
+    fn query_changed(&mut self, doc : &mut DocHandler) {
+        self.searched = None;
+        self.jump_from_origin(doc);
+
+        if let Some(project) = &mut self.project {
+            project.start(self.regex.clone().filter(|_| !self.query.is_empty()));
+        }
+    }
+
+    //search the doc again if it or the query changed since the last search
+    pub fn refresh(&mut self, doc : &DocHandler) {
+        if self.searched == Some((doc.id, doc.revision)) {
+            return
+        }
+
+        self.searched = Some((doc.id, doc.revision));
+        self.error = None;
+        self.notice = None;
+        self.regex = None;
+        self.matches.clear();
+        self.current = None;
+
+        if self.query.is_empty() {
+            return
+        }
+
+        match build_regex(&self.query, self.options) {
+            Ok(regex) => {
+                self.matches = find_all(&doc.text, &regex);
+                self.regex = Some(regex);
+            },
+            Err(e) => self.error = Some(e)
+        };
+
+        if let (true, Some((start, end))) = (self.options.in_selection, self.selection) {
+            self.matches.retain(|m| m.start_pos() >= start && m.end_pos() <= end);
+        }
+
+        //after an edit keep pointing at the match under the cursor, if there is one
+        let cursor = (doc.ptr_y, doc.ptr_x);
+        self.current = self.matches.iter().position(|m| m.start_pos() == cursor);
+    }
+
+    //incremental search, go to the first match at or after where the search started
+    fn jump_from_origin(&mut self, doc : &mut DocHandler) {
+        self.refresh(doc);
+
+        let next = self.matches.iter().position(|m| m.start_pos() >= self.origin);
+        self.current = next.or(match self.matches.is_empty() {
+            true => None,
+            false => Some(0)
+        });
+
+        let (y, x) = match self.current() {
+            Some(m) => m.start_pos(),
+            None => self.origin
+        };
+        doc.set_cursor(y, x);
+        doc.break_coalescing();
+    }
+
+    //go to the next or previous match from the cursor, wrapping around the doc
+    fn step(&mut self, doc : &mut DocHandler, forward : bool) -> Outcome {
+        if self.project.is_some() {
+            return self.step_project(doc, forward)
+        }
+
+        self.refresh(doc);
+
+        if self.matches.is_empty() {
+            return self.no_matches()
+        }
+
+        let cursor = (doc.ptr_y, doc.ptr_x);
+        let last = self.matches.len() - 1;
+
+        let index = match forward {
+            true => self.matches.iter().position(|m| m.start_pos() > cursor).unwrap_or(0),
+            false => self.matches.iter().rposition(|m| m.start_pos() < cursor).unwrap_or(last)
+        };
+
+        self.current = Some(index);
+        let (y, x) = self.matches[index].start_pos();
+        doc.set_cursor(y, x);
+        doc.break_coalescing();
+
+        match (y, x) == cursor {
+            true => Outcome::Unchanged,
+            false => Outcome::Applied
+        }
+    }
+
+    //go to the next or previous project result, which may be in another file
+    fn step_project(&mut self, doc : &mut DocHandler, forward : bool) -> Outcome {
+        let project = match &mut self.project {
+            Some(project) => project,
+            None => return Outcome::Unchanged
+        };
+
+        let found = match project.step(forward).cloned() {
+            Some(found) => found,
+            None if project.is_searching() => return Outcome::Unchanged,
+            None => return self.no_matches()
+        };
+
+        let in_doc = doc.path.as_ref().is_some_and(|path| same_file(path, &found.path));
+        match in_doc {
+            true => {
+                self.refresh(doc);
+                self.current = self.matches.iter().position(|m| *m == found.found);
+                doc.set_cursor(found.found.line, found.found.start);
+                doc.break_coalescing();
+                Outcome::Applied
+            },
+            false => Outcome::Open(found.path, found.found.start_pos())
+        }
+    }
+
+    fn no_matches(&self) -> Outcome {
+        match (&self.error, self.query.is_empty()) {
+            (Some(e), _) => Outcome::Failed(e.clone()),
+            (None, true) => Outcome::Unchanged,
+            (None, false) => Outcome::Failed(format!("No results for \"{}\"", self.query))
+        }
+    }
+
+    //replace the match at the cursor then go to the next one. If the cursor is
+    //not on a match this only goes to the next one, so it can be checked first.
+    fn replace_next(&mut self, doc : &mut DocHandler) -> Outcome {
+        self.refresh(doc);
+
+        let cursor = (doc.ptr_y, doc.ptr_x);
+        let (found, regex) = match (self.current(), &self.regex) {
+            (Some(m), Some(regex)) if m.start_pos() == cursor => (m, regex),
+            _ => return self.step(doc, true)
+        };
+
+        let with = replacement(&doc.text[found.line], &found, regex, &self.replacement, self.options);
+        self.shift_selection(found.line, with.chars().count() as isize - (found.end - found.start) as isize);
+
+        doc.checkpoint(false);
+        doc.delete_range(found.start_pos(), found.end_pos());
+        doc.insert_text(&with);
+        doc.anchor = None;
+
+        self.refresh(doc);
+        match self.matches.is_empty() {
+            true => Outcome::Applied,
+            false => {
+                self.step(doc, true);
+                Outcome::Applied
+            }
+        }
+    }
+
+    //replace every match as one undo step
+    fn replace_all(&mut self, doc : &mut DocHandler) -> Outcome {
+        self.refresh(doc);
+
+        let regex = match (&self.regex, self.matches.len()) {
+            (Some(regex), total) if total > 0 => regex.clone(),
+            _ => return self.no_matches()
+        };
+
+        //the end of the selection moves with the replacements on its last line
+        if let Some((_, (end_y, _))) = self.selection {
+            let delta : isize = self.matches.iter()
+                .filter(|m| m.line == end_y)
+                .map(|m| {
+                    let with = replacement(&doc.text[m.line], m, &regex, &self.replacement, self.options);
+                    with.chars().count() as isize - (m.end - m.start) as isize
+                })
+                .sum();
+            self.shift_selection(end_y, delta);
+        }
+
+        let matches = std::mem::take(&mut self.matches);
+        doc.checkpoint(false);
+        let total = replace_all(&mut doc.text, &matches, &regex, &self.replacement, self.options);
+        doc.revision += 1;
+        doc.anchor = None;
+        doc.set_cursor(doc.ptr_y, doc.ptr_x);
+
+        self.refresh(doc);
+        self.notice = Some(format!("Replaced {} matches", total));
+        Outcome::Applied
+    }
+
+    //replace every match in the project. The first time only the preview is shown
+    //in place of the results, the files are changed when it is confirmed.
+    fn replace_in_project(&mut self, tabs : &mut Tabs, confirmed : bool) -> Outcome {
+        self.refresh(tabs.doc());
+
+        let (project, regex) = match (&mut self.project, &self.regex) {
+            (Some(project), Some(regex)) if !project.results().is_empty() => (project, regex.clone()),
+            (Some(project), _) if project.is_searching() => return Outcome::Failed(String::from("Still searching the project")),
+            _ => return self.no_matches()
+        };
+
+        if !confirmed {
+            self.confirm_replace = true;
+            self.notice = Some(format!("Replace {} matches in {} files? Replace all again to confirm", project.results().len(), project.file_count()));
+            return Outcome::Applied
+        }
+
+        let outcome = match project.replace_all(tabs.docs_mut(), &regex, &self.replacement, self.options) {
+            Ok((total, files)) => {
+                self.notice = Some(format!("Replaced {} matches in {} files", total, files));
+                Outcome::Applied
+            },
+            Err(e) => Outcome::Failed(format!("Replacing in the project failed: {}", e))
+        };
+
+        //search again so the results match the files
+        let notice = self.notice.take();
+        self.query_changed(tabs.doc_mut());
+        self.notice = notice;
+        outcome
+    }
+
+    //move the end of the in_selection range after text on its last line grew or shrank
+    fn shift_selection(&mut self, line : usize, delta : isize) {
+        if let Some((start, (end_y, end_x))) = self.selection {
+            if end_y == line {
+                self.selection = Some((start, (end_y, end_x.saturating_add_signed(delta))));
+            }
+        }
+    }
+}
+
+impl Default for FindBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
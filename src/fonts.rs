@@ -0,0 +1,169 @@
+//Fonts
+//The chain of fonts text is drawn with. Each char is drawn in the first font of
+//the chain that has a glyph for it, so the chosen font comes first followed by
+//fallbacks for what it is missing: CJK, emoji, math symbols and so on.
+//
+//Fonts are named by family, e.g. "DejaVu Sans Mono", and looked up among the
+//system fonts (on linux in the directories fontconfig is configured with), or
+//given as the path of a .ttf/.otf/.ttc file. The bundled Roboto Mono stands in
+//for the chosen font when it can't be found, and otherwise ends the chain.
+//
+//Colour emoji fonts that only have bitmaps (e.g. Noto Color Emoji) have no
+//outlines to draw, so outline fonts such as Noto Emoji or Symbola are tried first.
+
+use std::fs;
+use std::path::Path;
+
+use fontdb::{Database, Family, Query};
+use wgpu_glyph::ab_glyph::{Font, FontArc, FontVec};
+
+use crate::settings::FontSettings;
+
+//Roboto Mono from Google Fonts, designed by Christian Robertson
+pub const BUNDLED_FONT : &[u8] = include_bytes!("RobotoMono-Regular.ttf");
+const BUNDLED_FAMILY : &str = "Roboto Mono";
+
+//families tried, when installed, for chars the chosen font has no glyph for
+//when no fallback list is set
+const DEFAULT_FALLBACK : [&str; 12] = [
+    "Noto Sans Mono CJK SC",
+    "Noto Sans CJK SC",
+    "Noto Sans CJK JP",
+    "WenQuanYi Micro Hei",
+    "Noto Emoji",
+    "Symbola",
+    "Noto Sans Math",
+    "STIX Two Math",
+    "DejaVu Math TeX Gyre",
+    "DejaVu Sans Mono",
+    "DejaVu Sans",
+    "Noto Sans Symbols2"
+];
+
+pub struct FontChain {
+    pub fonts: Vec<FontArc>,
+    pub names: Vec<String>  //family or path of each font, for messages
+}
+
+impl FontChain {
+    //the fonts named in the settings that could be found, then the bundled font
+    pub fn load(settings : &FontSettings) -> Self {
+        let mut chain = Self {
+            fonts: Vec::new(),
+            names: Vec::new()
+        };
+
+        //the system fonts are only looked through when a family is asked for
+        let mut db = Database::new();
+        let by_family = settings.family.iter()
+            .chain(settings.fallback.iter().flatten())
+            .any(|name| !is_path(name));
+        if by_family || settings.fallback.is_none() {
+            db.load_system_fonts();
+        }
+
+        let bundled = FontArc::try_from_slice(BUNDLED_FONT).expect("bundled font is valid");
+        let chosen = settings.family.as_ref().and_then(|name| match load_font(&db, name) {
+            Some(font) => Some((font, name)),
+            None => {
+                eprintln!("Font \"{}\" not found, using {}", name, BUNDLED_FAMILY);
+                None
+            }
+        });
+        let found = chosen.is_some();
+
+        match chosen {
+            Some((font, name)) => chain.push(font, name),
+            None => chain.push(bundled.clone(), BUNDLED_FAMILY)
+        };
+
+        //only fonts asked for by name are worth a message when they are missing
+        match &settings.fallback {
+            Some(names) => for name in names {
+                match load_font(&db, name) {
+                    Some(font) => chain.push(font, name),
+                    None => eprintln!("Fallback font \"{}\" not found", name)
+                }
+            },
+            None => for name in DEFAULT_FALLBACK {
+                if let Some(font) = load_font(&db, name) {
+                    chain.push(font, name);
+                }
+            }
+        };
+
+        if found {
+            chain.push(bundled, BUNDLED_FAMILY);
+        }
+
+        chain
+    }
+
+    //the font everything is laid out with
+    pub fn primary(&self) -> &FontArc {
+        &self.fonts[0]
+    }
+
+    //index of the first font with a glyph for c, the primary font draws the
+    //missing glyph box when none of them have one
+    pub fn font_for(&self, c : char) -> usize {
+        self.fonts.iter().position(|font| font.glyph_id(c).0 != 0).unwrap_or(0)
+    }
+
+    //split text into runs drawn in the same font, as (text, font index)
+    pub fn runs<'a>(&self, text : &'a str) -> Vec<(&'a str, usize)> {
+        let mut runs = Vec::new();
+        let mut start = 0;
+        let mut current = None;
+
+        for (i, c) in text.char_indices() {
+            let font = self.font_for(c);
+            if current != Some(font) {
+                if let Some(previous) = current {
+                    runs.push((&text[start..i], previous));
+                }
+                start = i;
+                current = Some(font);
+            }
+        }
+
+        if let Some(font) = current {
+            runs.push((&text[start..], font));
+        }
+
+        runs
+    }
+
+    fn push(&mut self, font : FontArc, name : &str) {
+        self.fonts.push(font);
+        self.names.push(name.to_string());
+    }
+}
+
+//a font by family name or file path
+fn load_font(db : &Database, name : &str) -> Option<FontArc> {
+    if is_path(name) {
+        let data = fs::read(name).ok()?;
+        return FontVec::try_from_vec(data).ok().map(FontArc::new)
+    }
+
+    //family names are matched ignoring case, then the regular face is picked
+    let family = db.faces()
+        .flat_map(|face| face.families.iter())
+        .find(|(family, _)| family.eq_ignore_ascii_case(name))
+        .map(|(family, _)| family.clone())?;
+
+    let id = db.query(&Query {
+        families: &[Family::Name(&family)],
+        ..Query::default()
+    })?;
+
+    db.with_face_data(id, |data, index| FontVec::try_from_vec_and_index(data.to_vec(), index).ok())
+        .flatten()
+        .map(FontArc::new)
+}
+
+fn is_path(name : &str) -> bool {
+    let path = Path::new(name);
+    path.components().count() > 1 || path.extension().is_some_and(|ext| ["ttf", "otf", "ttc", "otc"].iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
@@ -1,66 +1,376 @@
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::Path;
-use std::path::PathBuf;
-use std::env::consts;
-
-pub fn load_file_txt(path : &PathBuf) -> Result<Vec<String>, std::io::Error> {
-	//create vec we will return
-	let mut result : Vec<String> = Vec::new();
-
-	//string to hold file contents upon read
-	let mut text = String::new();
-
-	//open file using path provided
-	let mut file = match File::open(&path) {
-		Ok(file) => file,
-		Err(e) => return Err(e)
-	};
-
-	//read file as string to text variable
-	match file.read_to_string(&mut text) {
-		Ok(bytes) => bytes,
-		Err(e) => return Err(e)
-	};
-	
-	//convert text to vector of string slices splitting on newline char by OS
-	let mut text : Vec<&str> = match consts::OS {
-		"windows" => 	{ text.split("\r\n").collect() },
-		_ => 			{ text.split("\n").collect()   },
-	};
-
-	//for every entry in text write as String to result vec
-	for line in text {
-		let mut _line = String::from(line); 
-		result.push( _line )
-	};
-
-	//implicitly returns Ok(result)
-	Ok(result)
-}
-
-//untested
-pub fn save_file_txt(path : &PathBuf, text : &Vec<String>) {
-	//assume that the strings in text already have \n appended to the end
-
-	//string to write all the lines of text to so we can save
-	let mut result = String::new();
-
-	//create makes a new file if none exists or destories old one and replaces with new
-	//create file
-	let mut file = match File::create(&path) {
-		Ok(file) => file,
-		Err(_) => panic!("Save failed.")
-	};
-
-	//write to result string
-	for line in text {
-		result.push_str(&line)
-	};
-
-	//save the sting to the file that was created
-	match file.write_all(result.as_bytes()) {
-		Ok(_) => println!("{:?} saved successfully.", *path),
-		Err(e) => panic!("Save failed.")
-	};
-}
\ No newline at end of file
+use std::fmt;
+use std::str::FromStr;
+use std::fs::{self, File};
+use std::io::{self, prelude::*};
+use std::path::{Path, PathBuf};
+use std::env::consts;
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::book::{Book, Level, Section};
+use crate::compile::CompileOptions;
+use crate::pdf::PdfOptions;
+
+//how lines are ended in a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+	Lf,
+	CrLf
+}
+
+//how the text of a file is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+	Utf8,
+	Utf8Bom,
+	Utf16Le,	//only recognised with a byte order mark
+	Utf16Be,
+	Latin1		//anything that is not valid utf-8, every byte is a char
+}
+
+//line ending and encoding of a file, kept so it is saved the way it was loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileFormat {
+	pub line_ending: LineEnding,
+	pub encoding: Encoding
+}
+
+impl LineEnding {
+	//the usual line ending of the OS, used for new files
+	pub fn native() -> Self {
+		match consts::OS {
+			"windows" => 	{ LineEnding::CrLf },
+			_ => 			{ LineEnding::Lf   },
+		}
+	}
+
+	pub fn as_str(self) -> &'static str {
+		match self {
+			LineEnding::Lf => "\n",
+			LineEnding::CrLf => "\r\n"
+		}
+	}
+}
+
+impl fmt::Display for LineEnding {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			LineEnding::Lf => write!(f, "LF"),
+			LineEnding::CrLf => write!(f, "CRLF")
+		}
+	}
+}
+
+//as written on the command line, e.g. "lf"
+impl FromStr for LineEnding {
+	type Err = String;
+
+	fn from_str(text : &str) -> Result<Self, Self::Err> {
+		match text.to_lowercase().as_str() {
+			"lf" => Ok(LineEnding::Lf),
+			"crlf" => Ok(LineEnding::CrLf),
+			_ => Err(format!("unknown line ending \"{}\", expected lf or crlf", text))
+		}
+	}
+}
+
+impl fmt::Display for Encoding {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Encoding::Utf8 => write!(f, "UTF-8"),
+			Encoding::Utf8Bom => write!(f, "UTF-8 BOM"),
+			Encoding::Utf16Le => write!(f, "UTF-16 LE"),
+			Encoding::Utf16Be => write!(f, "UTF-16 BE"),
+			Encoding::Latin1 => write!(f, "Latin-1")
+		}
+	}
+}
+
+//as written on the command line, e.g. "utf-8" or "utf-16le"
+impl FromStr for Encoding {
+	type Err = String;
+
+	fn from_str(text : &str) -> Result<Self, Self::Err> {
+		match text.to_lowercase().replace('_', "-").as_str() {
+			"utf-8" | "utf8" => Ok(Encoding::Utf8),
+			"utf-8-bom" | "utf8-bom" => Ok(Encoding::Utf8Bom),
+			"utf-16le" | "utf-16-le" => Ok(Encoding::Utf16Le),
+			"utf-16be" | "utf-16-be" => Ok(Encoding::Utf16Be),
+			"latin-1" | "latin1" | "iso-8859-1" => Ok(Encoding::Latin1),
+			_ => Err(format!("unknown encoding \"{}\", expected utf-8, utf-8-bom, utf-16le, utf-16be or latin-1", text))
+		}
+	}
+}
+
+impl Encoding {
+	//whether a char can be stored, latin-1 only has room for the first 256
+	pub fn can_encode(self, c : char) -> bool {
+		match self {
+			Encoding::Latin1 => (c as u32) < 256,
+			_ => true
+		}
+	}
+}
+
+impl Default for FileFormat {
+	fn default() -> Self {
+		Self {
+			line_ending: LineEnding::native(),
+			encoding: Encoding::Utf8
+		}
+	}
+}
+
+pub fn load_file_txt(path : &PathBuf) -> Result<(Vec<String>, FileFormat), std::io::Error> {
+	//bytes of the file
+	let mut bytes : Vec<u8> = Vec::new();
+
+	//open file using path provided
+	let mut file = File::open(path)?;
+
+	//read the whole file, it is decoded below
+	file.read_to_end(&mut bytes)?;
+
+	let (text, encoding) = decode(&bytes);
+
+	//the first line break decides the line ending, with CRLF the \r is taken off every line
+	let line_ending = match text.find('\n') {
+		Some(i) if text[..i].ends_with('\r') => LineEnding::CrLf,
+		Some(_) => LineEnding::Lf,
+		None => LineEnding::native()
+	};
+
+	let result : Vec<String> = match line_ending {
+		LineEnding::CrLf => 	{ text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line).to_string()).collect() },
+		LineEnding::Lf => 		{ text.split('\n').map(String::from).collect() },
+	};
+
+	Ok( (result, FileFormat { line_ending, encoding }) )
+}
+
+pub fn save_file_txt(path : &PathBuf, text : &[String], format : FileFormat) -> Result<(), std::io::Error> {
+	//lines are stored without their line endings so join them back up
+	//using the line ending the file was loaded with
+	let result = text.join(format.line_ending.as_str());
+
+	//refuse rather than write chars the encoding has no room for as ?
+	let lost : Vec<char> = result.chars().filter(|c| !format.encoding.can_encode(*c)).collect();
+	if let Some(first) = lost.first() {
+		let message = match lost.len() {
+			1 => format!("\"{}\" cannot be stored in {}, convert the file to UTF-8 to keep it", first, format.encoding),
+			n => format!("\"{}\" and {} more chars cannot be stored in {}, convert the file to UTF-8 to keep them", first, n - 1, format.encoding)
+		};
+		return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+	}
+
+	//create makes a new file if none exists or destories old one and replaces with new
+	//create file
+	let mut file = File::create(path)?;
+
+	//save the sting to the file that was created, in the encoding it was loaded in
+	file.write_all(&encode(&result, format.encoding))?;
+	Ok(())
+}
+
+//work out the encoding of a file from its byte order mark, falling back to latin-1
+//when it is not valid utf-8
+pub fn decode(bytes : &[u8]) -> (String, Encoding) {
+	let utf16 = |bytes : &[u8], from : fn([u8; 2]) -> u16| {
+		let units = bytes.chunks_exact(2).map(|pair| from([pair[0], pair[1]]));
+		char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect::<String>()
+	};
+
+	match bytes {
+		[0xEF, 0xBB, 0xBF, rest @ ..] => (String::from_utf8_lossy(rest).into_owned(), Encoding::Utf8Bom),
+		[0xFF, 0xFE, rest @ ..] => (utf16(rest, u16::from_le_bytes), Encoding::Utf16Le),
+		[0xFE, 0xFF, rest @ ..] => (utf16(rest, u16::from_be_bytes), Encoding::Utf16Be),
+		_ => match std::str::from_utf8(bytes) {
+			Ok(text) => (text.to_string(), Encoding::Utf8),
+			Err(_) => (bytes.iter().map(|b| *b as char).collect(), Encoding::Latin1)
+		}
+	}
+}
+
+//the bytes to write for text in an encoding, chars latin-1 has no room for become ? (save_file_txt refuses to write those)
+pub fn encode(text : &str, encoding : Encoding) -> Vec<u8> {
+	match encoding {
+		Encoding::Utf8 => text.as_bytes().to_vec(),
+		Encoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat(),
+		Encoding::Utf16Le => [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect(),
+		Encoding::Utf16Be => [0xFE, 0xFF].into_iter().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect(),
+		Encoding::Latin1 => text.chars().map(|c| u8::try_from(c).unwrap_or(b'?')).collect()
+	}
+}
+
+//Manifest
+//book.toml lists the book's title and author and its sections in reading order,
+//as an array of parts each with an array of chapters, or straight away an array
+//of chapters for a book without parts, e.g.
+//
+//  title = "The Garbanzo"
+//  author = "A. Writer"
+//  language = "en-GB"          # optional, see book.rs for these three
+//  identifier = "urn:isbn:9780000000000"
+//  cover = "cover.jpg"
+//
+//  [[part]]
+//  title = "Spring"
+//
+//  [[part.chapter]]
+//  title = "Sowing"
+//
+//  [[part.chapter.scene]]
+//  title = "The field"
+//  file = "spring/sowing/field.txt"
+//
+//Files are relative to the folder of the manifest. A chapter can have a file of
+//its own instead of, or as well as, scenes. Any section can have include = false to
+//leave it out of the compiled book, which is set up by a [compile] table (see compile.rs)
+//and laid out on pages by a [pdf] table (see pdf.rs).
+
+#[derive(Debug)]
+pub enum ManifestError {
+	Io(io::Error),
+	Parse(String)
+}
+
+impl fmt::Display for ManifestError {
+	fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			ManifestError::Io(e) => write!(f, "could not read the book: {}", e),
+			ManifestError::Parse(e) => write!(f, "the book is not valid: {}", e)
+		}
+	}
+}
+
+impl Error for ManifestError {}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+	title: String,
+	#[serde(default)]
+	author: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	language: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	identifier: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	cover: Option<PathBuf>,
+	#[serde(default, skip_serializing_if = "CompileOptions::is_default")]
+	compile: CompileOptions,
+	#[serde(default, skip_serializing_if = "PdfOptions::is_default")]
+	pdf: PdfOptions,
+	#[serde(default, rename = "part", skip_serializing_if = "Vec::is_empty")]
+	parts: Vec<SectionFile>,
+	#[serde(default, rename = "chapter", skip_serializing_if = "Vec::is_empty")]
+	chapters: Vec<SectionFile>
+}
+
+//a part, chapter or scene, which of them is known from where it is
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SectionFile {
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	title: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	file: Option<PathBuf>,
+	#[serde(default = "included", skip_serializing_if = "is_included")]
+	include: bool,
+	#[serde(default, rename = "chapter", skip_serializing_if = "Vec::is_empty")]
+	chapters: Vec<SectionFile>,
+	#[serde(default, rename = "scene", skip_serializing_if = "Vec::is_empty")]
+	scenes: Vec<SectionFile>
+}
+
+//the book in a manifest, with the files of its sections resolved against the manifest's folder
+pub fn load_manifest(path : &Path) -> Result<Book, ManifestError> {
+	let text = fs::read_to_string(path).map_err(ManifestError::Io)?;
+	let manifest : ManifestFile = toml::from_str(&text).map_err(|e| ManifestError::Parse(e.to_string()))?;
+	let folder = path.parent().unwrap_or(Path::new(""));
+
+	let (level, sections) = match (manifest.parts.is_empty(), manifest.chapters.is_empty()) {
+		(false, false) => return Err(ManifestError::Parse(String::from("a book has parts or chapters at the top, not both"))),
+		(false, true) => (Level::Part, manifest.parts),
+		(true, _) => (Level::Chapter, manifest.chapters)
+	};
+	manifest.pdf.validate().map_err(ManifestError::Parse)?;
+
+	Ok(Book {
+		title: manifest.title,
+		author: manifest.author,
+		language: manifest.language,
+		identifier: manifest.identifier,
+		cover: manifest.cover.map(|cover| folder.join(cover)),
+		compile: manifest.compile,
+		pdf: manifest.pdf,
+		sections: sections.into_iter().map(|section| section_from_file(section, level, folder)).collect::<Result<_, _>>()?
+	})
+}
+
+//write a book to a manifest, files under the manifest's folder are written relative to it
+pub fn save_manifest(path : &Path, book : &Book) -> Result<(), ManifestError> {
+	let folder = path.parent().unwrap_or(Path::new(""));
+	let sections = book.sections.iter().map(|section| section_to_file(section, folder)).collect();
+	let (parts, chapters) = match book.sections.first().map(|section| section.level) {
+		Some(Level::Part) => (sections, Vec::new()),
+		_ => (Vec::new(), sections)
+	};
+	let manifest = ManifestFile {
+		title: book.title.clone(),
+		author: book.author.clone(),
+		language: book.language.clone(),
+		identifier: book.identifier.clone(),
+		cover: book.cover.as_ref().map(|cover| cover.strip_prefix(folder).unwrap_or(cover).to_path_buf()),
+		compile: book.compile.clone(),
+		pdf: book.pdf.clone(),
+		parts,
+		chapters
+	};
+
+	let text = toml::to_string(&manifest).map_err(|e| ManifestError::Parse(e.to_string()))?;
+	fs::write(path, text).map_err(ManifestError::Io)
+}
+
+fn section_from_file(section : SectionFile, level : Level, folder : &Path) -> Result<Section, ManifestError> {
+	//only the level below may be listed, e.g. a part has chapters and no scenes
+	let children = match (level, section.chapters.is_empty(), section.scenes.is_empty()) {
+		(Level::Part, _, true) => section.chapters,
+		(Level::Chapter, true, _) => section.scenes,
+		(Level::Scene, true, true) => Vec::new(),
+		_ => return Err(ManifestError::Parse(format!("a {} can only have {}s in it", level.name(), level.child().map_or("no section", Level::name))))
+	};
+
+	let mut parsed = Section::new(level, &section.title, section.file.map(|file| folder.join(file)));
+	parsed.include = section.include;
+	if let Some(child) = level.child() {
+		parsed.sections = children.into_iter().map(|section| section_from_file(section, child, folder)).collect::<Result<_, _>>()?;
+	}
+	Ok(parsed)
+}
+
+fn section_to_file(section : &Section, folder : &Path) -> SectionFile {
+	let children = section.sections.iter().map(|section| section_to_file(section, folder)).collect();
+	let (chapters, scenes) = match section.level {
+		Level::Part => (children, Vec::new()),
+		_ => (Vec::new(), children)
+	};
+
+	SectionFile {
+		title: section.title.clone(),
+		file: section.file.as_ref().map(|file| file.strip_prefix(folder).unwrap_or(file).to_path_buf()),
+		include: section.include,
+		chapters,
+		scenes
+	}
+}
+
+//sections are included unless the manifest says otherwise
+fn included() -> bool {
+	true
+}
+
+fn is_included(include : &bool) -> bool {
+	*include
+}
@@ -0,0 +1,794 @@
+//Keymap
+//Translates winit keyboard events (plus the modifiers held at the time) into
+//EditorCommands. Printable text arrives through WindowEvent::ReceivedCharacter so
+//shifted and non-ascii chars are handled by the OS; everything else is looked up
+//in a table of key sequences, e.g. "ctrl+s" or the two chord "ctrl+k ctrl+s".
+//
+//Bindings can be changed with a keymap.toml file. Each section maps key sequences
+//to command names and is layered over the built in defaults of the profile:
+//
+//  profile = "vim"             # optional, "default", "vim" or "emacs"
+//
+//  [global]                    # applies in every mode
+//  "ctrl+k ctrl+s" = "save"
+//  "ctrl+z" = "unbound"        # remove a default binding
+//
+//  [insert]                    # only applies in insert mode
+//  "alt+minus" = "insert:—"
+//
+//  [find]                      # only applies while the find bar is open
+//  "ctrl+g" = "find_next"
+//
+//  [sidebar]                   # only applies while the project sidebar is focused
+//  "ctrl+o" = "open_section"
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use winit::event::{
+    ElementState,
+    KeyboardInput,
+    ModifiersState,
+    VirtualKeyCode
+};
+
+use crate::command::{command_names, EditorCommand, Motion, Repeat};
+use crate::search::SearchOption;
+
+//command name used in a keymap file to remove a binding
+const UNBOUND : &str = "unbound";
+
+//Keymap section. Global bindings apply in every mode, the rest only apply while
+//the editor is in that mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Global,
+    Insert,
+    Normal,
+    Visual,
+    Find,   //while the find bar is open
+    Sidebar //while the project sidebar has the keyboard
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Global => "global",
+            Mode::Insert => "insert",
+            Mode::Normal => "normal",
+            Mode::Visual => "visual",
+            Mode::Find => "find",
+            Mode::Sidebar => "sidebar"
+        }
+    }
+}
+
+//Editing style picked with the profile key of keymap.toml
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    #[default]
+    Default,
+    Vim,    //modal editing, see vim.rs
+    Emacs   //emacs bindings with a kill ring, see emacs.rs
+}
+
+//A single key press together with the modifiers that must be held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: VirtualKeyCode,
+    pub modifiers: ModifiersState
+}
+
+pub type KeySequence = Vec<KeyChord>;
+
+pub struct Keymap {
+    pub profile: Profile,
+    pub mode: Mode, //mode used to pick bindings, never Global
+    bindings: HashMap<Mode, HashMap<KeySequence, EditorCommand>>,
+    pending: KeySequence, //chords typed so far of a multi chord sequence
+    swallow_char: bool    //set when the last key press was used by a binding
+}
+
+//result of looking up the pending chords
+enum Lookup {
+    Command(EditorCommand),
+    Prefix,
+    Unbound
+}
+
+//on disk layout of keymap.toml
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    #[serde(default, skip_serializing_if = "is_default_profile")]
+    profile: Profile,
+    #[serde(default)]
+    global: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    insert: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    normal: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    visual: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    find: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    sidebar: BTreeMap<String, String>
+}
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(io::Error),
+    Parse(String),
+    Binding {
+        mode: Mode,
+        keys: String,
+        message: String
+    }
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::Io(e) => write!(f, "could not read keymap: {}", e),
+            KeymapError::Parse(e) => write!(f, "keymap is not valid toml: {}", e),
+            KeymapError::Binding { mode, keys, message } => write!(f, "[{}] \"{}\": {}", mode.name(), keys, message)
+        }
+    }
+}
+
+impl Error for KeymapError {}
+
+impl Keymap {
+    //a keymap with no bindings at all
+    pub fn new() -> Self {
+        Self {
+            profile: Profile::Default,
+            mode: Mode::Insert,
+            bindings: HashMap::new(),
+            pending: Vec::new(),
+            swallow_char: false
+        }
+    }
+
+    //bind a single key chord to a command in the global section
+    pub fn bind(&mut self, key : VirtualKeyCode, modifiers : ModifiersState, command : EditorCommand) {
+        self.bind_sequence(Mode::Global, vec![KeyChord { key, modifiers }], command);
+    }
+
+    //bind a key sequence to a command, replacing any existing binding
+    pub fn bind_sequence(&mut self, mode : Mode, keys : KeySequence, command : EditorCommand) {
+        self.bindings.entry(mode).or_default().insert(keys, command);
+    }
+
+    //bindings for the find bar. Shared by every profile: tab switches between the
+    //query and the replacement, ctrl+enter replaces the match at the cursor and
+    //ctrl+alt+enter replaces them all, alt+c/w/r/p/l toggle the search options.
+    fn bind_find_bar(&mut self, bindings : &[(VirtualKeyCode, ModifiersState, EditorCommand)]) {
+        let shared = [
+            (VirtualKeyCode::Tab, ModifiersState::empty(), EditorCommand::SwitchFindField),
+            (VirtualKeyCode::Return, ModifiersState::CTRL, EditorCommand::ReplaceNext),
+            (VirtualKeyCode::Return, ModifiersState::CTRL | ModifiersState::ALT, EditorCommand::ReplaceAll)
+        ];
+
+        for (key, modifiers, command) in bindings.iter().chain(&shared) {
+            self.bind_sequence(Mode::Find, vec![KeyChord { key: *key, modifiers: *modifiers }], command.clone());
+        }
+
+        let options = [
+            (VirtualKeyCode::C, SearchOption::CaseSensitive),
+            (VirtualKeyCode::W, SearchOption::WholeWord),
+            (VirtualKeyCode::R, SearchOption::Regex),
+            (VirtualKeyCode::P, SearchOption::PreserveCase),
+            (VirtualKeyCode::L, SearchOption::InSelection)
+        ];
+        for (key, option) in options {
+            let keys = vec![KeyChord { key, modifiers: ModifiersState::ALT }];
+            self.bind_sequence(Mode::Find, keys, EditorCommand::ToggleSearchOption(option));
+        }
+    }
+
+    //bindings for the project sidebar. Shared by every profile: enter opens the selected
+    //section, alt+up/down move it, space leaves it out of the compiled book or puts it
+    //back and escape goes back to the text. The arrow keys
+    //move the selection through the usual movement commands.
+    fn bind_sidebar(&mut self, bindings : &[(VirtualKeyCode, ModifiersState, EditorCommand)]) {
+        let shared = [
+            (VirtualKeyCode::Return, ModifiersState::empty(), EditorCommand::OpenSection),
+            (VirtualKeyCode::NumpadEnter, ModifiersState::empty(), EditorCommand::OpenSection),
+            (VirtualKeyCode::Up, ModifiersState::ALT, EditorCommand::MoveSectionUp),
+            (VirtualKeyCode::Down, ModifiersState::ALT, EditorCommand::MoveSectionDown),
+            (VirtualKeyCode::Space, ModifiersState::empty(), EditorCommand::ToggleInclude),
+            (VirtualKeyCode::Escape, ModifiersState::empty(), EditorCommand::FocusSidebar)
+        ];
+
+        for (key, modifiers, command) in bindings.iter().chain(&shared) {
+            self.bind_sequence(Mode::Sidebar, vec![KeyChord { key: *key, modifiers: *modifiers }], command.clone());
+        }
+    }
+
+    pub fn unbind_sequence(&mut self, mode : Mode, keys : &KeySequence) {
+        if let Some(section) = self.bindings.get_mut(&mode) {
+            section.remove(keys);
+        }
+    }
+
+    //the built in bindings of a profile
+    pub fn for_profile(profile : Profile) -> Self {
+        let keymap = match profile {
+            Profile::Emacs => Self::emacs(),
+            Profile::Default | Profile::Vim => Self::default()
+        };
+
+        Self { profile, ..keymap }
+    }
+
+    //the default keymap with the bindings from a keymap file layered on top.
+    //A missing file is not an error, the defaults are used as is.
+    pub fn load(path : &Path) -> Result<Self, KeymapError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(KeymapError::Io(e))
+        }
+    }
+
+    //the profile's keymap with the bindings from the contents of a keymap file
+    pub fn from_toml(text : &str) -> Result<Self, KeymapError> {
+        let file : KeymapFile = toml::from_str(text).map_err(|e| KeymapError::Parse(e.to_string()))?;
+        let mut keymap = Self::for_profile(file.profile);
+
+        let sections = [
+            (Mode::Global, &file.global),
+            (Mode::Insert, &file.insert),
+            (Mode::Normal, &file.normal),
+            (Mode::Visual, &file.visual),
+            (Mode::Find, &file.find),
+            (Mode::Sidebar, &file.sidebar)
+        ];
+
+        for (mode, section) in sections {
+            //keys that are spelt differently but parse to the same sequence
+            let mut seen : HashMap<KeySequence, &str> = HashMap::new();
+
+            for (keys, name) in section {
+                let binding_error = |message : String| KeymapError::Binding { mode, keys: keys.clone(), message };
+
+                let sequence = parse_sequence(keys).map_err(binding_error)?;
+
+                if let Some(other) = seen.insert(sequence.clone(), keys) {
+                    return Err(binding_error(format!("same keys as \"{}\" in this section", other)))
+                }
+
+                match name.as_str() {
+                    UNBOUND => keymap.unbind_sequence(mode, &sequence),
+                    _ => {
+                        let command = name.parse::<EditorCommand>().map_err(|e| binding_error(with_suggestion(e, name)))?;
+                        keymap.bind_sequence(mode, sequence, command);
+                    }
+                }
+            }
+        }
+
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    //check that no binding is the start of a longer sequence in the same mode,
+    //since the longer one could never be typed
+    pub fn validate(&self) -> Result<(), KeymapError> {
+        for mode in [Mode::Insert, Mode::Normal, Mode::Visual, Mode::Find, Mode::Sidebar] {
+            let visible : Vec<(Mode, &KeySequence, &EditorCommand)> = [Mode::Global, mode].into_iter()
+                .filter_map(|m| self.bindings.get(&m).map(|section| (m, section)))
+                .flat_map(|(m, section)| section.iter().map(move |(keys, command)| (m, keys, command)))
+                .collect();
+
+            for (section, keys, command) in &visible {
+                for (_, longer, _) in &visible {
+                    if longer.len() > keys.len() && longer.starts_with(keys) {
+                        return Err(KeymapError::Binding {
+                            mode: *section,
+                            keys: format_sequence(keys),
+                            message: format!(
+                                "bound to {} but is also the start of \"{}\", unbind one of them with \"{}\"",
+                                command, format_sequence(longer), UNBOUND
+                            )
+                        })
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    //contents of a keymap file that recreates this keymap
+    pub fn to_toml(&self) -> String {
+        let mut file = KeymapFile {
+            profile: self.profile,
+            ..KeymapFile::default()
+        };
+
+        for (mode, section) in &self.bindings {
+            let target = match mode {
+                Mode::Global => &mut file.global,
+                Mode::Insert => &mut file.insert,
+                Mode::Normal => &mut file.normal,
+                Mode::Visual => &mut file.visual,
+                Mode::Find => &mut file.find,
+                Mode::Sidebar => &mut file.sidebar
+            };
+
+            for (keys, command) in section {
+                target.insert(format_sequence(keys), command.to_string());
+            }
+        }
+
+        let mut text = String::from("# garbanzo_book keymap\n");
+        text.push_str("# commands: ");
+        text.push_str(&command_names().join(", "));
+        text.push_str(", insert:<char>, macro:<name>[:<count>|:until_fail], save_macro:<name>, unbound\n\n");
+        text.push_str(&toml::to_string(&file).expect("keymap serializes to toml"));
+        text
+    }
+
+    //write this keymap to disk as a keymap file
+    pub fn save(&self, path : &Path) -> Result<(), io::Error> {
+        fs::write(path, self.to_toml())
+    }
+
+    //command bound to a key press, key releases are ignored.
+    //Returns None while in the middle of a multi chord sequence.
+    pub fn translate(&mut self, input : &KeyboardInput, modifiers : ModifiersState) -> Option<EditorCommand> {
+        match input {
+            KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. } => self.translate_key(*key, modifiers),
+            _ => None
+        }
+    }
+
+    //command bound to a pressed key, for frontends without winit's keyboard events
+    pub fn translate_key(&mut self, key : VirtualKeyCode, modifiers : ModifiersState) -> Option<EditorCommand> {
+        //pressing ctrl on its own should not break up "ctrl+k ctrl+s"
+        if is_modifier_key(key) {
+            return None
+        }
+
+        self.pending.push(KeyChord { key, modifiers });
+
+        match self.lookup(&self.pending) {
+            Lookup::Command(command) => {
+                self.pending.clear();
+                self.swallow_char = true;
+                Some(command)
+            },
+            Lookup::Prefix => {
+                self.swallow_char = true;
+                None
+            },
+            Lookup::Unbound => {
+                //the tail end of an unknown sequence should not be typed either
+                self.swallow_char = self.pending.len() > 1;
+                self.pending.clear();
+                None
+            }
+        }
+    }
+
+    //command for a char of typed text.
+    //Chars typed while ctrl/alt/logo are held belong to key chords, not the text,
+    //as do chars whose key press was already used by a binding.
+    pub fn translate_char(&mut self, c : char, modifiers : ModifiersState) -> Option<EditorCommand> {
+        let chord_held = modifiers.ctrl() || modifiers.alt() || modifiers.logo();
+        let swallowed = std::mem::take(&mut self.swallow_char);
+
+        match c.is_control() || chord_held || swallowed {
+            true => None,
+            false => Some(EditorCommand::InsertChar(c))
+        }
+    }
+
+    //true while the first chords of a multi chord sequence have been typed
+    pub fn is_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    //chords typed so far of a multi chord sequence, e.g. "ctrl+x"
+    pub fn pending_keys(&self) -> String {
+        format_sequence(&self.pending)
+    }
+
+    fn lookup(&self, keys : &KeySequence) -> Lookup {
+        //bindings for the current mode win over global ones
+        let sections : Vec<&HashMap<KeySequence, EditorCommand>> = [self.mode, Mode::Global].iter()
+            .filter_map(|m| self.bindings.get(m))
+            .collect();
+
+        for section in &sections {
+            if let Some(command) = section.get(keys) {
+                return Lookup::Command(command.clone())
+            }
+        }
+
+        let is_prefix = sections.iter().any(|section| section.keys().any(|bound| bound.starts_with(keys)));
+
+        match is_prefix {
+            true => Lookup::Prefix,
+            false => Lookup::Unbound
+        }
+    }
+}
+
+impl Default for Keymap {
+    //the built in bindings
+    fn default() -> Self {
+        use EditorCommand::*;
+        use VirtualKeyCode as Key;
+
+        let none = ModifiersState::empty();
+        let ctrl = ModifiersState::CTRL;
+        let alt = ModifiersState::ALT;
+        let shift = ModifiersState::SHIFT;
+        let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+        let chords = |keys : &[(Key, ModifiersState)]| -> KeySequence {
+            keys.iter().map(|(key, modifiers)| KeyChord { key: *key, modifiers: *modifiers }).collect()
+        };
+
+        let mut keymap = Self::new();
+
+        //Misc
+        keymap.bind(Key::Escape, none, Quit);
+        keymap.bind(Key::Return, none, InsertNewline);
+        keymap.bind(Key::NumpadEnter, none, InsertNewline);
+        keymap.bind(Key::S, ctrl, Save);
+        keymap.bind(Key::P, ctrl, ExportPdf);
+        keymap.bind(Key::Z, ctrl, Undo);
+        keymap.bind(Key::Y, ctrl, Redo);
+        keymap.bind(Key::Z, ctrl_shift, Redo);
+        keymap.bind(Key::R, ctrl_shift, RecordMacro);
+        keymap.bind(Key::P, ctrl_shift, PlayMacro(None, Repeat::Times(1)));
+        keymap.bind(Key::L, ctrl_shift, CycleLineNumbers);
+
+        //Zoom, ctrl+shift+equals is ctrl++ on most layouts
+        keymap.bind(Key::Equals, ctrl, ZoomIn);
+        keymap.bind(Key::Equals, ctrl_shift, ZoomIn);
+        keymap.bind(Key::NumpadAdd, ctrl, ZoomIn);
+        keymap.bind(Key::Minus, ctrl, ZoomOut);
+        keymap.bind(Key::NumpadSubtract, ctrl, ZoomOut);
+        keymap.bind(Key::Key0, ctrl, ZoomReset);
+        keymap.bind(Key::Numpad0, ctrl, ZoomReset);
+
+        //Tabs, ctrl+page down/up for terminals that don't pass ctrl+tab on
+        keymap.bind(Key::T, ctrl, NewTab);
+        keymap.bind(Key::W, ctrl, CloseTab);
+        keymap.bind(Key::Tab, ctrl, NextTab);
+        keymap.bind(Key::Tab, ctrl_shift, PreviousTab);
+        keymap.bind(Key::PageDown, ctrl, NextTab);
+        keymap.bind(Key::PageUp, ctrl, PreviousTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::K, ctrl), (Key::W, ctrl)]), CloseAll);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::K, ctrl), (Key::S, none)]), SaveAll);
+
+        //Panes
+        keymap.bind(Key::Backslash, ctrl, SplitVertical);
+        keymap.bind(Key::Backslash, ctrl_shift, SplitHorizontal);
+        keymap.bind(Key::F6, none, NextPane);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::K, ctrl), (Key::Backslash, ctrl)]), ClosePane);
+
+        //Project sidebar
+        keymap.bind(Key::B, ctrl, ToggleSidebar);
+        keymap.bind(Key::E, ctrl_shift, FocusSidebar);
+        keymap.bind_sidebar(&[]);
+
+        //Find
+        keymap.bind(Key::F, ctrl, Find);
+        keymap.bind(Key::H, ctrl, Replace);
+        keymap.bind(Key::F, ctrl_shift, FindInProject);
+        keymap.bind(Key::F3, none, FindNext);
+        keymap.bind(Key::F3, shift, FindPrevious);
+        keymap.bind_find_bar(&[
+            (Key::Return, none, FindNext),
+            (Key::NumpadEnter, none, FindNext),
+            (Key::Return, shift, FindPrevious),
+            (Key::Escape, none, CloseFind)
+        ]);
+
+        //Cursor Movement
+        //Ctrl moves by word, Alt moves by sentence (left/right) or paragraph (up/down)
+        keymap.bind(Key::Left, none, Move(Motion::Left));
+        keymap.bind(Key::Right, none, Move(Motion::Right));
+        keymap.bind(Key::Up, none, Move(Motion::Up));
+        keymap.bind(Key::Down, none, Move(Motion::Down));
+        keymap.bind(Key::Left, ctrl, Move(Motion::WordLeft));
+        keymap.bind(Key::Right, ctrl, Move(Motion::WordRight));
+        keymap.bind(Key::Left, alt, Move(Motion::SentenceBack));
+        keymap.bind(Key::Right, alt, Move(Motion::SentenceForward));
+        keymap.bind(Key::Up, alt, Move(Motion::ParagraphUp));
+        keymap.bind(Key::Down, alt, Move(Motion::ParagraphDown));
+        keymap.bind(Key::Home, none, Move(Motion::LineStart));
+        keymap.bind(Key::End, none, Move(Motion::LineEnd));
+        keymap.bind(Key::Home, ctrl, Move(Motion::DocStart));
+        keymap.bind(Key::End, ctrl, Move(Motion::DocEnd));
+
+        //Deletion
+        keymap.bind(Key::Back, none, Delete(Motion::Left));
+        keymap.bind(Key::Delete, none, Delete(Motion::Right));
+        keymap.bind(Key::Back, ctrl, Delete(Motion::WordLeft));
+        keymap.bind(Key::Delete, ctrl, Delete(Motion::WordRight));
+
+        keymap
+    }
+}
+
+impl Keymap {
+    //the built in bindings of the emacs profile.
+    //C is ctrl and M (meta) is alt, so C-x C-s is "ctrl+x ctrl+s" and C-x ( is "ctrl+x shift+9".
+    pub fn emacs() -> Self {
+        use EditorCommand::*;
+        use VirtualKeyCode as Key;
+
+        let none = ModifiersState::empty();
+        let ctrl = ModifiersState::CTRL;
+        let alt = ModifiersState::ALT;
+        let shift = ModifiersState::SHIFT;
+        let alt_shift = ModifiersState::ALT | ModifiersState::SHIFT;
+        let ctrl_shift = ModifiersState::CTRL | ModifiersState::SHIFT;
+        let chords = |keys : &[(Key, ModifiersState)]| -> KeySequence {
+            keys.iter().map(|(key, modifiers)| KeyChord { key: *key, modifiers: *modifiers }).collect()
+        };
+
+        let mut keymap = Self::new();
+        keymap.profile = Profile::Emacs;
+
+        //Misc
+        keymap.bind(Key::Return, none, InsertNewline);
+        keymap.bind(Key::NumpadEnter, none, InsertNewline);
+        keymap.bind(Key::G, ctrl, Cancel);
+        keymap.bind(Key::Slash, ctrl, Undo);
+        keymap.bind(Key::Minus, ctrl_shift, Undo);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::S, ctrl)]), Save);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::C, ctrl)]), Quit);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::U, none)]), Undo);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Key9, shift)]), RecordMacro);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Key0, shift)]), RecordMacro);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Equals, ctrl)]), ZoomIn);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Minus, ctrl)]), ZoomOut);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Key0, ctrl)]), ZoomReset);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::E, none)]), PlayMacro(None, Repeat::Times(1)));
+
+        //Tabs, as in the tab bar of emacs 27
+        keymap.bind(Key::Tab, ctrl, NextTab);
+        keymap.bind(Key::Tab, ctrl_shift, PreviousTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::T, none), (Key::Key2, none)]), NewTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::T, none), (Key::O, none)]), NextTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::T, none), (Key::O, shift)]), PreviousTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::T, none), (Key::Key0, none)]), CloseTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::K, none)]), CloseTab);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::S, none)]), SaveAll);
+
+        //Windows, which are panes here
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Key2, none)]), SplitHorizontal);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Key3, none)]), SplitVertical);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::O, none)]), NextPane);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::Key0, none)]), ClosePane);
+
+        //Project sidebar, C-x d like dired
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::D, none)]), FocusSidebar);
+        keymap.bind_sequence(Mode::Global, chords(&[(Key::X, ctrl), (Key::D, ctrl)]), ToggleSidebar);
+        keymap.bind_sidebar(&[
+            (Key::G, ctrl, FocusSidebar),
+            (Key::P, alt, MoveSectionUp),
+            (Key::N, alt, MoveSectionDown)
+        ]);
+
+        //Cursor Movement
+        keymap.bind(Key::F, ctrl, Move(Motion::Right));
+        keymap.bind(Key::B, ctrl, Move(Motion::Left));
+        keymap.bind(Key::N, ctrl, Move(Motion::Down));
+        keymap.bind(Key::P, ctrl, Move(Motion::Up));
+        keymap.bind(Key::A, ctrl, Move(Motion::LineStart));
+        keymap.bind(Key::E, ctrl, Move(Motion::LineEnd));
+        keymap.bind(Key::F, alt, Move(Motion::WordRight));
+        keymap.bind(Key::B, alt, Move(Motion::WordLeft));
+        keymap.bind(Key::A, alt, Move(Motion::SentenceBack));
+        keymap.bind(Key::E, alt, Move(Motion::SentenceForward));
+        keymap.bind(Key::LBracket, alt_shift, Move(Motion::ParagraphUp));
+        keymap.bind(Key::RBracket, alt_shift, Move(Motion::ParagraphDown));
+        keymap.bind(Key::Comma, alt_shift, Move(Motion::DocStart));
+        keymap.bind(Key::Period, alt_shift, Move(Motion::DocEnd));
+        keymap.bind(Key::Left, none, Move(Motion::Left));
+        keymap.bind(Key::Right, none, Move(Motion::Right));
+        keymap.bind(Key::Up, none, Move(Motion::Up));
+        keymap.bind(Key::Down, none, Move(Motion::Down));
+        keymap.bind(Key::Home, none, Move(Motion::LineStart));
+        keymap.bind(Key::End, none, Move(Motion::LineEnd));
+
+        //Deletion, killing and yanking
+        keymap.bind(Key::Back, none, Delete(Motion::Left));
+        keymap.bind(Key::Delete, none, Delete(Motion::Right));
+        keymap.bind(Key::D, ctrl, Delete(Motion::Right));
+        keymap.bind(Key::D, alt, Kill(Motion::WordRight));
+        keymap.bind(Key::Back, alt, Kill(Motion::WordLeft));
+        keymap.bind(Key::K, ctrl, KillLine);
+        keymap.bind(Key::W, ctrl, KillRegion);
+        keymap.bind(Key::W, alt, CopyRegion);
+        keymap.bind(Key::Y, ctrl, Yank);
+        keymap.bind(Key::Y, alt, YankPop);
+        keymap.bind(Key::Space, ctrl, SetMark);
+
+        //Incremental search, C-s and C-r step through the matches, M-c and M-r
+        //toggle case sensitivity and regex like in isearch
+        keymap.bind(Key::S, ctrl, Find);
+        keymap.bind(Key::R, ctrl, Find);
+        keymap.bind(Key::Key5, alt_shift, Replace);
+        keymap.bind(Key::F, ctrl_shift, FindInProject);
+        keymap.bind_find_bar(&[
+            (Key::S, ctrl, FindNext),
+            (Key::R, ctrl, FindPrevious),
+            (Key::Return, none, CloseFind),
+            (Key::G, ctrl, CloseFind),
+            (Key::Escape, none, CloseFind)
+        ]);
+
+        keymap
+    }
+}
+
+//Names used for keys in keymap files
+const KEY_NAMES : [(&str, VirtualKeyCode); 77] = [
+    ("a", VirtualKeyCode::A), ("b", VirtualKeyCode::B), ("c", VirtualKeyCode::C),
+    ("d", VirtualKeyCode::D), ("e", VirtualKeyCode::E), ("f", VirtualKeyCode::F),
+    ("g", VirtualKeyCode::G), ("h", VirtualKeyCode::H), ("i", VirtualKeyCode::I),
+    ("j", VirtualKeyCode::J), ("k", VirtualKeyCode::K), ("l", VirtualKeyCode::L),
+    ("m", VirtualKeyCode::M), ("n", VirtualKeyCode::N), ("o", VirtualKeyCode::O),
+    ("p", VirtualKeyCode::P), ("q", VirtualKeyCode::Q), ("r", VirtualKeyCode::R),
+    ("s", VirtualKeyCode::S), ("t", VirtualKeyCode::T), ("u", VirtualKeyCode::U),
+    ("v", VirtualKeyCode::V), ("w", VirtualKeyCode::W), ("x", VirtualKeyCode::X),
+    ("y", VirtualKeyCode::Y), ("z", VirtualKeyCode::Z),
+    ("0", VirtualKeyCode::Key0), ("1", VirtualKeyCode::Key1), ("2", VirtualKeyCode::Key2),
+    ("3", VirtualKeyCode::Key3), ("4", VirtualKeyCode::Key4), ("5", VirtualKeyCode::Key5),
+    ("6", VirtualKeyCode::Key6), ("7", VirtualKeyCode::Key7), ("8", VirtualKeyCode::Key8),
+    ("9", VirtualKeyCode::Key9),
+    ("f1", VirtualKeyCode::F1), ("f2", VirtualKeyCode::F2), ("f3", VirtualKeyCode::F3),
+    ("f4", VirtualKeyCode::F4), ("f5", VirtualKeyCode::F5), ("f6", VirtualKeyCode::F6),
+    ("f7", VirtualKeyCode::F7), ("f8", VirtualKeyCode::F8), ("f9", VirtualKeyCode::F9),
+    ("f10", VirtualKeyCode::F10), ("f11", VirtualKeyCode::F11), ("f12", VirtualKeyCode::F12),
+    ("escape", VirtualKeyCode::Escape), ("enter", VirtualKeyCode::Return),
+    ("numpad_enter", VirtualKeyCode::NumpadEnter), ("tab", VirtualKeyCode::Tab),
+    ("space", VirtualKeyCode::Space), ("backspace", VirtualKeyCode::Back),
+    ("delete", VirtualKeyCode::Delete), ("insert", VirtualKeyCode::Insert),
+    ("home", VirtualKeyCode::Home), ("end", VirtualKeyCode::End),
+    ("pageup", VirtualKeyCode::PageUp), ("pagedown", VirtualKeyCode::PageDown),
+    ("left", VirtualKeyCode::Left), ("right", VirtualKeyCode::Right),
+    ("up", VirtualKeyCode::Up), ("down", VirtualKeyCode::Down),
+    ("minus", VirtualKeyCode::Minus), ("equals", VirtualKeyCode::Equals),
+    ("comma", VirtualKeyCode::Comma), ("period", VirtualKeyCode::Period),
+    ("semicolon", VirtualKeyCode::Semicolon), ("slash", VirtualKeyCode::Slash),
+    ("backslash", VirtualKeyCode::Backslash), ("apostrophe", VirtualKeyCode::Apostrophe),
+    ("lbracket", VirtualKeyCode::LBracket), ("rbracket", VirtualKeyCode::RBracket),
+    ("numpad_add", VirtualKeyCode::NumpadAdd), ("numpad_subtract", VirtualKeyCode::NumpadSubtract),
+    ("numpad_0", VirtualKeyCode::Numpad0)
+];
+
+//modifier names in the order they are written out
+const MODIFIER_NAMES : [(&str, ModifiersState); 4] = [
+    ("ctrl", ModifiersState::CTRL),
+    ("alt", ModifiersState::ALT),
+    ("shift", ModifiersState::SHIFT),
+    ("logo", ModifiersState::LOGO)
+];
+
+fn key_name(key : VirtualKeyCode) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(_, k)| *k == key).map(|(name, _)| *name)
+}
+
+//parse a chord such as "ctrl+shift+z", case insensitive
+pub fn parse_chord(text : &str) -> Result<KeyChord, String> {
+    let lower = text.trim().to_lowercase();
+    let mut parts : Vec<&str> = lower.split('+').collect();
+
+    //"ctrl++" and "+" would otherwise split into empty parts
+    let key = match parts.pop() {
+        Some(key) if !key.is_empty() => key,
+        _ => return Err(format!("\"{}\" has no key, write + as \"equals\" or \"shift+equals\"", text))
+    };
+
+    let mut modifiers = ModifiersState::empty();
+    for part in parts {
+        let modifier = match part {
+            "control" => ModifiersState::CTRL,
+            "super" | "cmd" | "win" => ModifiersState::LOGO,
+            "meta" => ModifiersState::ALT,
+            _ => match MODIFIER_NAMES.iter().find(|(name, _)| *name == part) {
+                Some((_, modifier)) => *modifier,
+                None => return Err(format!("unknown modifier \"{}\", expected ctrl, alt, shift or logo", part))
+            }
+        };
+        modifiers |= modifier;
+    }
+
+    let key = match key {
+        "esc" => VirtualKeyCode::Escape,
+        "return" => VirtualKeyCode::Return,
+        "del" => VirtualKeyCode::Delete,
+        _ => match KEY_NAMES.iter().find(|(name, _)| *name == key) {
+            Some((_, k)) => *k,
+            None => return Err(format!("unknown key \"{}\"", key))
+        }
+    };
+
+    Ok(KeyChord { key, modifiers })
+}
+
+//parse space separated chords such as "ctrl+k ctrl+s"
+pub fn parse_sequence(text : &str) -> Result<KeySequence, String> {
+    let sequence = text.split_whitespace().map(parse_chord).collect::<Result<KeySequence, String>>()?;
+
+    match sequence.is_empty() {
+        true => Err(String::from("no keys given")),
+        false => Ok(sequence)
+    }
+}
+
+pub fn format_chord(chord : &KeyChord) -> String {
+    let mut parts : Vec<&str> = MODIFIER_NAMES.iter()
+        .filter(|(_, modifier)| chord.modifiers.contains(*modifier))
+        .map(|(name, _)| *name)
+        .collect();
+
+    let key = format!("{:?}", chord.key).to_lowercase();
+    parts.push(key_name(chord.key).unwrap_or(&key));
+    parts.join("+")
+}
+
+pub fn format_sequence(keys : &KeySequence) -> String {
+    keys.iter().map(format_chord).collect::<Vec<String>>().join(" ")
+}
+
+fn is_default_profile(profile : &Profile) -> bool {
+    *profile == Profile::Default
+}
+
+fn is_modifier_key(key : VirtualKeyCode) -> bool {
+    use VirtualKeyCode as Key;
+    matches!(key, Key::LControl | Key::RControl | Key::LShift | Key::RShift | Key::LAlt | Key::RAlt | Key::LWin | Key::RWin)
+}
+
+//append the closest known command name to an unknown command error, other
+//errors such as a bad macro repeat count already say what is wrong
+fn with_suggestion(message : String, name : &str) -> String {
+    if !message.starts_with("unknown command") {
+        return message
+    }
+
+    let closest = command_names().into_iter().min_by_key(|known| edit_distance(known, name));
+
+    match closest {
+        Some(known) if edit_distance(&known, name) <= 3 => format!("{}, did you mean \"{}\"?", message, known),
+        _ => format!("{}, run with --dump-keymap to list the commands", message)
+    }
+}
+
+//levenshtein distance between two strings
+fn edit_distance(a : &str, b : &str) -> usize {
+    let b : Vec<char> = b.chars().collect();
+    let mut row : Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(previous + 1);
+        }
+    }
+
+    row[b.len()]
+}
@@ -0,0 +1,65 @@
+//Layout
+//Splits the doc into the rows drawn on screen. The font is monospaced so a row is
+//just a run of chars from one line; lines longer than the window are soft wrapped
+//after the last space that fits, or mid word if a word is wider than the window.
+
+//chars start..end of a line drawn as one row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Row {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+//char ranges a line is wrapped into, at most cols chars each. Empty lines are one empty row.
+pub fn wrap_line(line : &str, cols : usize) -> Vec<(usize, usize)> {
+    let chars : Vec<char> = line.chars().collect();
+    let cols = cols.max(1);
+    let mut spans = Vec::new();
+    let mut start = 0;
+
+    while chars.len() - start > cols {
+        //break after the last space that fits on the row
+        let end = (start + 1..=start + cols).rev()
+            .find(|&i| chars[i - 1].is_whitespace())
+            .unwrap_or(start + cols);
+
+        spans.push((start, end));
+        start = end;
+    }
+
+    spans.push((start, chars.len()));
+    spans
+}
+
+//every row of the doc in order
+pub fn layout_rows(text : &[String], cols : usize) -> Vec<Row> {
+    text.iter().enumerate()
+        .flat_map(|(line, content)| {
+            wrap_line(content, cols).into_iter().map(move |(start, end)| Row { line, start, end })
+        })
+        .collect()
+}
+
+//(row, column in the row) of a (line, column) doc position.
+//A position at the point a line wraps is drawn at the start of the next row.
+pub fn row_of(rows : &[Row], pos : (usize, usize)) -> (usize, usize) {
+    let (y, x) = pos;
+    let first = rows.partition_point(|row| row.line < y);
+    let last = rows.partition_point(|row| row.line <= y).max(first + 1) - 1;
+
+    let index = (first..=last).find(|&i| x < rows[i].end).unwrap_or(last).min(rows.len() - 1);
+    (index, x.saturating_sub(rows[index].start))
+}
+
+//the parts of a line's char range start..end that fall on each row,
+//as (row, first column, last column + 1)
+pub fn row_spans(rows : &[Row], line : usize, start : usize, end : usize) -> Vec<(usize, usize, usize)> {
+    let first = rows.partition_point(|row| row.line < line);
+
+    rows[first..].iter().enumerate()
+        .take_while(|(_, row)| row.line == line)
+        .filter(|(_, row)| start < row.end && end > row.start)
+        .map(|(i, row)| (first + i, start.max(row.start) - row.start, end.min(row.end) - row.start))
+        .collect()
+}
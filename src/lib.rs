@@ -1,367 +1,309 @@
-//TODO:
-// implement txt file io - saving + handle when no .txt exists to grab
-// implement saving functionality
-// implement file creation
-// start display
-// implement GUI for above - see if there is an OS way to handle saving dialogue
-
-use winit::{
-    event::*,
-    event_loop::{EventLoop, ControlFlow},
-    window::{WindowBuilder, Window},
-};
-
-use wgpu_glyph::{
-    ab_glyph,
-    GlyphBrushBuilder,
-    GlyphBrush,
-    Section,
-    Text
-};
-
-use std::env;
-use std::path::Path;
-use std::path::PathBuf;
-
-//modules
-mod iomod;
-mod doc_handler;
-mod wgpu_handler;
-
-use crate::iomod::*;
-use crate::doc_handler::*;
-use crate::wgpu_handler::*;
-
-pub async fn run() {
-
-    env_logger::init();
-
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
-
-    let mut wgpu_handler = WgpuHandler::new(window).await;
-
-    let wkdir = match env::current_dir() {
-        Ok(path) => path,
-        Err(e) => panic!("{:?}", e)
-    };
-
-    let path = wkdir.join("text.txt"); //later prompt user to provide path 
-
-    let mut doc_handler = DocHandler::load(&path);
-
-
-
-    event_loop.run(move |event, _, control_flow| {
-
-        // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
-        // dispatched any events. This is ideal for games and similar applications.
-        // control_flow.set_poll();
-
-        // ControlFlow::Wait pauses the event loop if no events are available to process.
-        // This is ideal for non-game applications that only update in response to user
-        // input, and uses significantly less power/CPU time than ControlFlow::Poll.
-        control_flow.set_wait();
-
-        // Match events detected by the Window obj and perform the corresponding action using the
-        // ControFlow obj
-        // println!("{:?}", event);
-
-        match event { //match block 1
-
-            Event::WindowEvent {
-                ref event, //for notes on this syntax see The Rust Programming Language, 18.3 Pattern Syntax, Ignoring Remaining Parts of a Value with ..
-                window_id,  //https://doc.rust-lang.org/book/ch18-03-pattern-syntax.html
-
-            // Additional check to make sure that we are handling the correct window
-            } if window_id == wgpu_handler.window().id() => { //start WindowEvent block
-
-                // Filter for specific events we want to handle in render loop
-                if wgpu_handler.input(event) == false { //start if statment block
-
-                    match event { //match block 2
-
-                        // Close the window when CloseRequested event is detected
-                        WindowEvent::CloseRequested => {
-                            println!("The close button was pressed, stopping.");
-                            control_flow.set_exit();
-                        },
-
-                        // Close the window when the Escape key is pressed
-                        WindowEvent::KeyboardInput {
-                            input: KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                            ..
-                        } => {
-                            println!("The escape button was pressed, stopping.");
-                            control_flow.set_exit();
-                        },
-
-                        //Compressed version of above for conscision
-                        //Key1
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key1), .. },  ..
-                        } => { doc_handler.update('1') },
-                        //Key2
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key2), .. },  ..
-                        } => { doc_handler.update('2') },
-                        //Key3
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key3), .. },  ..
-                        } => { doc_handler.update('3') },
-                        //Key4
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key4), .. },  ..
-                        } => { doc_handler.update('4') },
-                        //Key5
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key5), .. },  ..
-                        } => { doc_handler.update('5') },
-                        //Key6
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key6), .. },  ..
-                        } => { doc_handler.update('6') },
-                        //Key7
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key7), .. },  ..
-                        } => { doc_handler.update('7') },
-                        //Key8
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key8), .. },  ..
-                        } => { doc_handler.update('8') },
-                        //Key9
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key9), .. },  ..
-                        } => { doc_handler.update('9') },
-                        //Key0
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Key0), .. },  ..
-                        } => { doc_handler.update('0') },
-                        //A
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::A), .. },  ..
-                        } => { doc_handler.update('a') },
-                        //B
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::B), .. },  ..
-                        } => { doc_handler.update('b') },
-                        //C
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::C), .. },  ..
-                        } => { doc_handler.update('c') },
-                        //D
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::D), .. },  ..
-                        } => { doc_handler.update('d') },
-                        //E
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::E), .. },  ..
-                        } => { doc_handler.update('e') },
-                        //F
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::F), .. },  ..
-                        } => { doc_handler.update('f') },
-                        //G
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::G), .. },  ..
-                        } => { doc_handler.update('g') },
-                        //H
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::H), .. },  ..
-                        } => { doc_handler.update('h') },
-                        //I
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::I), .. },  ..
-                        } => { doc_handler.update('i') },
-                        //J
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::J), .. },  ..
-                        } => { doc_handler.update('j') },
-                        //K
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::K), .. },  ..
-                        } => { doc_handler.update('k') },
-                        //L
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::L), .. },  ..
-                        } => { doc_handler.update('l') },
-                        //M
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::M), .. },  ..
-                        } => { doc_handler.update('m') },
-                        //N
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::N), .. },  ..
-                        } => { doc_handler.update('n') },
-                        //O
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::O), .. },  ..
-                        } => { doc_handler.update('o') },
-                        //P
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::P), .. },  ..
-                        } => { doc_handler.update('p') },
-                        //Q
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Q), .. },  ..
-                        } => { doc_handler.update('q') },
-                        //R
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::R), .. },  ..
-                        } => { doc_handler.update('r') },
-                        //S
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::S), .. },  ..
-                        } => { doc_handler.update('s') },
-                        //T
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::T), .. },  ..
-                        } => { doc_handler.update('t') },
-                        //U
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::U), .. },  ..
-                        } => { doc_handler.update('u') },
-                        //V
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::V), .. },  ..
-                        } => { doc_handler.update('v') },
-                        //W
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::W), .. },  ..
-                        } => { doc_handler.update('w') },
-                        //X
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::X), .. },  ..
-                        } => { doc_handler.update('x') },
-                        //Y
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Y), .. },  ..
-                        } => { doc_handler.update('y') },
-                        //Z
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Z), .. },  ..
-                        } => { doc_handler.update('z') },
-
-
-                        //Punctuation
-                        //Colon
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Colon), .. },  ..
-                        } => { doc_handler.update(':') },
-                        //Comma
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Comma), .. },  ..
-                        } => { doc_handler.update(',') },
-                        //Period
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Period), .. },  ..
-                        } => { doc_handler.update('.') },
-                        //Semicolon
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Semicolon), .. },  ..
-                        } => { doc_handler.update(';') },
-                        //Space
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Space), .. },  ..
-                        } => { doc_handler.update(' ') },
-
-                        //Cursor Movement
-                        //Up
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Up), .. },  ..
-                        } => { doc_handler.update_cursor("up") },
-                        //Down
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Down), .. },  ..
-                        } => { doc_handler.update_cursor("down") },
-                        //Left
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Left), .. },  ..
-                        } => { doc_handler.update_cursor("left") },
-                        //Right
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Right), .. },  ..
-                        } => { doc_handler.update_cursor("right") },
-
-                        //Misc Inputs
-                        //Return
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Return), .. },  ..
-                        } => { doc_handler.newline() },
-
-                        //Backspace
-                        WindowEvent::KeyboardInput {input: KeyboardInput {state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Back), .. },  ..
-                        } => { doc_handler.backspace() },
-
-
-                        //GL Bindings
-                        // Resize the surface when window is resized
-                        WindowEvent::Resized(physical_size) => {
-                            wgpu_handler.resize(*physical_size);
-                        },
-
-                        // Resize the surface when scale factor is changed
-                        WindowEvent::ScaleFactorChanged {new_inner_size, .. } => {
-                            wgpu_handler.resize(**new_inner_size);
-                        },
-
-                        //For all other WindowEvents do nothing
-                        _ => {}
-                    }//end match block 2
-                }//end if statement block
-            }//end WindowEvent block
-
-            //
-            Event::MainEventsCleared => {
-                // Application update code.
-
-                // Queue a RedrawRequested event.
-                //
-                // You only need to call this if you've determined that you need to redraw, in
-                // applications which do not always need to. Applications that redraw continuously
-                // can just render here instead.
-                // RedrawRequested will only evaluate once unless we request it here
-
-                wgpu_handler.window().request_redraw();
-            },
-
-            //
-            Event::RedrawRequested(window_id) if window_id == wgpu_handler.window().id() => {
-                // Redraw the application.
-                //
-                // It's preferable for applications that do not render continuously to render in
-                // this event rather than in MainEventsCleared, since rendering in here allows
-                // the program to gracefully handle redraws requested by the OS.
-
-                wgpu_handler.update(); //currently does nothing
-
-                // For notes on error handling with match blocks see The Rust Programming Language > 9.2 Recoverable Errors with Result > Mathing on Different Errors
-                // https://doc.rust-lang.org/book/ch09-02-recoverable-errors-with-result.html
-                match wgpu_handler.render(&doc_handler.text) {
-                    Ok(_) => {},
-
-                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => wgpu_handler.resize(wgpu_handler.size),
-
-                    Err(wgpu::SurfaceError::OutOfMemory) => control_flow.set_exit(),
-
-                    Err(wgpu::SurfaceError::Timeout) => log::warn!("Surface timeout"),
-
-                    //Err(e) => eprintln!("{:?}", e), //catch all for other errors to print to std output
-                }
-
-
-            },
-
-            // For all other inputs, do nothing
-            _ => {}
-
-        }//end match block 1
-
-    });
-
-}//end main()
+//TODO:
+// implement txt file io - saving + handle when no .txt exists to grab
+// implement saving functionality
+// implement file creation
+// start display
+// implement GUI for above - see if there is an OS way to handle saving dialogue
+
+use winit::{
+    event::*,
+    event_loop::EventLoop,
+    window::WindowBuilder,
+};
+
+use std::env;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// How often results of a background project search are collected
+const SEARCH_POLL_INTERVAL: Duration = Duration::from_millis(50);
+// How often the text scrolls by a row while dragging a selection past the edge of the window
+const AUTOSCROLL_INTERVAL: Duration = Duration::from_millis(50);
+
+//modules
+pub mod iomod;
+pub mod doc_handler;
+pub mod wgpu_handler;
+pub mod cpu_renderer;
+pub mod renderer;
+pub mod view;
+pub mod screen;
+pub mod command;
+pub mod keymap;
+pub mod vim;
+pub mod emacs;
+pub mod macros;
+pub mod search;
+pub mod find;
+pub mod project_search;
+pub mod mouse;
+pub mod settings;
+pub mod theme;
+pub mod status;
+pub mod fonts;
+pub mod layout;
+pub mod editor;
+pub mod tui;
+pub mod cli;
+pub mod export;
+pub mod epub;
+pub mod pdf;
+pub mod lint;
+pub mod tabs;
+pub mod panes;
+pub mod book;
+pub mod compile;
+pub mod sidebar;
+
+use crate::command::EditorCommand;
+use crate::editor::Editor;
+use crate::keymap::*;
+use crate::mouse::*;
+use crate::theme::Theme;
+use crate::renderer::RenderError;
+use crate::screen::Screen;
+
+pub async fn run() {
+
+    env_logger::init();
+
+    let args : Vec<String> = env::args().collect();
+
+    // Subcommands such as "stats FILE" work on a file without opening a window, see cli.rs
+    if let Some(code) = cli::run(&args[1..]) {
+        std::process::exit(code);
+    }
+
+    // --dump-keymap [PATH] writes the built in keymap to disk instead of opening the editor
+    if let Some(i) = args.iter().position(|arg| arg == "--dump-keymap") {
+        let path = PathBuf::from(args.get(i + 1).map_or("keymap.toml", |path| path.as_str()));
+        match Keymap::default().save(&path) {
+            Ok(_) => println!("Default keymap written to {:?}.", path),
+            Err(e) => eprintln!("Failed to write keymap to {:?} due to {:?}", path, e)
+        };
+        return
+    }
+
+    let wkdir = match env::current_dir() {
+        Ok(path) => path,
+        Err(e) => panic!("{:?}", e)
+    };
+
+    // The doc, keymap, settings and macros from the working directory
+    let mut editor = Editor::load(&wkdir);
+
+    // --tui edits in the terminal instead of opening a window, e.g. over ssh
+    if args.iter().any(|arg| arg == "--tui") {
+        if let Err(e) = tui::run(&mut editor) {
+            eprintln!("Failed to run in the terminal: {}", e);
+        }
+        return
+    }
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new().build(&event_loop).unwrap();
+
+    let mut screen = Screen::new(window, &editor.settings.font).await;
+
+    // Colours from the theme setting, "auto" asks the desktop whether it is light or dark
+    let mut theme = Theme::choose(&editor.settings, &wkdir, None);
+    screen.set_theme(theme);
+
+    let mut mouse = Mouse::new();
+
+    // Row of the section picked up in the sidebar, opened when the button is let go over it or moved where it is let go
+    let mut dragged_section: Option<usize> = None;
+
+    // Modifier keys currently held down, kept up to date by ModifiersChanged events
+    let mut modifiers = ModifiersState::empty();
+
+
+    event_loop.run(move |event, _, control_flow| {
+
+        // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
+        // dispatched any events. This is ideal for games and similar applications.
+        // control_flow.set_poll();
+
+        // ControlFlow::Wait pauses the event loop if no events are available to process.
+        // This is ideal for non-game applications that only update in response to user
+        // input, and uses significantly less power/CPU time than ControlFlow::Poll.
+        control_flow.set_wait();
+
+        // Match events detected by the Window obj and perform the corresponding action using the
+        // ControFlow obj
+        // println!("{:?}", event);
+
+        match event { //match block 1
+
+            Event::WindowEvent {
+                ref event, //for notes on this syntax see The Rust Programming Language, 18.3 Pattern Syntax, Ignoring Remaining Parts of a Value with ..
+                window_id,  //https://doc.rust-lang.org/book/ch18-03-pattern-syntax.html
+
+            // Additional check to make sure that we are handling the correct window
+            // Filter for specific events we want to handle in render loop
+            } if window_id == screen.window().id() && !screen.input(event) => { //start WindowEvent block
+
+                match event { //match block 2
+
+                    // The close button quits like the quit command, which refuses while a doc has unsaved changes.
+                    // The window closes once editor.quit is set, see MainEventsCleared.
+                    WindowEvent::CloseRequested => editor.run_command(&EditorCommand::Quit),
+
+                    // Track ctrl/alt/shift so the keymap can match key chords
+                    WindowEvent::ModifiersChanged(state) => {
+                        modifiers = *state;
+                    },
+
+                    // Typed text
+                    WindowEvent::ReceivedCharacter(c) => editor.char(*c, modifiers),
+
+                    // Every other key press is looked up in the keymap, unless vim wants it
+                    WindowEvent::KeyboardInput { input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. }, .. } => {
+                        editor.key(*key, modifiers);
+                    },
+
+                    // Dragging with the left button down extends the selection
+                    WindowEvent::CursorMoved { position, .. } => {
+                        mouse.position = (position.x, position.y);
+                        if mouse.dragging {
+                            let pos = screen.view.position_at(editor.doc(), editor.panes.active(), mouse.position);
+                            mouse.drag(editor.doc_mut(), pos);
+                        }
+                    },
+
+                    // Clicking the text places the cursor, shift+click extends the selection.
+                    // Clicking a tab switches to its doc, clicking another pane focuses it first.
+                    // Clicking a section in the sidebar opens it, dragging it onto another moves it there.
+                    WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                        match (state, screen.view.tab_at(mouse.position), screen.view.sidebar_at(mouse.position)) {
+                            (ElementState::Pressed, Some(i), _) => editor.select_tab(i),
+                            (ElementState::Pressed, None, Some(row)) => {
+                                editor.sidebar.select(row);
+                                dragged_section = Some(row);
+                            },
+                            (ElementState::Pressed, None, None) if screen.view.over_text(mouse.position) => {
+                                if let Some(pane) = screen.view.pane_at(mouse.position) {
+                                    editor.focus_pane(pane);
+                                }
+                                let pos = screen.view.position_at(editor.doc(), editor.panes.active(), mouse.position);
+                                mouse.press(editor.doc_mut(), pos, modifiers.shift());
+                            },
+                            (ElementState::Pressed, None, None) => {},
+                            (ElementState::Released, _, onto) => {
+                                mouse.release();
+                                match (dragged_section.take(), onto) {
+                                    (Some(from), Some(onto)) if from == onto => editor.open_section(from),
+                                    (Some(from), Some(onto)) => editor.drop_section(from, onto),
+                                    _ => {}
+                                }
+                            }
+                        }
+                    },
+
+
+                    //GL Bindings
+                    // Resize the surface when window is resized
+                    WindowEvent::Resized(physical_size) => {
+                        screen.resize(*physical_size);
+                    },
+
+                    // Resize the surface and rescale the text when scale factor is changed,
+                    // e.g. when the window is dragged to another monitor
+                    WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                        screen.rescale(*scale_factor, **new_inner_size);
+                    },
+
+                    // Follow the desktop between light and dark. Only some platforms send
+                    // ThemeChanged, so the desktop is asked again whenever the window is focused.
+                    WindowEvent::ThemeChanged(desktop) if editor.settings.follows_desktop() => {
+                        theme = Theme::choose(&editor.settings, &wkdir, Some(*desktop));
+                        screen.set_theme(theme);
+                    },
+
+                    WindowEvent::Focused(true) if editor.settings.follows_desktop() => {
+                        let chosen = Theme::choose(&editor.settings, &wkdir, None);
+                        if chosen != theme {
+                            theme = chosen;
+                            screen.set_theme(theme);
+                        }
+                    },
+
+                    //For all other WindowEvents do nothing
+                    _ => {}
+                }//end match block 2
+            }//end WindowEvent block
+
+            //
+            Event::MainEventsCleared => {
+                // Application update code.
+
+                // Queue a RedrawRequested event.
+                //
+                // You only need to call this if you've determined that you need to redraw, in
+                // applications which do not always need to. Applications that redraw continuously
+                // can just render here instead.
+                // RedrawRequested will only evaluate once unless we request it here
+
+                // Stop when asked to quit, why a command failed is shown in the status bar
+                if editor.quit {
+                    control_flow.set_exit();
+                }
+
+                // Pick up results from a project search running in the background
+                editor.find_bar.poll();
+
+                // Keep scrolling while a selection is dragged past the top or bottom of the text
+                if mouse.dragging && screen.view.scrolls_at(editor.panes.active(), mouse.position) {
+                    let pos = screen.view.position_at(editor.doc(), editor.panes.active(), mouse.position);
+                    mouse.drag(editor.doc_mut(), pos);
+                }
+
+                screen.window().request_redraw();
+            },
+
+            //
+            Event::RedrawRequested(window_id) if window_id == screen.window().id() => {
+                // Redraw the application.
+                //
+                // It's preferable for applications that do not render continuously to render in
+                // this event rather than in MainEventsCleared, since rendering in here allows
+                // the program to gracefully handle redraws requested by the OS.
+
+                // For notes on error handling with match blocks see The Rust Programming Language > 9.2 Recoverable Errors with Result > Mathing on Different Errors
+                // https://doc.rust-lang.org/book/ch09-02-recoverable-errors-with-result.html
+                editor.refresh();
+
+                match screen.render(&editor) {
+                    Ok(_) => {},
+
+                    Err(RenderError::Lost) => screen.resize(screen.size()),
+
+                    Err(RenderError::OutOfMemory) => control_flow.set_exit(),
+
+                    Err(RenderError::Timeout) => log::warn!("Surface timeout"),
+
+                    Err(e) => eprintln!("Failed to draw: {}", e), //catch all for other errors to print to std output
+                }
+
+
+            },
+
+            // Wake up now and then while dragging past the edge of the text to keep scrolling,
+            // or while a project search is running to show its results
+            Event::RedrawEventsCleared => {
+                let autoscrolling = mouse.dragging && screen.view.scrolls_at(editor.panes.active(), mouse.position);
+                match (autoscrolling, editor.find_bar.is_searching()) {
+                    (true, _) => control_flow.set_wait_until(Instant::now() + AUTOSCROLL_INTERVAL),
+                    (false, true) => control_flow.set_wait_until(Instant::now() + SEARCH_POLL_INTERVAL),
+                    (false, false) => {}
+                }
+            },
+
+            // For all other inputs, do nothing
+            _ => {}
+
+        }//end match block 1
+
+    });
+
+}//end main()
@@ -0,0 +1,100 @@
+//Lint
+//Checks a doc for slips that are easy to miss on screen: spaces at the end of a
+//line, doubled spaces between words, a word typed twice, a space before a comma
+//or full stop, and straight quotes in a doc that otherwise uses curly ones.
+
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    TrailingWhitespace,
+    DoubleSpace,
+    RepeatedWord,
+    SpaceBeforePunctuation,
+    StraightQuote
+}
+
+//something a rule found, line and column count from 0 like doc positions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub line: usize,
+    pub column: usize,
+    pub rule: Rule,
+    pub message: String
+}
+
+//every problem in the text, in the order they appear
+pub fn lint(text : &[String]) -> Vec<Problem> {
+    let curly = text.iter().any(|line| line.contains(['\u{201C}', '\u{201D}', '\u{2018}', '\u{2019}']));
+    let mut problems = Vec::new();
+
+    for (y, line) in text.iter().enumerate() {
+        let mut found = Vec::new();
+        let chars : Vec<char> = line.chars().collect();
+        let content_end = chars.iter().rposition(|c| !c.is_whitespace()).map_or(0, |i| i + 1);
+
+        if content_end < chars.len() {
+            found.push((content_end, Rule::TrailingWhitespace, String::from("whitespace at the end of the line")));
+        }
+
+        //runs of spaces between words, indentation and the end of the line are left alone
+        let content_start = chars.iter().position(|c| !c.is_whitespace()).unwrap_or(chars.len());
+        let mut x = content_start;
+        while x < content_end {
+            let run = chars[x..].iter().take_while(|c| **c == ' ').count();
+            if run >= 2 && x + run < content_end {
+                found.push((x, Rule::DoubleSpace, format!("{} spaces between words", run)));
+            }
+            x += run.max(1);
+        }
+
+        for x in 1..content_end {
+            let after_space = chars[x - 1].is_whitespace() && x > content_start;
+            let ellipsis = chars[x] == '.' && chars.get(x + 1) == Some(&'.');
+            if after_space && matches!(chars[x], ',' | '.' | ';' | ':' | '!' | '?') && !ellipsis {
+                found.push((x - 1, Rule::SpaceBeforePunctuation, format!("space before \"{}\"", chars[x])));
+            }
+        }
+
+        found.extend(repeated_words(line));
+
+        if curly {
+            for (x, c) in chars.iter().enumerate().filter(|(_, c)| matches!(c, '"' | '\'')) {
+                found.push((x, Rule::StraightQuote, format!("straight {} in a doc with curly quotes", c)));
+            }
+        }
+
+        found.sort_by_key(|(x, _, _)| *x);
+        problems.extend(found.into_iter().map(|(column, rule, message)| Problem { line: y, column, rule, message }));
+    }
+
+    problems
+}
+
+//a word straight after the same word with only spaces between, e.g. "the the"
+fn repeated_words(line : &str) -> Vec<(usize, Rule, String)> {
+    let mut found = Vec::new();
+    let mut previous : Option<String> = None;
+    let mut column = 0;
+
+    for segment in line.split_word_bounds() {
+        let is_word = segment.chars().any(|c| c.is_alphanumeric());
+        match (is_word, segment.trim().is_empty()) {
+            (true, _) => {
+                let word = segment.to_lowercase();
+                if previous.as_ref() == Some(&word) {
+                    found.push((column, Rule::RepeatedWord, format!("\"{}\" is repeated", segment)));
+                }
+                previous = Some(word);
+            },
+            //spaces keep the previous word, anything else breaks up the run
+            (false, true) => {},
+            (false, false) => previous = None
+        }
+        column += segment.chars().count();
+    }
+
+    found
+}
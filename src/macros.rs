@@ -0,0 +1,257 @@
+//Macros
+//Keyboard macros record the EditorCommands that were run rather than the keys
+//pressed, so a macro plays back the same whatever keymap it was recorded with.
+//
+//record_macro starts and stops recording, play_macro plays the last recording and
+//save_macro:<name> keeps it in macros.toml so it can be bound to keys with
+//macro:<name>. A repeat count or until_fail can follow either, e.g. "macro:tidy:10".
+//
+//  # macros.toml
+//  tidy = ["move_line_start", "delete_right", "move_down"]
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::command::{check_macro_name, EditorCommand, Motion, Outcome, Repeat};
+
+//macros playing macros stop this deep, a macro can end up playing itself
+const MAX_DEPTH : usize = 16;
+
+//an until_fail run that keeps succeeding stops after this many passes
+const MAX_PASSES : usize = 10_000;
+
+pub struct Macros {
+    path: Option<PathBuf>,  //file save_macro writes to
+    saved: BTreeMap<String, Vec<EditorCommand>>,
+    recording: Option<Vec<EditorCommand>>,
+    last: Vec<EditorCommand>, //the last finished recording
+    depth: usize            //how many macros are playing inside each other
+}
+
+#[derive(Debug)]
+pub enum MacroError {
+    Io(io::Error),
+    Parse(String),
+    Command {
+        name: String,
+        message: String
+    }
+}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacroError::Io(e) => write!(f, "could not read macros: {}", e),
+            MacroError::Parse(e) => write!(f, "macros are not valid toml: {}", e),
+            MacroError::Command { name, message } => write!(f, "macro \"{}\": {}", name, message)
+        }
+    }
+}
+
+impl Error for MacroError {}
+
+impl Macros {
+    //no saved macros and nowhere to save them
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            saved: BTreeMap::new(),
+            recording: None,
+            last: Vec::new(),
+            depth: 0
+        }
+    }
+
+    //macros saved in a file, which save_macro will also write to.
+    //A missing file is not an error, there are just no saved macros yet.
+    pub fn load(path : &Path) -> Result<Self, MacroError> {
+        let mut macros = match fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::new(),
+            Err(e) => return Err(MacroError::Io(e))
+        };
+
+        macros.path = Some(path.to_path_buf());
+        Ok(macros)
+    }
+
+    pub fn from_toml(text : &str) -> Result<Self, MacroError> {
+        let file : BTreeMap<String, Vec<String>> = toml::from_str(text).map_err(|e| MacroError::Parse(e.to_string()))?;
+        let mut macros = Self::new();
+
+        for (name, commands) in file {
+            let command_error = |message : String| MacroError::Command { name: name.clone(), message };
+
+            check_macro_name(&name).map_err(command_error)?;
+            let commands = commands.iter()
+                .map(|command| command.parse::<EditorCommand>())
+                .collect::<Result<Vec<EditorCommand>, String>>()
+                .map_err(command_error)?;
+
+            macros.saved.insert(name, commands);
+        }
+
+        Ok(macros)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let file : BTreeMap<&String, Vec<String>> = self.saved.iter()
+            .map(|(name, commands)| (name, commands.iter().map(|command| command.to_string()).collect()))
+            .collect();
+
+        toml::to_string(&file).expect("macros serialize to toml")
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    //commands of a saved macro, or of the last recording when name is None
+    pub fn get(&self, name : Option<&str>) -> Option<&[EditorCommand]> {
+        match name {
+            Some(name) => self.saved.get(name).map(|commands| commands.as_slice()),
+            None => Some(self.last.as_slice()).filter(|commands| !commands.is_empty())
+        }
+    }
+
+    //run a command, handling the macro commands here and passing the rest to apply.
+    //Commands run while recording are added to the recording.
+    pub fn run<F>(&mut self, command : &EditorCommand, apply : &mut F) -> Outcome
+    where F: FnMut(&EditorCommand) -> Outcome {
+        match command {
+            EditorCommand::RecordMacro => self.toggle_recording(),
+
+            EditorCommand::SaveMacro(name) => self.save_last(name),
+
+            EditorCommand::PlayMacro(None, _) if self.is_recording() => {
+                Outcome::Failed(String::from("Can't play the last macro while recording it"))
+            },
+
+            EditorCommand::PlayMacro(name, repeat) => {
+                let commands = match self.get(name.as_deref()) {
+                    Some(commands) => commands.to_vec(),
+                    None => return Outcome::Failed(match name {
+                        Some(name) => format!("No macro named \"{}\"", name),
+                        None => String::from("No macro has been recorded")
+                    })
+                };
+
+                self.record(command);
+                self.play(&commands, *repeat, apply)
+            },
+
+            _ => {
+                self.record(command);
+                apply(command)
+            }
+        }
+    }
+
+    fn toggle_recording(&mut self) -> Outcome {
+        match self.recording.take() {
+            Some(commands) => {
+                let notice = format!("Macro recorded, {} commands", commands.len());
+                self.last = commands;
+                Outcome::Notice(notice)
+            },
+            None => {
+                self.recording = Some(Vec::new());
+                Outcome::Applied
+            }
+        }
+    }
+
+    //only commands typed by the user are recorded, not the ones a macro plays
+    fn record(&mut self, command : &EditorCommand) {
+        if let (Some(recording), 0) = (self.recording.as_mut(), self.depth) {
+            recording.push(command.clone());
+        }
+    }
+
+    fn save_last(&mut self, name : &str) -> Outcome {
+        if self.last.is_empty() {
+            return Outcome::Failed(String::from("No macro has been recorded"))
+        }
+
+        self.saved.insert(name.to_string(), self.last.clone());
+
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Outcome::Applied
+        };
+
+        match fs::write(path, self.to_toml()) {
+            Ok(_) => Outcome::Notice(format!("Macro \"{}\" saved to {}", name, path.display())),
+            Err(e) => Outcome::Failed(format!("Failed to save macro \"{}\" to {:?} due to {:?}", name, path, e))
+        }
+    }
+
+    //Play commands over. Until failure stops at the first command that fails, at a
+    //movement that can't go any further (e.g. moving down on the last line), or
+    //once a pass changes nothing. A count stops early only on failure.
+    fn play<F>(&mut self, commands : &[EditorCommand], repeat : Repeat, apply : &mut F) -> Outcome
+    where F: FnMut(&EditorCommand) -> Outcome {
+        if self.depth >= MAX_DEPTH {
+            return Outcome::Failed(String::from("Macros nested too deep, does a macro play itself?"))
+        }
+
+        let (passes, until_failure) = match repeat {
+            Repeat::Times(n) => (n, false),
+            Repeat::UntilFailure => (MAX_PASSES, true)
+        };
+
+        self.depth += 1;
+        let mut result = Outcome::Unchanged;
+
+        'passes: for _ in 0..passes {
+            let mut changed = false;
+
+            for command in commands {
+                match self.run(command, apply) {
+                    Outcome::Applied | Outcome::Notice(_) => changed = true,
+                    Outcome::Unchanged if until_failure && at_edge(command) => break 'passes,
+                    Outcome::Unchanged => {},
+                    outcome => {
+                        //running out of things to do is how until_fail is meant to end
+                        if !until_failure || outcome == Outcome::Quit {
+                            result = outcome;
+                        }
+                        break 'passes
+                    }
+                }
+            }
+
+            if changed {
+                result = Outcome::Applied;
+            }
+
+            if until_failure && !changed {
+                break
+            }
+        }
+
+        self.depth -= 1;
+        result
+    }
+}
+
+impl Default for Macros {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//a relative movement that changed nothing has hit the start or end of the doc
+fn at_edge(command : &EditorCommand) -> bool {
+    match command {
+        EditorCommand::Move(motion) | EditorCommand::Delete(motion) | EditorCommand::Kill(motion) => !matches!(
+            motion,
+            Motion::LineStart | Motion::LineEnd | Motion::DocStart | Motion::DocEnd
+        ),
+        _ => false
+    }
+}
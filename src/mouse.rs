@@ -0,0 +1,137 @@
+//Mouse
+//Turns clicks and drags into cursor moves and selections. The view maps the
+//pointer to a (line, column) in the doc, this decides what to select there.
+//
+//A click places the cursor, shift+click extends the selection to it. Clicking
+//again quickly in the same place selects the word, then the line, then the
+//paragraph, and dragging after that extends the selection a whole word, line or
+//paragraph at a time.
+
+use std::time::{Duration, Instant};
+
+use crate::doc_handler::DocHandler;
+
+//clicks closer together than this count as one double or triple click
+const MULTI_CLICK_TIME : Duration = Duration::from_millis(400);
+//as long as the pointer moved less than this many pixels between them
+const MULTI_CLICK_DISTANCE : f64 = 4.0;
+
+type Pos = (usize, usize);
+
+//what a click selects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Char,
+    Word,
+    Line,
+    Paragraph
+}
+
+pub struct Mouse {
+    pub position: (f64, f64),   //pointer in pixels from the top left of the window
+    pub dragging: bool,         //the left button is held down
+    clicks: usize,              //1 for a single click, 2 for a double click...
+    last_click: Option<(Instant, (f64, f64))>,
+    unit: Unit,
+    origin: (Pos, Pos)          //what the press selected, a drag extends from this
+}
+
+impl Mouse {
+    pub fn new() -> Self {
+        Self {
+            position: (0.0, 0.0),
+            dragging: false,
+            clicks: 0,
+            last_click: None,
+            unit: Unit::Char,
+            origin: ((0, 0), (0, 0))
+        }
+    }
+
+
+    //the left button went down over pos. With extend (shift held) the selection
+    //grows from where it is to pos instead of starting over.
+    pub fn press(&mut self, doc : &mut DocHandler, pos : Pos, extend : bool) {
+        let now = Instant::now();
+        let (x, y) = self.position;
+
+        let repeated = self.last_click.is_some_and(|(time, (last_x, last_y))| {
+            now.duration_since(time) < MULTI_CLICK_TIME && (x - last_x).hypot(y - last_y) < MULTI_CLICK_DISTANCE
+        });
+        self.clicks = match repeated {
+            true => self.clicks % 4 + 1,
+            false => 1
+        };
+        self.last_click = Some((now, self.position));
+
+        self.unit = match self.clicks {
+            1 => Unit::Char,
+            2 => Unit::Word,
+            3 => Unit::Line,
+            _ => Unit::Paragraph
+        };
+        self.dragging = true;
+        doc.break_coalescing();
+
+        if extend && self.unit == Unit::Char {
+            let cursor = (doc.ptr_y, doc.ptr_x);
+            let anchor = doc.anchor.unwrap_or(cursor);
+            self.origin = (anchor, anchor);
+            self.drag(doc, pos);
+            return
+        }
+
+        let (start, end) = range_at(doc, pos, self.unit);
+        self.origin = (start, end);
+        select(doc, start, end);
+    }
+
+    //the pointer moved over pos with the button down
+    pub fn drag(&mut self, doc : &mut DocHandler, pos : Pos) {
+        if !self.dragging {
+            return
+        }
+
+        let (start, end) = range_at(doc, pos, self.unit);
+        let (origin_start, origin_end) = self.origin;
+
+        //selecting backwards keeps the cursor at the start
+        match start < origin_start {
+            true => select(doc, origin_end, start),
+            false => select(doc, origin_start, end.max(origin_end))
+        };
+    }
+
+    pub fn release(&mut self) {
+        self.dragging = false;
+    }
+}
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//the range a click of the unit at pos selects
+fn range_at(doc : &DocHandler, pos : Pos, unit : Unit) -> (Pos, Pos) {
+    let (y, x) = pos;
+    let pos = (y.min(doc.text.len() - 1), x);
+    let pos = (pos.0, x.min(doc.line_len(pos.0)));
+
+    match unit {
+        Unit::Char => (pos, pos),
+        Unit::Word => doc.word_range_at(pos),
+        Unit::Line => doc.line_range_at(pos.0),
+        Unit::Paragraph => doc.paragraph_range_at(pos.0)
+    }
+}
+
+//select from anchor to cursor, nothing is selected when they are the same
+fn select(doc : &mut DocHandler, anchor : Pos, cursor : Pos) {
+    doc.set_cursor(cursor.0, cursor.1);
+    doc.anchor = match anchor == cursor {
+        true => None,
+        false => Some(anchor)
+    };
+}
@@ -0,0 +1,233 @@
+//Panes
+//The text area split into panes, one above the other or side by side, each showing
+//one of the open docs with its own cursor and scroll. Panes on the same doc show the
+//same DocHandler, so an edit in one is in the others straight away.
+//
+//The doc being edited holds the cursor of the focused pane. The other panes keep
+//theirs here and hand it back to the doc when they are focused again, so a pane's
+//cursor may be left past the end of text deleted in another pane until then.
+
+use crate::command::{EditorCommand, Outcome};
+use crate::doc_handler::DocHandler;
+use crate::tabs::Tabs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    Horizontal,     //one pane above the other
+    Vertical        //side by side
+}
+
+//a cursor and the other end of its selection, as kept by DocHandler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub pos: (usize, usize),
+    pub anchor: Option<(usize, usize)>
+}
+
+pub struct Pane {
+    pub id: u64,        //different for every pane, panes move in the list when one is closed
+    pub doc: u64,       //id of the doc shown
+    cursor: Cursor      //only up to date while the pane isn't focused
+}
+
+//how the text area is divided, the leaves are indexes into the panes
+enum Node {
+    Pane(usize),
+    Split(Split, Box<Node>, Box<Node>)
+}
+
+pub struct Panes {
+    panes: Vec<Pane>,
+    layout: Node,
+    active: usize,
+    next_id: u64
+}
+
+impl Cursor {
+    pub fn of(doc : &DocHandler) -> Self {
+        Self {
+            pos: (doc.ptr_y, doc.ptr_x),
+            anchor: doc.anchor
+        }
+    }
+
+    //moved back inside the text, which may have got shorter since it was kept
+    pub fn clamped(self, doc : &DocHandler) -> Self {
+        let clamp = |(y, x) : (usize, usize)| {
+            let y = y.min(doc.text.len() - 1);
+            (y, x.min(doc.line_len(y)))
+        };
+
+        Self {
+            pos: clamp(self.pos),
+            anchor: self.anchor.map(clamp)
+        }
+    }
+
+    //start and end of the selection in document order, as DocHandler::selection
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        self.anchor.map(|anchor| match anchor <= self.pos {
+            true => (anchor, self.pos),
+            false => (self.pos, anchor)
+        })
+    }
+
+    fn apply(self, doc : &mut DocHandler) {
+        let cursor = self.clamped(doc);
+        doc.set_cursor(cursor.pos.0, cursor.pos.1);
+        doc.anchor = cursor.anchor;
+    }
+}
+
+impl Panes {
+    //a single pane on the doc
+    pub fn new(doc : &DocHandler) -> Self {
+        Self {
+            panes: vec![Pane { id: 0, doc: doc.id, cursor: Cursor::of(doc) }],
+            layout: Node::Pane(0),
+            active: 0,
+            next_id: 1
+        }
+    }
+
+    pub fn panes(&self) -> &[Pane] {
+        &self.panes
+    }
+
+    //index of the focused pane
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    //the doc shown in a pane and where its cursor is, the focused pane's is the doc's own
+    pub fn view<'a>(&self, tabs : &'a Tabs, i : usize) -> (&'a DocHandler, Cursor) {
+        let doc = tabs.docs().iter().find(|doc| doc.id == self.panes[i].doc).unwrap_or(tabs.doc());
+        match i == self.active {
+            true => (doc, Cursor::of(doc)),
+            false => (doc, self.panes[i].cursor.clamped(doc))
+        }
+    }
+
+    //pane commands, None for commands meant for something else
+    pub fn run(&mut self, tabs : &mut Tabs, command : &EditorCommand) -> Option<Outcome> {
+        let outcome = match command {
+            EditorCommand::SplitHorizontal => self.split(tabs, Split::Horizontal),
+            EditorCommand::SplitVertical => self.split(tabs, Split::Vertical),
+
+            EditorCommand::NextPane => match self.panes.len() {
+                1 => Outcome::Unchanged,
+                _ => {
+                    self.focus(tabs, (self.active + 1) % self.panes.len());
+                    Outcome::Applied
+                }
+            },
+
+            EditorCommand::ClosePane => match self.panes.len() {
+                1 => Outcome::Unchanged,
+                _ => {
+                    let closed = self.active;
+                    self.layout = remove(std::mem::replace(&mut self.layout, Node::Pane(0)), closed);
+                    self.panes.remove(closed);
+
+                    //the closed pane's cursor is dropped rather than kept
+                    self.enter(tabs, closed.min(self.panes.len() - 1));
+                    Outcome::Applied
+                }
+            },
+
+            _ => return None
+        };
+
+        Some(outcome)
+    }
+
+    //focus another pane, switching to its doc
+    pub fn focus(&mut self, tabs : &mut Tabs, i : usize) {
+        if i == self.active || i >= self.panes.len() {
+            return
+        }
+
+        self.panes[self.active].cursor = Cursor::of(tabs.doc());
+        self.enter(tabs, i);
+    }
+
+    //make pane i the focused one and give its doc the pane's cursor
+    fn enter(&mut self, tabs : &mut Tabs, i : usize) {
+        self.active = i;
+
+        let pane = &self.panes[i];
+        if let Some(index) = tabs.docs().iter().position(|doc| doc.id == pane.doc) {
+            tabs.select(index);
+        }
+        pane.cursor.apply(tabs.doc_mut());
+    }
+
+    //catch up with the tabs after a command: the focused pane shows the doc being
+    //edited, and panes on docs that were closed show it too
+    pub fn sync(&mut self, tabs : &Tabs) {
+        let doc = tabs.doc();
+        for (i, pane) in self.panes.iter_mut().enumerate() {
+            let open = tabs.docs().iter().any(|open| open.id == pane.doc);
+            if i == self.active || !open {
+                pane.doc = doc.id;
+                pane.cursor = Cursor::of(doc);
+            }
+        }
+    }
+
+    //the area of each pane in the text area given as x, y, width and height, in the order of panes()
+    pub fn areas(&self, area : (f32, f32, f32, f32)) -> Vec<(f32, f32, f32, f32)> {
+        let mut areas = vec![area; self.panes.len()];
+        lay_out(&self.layout, area, &mut areas);
+        areas
+    }
+
+    //split the focused pane in two, the new pane shows the same doc and is focused
+    fn split(&mut self, tabs : &mut Tabs, split : Split) -> Outcome {
+        let i = self.panes.len();
+        self.panes.push(Pane { id: self.next_id, doc: tabs.doc().id, cursor: Cursor::of(tabs.doc()) });
+        self.next_id += 1;
+
+        self.layout = split_leaf(std::mem::replace(&mut self.layout, Node::Pane(0)), self.active, split, i);
+        self.focus(tabs, i);
+        Outcome::Applied
+    }
+}
+
+//the node with the leaf for pane i split, pane added below or right of it
+fn split_leaf(node : Node, i : usize, split : Split, added : usize) -> Node {
+    match node {
+        Node::Pane(pane) if pane == i => Node::Split(split, Box::new(Node::Pane(pane)), Box::new(Node::Pane(added))),
+        Node::Pane(pane) => Node::Pane(pane),
+        Node::Split(inner, first, second) => Node::Split(inner, Box::new(split_leaf(*first, i, split, added)), Box::new(split_leaf(*second, i, split, added)))
+    }
+}
+
+//the node without the leaf for pane i, its sibling takes the place of their split.
+//Panes after it move down one in the list so their leaves do too.
+fn remove(node : Node, i : usize) -> Node {
+    match node {
+        Node::Pane(pane) if pane > i => Node::Pane(pane - 1),
+        Node::Pane(pane) => Node::Pane(pane),
+        Node::Split(split, first, second) => match (*first, *second) {
+            (Node::Pane(pane), other) | (other, Node::Pane(pane)) if pane == i => remove(other, i),
+            (first, second) => Node::Split(split, Box::new(remove(first, i)), Box::new(remove(second, i)))
+        }
+    }
+}
+
+//split the area in half at each split down to the panes
+fn lay_out(node : &Node, area : (f32, f32, f32, f32), areas : &mut [(f32, f32, f32, f32)]) {
+    let (x, y, w, h) = area;
+    match node {
+        Node::Pane(pane) => areas[*pane] = area,
+        Node::Split(Split::Horizontal, first, second) => {
+            lay_out(first, (x, y, w, h / 2.0), areas);
+            lay_out(second, (x, y + h / 2.0, w, h - h / 2.0), areas);
+        },
+        Node::Split(Split::Vertical, first, second) => {
+            lay_out(first, (x, y, w / 2.0, h), areas);
+            lay_out(second, (x + w / 2.0, y, w - w / 2.0, h), areas);
+        }
+    }
+}
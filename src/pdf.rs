@@ -0,0 +1,676 @@
+//PDF
+//Lays a document (see export.rs) out on pages and writes it as a PDF, so a manuscript
+//can be printed without leaving the editor. The text is set in the bundled Roboto Mono,
+//embedded with only the glyphs the document uses. Paragraphs are indented and wrapped at
+//the margins and a paragraph split across pages leaves at least `orphans` lines at the
+//bottom of the first page and `widows` lines at the top of the next. The title page, the
+//dedication and parts get a page to themselves and chapters start on a new page unless
+//chapter_new_page is turned off.
+//
+//The layout is the [pdf] table of book.toml, every one is optional, e.g.
+//
+//  [pdf]
+//  page_size = "a4"                # a5, letter, legal or width x height, e.g. "6x9in" or "148x210mm"
+//  margins = { top = "1in", bottom = "1in", left = "1.25in", right = "1in" }   # in, mm, cm or pt
+//  font_size = 12                  # points
+//  line_spacing = 2                # 1 for single spaced
+//  header = "{author} / {title}"   # {title}, {author}, {chapter}, {page} and {pages} are filled in
+//  footer = "{page}"               # an empty header or footer leaves it out
+//  widows = 2
+//  orphans = 2
+//  chapter_new_page = true
+//
+//Headers are left out on the first page of a part or chapter and neither is put on the
+//title page or the dedication.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::{Content, Filter, Name, Pdf, Rect, Ref, Str, TextStr};
+use serde::{Deserialize, Serialize};
+use ttf_parser::{Face, GlyphId};
+
+use crate::book::Level;
+use crate::export::{Block, Document};
+use crate::fonts::BUNDLED_FONT;
+
+//page sizes by name, in points
+const PAGE_SIZES : [(&str, f32, f32); 4] = [
+    ("a4", 595.28, 841.89),
+    ("a5", 419.53, 595.28),
+    ("letter", 612.0, 792.0),
+    ("legal", 612.0, 1008.0)
+];
+
+//units a length can be given in, in points
+const UNITS : [(&str, f32); 4] = [("in", 72.0), ("mm", 72.0 / 25.4), ("cm", 72.0 / 2.54), ("pt", 1.0)];
+
+//distance between baselines of single spaced text, in font sizes
+const LINE_HEIGHT : f32 = 1.2;
+
+const FONT_NAME : Name = Name(b"F1");
+const CATALOG : Ref = Ref::new(1);
+const PAGE_TREE : Ref = Ref::new(2);
+const FONT : Ref = Ref::new(3);
+const CID_FONT : Ref = Ref::new(4);
+const DESCRIPTOR : Ref = Ref::new(5);
+const FONT_FILE : Ref = Ref::new(6);
+const CMAP : Ref = Ref::new(7);
+const INFO : Ref = Ref::new(8);
+const FIRST_PAGE : i32 = 9;     //then the page's contents, then the next page
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    pub margins: Margins,
+    pub font_size: f32,
+    pub line_spacing: f32,
+    pub header: String,
+    pub footer: String,
+    pub widows: usize,
+    pub orphans: usize,
+    pub chapter_new_page: bool
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Margins {
+    pub top: Length,
+    pub bottom: Length,
+    pub left: Length,
+    pub right: Length
+}
+
+//a page size as written, e.g. "a4" or "6x9in", and in points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PageSize {
+    text: String,
+    pub width: f32,
+    pub height: f32
+}
+
+//a length as written, e.g. "1in" or "25mm", and in points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Length {
+    text: String,
+    pub points: f32
+}
+
+//a page as laid out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    pub lines: Vec<Line>
+}
+
+//a line of text and where it goes on the page, in points from the bottom left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,     //of the baseline
+    pub size: f32
+}
+
+//what a page holds, which decides whether it gets a header and footer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    FrontMatter,    //the title page or dedication, neither
+    Opening,        //starts a part or chapter, only a footer
+    Body
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+    Left(f32),  //indented by this many points
+    Center
+}
+
+//the pages laid out so far and where the next line goes on the last one
+struct Layout<'a> {
+    document: &'a Document,
+    options: &'a PdfOptions,
+    face: Face<'a>,
+    pages: Vec<(Page, Kind, Option<String>)>,   //with the chapter they end in, for {chapter}
+    lines: Vec<Line>,
+    kind: Kind,
+    chapter: Option<String>,
+    used: f32,      //down from the top margin
+    full: bool      //nothing more goes on the page, e.g. the title page
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            page_size: "a4".parse().expect("a4 is a page size"),
+            margins: Margins::default(),
+            font_size: 12.0,
+            line_spacing: 1.5,
+            header: String::from("{title}"),
+            footer: String::from("{page}"),
+            widows: 2,
+            orphans: 2,
+            chapter_new_page: true
+        }
+    }
+}
+
+impl Default for Margins {
+    fn default() -> Self {
+        let inch : Length = "1in".parse().expect("1in is a length");
+        Self {
+            top: inch.clone(),
+            bottom: inch.clone(),
+            left: inch.clone(),
+            right: inch
+        }
+    }
+}
+
+impl PdfOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    //why the options can't be used, checked when a book is loaded
+    pub fn validate(&self) -> Result<(), String> {
+        let (width, height) = self.text_area();
+        match (self.font_size, self.line_spacing, self.widows.min(self.orphans)) {
+            (size, _, _) if !(4.0..=72.0).contains(&size) => Err(format!("pdf font size {} is not between 4 and 72", size)),
+            (_, spacing, _) if !(0.5..=4.0).contains(&spacing) => Err(format!("pdf line spacing {} is not between 0.5 and 4", spacing)),
+            (_, _, 0) => Err(String::from("pdf widows and orphans are at least 1 line")),
+            (size, spacing, _) if width < size * 10.0 || height < size * LINE_HEIGHT * spacing * 10.0 => {
+                Err(format!("the pdf margins leave no room for text on a {} page", self.page_size))
+            },
+            _ => Ok(())
+        }
+    }
+
+    //width and height inside the margins
+    fn text_area(&self) -> (f32, f32) {
+        let margins = &self.margins;
+        (
+            self.page_size.width - margins.left.points - margins.right.points,
+            self.page_size.height - margins.top.points - margins.bottom.points
+        )
+    }
+}
+
+impl fmt::Display for PageSize {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+//a name or a width and height with one unit, e.g. "6x9in"
+impl FromStr for PageSize {
+    type Err = String;
+
+    fn from_str(text : &str) -> Result<Self, Self::Err> {
+        let trimmed = text.trim().to_lowercase();
+        let named = PAGE_SIZES.iter().find(|(name, _, _)| *name == trimmed).map(|(_, width, height)| (*width, *height));
+        let measured = || {
+            let (number, per_unit) = split_unit(&trimmed)?;
+            let (width, height) = number.split_once('x')?;
+            Some((positive(width)? * per_unit, positive(height)? * per_unit))
+        };
+
+        match named.or_else(measured) {
+            Some((width, height)) => Ok(Self { text: text.to_string(), width, height }),
+            None => Err(format!("\"{}\" is not a page size, expected a4, a5, letter, legal or a width and height such as \"6x9in\"", text))
+        }
+    }
+}
+
+impl TryFrom<String> for PageSize {
+    type Error = String;
+
+    fn try_from(text : String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<PageSize> for String {
+    fn from(size : PageSize) -> Self {
+        size.text
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(text : &str) -> Result<Self, Self::Err> {
+        match split_unit(&text.trim().to_lowercase()).and_then(|(number, per_unit)| Some(positive(number)? * per_unit)) {
+            Some(points) => Ok(Self { text: text.to_string(), points }),
+            None => Err(format!("\"{}\" is not a length, expected a number and in, mm, cm or pt such as \"1in\"", text))
+        }
+    }
+}
+
+impl TryFrom<String> for Length {
+    type Error = String;
+
+    fn try_from(text : String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Length> for String {
+    fn from(length : Length) -> Self {
+        length.text
+    }
+}
+
+//the number before a unit and the points in one of the unit
+fn split_unit(text : &str) -> Option<(&str, f32)> {
+    UNITS.iter().find_map(|(unit, points)| text.strip_suffix(unit).map(|number| (number.trim(), *points)))
+}
+
+//a number that isn't negative
+fn positive(text : &str) -> Option<f32> {
+    text.trim().parse::<f32>().ok().filter(|number| number.is_finite() && *number >= 0.0)
+}
+
+//the document on pages in the layout of its pdf options, with the headers and footers
+pub fn lay_out(document : &Document) -> Vec<Page> {
+    let mut layout = Layout {
+        document,
+        options: &document.pdf,
+        face: Face::parse(BUNDLED_FONT, 0).expect("the bundled font is valid"),
+        pages: Vec::new(),
+        lines: Vec::new(),
+        kind: Kind::Body,
+        chapter: None,
+        used: 0.0,
+        full: false
+    };
+
+    for (i, block) in document.blocks.iter().enumerate() {
+        layout.block(block, i.checked_sub(1).map(|previous| &document.blocks[previous]));
+    }
+    layout.start_page(Kind::Body);
+    //a pdf has at least one page, even for an empty document
+    if layout.pages.is_empty() {
+        layout.pages.push((Page { lines: Vec::new() }, Kind::Body, None));
+    }
+    layout.headers_and_footers()
+}
+
+impl Layout<'_> {
+    fn block(&mut self, block : &Block, previous : Option<&Block>) {
+        if self.full {
+            self.start_page(Kind::Body);
+        }
+        let size = self.options.font_size;
+
+        match block {
+            Block::TitlePage => {
+                self.start_page(Kind::FrontMatter);
+                self.sink(3.0);
+                self.centered(&self.document.title.clone(), size * 2.0);
+                if !self.document.author.is_empty() {
+                    self.skip(1);
+                    self.centered(&format!("by {}", self.document.author), size * 1.2);
+                }
+                self.full = true;
+            },
+
+            Block::Dedication(lines) => {
+                self.start_page(Kind::FrontMatter);
+                self.sink(3.0);
+                for line in lines {
+                    self.centered(line, size);
+                }
+                self.full = true;
+            },
+
+            Block::Heading(Level::Part, heading) => {
+                self.start_page(Kind::Opening);
+                self.chapter = None;
+                self.sink(3.0);
+                self.centered(heading, size * 1.6);
+                self.full = true;
+            },
+
+            Block::Heading(_, heading) => {
+                let lines = self.wrap(heading, size * 1.4, 0.0);
+                let needed = lines.len() + 1 + self.options.orphans;
+                match (self.options.chapter_new_page, self.lines.is_empty()) {
+                    (true, _) => {
+                        self.start_page(Kind::Opening);
+                        self.sink(6.0);
+                    },
+                    (false, true) => self.kind = Kind::Opening,
+                    //the heading goes on the next page when the lines after it wouldn't fit under it
+                    (false, false) => match self.room(size) > needed {
+                        true => self.skip(1),
+                        false => self.start_page(Kind::Opening)
+                    }
+                }
+                self.chapter = Some(heading.clone());
+                for line in lines {
+                    self.place(&line, size * 1.4, Align::Center);
+                }
+                self.skip(1);
+            },
+
+            //kept with the text after it
+            Block::SceneBreak => {
+                if self.room(size) < 1 + self.options.orphans {
+                    self.start_page(Kind::Body);
+                }
+                let separator = self.document.scene_break.clone().unwrap_or_else(|| String::from("*   *   *"));
+                self.place(&separator, size, Align::Center);
+            },
+
+            Block::Paragraph(lines) => {
+                //the first paragraph after a heading or a break isn't indented
+                let indent = match previous {
+                    Some(Block::Paragraph(_)) => size * 3.0,
+                    _ => 0.0
+                };
+                let wrapped : Vec<(String, f32)> = lines.iter()
+                    .enumerate()
+                    .flat_map(|(i, line)| {
+                        let indent = match i {
+                            0 => indent,
+                            _ => 0.0
+                        };
+                        self.wrap(line, size, indent).into_iter().enumerate().map(move |(j, line)| match j {
+                            0 => (line, indent),
+                            _ => (line, 0.0)
+                        })
+                    })
+                    .collect();
+                self.paragraph(&wrapped);
+            }
+        }
+    }
+
+    //the lines of a paragraph with their indents, over as many pages as it takes
+    //keeping the orphans at the bottom of one page and the widows at the top of the next
+    fn paragraph(&mut self, lines : &[(String, f32)]) {
+        let size = self.options.font_size;
+        let (widows, orphans) = (self.options.widows, self.options.orphans);
+
+        let mut rest = lines;
+        while !rest.is_empty() {
+            let room = self.room(size);
+            let take = match rest.len() <= room {
+                true => rest.len(),
+                false => {
+                    let take = room.min(rest.len().saturating_sub(widows));
+                    match take < orphans {
+                        true => 0,
+                        false => take
+                    }
+                }
+            };
+            //a page too short for the rules still has to take something
+            let take = match (take, self.lines.is_empty()) {
+                (0, true) => room.clamp(1, rest.len()),
+                (take, _) => take
+            };
+
+            for (line, indent) in &rest[..take] {
+                self.place(line, size, Align::Left(*indent));
+            }
+            rest = &rest[take..];
+            if !rest.is_empty() {
+                self.start_page(Kind::Body);
+            }
+        }
+    }
+
+    //finish the page, when anything is on it, and start another
+    fn start_page(&mut self, kind : Kind) {
+        if !self.lines.is_empty() {
+            self.pages.push((Page { lines: std::mem::take(&mut self.lines) }, self.kind, self.chapter.clone()));
+        }
+        self.kind = kind;
+        self.used = 0.0;
+        self.full = false;
+    }
+
+    fn leading(&self, size : f32) -> f32 {
+        size * LINE_HEIGHT * self.options.line_spacing
+    }
+
+    //how many more lines of a size fit on the page
+    fn room(&self, size : f32) -> usize {
+        let (_, height) = self.options.text_area();
+        let left = height - self.used - size * LINE_HEIGHT;
+        match (self.full, left < 0.0) {
+            (false, false) => (left / self.leading(size)) as usize + 1,
+            _ => 0
+        }
+    }
+
+    //move down to a fraction of the text area, e.g. 3.0 for a third of the way down
+    fn sink(&mut self, fraction : f32) {
+        let (_, height) = self.options.text_area();
+        self.used = self.used.max(height / fraction);
+    }
+
+    //leave blank lines of the body text
+    fn skip(&mut self, lines : usize) {
+        self.used += self.leading(self.options.font_size) * lines as f32;
+    }
+
+    fn centered(&mut self, text : &str, size : f32) {
+        for line in self.wrap(text, size, 0.0) {
+            self.place(&line, size, Align::Center);
+        }
+    }
+
+    //a line at the next place down the page
+    fn place(&mut self, text : &str, size : f32, align : Align) {
+        let margins = &self.options.margins;
+        let (width, _) = self.options.text_area();
+        let x = margins.left.points + match align {
+            Align::Left(indent) => indent,
+            Align::Center => (width - self.width(text, size)) / 2.0
+        };
+        let ascent = self.face.ascender() as f32 / self.face.units_per_em() as f32 * size;
+        let y = self.options.page_size.height - margins.top.points - self.used - ascent;
+
+        self.lines.push(Line { text: text.to_string(), x, y, size });
+        self.used += self.leading(size);
+    }
+
+    //width of text in points
+    fn width(&self, text : &str, size : f32) -> f32 {
+        let units : u32 = text.chars().map(|c| advance(&self.face, glyph(&self.face, c)) as u32).sum();
+        units as f32 / self.face.units_per_em() as f32 * size
+    }
+
+    //text broken into lines that fit between the margins, at spaces unless a word is longer than a line.
+    //The first line is indented.
+    fn wrap(&self, text : &str, size : f32, indent : f32) -> Vec<String> {
+        let (width, _) = self.options.text_area();
+        let room = |lines : &Vec<String>| match lines.is_empty() {
+            true => width - indent,
+            false => width
+        };
+
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let joined = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{} {}", line, word)
+            };
+            if self.width(&joined, size) <= room(&lines) {
+                line = joined;
+                continue
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+
+            //as many chars of a long word as fit, at least one, on each line
+            let mut word = word;
+            while self.width(word, size) > room(&lines) {
+                let mut end = word.chars().next().map_or(0, char::len_utf8);
+                for (i, c) in word.char_indices().skip(1) {
+                    if self.width(&word[..i + c.len_utf8()], size) > room(&lines) {
+                        break
+                    }
+                    end = i + c.len_utf8();
+                }
+                lines.push(word[..end].to_string());
+                word = &word[end..];
+            }
+            line = word.to_string();
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+
+    //the pages with the header and footer filled in and added
+    fn headers_and_footers(self) -> Vec<Page> {
+        let options = self.options;
+        let margins = &options.margins;
+        let count = self.pages.len();
+        let fill = |template : &str, page : usize, chapter : &Option<String>| template
+            .replace("{title}", &self.document.title)
+            .replace("{author}", &self.document.author)
+            .replace("{chapter}", chapter.as_deref().unwrap_or(""))
+            .replace("{page}", &page.to_string())
+            .replace("{pages}", &count.to_string())
+            .trim()
+            .to_string();
+
+        let ascent = self.face.ascender() as f32 / self.face.units_per_em() as f32 * options.font_size;
+        let header_y = options.page_size.height - margins.top.points / 2.0;
+        let footer_y = margins.bottom.points / 2.0 - ascent / 2.0;
+        let (width, _) = options.text_area();
+
+        let mut pages = Vec::new();
+        for (i, (mut page, kind, chapter)) in self.pages.iter().cloned().enumerate() {
+            let header = fill(&options.header, i + 1, &chapter);
+            if kind == Kind::Body && !header.is_empty() {
+                let x = margins.left.points + width - self.width(&header, options.font_size);
+                page.lines.push(Line { text: header, x, y: header_y, size: options.font_size });
+            }
+            let footer = fill(&options.footer, i + 1, &chapter);
+            if kind != Kind::FrontMatter && !footer.is_empty() {
+                let x = margins.left.points + (width - self.width(&footer, options.font_size)) / 2.0;
+                page.lines.push(Line { text: footer, x, y: footer_y, size: options.font_size });
+            }
+            pages.push(page);
+        }
+        pages
+    }
+}
+
+//the glyph drawn for a char, the missing glyph when the font has none
+fn glyph(face : &Face, c : char) -> u16 {
+    face.glyph_index(c).map_or(0, |glyph| glyph.0)
+}
+
+fn advance(face : &Face, glyph : u16) -> u16 {
+    face.glyph_hor_advance(GlyphId(glyph)).unwrap_or(0)
+}
+
+//the bytes of a pdf file holding the document
+pub fn pdf(document : &Document) -> Vec<u8> {
+    let face = Face::parse(BUNDLED_FONT, 0).expect("the bundled font is valid");
+    let pages = lay_out(document);
+    let (width, height) = (document.pdf.page_size.width, document.pdf.page_size.height);
+
+    //the glyphs used and a char each one is drawn for, so the text can be copied out of the pdf
+    let mut used : BTreeMap<u16, char> = BTreeMap::new();
+    for c in pages.iter().flat_map(|page| page.lines.iter()).flat_map(|line| line.text.chars()) {
+        used.entry(glyph(&face, c)).or_insert(c);
+    }
+    let glyphs : Vec<u16> = [0].into_iter().chain(used.keys().copied()).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(CATALOG).pages(PAGE_TREE).lang(TextStr(&document.language));
+    let page_ids : Vec<Ref> = (0..pages.len()).map(|i| Ref::new(FIRST_PAGE + 2 * i as i32)).collect();
+    pdf.pages(PAGE_TREE).kids(page_ids.iter().copied()).count(pages.len() as i32);
+    {
+        let mut info = pdf.document_info(INFO);
+        info.title(TextStr(&document.title)).creator(TextStr("garbanzo_book"));
+        if !document.author.is_empty() {
+            info.author(TextStr(&document.author));
+        }
+    }
+
+    for (page, id) in pages.iter().zip(&page_ids) {
+        let contents = Ref::new(id.get() + 1);
+        {
+            let mut writer = pdf.page(*id);
+            writer.media_box(Rect::new(0.0, 0.0, width, height)).parent(PAGE_TREE).contents(contents);
+            writer.resources().fonts().pair(FONT_NAME, FONT);
+        }
+
+        let mut content = Content::new();
+        content.begin_text();
+        for line in &page.lines {
+            let text : Vec<u8> = line.text.chars().flat_map(|c| glyph(&face, c).to_be_bytes()).collect();
+            content.set_font(FONT_NAME, line.size);
+            content.set_text_matrix([1.0, 0.0, 0.0, 1.0, line.x, line.y]);
+            content.show(Str(&text));
+        }
+        content.end_text();
+        pdf.stream(contents, &compress_to_vec_zlib(&content.finish(), 6)).filter(Filter::FlateDecode);
+    }
+
+    //a subset is named with a tag made up from the glyphs in it, e.g. ABCDEF+RobotoMono-Regular
+    let hash = glyphs.iter().fold(0xcbf29ce484222325u64, |hash, glyph| (hash ^ *glyph as u64).wrapping_mul(0x100000001b3));
+    let tag : String = (0..6).map(|i| (b'A' + ((hash >> (i * 8)) % 26) as u8) as char).collect();
+    let base_font = format!("{}+RobotoMono-Regular", tag);
+    let scale = |units : f32| units * 1000.0 / face.units_per_em() as f32;
+
+    pdf.type0_font(FONT)
+        .base_font(Name(base_font.as_bytes()))
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(CID_FONT)
+        .to_unicode(CMAP);
+    {
+        let mut cid_font = pdf.cid_font(CID_FONT);
+        cid_font.subtype(CidFontType::Type2)
+            .base_font(Name(base_font.as_bytes()))
+            .system_info(SystemInfo { registry: Str(b"Adobe"), ordering: Str(b"Identity"), supplement: 0 })
+            .font_descriptor(DESCRIPTOR)
+            .cid_to_gid_map_predefined(Name(b"Identity"));
+        let mut widths = cid_font.widths();
+        for glyph in &glyphs {
+            widths.consecutive(*glyph, [scale(advance(&face, *glyph) as f32)]);
+        }
+    }
+
+    let bbox = face.global_bounding_box();
+    pdf.font_descriptor(DESCRIPTOR)
+        .name(Name(base_font.as_bytes()))
+        .flags(FontFlags::FIXED_PITCH | FontFlags::NON_SYMBOLIC)
+        .bbox(Rect::new(scale(bbox.x_min as f32), scale(bbox.y_min as f32), scale(bbox.x_max as f32), scale(bbox.y_max as f32)))
+        .italic_angle(0.0)
+        .ascent(scale(face.ascender() as f32))
+        .descent(scale(face.descender() as f32))
+        .cap_height(scale(face.capital_height().unwrap_or(face.ascender()) as f32))
+        .stem_v(80.0)
+        .font_file2(FONT_FILE);
+
+    let subset = subsetter::subset(BUNDLED_FONT, 0, subsetter::Profile::pdf(&glyphs)).expect("the bundled font can be subsetted");
+    pdf.stream(FONT_FILE, &compress_to_vec_zlib(&subset, 6))
+        .filter(Filter::FlateDecode)
+        .pair(Name(b"Length1"), subset.len() as i32);
+
+    let mut cmap = UnicodeCmap::new(Name(b"Custom"), SystemInfo { registry: Str(b"Adobe"), ordering: Str(b"UCS"), supplement: 0 });
+    for (glyph, c) in &used {
+        cmap.pair(*glyph, *c);
+    }
+    pdf.cmap(CMAP, &cmap.finish());
+
+    pdf.finish()
+}
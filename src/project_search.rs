@@ -0,0 +1,306 @@
+//Project search
+//Searches every text file under the project directory on a background thread,
+//skipping whatever .gitignore, .ignore and .garbanzoignore files exclude as well
+//as hidden and binary files. Results arrive file by file and are collected by
+//poll(), which the event loop calls while a search is running.
+//
+//Replacing across the project writes the files straight to disk, so the find bar
+//asks for confirmation first. Files open in a tab are the exception, they are
+//replaced in memory as an undo step (searching them again first, in case of
+//unsaved edits) so saving them later doesn't undo the replacement.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use crate::doc_handler::DocHandler;
+use crate::search::{find_all, replace_all, replacement, Match, SearchOptions};
+
+//ignore file for excluding drafts, exports etc. from project search
+const IGNORE_FILE : &str = ".garbanzoignore";
+
+//a search stops collecting results past this many
+const MAX_RESULTS : usize = 10_000;
+
+//A match in a file, with the text of its line for the results list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectMatch {
+    pub path: PathBuf,
+    pub found: Match,
+    pub text: String
+}
+
+//sent from the search thread
+enum Found {
+    File(Vec<ProjectMatch>),
+    Done(usize) //number of files searched
+}
+
+pub struct ProjectSearch {
+    pub root: PathBuf,
+    results: Vec<ProjectMatch>,
+    pub selected: Option<usize>, //index into results of the match last gone to
+    files: Option<usize>,        //files searched, once the search is done
+    receiver: Option<Receiver<Found>>,
+    cancel: Arc<AtomicBool>      //set to stop the running search thread
+}
+
+impl ProjectSearch {
+    pub fn new(root : &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            results: Vec::new(),
+            selected: None,
+            files: None,
+            receiver: None,
+            cancel: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    pub fn results(&self) -> &[ProjectMatch] {
+        &self.results
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    //number of files with at least one match
+    pub fn file_count(&self) -> usize {
+        let mut count = 0;
+        let mut last : Option<&Path> = None;
+        for result in &self.results {
+            if last != Some(result.path.as_path()) {
+                count += 1;
+                last = Some(&result.path);
+            }
+        }
+        count
+    }
+
+    //e.g. "17 matches in 4 files", or how far the search has got
+    pub fn status(&self) -> String {
+        match (self.files, self.results.len()) {
+            (None, found) => format!("Searching... {} matches so far", found),
+            (Some(files), 0) => format!("No results in {} files", files),
+            (Some(_), found) => {
+                let capped = match found >= MAX_RESULTS {
+                    true => " (stopped early)",
+                    false => ""
+                };
+                format!("{} matches in {} files{}", found, self.file_count(), capped)
+            }
+        }
+    }
+
+    //start searching, stopping any search that is still running
+    pub fn start(&mut self, regex : Option<Regex>) {
+        self.stop();
+        self.results.clear();
+        self.selected = None;
+        self.files = None;
+
+        let regex = match regex {
+            Some(regex) => regex,
+            None => {
+                self.files = Some(0);
+                return
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let root = self.root.clone();
+
+        self.receiver = Some(receiver);
+        self.cancel = cancel.clone();
+
+        thread::spawn(move || search_files(&root, &regex, &sender, &cancel));
+    }
+
+    pub fn stop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.receiver = None;
+    }
+
+    //collect the results found since the last poll, true if there were any changes
+    pub fn poll(&mut self) -> bool {
+        let receiver = match &self.receiver {
+            Some(receiver) => receiver,
+            None => return false
+        };
+
+        let mut changed = false;
+        let mut done = None;
+
+        for found in receiver.try_iter() {
+            changed = true;
+            match found {
+                Found::File(matches) => {
+                    let room = MAX_RESULTS.saturating_sub(self.results.len());
+                    self.results.extend(matches.into_iter().take(room));
+                },
+                Found::Done(files) => done = Some(files)
+            }
+        }
+
+        if done.is_some() || self.results.len() >= MAX_RESULTS {
+            self.files = Some(done.unwrap_or_default());
+            self.stop();
+        }
+
+        changed
+    }
+
+    //select the next or previous result, wrapping around
+    pub fn step(&mut self, forward : bool) -> Option<&ProjectMatch> {
+        let last = self.results.len().checked_sub(1)?;
+
+        self.selected = Some(match (self.selected, forward) {
+            (None, true) => 0,
+            (None, false) => last,
+            (Some(i), true) => match i == last { true => 0, false => i + 1 },
+            (Some(i), false) => match i == 0 { true => last, false => i - 1 }
+        });
+
+        self.selected.map(|i| &self.results[i])
+    }
+
+    //lines describing what replace all would do, one per file, e.g.
+    //"chapter1.txt: 3 matches, Anna → Bella"
+    pub fn replace_preview(&self, regex : &Regex, template : &str, options : SearchOptions) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut i = 0;
+
+        while i < self.results.len() {
+            let path = &self.results[i].path;
+            let count = self.results[i..].iter().take_while(|result| &result.path == path).count();
+
+            let first = &self.results[i];
+            let before : String = first.text.chars().skip(first.found.start).take(first.found.end - first.found.start).collect();
+            let after = replacement(&first.text, &first.found, regex, template, options);
+
+            lines.push(format!("{}: {} matches, {} → {}", self.display_path(path), count, before, after));
+            i += count;
+        }
+
+        lines
+    }
+
+    //replace every match in the project. Files open in a tab are changed in memory
+    //as an undo step each, every other file is rewritten on disk. Nothing is written
+    //unless all of those files can be read.
+    //Returns the number of matches replaced and files changed.
+    pub fn replace_all(&mut self, docs : &mut [DocHandler], regex : &Regex, template : &str, options : SearchOptions) -> Result<(usize, usize), io::Error> {
+        let mut paths : Vec<&PathBuf> = self.results.iter().map(|result| &result.path).collect();
+        paths.dedup();
+
+        let failed = |path : &Path, e : io::Error| io::Error::new(e.kind(), format!("{}: {}", self.display_path(path), e));
+        let is_open = |path : &Path| docs.iter().any(|doc| doc.path.as_ref().is_some_and(|doc_path| same_file(doc_path, path)));
+        let mut contents = Vec::new();
+        for path in paths.iter().filter(|path| !is_open(path)) {
+            let content = fs::read_to_string(path).map_err(|e| failed(path, e))?;
+            contents.push((*path, content));
+        }
+
+        let mut replaced = 0;
+        let mut files = 0;
+
+        //open docs are searched again first, in case of unsaved edits
+        for doc in docs.iter_mut().filter(|doc| doc.path.as_ref().is_some_and(|doc_path| paths.iter().any(|path| same_file(doc_path, path)))) {
+            let matches = find_all(&doc.text, regex);
+            if !matches.is_empty() {
+                doc.checkpoint(false);
+                replaced += replace_all(&mut doc.text, &matches, regex, template, options);
+                doc.revision += 1;
+                doc.anchor = None;
+                doc.set_cursor(doc.ptr_y, doc.ptr_x);
+                files += 1;
+            }
+        }
+
+        for (path, content) in contents {
+            //lines keep their \r so line endings are written back as they were
+            let mut lines : Vec<String> = content.split('\n').map(String::from).collect();
+            let matches = find_all(&trimmed_lines(&content), regex);
+
+            if !matches.is_empty() {
+                replaced += replace_all(&mut lines, &matches, regex, template, options);
+                fs::write(path, lines.join("\n")).map_err(|e| failed(path, e))?;
+                files += 1;
+            }
+        }
+
+        self.results.clear();
+        self.selected = None;
+        Ok((replaced, files))
+    }
+
+    //path relative to the project directory
+    pub fn display_path(&self, path : &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).display().to_string()
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+//the search thread, sends the matches of each file as it goes
+fn search_files(root : &Path, regex : &Regex, sender : &Sender<Found>, cancel : &AtomicBool) {
+    let walker = WalkBuilder::new(root)
+        .add_custom_ignore_filename(IGNORE_FILE)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+    let mut files = 0;
+
+    for entry in walker.flatten() {
+        if cancel.load(Ordering::Relaxed) {
+            return
+        }
+
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue
+        }
+
+        //binary and non utf-8 files are skipped
+        let content = match fs::read_to_string(entry.path()) {
+            Ok(content) if !content.contains('\0') => content,
+            _ => continue
+        };
+        files += 1;
+
+        let lines = trimmed_lines(&content);
+        let matches : Vec<ProjectMatch> = find_all(&lines, regex).into_iter()
+            .map(|found| ProjectMatch { path: entry.path().to_path_buf(), found, text: lines[found.line].clone() })
+            .collect();
+
+        if !matches.is_empty() && sender.send(Found::File(matches)).is_err() {
+            return
+        }
+    }
+
+    let _ = sender.send(Found::Done(files));
+}
+
+//lines of a file without their \r, for searching
+fn trimmed_lines(content : &str) -> Vec<String> {
+    content.split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect()
+}
+
+//whether two paths are the same file
+pub fn same_file(a : &Path, b : &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b
+    }
+}
@@ -0,0 +1,98 @@
+//Renderer
+//What a frame is drawn with. The view (view.rs) lays out the doc, bars and
+//highlights into a Frame of coloured rects and lines of text in pixels, which a
+//Renderer draws: WgpuHandler on the GPU, or CpuRenderer in software on machines
+//without a usable graphics adapter. Both can also draw offscreen, for tests.
+
+use std::error::Error;
+use std::fmt;
+
+use winit::dpi::PhysicalSize;
+
+use crate::theme::Color;
+
+//everything on screen for one frame, positions are in physical pixels from the top left
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub background: Color,
+    pub rects: Vec<Rect>,       //drawn in order, under the text
+    pub lines: Vec<TextLine>
+}
+
+//a flat coloured rectangle, e.g. the selection or the status bar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+    pub color: Color
+}
+
+//a single line of text with its top left at x, y
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLine {
+    pub runs: Vec<(String, usize)>, //text and the index in the font chain it is drawn in
+    pub x: f32,
+    pub y: f32,
+    pub scale: f32,                 //font size in pixels
+    pub color: Color,
+    pub clip: Option<Clip>          //the pane the line is in, nothing outside it is drawn
+}
+
+//an area of the frame that text is cut off at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clip {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32
+}
+
+impl Clip {
+    //left, top, right and bottom in whole pixels, inside a frame of the size given
+    pub fn pixels(&self, width : u32, height : u32) -> (u32, u32, u32, u32) {
+        let left = (self.x.max(0.0) as u32).min(width);
+        let top = (self.y.max(0.0) as u32).min(height);
+        let right = ((self.x + self.w).max(0.0).ceil() as u32).clamp(left, width);
+        let bottom = ((self.y + self.h).max(0.0).ceil() as u32).clamp(top, height);
+        (left, top, right, bottom)
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    Lost,           //the window's surface has to be set up again, e.g. after a resize
+    OutOfMemory,
+    Timeout,
+    Other(String)
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Lost => write!(f, "the surface was lost"),
+            RenderError::OutOfMemory => write!(f, "out of memory"),
+            RenderError::Timeout => write!(f, "timed out waiting for the surface"),
+            RenderError::Other(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+pub trait Renderer {
+    //size of the window or offscreen image in pixels
+    fn size(&self) -> PhysicalSize<u32>;
+
+    fn resize(&mut self, size : PhysicalSize<u32>);
+
+    //draw a frame and show it in the window, if there is one
+    fn render(&mut self, frame : &Frame) -> Result<(), RenderError>;
+
+    //the last frame drawn offscreen as rgba bytes row by row from the top left,
+    //None when drawing to a window
+    fn read_pixels(&self) -> Option<Vec<u8>>;
+}
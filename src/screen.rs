@@ -0,0 +1,92 @@
+//Screen
+//The window and what draws in it: the view lays out each frame and a renderer
+//draws it, on the GPU when there is a graphics adapter and in software otherwise.
+
+use winit::dpi::PhysicalSize;
+use winit::event::WindowEvent;
+use winit::window::{CursorIcon, Window};
+
+use crate::cpu_renderer::CpuRenderer;
+use crate::editor::Editor;
+use crate::fonts::FontChain;
+use crate::renderer::{RenderError, Renderer};
+use crate::settings::FontSettings;
+use crate::theme::Theme;
+use crate::view::View;
+use crate::wgpu_handler::WgpuHandler;
+
+pub struct Screen {
+    renderer: Box<dyn Renderer>,    //declared before the window so it is dropped first
+    pub view: View,
+    window: Window,
+    ibeam: bool                     //the mouse cursor is the I-beam used over text
+}
+
+impl Screen {
+    pub async fn new(window : Window, font_settings : &FontSettings) -> Self {
+        //the chosen font and its fallbacks, see fonts.rs
+        let fonts = FontChain::load(font_settings);
+
+        let renderer : Box<dyn Renderer> = match WgpuHandler::new(&window, &fonts).await {
+            Some(gpu) => Box::new(gpu),
+            None => {
+                eprintln!("No graphics adapter found, drawing in software");
+                Box::new(CpuRenderer::new(&window, &fonts).expect("the window system can be drawn to in software"))
+            }
+        };
+
+        Self {
+            renderer,
+            view: View::new(fonts, window.inner_size(), window.scale_factor(), font_settings),
+            window,
+            ibeam: false
+        }
+    }
+
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.renderer.size()
+    }
+
+    //show the I-beam while the mouse is over the text. Returns whether the event was
+    //used up, when it is the event loop doesn't handle it any further
+    pub fn input(&mut self, event : &WindowEvent) -> bool {
+        if let WindowEvent::CursorMoved { position, .. } = event {
+            let over_text = self.view.over_text((position.x, position.y));
+            if over_text != self.ibeam {
+                self.ibeam = over_text;
+                self.window.set_cursor_icon(match over_text {
+                    true => CursorIcon::Text,
+                    false => CursorIcon::Default
+                });
+            }
+        }
+
+        false
+    }
+
+    pub fn resize(&mut self, size : PhysicalSize<u32>) {
+        if size.width > 0 && size.height > 0 {
+            self.renderer.resize(size);
+            self.view.resize(size);
+        }
+    }
+
+    //the window moved to a monitor with a different scale factor
+    pub fn rescale(&mut self, scale_factor : f64, size : PhysicalSize<u32>) {
+        self.view.rescale(scale_factor, size);
+        self.resize(size);
+    }
+
+    pub fn set_theme(&mut self, theme : Theme) {
+        self.view.set_theme(theme);
+    }
+
+    pub fn render(&mut self, editor : &Editor) -> Result<(), RenderError> {
+        let frame = self.view.frame(editor);
+        self.renderer.render(&frame)
+    }
+}
@@ -0,0 +1,189 @@
+//Search
+//Finds every match of a query in a doc. Plain queries are escaped and run through
+//the same regex engine as regex queries, so the case and whole word options work
+//the same way for both. Matches never span more than one line.
+//
+//Replacements can use the capture groups of regex queries ($1, ${name}) and can
+//follow the case of the text they replace, so with preserve_case replacing
+//"anna" with "bella" turns "Anna" into "Bella" and "ANNA" into "BELLA".
+
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    pub preserve_case: bool,    //replacements follow the case of the match
+    pub in_selection: bool      //only search the selection the find bar was opened with
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SearchOption {
+    CaseSensitive,
+    WholeWord,
+    Regex,
+    PreserveCase,
+    InSelection
+}
+
+//A match on one line, start and end are char columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Match {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize
+}
+
+impl SearchOptions {
+    pub fn toggle(&mut self, option : SearchOption) {
+        match option {
+            SearchOption::CaseSensitive => self.case_sensitive = !self.case_sensitive,
+            SearchOption::WholeWord => self.whole_word = !self.whole_word,
+            SearchOption::Regex => self.regex = !self.regex,
+            SearchOption::PreserveCase => self.preserve_case = !self.preserve_case,
+            SearchOption::InSelection => self.in_selection = !self.in_selection
+        }
+    }
+}
+
+impl SearchOption {
+    pub const ALL : [SearchOption; 5] = [
+        SearchOption::CaseSensitive,
+        SearchOption::WholeWord,
+        SearchOption::Regex,
+        SearchOption::PreserveCase,
+        SearchOption::InSelection
+    ];
+
+    //name used for the option in config files, e.g. "whole_word"
+    pub fn name(self) -> &'static str {
+        match self {
+            SearchOption::CaseSensitive => "case_sensitive",
+            SearchOption::WholeWord => "whole_word",
+            SearchOption::Regex => "regex",
+            SearchOption::PreserveCase => "preserve_case",
+            SearchOption::InSelection => "in_selection"
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<SearchOption> {
+        SearchOption::ALL.into_iter().find(|option| option.name() == name)
+    }
+}
+
+impl Match {
+    pub fn start_pos(&self) -> (usize, usize) {
+        (self.line, self.start)
+    }
+
+    pub fn end_pos(&self) -> (usize, usize) {
+        (self.line, self.end)
+    }
+}
+
+//compile a query, the error is the regex syntax error for bad regex queries
+pub fn build_regex(query : &str, options : SearchOptions) -> Result<Regex, String> {
+    let pattern = match options.regex {
+        true => query.to_string(),
+        false => regex::escape(query)
+    };
+
+    let pattern = match options.whole_word {
+        true => format!(r"\b(?:{})\b", pattern),
+        false => pattern
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+//every match in the text in doc order. Empty matches, e.g. of "^", are skipped
+//since there is nothing to highlight or jump to.
+pub fn find_all(text : &[String], regex : &Regex) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    for (line, content) in text.iter().enumerate() {
+        for found in regex.find_iter(content) {
+            if found.start() == found.end() {
+                continue
+            }
+
+            let start = content[..found.start()].chars().count();
+            let end = start + found.as_str().chars().count();
+            matches.push(Match { line, start, end });
+        }
+    }
+
+    matches
+}
+
+//text to put in place of a match on a line. In regex mode $1, ${name} and $$ in
+//the template are expanded from the match's capture groups.
+pub fn replacement(line : &str, found : &Match, regex : &Regex, template : &str, options : SearchOptions) -> String {
+    let start = byte_index(line, found.start);
+    let matched = &line[start..byte_index(line, found.end)];
+
+    let text = match options.regex {
+        true => match regex.captures_at(line, start) {
+            Some(captures) => {
+                let mut text = String::new();
+                captures.expand(template, &mut text);
+                text
+            },
+            None => template.to_string()
+        },
+        false => template.to_string()
+    };
+
+    match options.preserve_case {
+        true => preserve_case(matched, &text),
+        false => text
+    }
+}
+
+//give the replacement the case of the text it replaces when that is all caps or
+//capitalised, otherwise the replacement is used as typed
+pub fn preserve_case(matched : &str, replacement : &str) -> String {
+    let letters : Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+
+    let all_upper = letters.iter().all(|c| c.is_uppercase());
+    let capitalised = letters.first().is_some_and(|c| c.is_uppercase()) && letters[1..].iter().all(|c| c.is_lowercase());
+
+    if letters.len() > 1 && all_upper {
+        return replacement.to_uppercase()
+    }
+
+    match capitalised {
+        true => {
+            let mut chars = replacement.chars();
+            chars.next().map_or(String::new(), |first| first.to_uppercase().chain(chars).collect())
+        },
+        false => replacement.to_string()
+    }
+}
+
+//replace every match in the text, returns how many were replaced.
+//A replacement containing a line break splits the line.
+pub fn replace_all(text : &mut Vec<String>, matches : &[Match], regex : &Regex, template : &str, options : SearchOptions) -> usize {
+    //back to front so the columns of the matches still to do stay put
+    for found in matches.iter().rev() {
+        let line = &text[found.line];
+        let with = replacement(line, found, regex, template, options);
+        let range = byte_index(line, found.start)..byte_index(line, found.end);
+        text[found.line].replace_range(range, &with);
+    }
+
+    if text.iter().any(|line| line.contains('\n')) {
+        *text = text.iter().flat_map(|line| line.split('\n')).map(String::from).collect();
+    }
+
+    matches.len()
+}
+
+//convert a char index into a byte index
+fn byte_index(line : &str, index : usize) -> usize {
+    line.char_indices().nth(index).map_or(line.len(), |(i, _)| i)
+}
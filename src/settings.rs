@@ -0,0 +1,168 @@
+//Settings
+//Display options, read from settings.toml in the working directory. Every option
+//is optional, e.g.
+//
+//  line_numbers = "relative"   # "off", "absolute" or "relative"
+//  status_bar = false          # hide the status bar
+//  theme = "auto"              # "light", "dark", "sepia", a theme file or "auto"
+//  light_theme = "sepia"       # used by "auto" when the desktop is light
+//  dark_theme = "dark"         # and when it is dark, see theme.rs
+//
+//  [font]
+//  family = "DejaVu Sans Mono" # a family name or the path of a font file
+//  size = 19.0                 # in points
+//  line_height = 1.2           # times the font's own line spacing
+//  fallback = ["Noto Sans CJK SC", "Symbola"] # tried in order for missing chars
+//
+//Options that are handy to change while writing are also commands, so they can
+//be bound to keys; those changes last until the editor is closed. So does the
+//zoom, which scales the font size.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::command::{EditorCommand, Outcome};
+use crate::theme;
+
+//each zoom in multiplies the font size by this much
+const ZOOM_STEP : f32 = 1.1;
+const MIN_ZOOM : f32 = 0.25;
+const MAX_ZOOM : f32 = 4.0;
+
+//What the gutter left of the text shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineNumbers {
+    #[default]
+    Off,
+    Absolute,
+    Relative    //distance from the cursor's line, which shows its own number
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub line_numbers: LineNumbers,
+    pub status_bar: bool,
+    pub theme: String,          //a theme name or path, or "auto" to follow the desktop
+    pub light_theme: String,
+    pub dark_theme: String,
+    pub font: FontSettings,
+    #[serde(skip)]
+    pub zoom: f32   //font size multiplier, changed with the zoom commands
+}
+
+//The font text is drawn in, see fonts.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FontSettings {
+    pub family: Option<String>,         //None for the bundled Roboto Mono
+    pub size: f32,                      //points, scaled to the monitor by the view
+    pub line_height: f32,
+    pub fallback: Option<Vec<String>>   //None to try a list of common CJK, emoji and math fonts
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(io::Error),
+    Parse(String)
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "could not read settings: {}", e),
+            SettingsError::Parse(e) => write!(f, "settings are not valid toml: {}", e)
+        }
+    }
+}
+
+impl Error for SettingsError {}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            line_numbers: LineNumbers::Off,
+            status_bar: true,
+            theme: String::from("light"),
+            light_theme: String::from("light"),
+            dark_theme: String::from("dark"),
+            font: FontSettings::default(),
+            zoom: 1.0
+        }
+    }
+}
+
+impl Default for FontSettings {
+    fn default() -> Self {
+        Self {
+            family: None,
+            size: 19.0,
+            line_height: 1.0,
+            fallback: None
+        }
+    }
+}
+
+impl Settings {
+    //the settings in a settings file, the defaults if there is no file
+    pub fn load(path : &Path) -> Result<Self, SettingsError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::from_toml(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(SettingsError::Io(e))
+        }
+    }
+
+    pub fn from_toml(text : &str) -> Result<Self, SettingsError> {
+        let settings : Self = toml::from_str(text).map_err(|e| SettingsError::Parse(e.to_string()))?;
+
+        match (settings.font.size, settings.font.line_height) {
+            (size, _) if !(1.0..=500.0).contains(&size) => Err(SettingsError::Parse(format!("font size {} is not between 1 and 500", size))),
+            (_, height) if !(0.5..=5.0).contains(&height) => Err(SettingsError::Parse(format!("line height {} is not between 0.5 and 5", height))),
+            _ => Ok(settings)
+        }
+    }
+
+    pub fn follows_desktop(&self) -> bool {
+        self.theme.eq_ignore_ascii_case(theme::AUTO)
+    }
+
+    //handle a command that changes a setting, None if it is not one
+    pub fn run(&mut self, command : &EditorCommand) -> Option<Outcome> {
+        match command {
+            EditorCommand::CycleLineNumbers => {
+                self.line_numbers = match self.line_numbers {
+                    LineNumbers::Off => LineNumbers::Absolute,
+                    LineNumbers::Absolute => LineNumbers::Relative,
+                    LineNumbers::Relative => LineNumbers::Off
+                };
+                Some(Outcome::Applied)
+            },
+
+            EditorCommand::ZoomIn => Some(self.set_zoom(self.zoom * ZOOM_STEP)),
+
+            EditorCommand::ZoomOut => Some(self.set_zoom(self.zoom / ZOOM_STEP)),
+
+            EditorCommand::ZoomReset => Some(self.set_zoom(1.0)),
+
+            _ => None
+        }
+    }
+
+    fn set_zoom(&mut self, zoom : f32) -> Outcome {
+        let zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+        match zoom == self.zoom {
+            true => Outcome::Unchanged,
+            false => {
+                self.zoom = zoom;
+                Outcome::Applied
+            }
+        }
+    }
+}
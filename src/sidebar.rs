@@ -0,0 +1,288 @@
+//Sidebar
+//The book being written, listed down the left of the window when the working
+//directory has a book.toml (see book.rs): its parts, chapters and scenes in reading
+//order. Picking a section opens its file in a tab. Sections are reordered by moving
+//them up and down with the keyboard or dragging them with the mouse, and can be left
+//out of the compiled book (see compile.rs). The manifest is saved straight away.
+//
+//The export_pdf command is answered here too. With a book open it writes the whole book,
+//compiled from its files as last saved, next to book.toml, otherwise the doc being edited
+//as it is in the editor, next to the doc. A pdf that is already there is only replaced
+//when export_pdf is given twice in a row.
+//
+//While the sidebar is focused it takes over the keyboard like the find bar: the up
+//and down movement commands pick a section and typing is ignored.
+
+use std::path::{Path, PathBuf};
+
+use crate::book::{Book, MANIFEST};
+use crate::command::{EditorCommand, Motion, Outcome};
+use crate::compile::compile;
+use crate::export::{self, Document, Format};
+use crate::iomod::{self, ManifestError};
+use crate::tabs::Tabs;
+
+pub struct Sidebar {
+    book: Option<Book>,
+    path: PathBuf,      //of the manifest, saved to when sections are moved
+    pub open: bool,
+    pub focused: bool,
+    selected: usize,    //row of the selected section, see Book::rows
+    confirm_export: bool //export_pdf was refused because the pdf is already there
+}
+
+impl Sidebar {
+    //a sidebar with no book, which stays hidden
+    pub fn new() -> Self {
+        Self {
+            book: None,
+            path: PathBuf::new(),
+            open: false,
+            focused: false,
+            selected: 0,
+            confirm_export: false
+        }
+    }
+
+    //the book in a manifest, shown straight away. No manifest is not an error, there is just no book.
+    pub fn load(path : &Path) -> Result<Self, ManifestError> {
+        match iomod::load_manifest(path) {
+            Ok(book) => Ok(Self {
+                book: Some(book),
+                path: path.to_path_buf(),
+                open: true,
+                ..Self::new()
+            }),
+            Err(ManifestError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e)
+        }
+    }
+
+    pub fn book(&self) -> Option<&Book> {
+        self.book.as_ref()
+    }
+
+    //whether there is a sidebar to draw
+    pub fn shown(&self) -> bool {
+        self.open && self.book.is_some()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    //row of the section whose file is the doc being edited
+    pub fn current(&self, tabs : &Tabs) -> Option<usize> {
+        let path = tabs.doc().path.as_deref()?;
+        self.book.as_ref()?.rows().iter().position(|(_, section)| section.file.as_deref() == Some(path))
+    }
+
+    //sidebar commands, and while it is focused the movement and typing meant for the text.
+    //None for commands meant for something else.
+    pub fn run(&mut self, tabs : &mut Tabs, command : &EditorCommand) -> Option<Outcome> {
+        let confirmed = std::mem::take(&mut self.confirm_export) && *command == EditorCommand::ExportPdf;
+
+        let rows = match (&self.book, command) {
+            (Some(book), _) => book.rows().len(),
+            (None, EditorCommand::ExportPdf) => return Some(self.export_doc(tabs, confirmed)),
+            (None, EditorCommand::ToggleSidebar | EditorCommand::FocusSidebar | EditorCommand::OpenSection |
+                EditorCommand::MoveSectionUp | EditorCommand::MoveSectionDown | EditorCommand::ToggleInclude) => {
+                return Some(Outcome::Failed(format!("{} needs a book, there is no {} in the working directory", command, MANIFEST)))
+            },
+            (None, _) => return None
+        };
+
+        let outcome = match (command, self.focused) {
+            (EditorCommand::ToggleSidebar, _) => {
+                self.open = !self.open;
+                self.focused &= self.open;
+                Outcome::Applied
+            },
+
+            (EditorCommand::FocusSidebar, true) | (EditorCommand::Cancel, true) => {
+                self.focused = false;
+                Outcome::Applied
+            },
+
+            (EditorCommand::FocusSidebar, false) => {
+                self.open = true;
+                self.focused = true;
+                self.selected = self.current(tabs).unwrap_or(self.selected);
+                Outcome::Applied
+            },
+
+            (EditorCommand::OpenSection, _) | (EditorCommand::InsertNewline, true) => self.open_section(tabs, self.selected),
+
+            (EditorCommand::ExportPdf, _) => self.export_book(confirmed),
+
+            (EditorCommand::MoveSectionUp, _) => self.move_section(tabs, Book::move_up),
+
+            (EditorCommand::MoveSectionDown, _) => self.move_section(tabs, Book::move_down),
+
+            (EditorCommand::ToggleInclude, _) => self.move_section(tabs, |book, path| {
+                book.toggle_include(path).map(|_| path.to_vec())
+            }),
+
+            (EditorCommand::Move(motion), true) => {
+                let selected = match motion {
+                    Motion::Up | Motion::Left | Motion::ParagraphUp => self.selected.saturating_sub(1),
+                    Motion::Down | Motion::Right | Motion::ParagraphDown => self.selected + 1,
+                    Motion::DocStart | Motion::LineStart => 0,
+                    Motion::DocEnd | Motion::LineEnd => rows,
+                    _ => self.selected
+                }.min(rows.saturating_sub(1));
+
+                match selected == self.selected {
+                    true => Outcome::Unchanged,
+                    false => {
+                        self.selected = selected;
+                        Outcome::Applied
+                    }
+                }
+            },
+
+            //nothing to type into
+            (EditorCommand::InsertChar(_) | EditorCommand::Delete(_) | EditorCommand::Kill(_) |
+                EditorCommand::KillLine | EditorCommand::KillRegion | EditorCommand::Yank | EditorCommand::YankPop, true) => Outcome::Unchanged,
+
+            _ => return None
+        };
+
+        Some(outcome)
+    }
+
+    //pick a section, e.g. when it is clicked
+    pub fn select(&mut self, row : usize) {
+        if let Some(book) = &self.book {
+            self.selected = row.min(book.rows().len().saturating_sub(1));
+        }
+    }
+
+    //open the file of the section in a row and give the keyboard back to the text
+    pub fn open_section(&mut self, tabs : &mut Tabs, row : usize) -> Outcome {
+        let section = match self.book.as_ref().and_then(|book| book.rows().get(row).map(|(_, section)| (*section).clone())) {
+            Some(section) => section,
+            None => return Outcome::Unchanged
+        };
+        self.selected = row;
+
+        match &section.file {
+            None => Outcome::Failed(format!("{} has no file of its own, open one of its {}s", section.label(), section.level.child().map_or("section", |level| level.name()))),
+            Some(file) => match tabs.open_or_new(file) {
+                Ok(_) => {
+                    self.focused = false;
+                    Outcome::Applied
+                },
+                Err(e) => Outcome::Failed(format!("Could not open {}: {}", file.display(), e))
+            }
+        }
+    }
+
+    //move the section in one row to where it was dropped in another, see Book::drop_onto
+    pub fn drop_section(&mut self, from : usize, onto : usize) -> Outcome {
+        let book = match &mut self.book {
+            Some(book) => book,
+            None => return Outcome::Unchanged
+        };
+
+        let rows = book.rows();
+        let (from, onto) = match (rows.get(from), rows.get(onto)) {
+            (Some((from, _)), Some((onto, _))) if from != onto => (from.clone(), onto.clone()),
+            _ => return Outcome::Unchanged
+        };
+
+        match book.drop_onto(&from, &onto) {
+            Ok(moved) => self.moved(&moved),
+            Err(e) => Outcome::Failed(e)
+        }
+    }
+
+    //compile the book and write it as a pdf next to the manifest
+    fn export_book(&mut self, confirmed : bool) -> Outcome {
+        let path = self.path.with_extension(Format::Pdf.extension());
+        if let Some(refused) = self.refuse_overwrite(&path, confirmed) {
+            return refused
+        }
+
+        let book = self.book.as_ref().expect("the sidebar has a book");
+        match compile(book) {
+            Ok(document) => write_pdf(&path, &document),
+            Err(e) => Outcome::Failed(format!("Could not compile the book, {}", e))
+        }
+    }
+
+    //write the doc as it is in the editor, saved or not, as a pdf next to it
+    fn export_doc(&mut self, tabs : &Tabs, confirmed : bool) -> Outcome {
+        let doc = tabs.doc();
+        let path = match &doc.path {
+            Some(path) => path.with_extension(Format::Pdf.extension()),
+            None => return Outcome::Failed(String::from("Save the doc before exporting it, the pdf is written next to it"))
+        };
+        if let Some(refused) = self.refuse_overwrite(&path, confirmed) {
+            return refused
+        }
+
+        let title = path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+        write_pdf(&path, &Document::from_text(&doc.text, &title))
+    }
+
+    //ask before replacing a pdf that is already there, unless this is the second export_pdf in a row
+    fn refuse_overwrite(&mut self, path : &Path, confirmed : bool) -> Option<Outcome> {
+        match path.exists() && !confirmed {
+            true => {
+                self.confirm_export = true;
+                Some(Outcome::Failed(format!("{} already exists, export again to replace it", path.display())))
+            },
+            false => None
+        }
+    }
+
+    //move or change the selected section with the keyboard, or the current doc's section when the sidebar
+    //isn't focused. step returns where the section ends up.
+    fn move_section(&mut self, tabs : &Tabs, step : fn(&mut Book, &[usize]) -> Option<Vec<usize>>) -> Outcome {
+        let row = match self.focused {
+            true => Some(self.selected),
+            false => self.current(tabs)
+        };
+
+        let (book, row) = match (&mut self.book, row) {
+            (Some(book), Some(row)) => (book, row),
+            _ => return Outcome::Failed(String::from("Pick a section in the sidebar first"))
+        };
+
+        let path = match book.rows().get(row) {
+            Some((path, _)) => path.clone(),
+            None => return Outcome::Unchanged
+        };
+
+        match step(book, &path) {
+            Some(moved) => self.moved(&moved),
+            None => Outcome::Unchanged
+        }
+    }
+
+    //select a section that was just moved or changed and save the book
+    fn moved(&mut self, path : &[usize]) -> Outcome {
+        let book = self.book.as_ref().expect("a section was moved in the book");
+        self.selected = book.rows().iter().position(|(row, _)| row == path).unwrap_or(self.selected);
+
+        match iomod::save_manifest(&self.path, book) {
+            Ok(_) => Outcome::Applied,
+            Err(e) => Outcome::Failed(format!("Could not save {}: {}", self.path.display(), e))
+        }
+    }
+}
+
+impl Default for Sidebar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//write a document as a pdf, the notice says where it went
+fn write_pdf(path : &Path, document : &Document) -> Outcome {
+    match std::fs::write(path, export::export(document, Format::Pdf)) {
+        Ok(_) => Outcome::Notice(format!("Exported {}", path.display())),
+        Err(e) => Outcome::Failed(format!("Could not write {}: {}", path.display(), e))
+    }
+}
@@ -0,0 +1,106 @@
+//Status
+//Text of the status bar at the bottom of the window: the file name and whether it
+//has unsaved changes, the cursor position, the line ending and encoding the file
+//is saved with, and how many words and chars there are in the doc and in the
+//selection. Counting the whole doc is only done again after it changes.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::doc_handler::DocHandler;
+
+pub struct Status {
+    words: usize,
+    chars: usize,
+    counted: Option<(u64, u64)> //doc id and revision the counts are for
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Self {
+            words: 0,
+            chars: 0,
+            counted: None
+        }
+    }
+
+    //count the doc again if it changed since the last count
+    pub fn refresh(&mut self, doc : &DocHandler) {
+        if self.counted == Some((doc.id, doc.revision)) {
+            return
+        }
+
+        self.counted = Some((doc.id, doc.revision));
+        self.words = doc.text.iter().map(|line| count_words(line)).sum();
+        self.chars = doc.text.iter().map(|line| line.chars().count()).sum();
+    }
+
+    //left and right sides of the bar, the mode and file on the left and the counts and
+    //file format on the right. Without the full bar only the mode is shown.
+    pub fn text(&self, doc : &DocHandler, mode_indicator : &str, full : bool) -> (String, String) {
+        match full {
+            true => {
+                let file_info = self.file_info(doc);
+                let left = match mode_indicator.is_empty() {
+                    true => file_info,
+                    false => format!("{}   {}", mode_indicator, file_info)
+                };
+                (left, self.doc_info(doc))
+            },
+            false => (mode_indicator.to_string(), String::new())
+        }
+    }
+
+    //left side of the bar, e.g. "chapter1.txt [modified]   Ln 12, Col 4"
+    pub fn file_info(&self, doc : &DocHandler) -> String {
+        let name = doc.name();
+        let dirty = match doc.is_dirty() {
+            true => " [modified]",
+            false => ""
+        };
+
+        format!("{}{}   Ln {}, Col {}", name, dirty, doc.ptr_y + 1, doc.ptr_x + 1)
+    }
+
+    //right side of the bar, e.g. "12 of 1,204 words   60 of 6,630 chars   LF   UTF-8".
+    //Line breaks are not counted as chars.
+    pub fn doc_info(&self, doc : &DocHandler) -> String {
+        let counts = match doc.selection() {
+            Some((start, end)) => {
+                let selected = doc.get_range(start, end);
+                let words = selected.split('\n').map(count_words).sum();
+                let chars = selected.chars().filter(|c| *c != '\n').count();
+                format!("{} of {} words   {} of {} chars", group(words), group(self.words), group(chars), group(self.chars))
+            },
+            None => format!("{} words   {} chars", group(self.words), group(self.chars))
+        };
+
+        format!("{}   {}   {}", counts, doc.format.line_ending, doc.format.encoding)
+    }
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//words as counted by unicode word segmentation, so "don't" is one word and
+//punctuation is none
+pub fn count_words(line : &str) -> usize {
+    line.unicode_words().count()
+}
+
+//a count with commas between the thousands, e.g. 12,345
+fn group(n : usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+
+    grouped
+}
@@ -0,0 +1,236 @@
+//Tabs
+//The docs open in the editor, one of which is shown and edited at a time. Each
+//keeps its own cursor and selection, the view keeps where each is scrolled to.
+//New docs are named untitled.txt, untitled-2.txt and so on, next to the doc that
+//was open, so they can be saved like any other.
+//
+//Closing a doc with unsaved changes only goes ahead when asked twice in a row, the
+//first time says what would be lost. Close all closes the saved docs straight away
+//and asks about the rest, and quitting with unsaved changes in any doc asks too.
+
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::command::{EditorCommand, Outcome};
+use crate::doc_handler::DocHandler;
+use crate::project_search::same_file;
+
+pub struct Tabs {
+    docs: Vec<DocHandler>,
+    active: usize,
+    confirm: Option<EditorCommand>  //refused because of unsaved changes, going ahead if given again
+}
+
+impl Tabs {
+    pub fn new(doc : DocHandler) -> Self {
+        Self {
+            docs: vec![doc],
+            active: 0,
+            confirm: None
+        }
+    }
+
+    pub fn doc(&self) -> &DocHandler {
+        &self.docs[self.active]
+    }
+
+    pub fn doc_mut(&mut self) -> &mut DocHandler {
+        &mut self.docs[self.active]
+    }
+
+    pub fn docs(&self) -> &[DocHandler] {
+        &self.docs
+    }
+
+    pub fn docs_mut(&mut self) -> &mut [DocHandler] {
+        &mut self.docs
+    }
+
+    //index of the doc being edited
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn select(&mut self, i : usize) {
+        self.active = i.min(self.docs.len() - 1);
+    }
+
+    //name shown on each tab, with a * when there are unsaved changes
+    pub fn titles(&self) -> Vec<String> {
+        self.docs.iter()
+            .map(|doc| match doc.is_dirty() {
+                true => format!("{}*", doc.name()),
+                false => doc.name()
+            })
+            .collect()
+    }
+
+    //switch to the file, opening it in a new tab if it isn't open yet
+    pub fn open(&mut self, path : &Path) -> Result<(), io::Error> {
+        self.open_with(path, DocHandler::open)
+    }
+
+    //as open, but a file that isn't there yet is opened as a new doc, e.g. a scene listed in a book before it is written
+    pub fn open_or_new(&mut self, path : &Path) -> Result<(), io::Error> {
+        self.open_with(path, DocHandler::open_or_new)
+    }
+
+    fn open_with(&mut self, path : &Path, open : fn(&PathBuf) -> Result<DocHandler, io::Error>) -> Result<(), io::Error> {
+        if let Some(i) = self.docs.iter().position(|doc| doc.path.as_deref().is_some_and(|open| same_file(open, path))) {
+            self.active = i;
+            return Ok(())
+        }
+
+        let doc = open(&path.to_path_buf())?;
+        self.add(doc);
+        Ok(())
+    }
+
+    //add a doc after the current one and switch to it
+    pub fn add(&mut self, doc : DocHandler) {
+        self.active += 1;
+        self.docs.insert(self.active, doc);
+    }
+
+    //tab commands, None for commands meant for the doc. Quit is only answered
+    //when it has to be refused.
+    pub fn run(&mut self, command : &EditorCommand) -> Option<Outcome> {
+        let confirmed = self.confirm.take().as_ref() == Some(command);
+
+        let outcome = match command {
+            EditorCommand::NewTab => {
+                let doc = self.untitled();
+                self.add(doc);
+                Outcome::Applied
+            },
+
+            EditorCommand::NextTab | EditorCommand::PreviousTab => {
+                let step = match command {
+                    EditorCommand::NextTab => 1,
+                    _ => self.docs.len() - 1
+                };
+                let before = self.active;
+                self.active = (self.active + step) % self.docs.len();
+                match self.active == before {
+                    true => Outcome::Unchanged,
+                    false => Outcome::Applied
+                }
+            },
+
+            EditorCommand::CloseTab => match (self.doc().is_dirty(), confirmed) {
+                (true, false) => {
+                    self.confirm = Some(command.clone());
+                    Outcome::Failed(format!("{} has unsaved changes, save it or close it again to discard them", self.doc().name()))
+                },
+                _ => {
+                    self.close(self.active);
+                    Outcome::Applied
+                }
+            },
+
+            EditorCommand::CloseAll => {
+                let unsaved = self.unsaved();
+                match (unsaved.is_empty(), confirmed) {
+                    (false, false) => {
+                        //close what can be closed without losing anything
+                        let saved : Vec<usize> = (0..self.docs.len()).rev().filter(|i| !self.docs[*i].is_dirty()).collect();
+                        for i in saved {
+                            self.close(i);
+                        }
+                        self.confirm = Some(command.clone());
+                        Outcome::Failed(format!("Unsaved changes in {}, save all or close all again to discard them", unsaved.join(", ")))
+                    },
+                    _ => {
+                        self.docs = vec![self.untitled()];
+                        self.active = 0;
+                        Outcome::Applied
+                    }
+                }
+            },
+
+            EditorCommand::SaveAll => {
+                let mut saved = Vec::new();
+                let mut failed = Vec::new();
+                for doc in self.docs.iter_mut().filter(|doc| doc.is_dirty()) {
+                    match doc.save() {
+                        Ok(_) => saved.push(doc.name()),
+                        Err(e) => failed.push(format!("{}: {}", doc.name(), e))
+                    }
+                }
+
+                match (failed.is_empty(), saved.is_empty()) {
+                    (false, _) => Outcome::Failed(format!("Save failed for {}", failed.join(", "))),
+                    (true, false) => Outcome::Notice(format!("Saved {}", saved.join(", "))),
+                    (true, true) => Outcome::Unchanged
+                }
+            },
+
+            EditorCommand::Quit => {
+                let unsaved = self.unsaved();
+                match (unsaved.is_empty(), confirmed) {
+                    (false, false) => {
+                        self.confirm = Some(command.clone());
+                        Outcome::Failed(format!("Unsaved changes in {}, save all or quit again to discard them", unsaved.join(", ")))
+                    },
+                    _ => return None
+                }
+            },
+
+            _ => return None
+        };
+
+        Some(outcome)
+    }
+
+    //the last doc closed is replaced by a new one so there is always something to edit
+    fn close(&mut self, i : usize) {
+        if self.docs.len() == 1 {
+            self.docs[0] = self.untitled();
+            return
+        }
+
+        self.docs.remove(i);
+        if i < self.active || self.active == self.docs.len() {
+            self.active -= 1;
+        }
+    }
+
+    //names of the docs with unsaved changes
+    fn unsaved(&self) -> Vec<String> {
+        self.docs.iter().filter(|doc| doc.is_dirty()).map(DocHandler::name).collect()
+    }
+
+    //names of the docs other than the one being edited with unsaved changes
+    pub fn unsaved_elsewhere(&self) -> Vec<String> {
+        self.docs.iter().enumerate()
+            .filter(|(i, doc)| *i != self.active && doc.is_dirty())
+            .map(|(_, doc)| doc.name())
+            .collect()
+    }
+
+    //an empty doc to be saved as untitled.txt, or untitled-2.txt and so on if that is taken
+    fn untitled(&self) -> DocHandler {
+        let mut doc = DocHandler::new();
+        doc.path = Some(self.untitled_path());
+        doc
+    }
+
+    //a file name for a new doc that is neither on disk nor open, in the folder of the current doc
+    fn untitled_path(&self) -> PathBuf {
+        let dir = self.docs.get(self.active)
+            .and_then(|doc| doc.path.as_deref())
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .or_else(|| env::current_dir().ok())
+            .unwrap_or_default();
+
+        (1..)
+            .map(|n| match n {
+                1 => dir.join("untitled.txt"),
+                n => dir.join(format!("untitled-{}.txt", n))
+            })
+            .find(|path| !path.exists() && !self.docs.iter().any(|doc| doc.path.as_ref() == Some(path)))
+            .expect("some untitled name is free")
+    }
+}
@@ -0,0 +1,232 @@
+//Themes
+//The colours everything is drawn in. Themes are toml files of "#rrggbb" or
+//"#rrggbbaa" colours, e.g.
+//
+//  base = "dark"           # colours left out are taken from this theme
+//  background = "#101010"
+//  selection = "#3a5a8099"
+//
+//Light, dark and sepia themes are bundled. The theme setting names one of them,
+//a file in the themes directory of the working directory (without the .toml), or
+//the path of a theme file. "auto" follows the desktop's light or dark preference,
+//switching between the light_theme and dark_theme settings when it changes.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use serde::Deserialize;
+use winit::window;
+
+use crate::settings::Settings;
+
+//theme setting that follows the desktop
+pub const AUTO : &str = "auto";
+const DEFAULT_THEME : &str = "light";
+
+const BUNDLED : [(&str, &str); 3] = [
+    ("light", include_str!("themes/light.toml")),
+    ("dark", include_str!("themes/dark.toml")),
+    ("sepia", include_str!("themes/sepia.toml"))
+];
+
+//rgba, each 0.0 to 1.0 in srgb as written in the theme file
+pub type Color = [f32; 4];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    pub text: Color,
+    pub caret: Color,
+    pub selection: Color,
+    pub gutter: Color,
+    pub line_number: Color,
+    pub current_line_number: Color,
+    pub status_bar: Color,
+    pub status_text: Color,
+    pub find_bar: Color,
+    pub search_match: Color,
+    pub current_match: Color
+}
+
+//a theme file, every colour is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    background: Option<String>,
+    text: Option<String>,
+    caret: Option<String>,
+    selection: Option<String>,
+    gutter: Option<String>,
+    line_number: Option<String>,
+    current_line_number: Option<String>,
+    status_bar: Option<String>,
+    status_text: Option<String>,
+    find_bar: Option<String>,
+    search_match: Option<String>,
+    current_match: Option<String>
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(io::Error),
+    Parse(String),
+    NotFound(String)
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeError::Io(e) => write!(f, "could not read theme: {}", e),
+            ThemeError::Parse(e) => write!(f, "theme is not valid: {}", e),
+            ThemeError::NotFound(name) => write!(f, "no theme called \"{}\"", name)
+        }
+    }
+}
+
+impl Error for ThemeError {}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::bundled(DEFAULT_THEME).expect("bundled theme is valid")
+    }
+}
+
+impl Theme {
+    //bundled themes set every colour so they are parsed over a blank theme
+    pub fn bundled(name : &str) -> Option<Self> {
+        let (_, text) = BUNDLED.iter().find(|(bundled, _)| bundled.eq_ignore_ascii_case(name))?;
+        Self::parse(text, Self::blank()).ok()
+    }
+
+    //a theme by name or path, user themes in dir/themes come before bundled ones
+    pub fn load(name : &str, dir : &Path) -> Result<Self, ThemeError> {
+        let path = Path::new(name);
+        let is_path = path.components().count() > 1 || path.extension().is_some_and(|ext| ext == "toml");
+        let user_theme = dir.join("themes").join(format!("{}.toml", name));
+
+        match (is_path, user_theme.is_file()) {
+            (true, _) => fs::read_to_string(path).map_err(ThemeError::Io).and_then(|text| Self::from_toml(&text)),
+            (false, true) => fs::read_to_string(&user_theme).map_err(ThemeError::Io).and_then(|text| Self::from_toml(&text)),
+            (false, false) => Self::bundled(name).ok_or_else(|| ThemeError::NotFound(name.to_string()))
+        }
+    }
+
+    //a theme file, colours it leaves out come from the light theme unless it has a base
+    pub fn from_toml(text : &str) -> Result<Self, ThemeError> {
+        Self::parse(text, Self::default())
+    }
+
+    fn parse(text : &str, default_base : Self) -> Result<Self, ThemeError> {
+        let file : ThemeFile = toml::from_str(text).map_err(|e| ThemeError::Parse(e.to_string()))?;
+
+        //only bundled themes can be a base, so bases can't go round in circles
+        let base = match &file.base {
+            Some(name) => Self::bundled(name).ok_or_else(|| ThemeError::Parse(format!("base \"{}\" is not a bundled theme", name)))?,
+            None => default_base
+        };
+
+        let color = |key : &str, value : &Option<String>, base : Color| match value {
+            Some(value) => parse_color(value).ok_or_else(|| ThemeError::Parse(format!("{} = \"{}\" is not a #rrggbb or #rrggbbaa colour", key, value))),
+            None => Ok(base)
+        };
+
+        Ok(Self {
+            background: color("background", &file.background, base.background)?,
+            text: color("text", &file.text, base.text)?,
+            caret: color("caret", &file.caret, base.caret)?,
+            selection: color("selection", &file.selection, base.selection)?,
+            gutter: color("gutter", &file.gutter, base.gutter)?,
+            line_number: color("line_number", &file.line_number, base.line_number)?,
+            current_line_number: color("current_line_number", &file.current_line_number, base.current_line_number)?,
+            status_bar: color("status_bar", &file.status_bar, base.status_bar)?,
+            status_text: color("status_text", &file.status_text, base.status_text)?,
+            find_bar: color("find_bar", &file.find_bar, base.find_bar)?,
+            search_match: color("search_match", &file.search_match, base.search_match)?,
+            current_match: color("current_match", &file.current_match, base.current_match)?
+        })
+    }
+
+    //the theme the settings ask for, given the desktop's theme when it is known.
+    //Falls back to the light theme when the one asked for can't be loaded.
+    pub fn choose(settings : &Settings, dir : &Path, desktop : Option<window::Theme>) -> Self {
+        let name = match settings.follows_desktop() {
+            true => match desktop.unwrap_or_else(desktop_theme) {
+                window::Theme::Light => &settings.light_theme,
+                window::Theme::Dark => &settings.dark_theme
+            },
+            false => &settings.theme
+        };
+
+        Self::load(name, dir).unwrap_or_else(|e| {
+            eprintln!("Ignoring theme \"{}\", using the {} theme: {}", name, DEFAULT_THEME, e);
+            Self::default()
+        })
+    }
+
+    fn blank() -> Self {
+        let black = [0.0, 0.0, 0.0, 1.0];
+        Self {
+            background: black,
+            text: black,
+            caret: black,
+            selection: black,
+            gutter: black,
+            line_number: black,
+            current_line_number: black,
+            status_bar: black,
+            status_text: black,
+            find_bar: black,
+            search_match: black,
+            current_match: black
+        }
+    }
+}
+
+//a colour converted for drawing to an srgb surface, which expects linear colours
+pub fn to_linear(color : Color) -> Color {
+    let [r, g, b, a] = color;
+    let channel = |c : f32| match c <= 0.04045 {
+        true => c / 12.92,
+        false => ((c + 0.055) / 1.055).powf(2.4)
+    };
+    [channel(r), channel(g), channel(b), a]
+}
+
+//"#rrggbb" or "#rrggbbaa"
+pub fn parse_color(text : &str) -> Option<Color> {
+    let hex = text.trim().strip_prefix('#')?;
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None
+    }
+
+    let channel = |i : usize| hex.get(i..i + 2).map_or(Some(255), |pair| u8::from_str_radix(pair, 16).ok());
+    Some([channel(0)? as f32 / 255.0, channel(2)? as f32 / 255.0, channel(4)? as f32 / 255.0, channel(6)? as f32 / 255.0])
+}
+
+//whether the desktop prefers light or dark windows, asked of the OS since winit
+//only reports it on some platforms. Light when it can't be told.
+pub fn desktop_theme() -> window::Theme {
+    let output = |program : &str, args : &[&str]| Command::new(program).args(args).output().ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_lowercase());
+
+    let dark = match std::env::consts::OS {
+        "macos" => output("defaults", &["read", "-g", "AppleInterfaceStyle"]).is_some_and(|style| style.contains("dark")),
+        "windows" => output("reg", &["query", r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize", "/v", "AppsUseLightTheme"])
+            .is_some_and(|value| value.contains("0x0")),
+        //the freedesktop colour scheme, then the name of the gtk theme
+        _ => output("gsettings", &["get", "org.gnome.desktop.interface", "color-scheme"]).is_some_and(|scheme| scheme.contains("dark"))
+            || std::env::var("GTK_THEME").is_ok_and(|theme| theme.to_lowercase().contains("dark"))
+            || output("gsettings", &["get", "org.gnome.desktop.interface", "gtk-theme"]).is_some_and(|theme| theme.contains("dark"))
+    };
+
+    match dark {
+        true => window::Theme::Dark,
+        false => window::Theme::Light
+    }
+}
@@ -0,0 +1,555 @@
+//Terminal frontend
+//Edits the doc in a terminal, e.g. over ssh, with the same editor as the window.
+//Terminal key events are turned into the winit key presses and typed chars the
+//window would get, so the keymap, vim, emacs and macros all work the same.
+//
+//Each frame is laid out into a Buffer of cells, then the whole buffer is written
+//to the terminal. Redrawing everything is cheap at terminal sizes and covers up
+//anything printed over the screen, e.g. by saving. The layout follows the view:
+//a row of tabs when more than one doc is open, the book's sections down the left while
+//the sidebar is open, panes of wrapped rows scrolled to keep the cursor on screen, a gutter for line numbers, the find bar and a status line along the bottom. Colours are left to the terminal,
+//styles only use reverse video, bold, dim and underline.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, BeginSynchronizedUpdate, EndSynchronizedUpdate, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use crate::book::Book;
+use crate::editor::Editor;
+use crate::keymap::parse_chord;
+use crate::layout::*;
+use crate::settings::LineNumbers;
+
+//how often results of a background project search are collected
+const SEARCH_POLL_INTERVAL : Duration = Duration::from_millis(50);
+//columns of the sidebar, it takes at most a third of the terminal
+const SIDEBAR_COLS : u16 = 28;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Text,
+    LineNumber,
+    CurrentLineNumber,
+    Selection,
+    Match,
+    CurrentMatch,
+    Bar             //the find bar and status line
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub c: char,
+    pub style: Style
+}
+
+const BLANK : Cell = Cell { c: ' ', style: Style::Text };
+
+//a grid of cells the size of the terminal, one char to a cell
+pub struct Buffer {
+    pub width: u16,
+    pub height: u16,
+    cells: Vec<Cell>
+}
+
+impl Buffer {
+    pub fn new(width : u16, height : u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![BLANK; width as usize * height as usize]
+        }
+    }
+
+    pub fn resize(&mut self, width : u16, height : u16) {
+        *self = Self::new(width, height);
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.fill(BLANK);
+    }
+
+    pub fn cell(&self, x : u16, y : u16) -> Cell {
+        self.cells[y as usize * self.width as usize + x as usize]
+    }
+
+    //text of a row of cells, without the trailing blanks
+    pub fn line(&self, y : u16) -> String {
+        let start = y as usize * self.width as usize;
+        let line : String = self.cells[start..start + self.width as usize].iter().map(|cell| cell.c).collect();
+        line.trim_end().to_string()
+    }
+
+    //write text from x along row y, cut off at the right edge. Returns the column after it.
+    pub fn put(&mut self, x : u16, y : u16, text : &str, style : Style) -> u16 {
+        let mut x = x;
+        for c in text.chars() {
+            if x >= self.width || y >= self.height {
+                break
+            }
+
+            //tabs and other control chars would move the terminal's cursor
+            let c = match c.is_control() {
+                true => ' ',
+                false => c
+            };
+            self.cells[y as usize * self.width as usize + x as usize] = Cell { c, style };
+            x += 1;
+        }
+        x
+    }
+
+    //restyle the cells x..x + len of row y
+    fn style(&mut self, x : u16, y : u16, len : usize, style : Style) {
+        for x in x as usize..(x as usize + len).min(self.width as usize) {
+            self.cells[y as usize * self.width as usize + x].style = style;
+        }
+    }
+
+    fn fill(&mut self, y : u16, style : Style) {
+        self.style(0, y, self.width as usize, style);
+    }
+}
+
+pub struct Tui {
+    scrolls: HashMap<(u64, u64), usize>,    //first row on screen of each open doc in each pane, by pane and doc id
+    sidebar_scroll: usize                   //first section on screen in the sidebar
+}
+
+impl Tui {
+    pub fn new() -> Self {
+        Self {
+            scrolls: HashMap::new(),
+            sidebar_scroll: 0
+        }
+    }
+
+    //pass a key event on to the editor. Chars are pressed as a key first, so chords
+    //such as ctrl+s reach the keymap, then typed.
+    pub fn key(&mut self, editor : &mut Editor, event : KeyEvent) {
+        if event.kind == KeyEventKind::Release {
+            return
+        }
+
+        let mut modifiers = ModifiersState::empty();
+        modifiers.set(ModifiersState::CTRL, event.modifiers.contains(KeyModifiers::CONTROL));
+        modifiers.set(ModifiersState::ALT, event.modifiers.contains(KeyModifiers::ALT));
+        modifiers.set(ModifiersState::SHIFT, event.modifiers.contains(KeyModifiers::SHIFT));
+        modifiers.set(ModifiersState::LOGO, event.modifiers.contains(KeyModifiers::SUPER));
+
+        match event.code {
+            KeyCode::Char(c) => {
+                if let Some((key, shifted)) = char_key_code(c) {
+                    editor.key(key, modifiers | shifted);
+                }
+                editor.char(c, modifiers);
+            },
+
+            //shift+tab
+            KeyCode::BackTab => editor.key(VirtualKeyCode::Tab, modifiers | ModifiersState::SHIFT),
+
+            code => if let Some(key) = key_code(code) {
+                editor.key(key, modifiers);
+            }
+        }
+    }
+
+    //lay out the editor into the buffer, scrolling each pane to keep its cursor on screen.
+    //Returns where the terminal's cursor goes.
+    pub fn draw(&mut self, editor : &Editor, buffer : &mut Buffer) -> (u16, u16) {
+        let doc = editor.doc();
+        let find_bar = &editor.find_bar;
+        let settings = &editor.settings;
+        buffer.clear();
+
+        //the tabs take the first row when there is more than one, the one being edited
+        //is drawn like the text
+        let titles = editor.tabs.titles();
+        let top = match titles.len() > 1 {
+            true => {
+                buffer.fill(0, Style::Bar);
+                let mut x = 0;
+                for (i, title) in titles.iter().enumerate() {
+                    let end = buffer.put(x, 0, &format!(" {} ", title), Style::Bar);
+                    if i == editor.tabs.active() {
+                        buffer.style(x, 0, (end - x) as usize, Style::Text);
+                    }
+                    x = end;
+                }
+                1
+            },
+            false => 0
+        };
+
+        //the status line is the last row, the find bar goes above it
+        let bar_lines = find_bar.lines(doc);
+        let status_y = buffer.height.saturating_sub(1);
+        let text_rows = (buffer.height as usize).saturating_sub(top + 1 + bar_lines.len()).max(1);
+
+        //the sidebar takes the columns on the left down to the find bar, with a line of the bar style right of it
+        let sidebar_w = match editor.sidebar.book() {
+            Some(book) if editor.sidebar.shown() => {
+                let w = SIDEBAR_COLS.min(buffer.width / 3);
+                for y in top..top + text_rows {
+                    buffer.put(w.saturating_sub(1), y as u16, "\u{2502}", Style::Bar);
+                }
+                self.draw_sidebar(editor, book, (0, top as u16, w.saturating_sub(1), text_rows as u16), buffer);
+                w
+            },
+            _ => 0
+        };
+
+        //the panes share out the rest of the rows between the tabs and the find bar, with a line
+        //of the bar style between them
+        self.scrolls.retain(|(pane, doc), _| {
+            editor.panes.panes().iter().any(|open| open.id == *pane) && editor.tabs.docs().iter().any(|open| open.id == *doc)
+        });
+        let areas = editor.panes.areas((sidebar_w as f32, top as f32, (buffer.width - sidebar_w) as f32, text_rows as f32));
+        let mut cursor = (sidebar_w, top as u16);
+        for (i, (x, y, w, h)) in areas.into_iter().enumerate() {
+            let (left, top_y) = (x.round() as u16, y.round() as u16);
+            let (right, bottom) = ((x + w).round() as u16, (y + h).round() as u16);
+            let divided = (right < buffer.width, (bottom as usize) < top + text_rows);
+
+            if divided.0 {
+                for y in top_y..bottom {
+                    buffer.put(right - 1, y, "\u{2502}", Style::Bar);
+                }
+            }
+            if divided.1 {
+                buffer.put(left, bottom - 1, &"\u{2500}".repeat((right - left) as usize), Style::Bar);
+            }
+
+            let area = (left, top_y, right - left - divided.0 as u16, bottom - top_y - divided.1 as u16);
+            let at = self.draw_pane(editor, i, area, buffer);
+            if i == editor.panes.active() {
+                cursor = at;
+            }
+        }
+
+        //while the sidebar has the keyboard the cursor goes to the selected section
+        if let Some(row) = editor.sidebar.selected().checked_sub(self.sidebar_scroll).filter(|_| editor.sidebar.focused && sidebar_w > 0) {
+            cursor = (0, (top + 1 + row).min(top + text_rows - 1) as u16);
+        }
+
+        for (i, line) in bar_lines.iter().enumerate() {
+            let y = (top + text_rows + i) as u16;
+            if y < status_y {
+                buffer.put(0, y, line, Style::Bar);
+                buffer.fill(y, Style::Bar);
+            }
+        }
+
+        //why the last command failed takes the place of the mode and file name.
+        //The right side is left off when there is no room for it.
+        let (left, right) = editor.status.text(doc, &editor.mode_indicator(), settings.status_bar);
+        let left = editor.message.clone().unwrap_or(left);
+        let style = match settings.status_bar {
+            true => Style::Bar,
+            false => Style::Text
+        };
+        let left_end = buffer.put(1, status_y, &left, style) as usize;
+        let right_len = right.chars().count();
+        if left_end + 3 + right_len < buffer.width as usize {
+            buffer.put(buffer.width - 1 - right_len as u16, status_y, &right, style);
+        }
+        buffer.fill(status_y, style);
+
+        cursor
+    }
+
+    //lay out the book's title and below it its sections, indented by how deep they are. The section
+    //being edited is drawn like the current line number, the selected one like a selection while the
+    //sidebar is focused and those left out of the compiled book like line numbers.
+    fn draw_sidebar(&mut self, editor : &Editor, book : &Book, area : (u16, u16, u16, u16), buffer : &mut Buffer) {
+        let sidebar = &editor.sidebar;
+        let (left, top, width, height) = area;
+        let clipped = |text : &str, indent : usize| -> String {
+            text.chars().take((width as usize).saturating_sub(indent)).collect()
+        };
+        if width == 0 || height < 2 {
+            return
+        }
+
+        buffer.put(left, top, &clipped(&book.title, 0), Style::CurrentLineNumber);
+
+        let rows = book.rows();
+        let visible_rows = height as usize - 1;
+        let selected = sidebar.selected();
+        if sidebar.focused && selected < self.sidebar_scroll {
+            self.sidebar_scroll = selected;
+        } else if sidebar.focused && selected >= self.sidebar_scroll + visible_rows {
+            self.sidebar_scroll = selected + 1 - visible_rows;
+        }
+        self.sidebar_scroll = self.sidebar_scroll.min(rows.len().saturating_sub(visible_rows));
+
+        let current = sidebar.current(&editor.tabs);
+        for (i, (path, section)) in rows.iter().enumerate().skip(self.sidebar_scroll).take(visible_rows) {
+            let y = top + 1 + (i - self.sidebar_scroll) as u16;
+            let indent = 2 * path.len() - 1;
+            let style = match (sidebar.focused && i == selected, current == Some(i), section.include) {
+                (true, _, _) => Style::Selection,
+                (false, true, _) => Style::CurrentLineNumber,
+                (false, false, true) => Style::Text,
+                (false, false, false) => Style::LineNumber
+            };
+            buffer.put(left + indent as u16, y, &clipped(&section.label(), indent), style);
+            if style == Style::Selection {
+                buffer.style(left, y, width as usize, style);
+            }
+        }
+    }
+
+    //lay out a pane's doc into its area of the buffer, given as x, y, width and height.
+    //Returns where the cursor is, the caret is only shown for the focused pane.
+    fn draw_pane(&mut self, editor : &Editor, i : usize, area : (u16, u16, u16, u16), buffer : &mut Buffer) -> (u16, u16) {
+        let (doc, cursor) = editor.panes.view(&editor.tabs, i);
+        let find_bar = &editor.find_bar;
+        let settings = &editor.settings;
+        let (left, top, width, height) = area;
+        if width == 0 || height == 0 {
+            return (left, top)
+        }
+
+        //the gutter is as wide as the last line number, plus a gap before the text
+        let digits = doc.text.len().to_string().len();
+        let gutter_w = match settings.line_numbers {
+            LineNumbers::Off => 0,
+            LineNumbers::Absolute | LineNumbers::Relative => digits + 1
+        };
+        let cols = (width as usize).saturating_sub(gutter_w).max(1);
+        let text_rows = height as usize;
+        let rows = layout_rows(&doc.text, cols);
+
+        //scroll just far enough to bring the cursor's row on screen, each doc keeps its own scroll in each pane
+        let key = (editor.panes.panes()[i].id, doc.id);
+        let mut scroll = self.scrolls.get(&key).copied().unwrap_or(0);
+        let (cursor_row, cursor_col) = row_of(&rows, cursor.pos);
+        if cursor_row < scroll {
+            scroll = cursor_row;
+        } else if cursor_row >= scroll + text_rows {
+            scroll = cursor_row + 1 - text_rows;
+        }
+        scroll = scroll.min(rows.len() - 1);
+        self.scrolls.insert(key, scroll);
+        let shown = &rows[scroll..(scroll + text_rows).min(rows.len())];
+
+        let text_left = left + gutter_w as u16;
+        for (i, row) in shown.iter().enumerate() {
+            let y = top + i as u16;
+            let text : String = doc.text[row.line].chars().skip(row.start).take(row.end - row.start).collect();
+            buffer.put(text_left, y, &text, Style::Text);
+
+            //line numbers, right aligned on the first row of each line
+            let current = row.line == cursor.pos.0;
+            let number = match (row.start == 0, settings.line_numbers, current) {
+                (_, LineNumbers::Off, _) | (false, _, _) => continue,
+                (true, LineNumbers::Relative, false) => row.line.abs_diff(cursor.pos.0).to_string(),
+                (true, _, _) => (row.line + 1).to_string()
+            };
+            let style = match current {
+                true => Style::CurrentLineNumber,
+                false => Style::LineNumber
+            };
+            buffer.put(left + (digits - number.len()) as u16, y, &number, style);
+        }
+
+        //highlight the selection, and the find bar's matches in panes on the doc being edited
+        let mut spans = Vec::new();
+        if let Some(((start_y, start_x), (end_y, end_x))) = cursor.selection() {
+            for y in start_y..=end_y {
+                let from = match y == start_y { true => start_x, false => 0 };
+                let to = match y == end_y { true => end_x, false => doc.line_len(y) };
+                spans.push((y, from, to, Style::Selection));
+            }
+        }
+        if find_bar.open && doc.id == editor.doc().id {
+            let (first_line, last_line) = (shown[0].line, shown[shown.len() - 1].line);
+            let matches = find_bar.matches();
+            let on_screen = &matches[matches.partition_point(|m| m.line < first_line)..matches.partition_point(|m| m.line <= last_line)];
+
+            for m in on_screen {
+                let style = match find_bar.current() == Some(*m) {
+                    true => Style::CurrentMatch,
+                    false => Style::Match
+                };
+                spans.push((m.line, m.start, m.end, style));
+            }
+        }
+        for (line, start, end, style) in spans {
+            for (row, from, to) in row_spans(&rows, line, start, end) {
+                if row >= scroll && row < scroll + shown.len() {
+                    buffer.style(text_left + from as u16, top + (row - scroll) as u16, to - from, style);
+                }
+            }
+        }
+
+        //the cursor stays in the pane even at the end of a full row
+        let x = (gutter_w + cursor_col).min(width as usize - 1);
+        (left + x as u16, top + (cursor_row - scroll) as u16)
+    }
+}
+
+impl Default for Tui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//raw mode on the alternate screen while alive, the terminal is put back when it is
+//dropped, including when unwinding from a panic
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), SetAttribute(Attribute::Reset), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+//edit in the terminal until a quit command
+pub fn run(editor : &mut Editor) -> io::Result<()> {
+    let _terminal = Terminal::enter()?;
+    let mut stdout = io::stdout();
+
+    let (width, height) = terminal::size()?;
+    let mut buffer = Buffer::new(width, height);
+    let mut tui = Tui::new();
+
+    while !editor.quit {
+        editor.refresh();
+        let cursor = tui.draw(editor, &mut buffer);
+        show(&mut stdout, &buffer, cursor)?;
+
+        //wait for input, waking up now and then while a project search is running to show its results
+        let searching = editor.find_bar.is_searching();
+        let ready = match searching {
+            true => event::poll(SEARCH_POLL_INTERVAL)?,
+            false => true
+        };
+        if searching {
+            editor.find_bar.poll();
+        }
+        if !ready {
+            continue
+        }
+
+        match event::read()? {
+            Event::Key(key) => tui.key(editor, key),
+            Event::Resize(width, height) => buffer.resize(width, height),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+//write the whole buffer to the terminal, each run of cells in one style at once
+fn show(out : &mut impl Write, buffer : &Buffer, cursor : (u16, u16)) -> io::Result<()> {
+    queue!(out, BeginSynchronizedUpdate, Hide)?;
+
+    for y in 0..buffer.height {
+        queue!(out, MoveTo(0, y))?;
+        let mut x = 0;
+        while x < buffer.width {
+            let style = buffer.cell(x, y).style;
+            let mut run = String::new();
+            while x < buffer.width && buffer.cell(x, y).style == style {
+                run.push(buffer.cell(x, y).c);
+                x += 1;
+            }
+
+            queue!(out, SetAttribute(Attribute::Reset))?;
+            for attribute in attributes(style) {
+                queue!(out, SetAttribute(*attribute))?;
+            }
+            queue!(out, Print(run))?;
+        }
+    }
+
+    queue!(out, SetAttribute(Attribute::Reset), MoveTo(cursor.0, cursor.1), Show, EndSynchronizedUpdate)?;
+    out.flush()
+}
+
+fn attributes(style : Style) -> &'static [Attribute] {
+    match style {
+        Style::Text => &[],
+        Style::LineNumber => &[Attribute::Dim],
+        Style::CurrentLineNumber => &[Attribute::Bold],
+        Style::Selection | Style::Bar => &[Attribute::Reverse],
+        Style::Match => &[Attribute::Underlined],
+        Style::CurrentMatch => &[Attribute::Reverse, Attribute::Underlined]
+    }
+}
+
+//winit key for a special key
+fn key_code(code : KeyCode) -> Option<VirtualKeyCode> {
+    let key = match code {
+        KeyCode::Enter => VirtualKeyCode::Return,
+        KeyCode::Backspace => VirtualKeyCode::Back,
+        KeyCode::Delete => VirtualKeyCode::Delete,
+        KeyCode::Insert => VirtualKeyCode::Insert,
+        KeyCode::Esc => VirtualKeyCode::Escape,
+        KeyCode::Tab => VirtualKeyCode::Tab,
+        KeyCode::Left => VirtualKeyCode::Left,
+        KeyCode::Right => VirtualKeyCode::Right,
+        KeyCode::Up => VirtualKeyCode::Up,
+        KeyCode::Down => VirtualKeyCode::Down,
+        KeyCode::Home => VirtualKeyCode::Home,
+        KeyCode::End => VirtualKeyCode::End,
+        KeyCode::PageUp => VirtualKeyCode::PageUp,
+        KeyCode::PageDown => VirtualKeyCode::PageDown,
+        KeyCode::F(n) => return parse_chord(&format!("f{}", n)).ok().map(|chord| chord.key),
+        _ => return None
+    };
+
+    Some(key)
+}
+
+//winit key a char is typed with on a US layout, and whether it needs shift.
+//None for chars without a key of their own, they are only typed.
+fn char_key_code(c : char) -> Option<(VirtualKeyCode, ModifiersState)> {
+    let (name, shifted) = match c {
+        'a'..='z' | '0'..='9' => (c.to_string(), false),
+        'A'..='Z' => (c.to_ascii_lowercase().to_string(), true),
+        ' ' => (String::from("space"), false),
+        _ => {
+            let (name, shifted) = match c {
+                '-' => ("minus", false),
+                '_' => ("minus", true),
+                '=' => ("equals", false),
+                '+' => ("equals", true),
+                ',' => ("comma", false),
+                '.' => ("period", false),
+                ';' => ("semicolon", false),
+                '/' => ("slash", false),
+                '\\' => ("backslash", false),
+                '\'' => ("apostrophe", false),
+                '[' => ("lbracket", false),
+                ']' => ("rbracket", false),
+                _ => return None
+            };
+            (name.to_string(), shifted)
+        }
+    };
+
+    let shift = match shifted {
+        true => ModifiersState::SHIFT,
+        false => ModifiersState::empty()
+    };
+    parse_chord(&name).ok().map(|chord| (chord.key, shift))
+}
@@ -0,0 +1,574 @@
+//View
+//Lays out what is on screen into a Frame for a Renderer to draw (see renderer.rs),
+//and keeps the layout of the last frame to find what is under the mouse. Sizes
+//are in physical pixels: the font size and margins are scaled to the monitor.
+//Each pane (see panes.rs) is laid out in its own part of the text area, with its
+//text clipped to it. When a book is open its sections are listed in a sidebar left of
+//the text area (see sidebar.rs).
+
+use std::collections::HashMap;
+
+use winit::dpi::PhysicalSize;
+use wgpu_glyph::ab_glyph::{Font, ScaleFont};
+
+use crate::book::Book;
+use crate::doc_handler::DocHandler;
+use crate::editor::Editor;
+use crate::fonts::FontChain;
+use crate::layout::*;
+use crate::renderer::{Clip, Frame, Rect, TextLine};
+use crate::settings::{FontSettings, LineNumbers};
+use crate::theme::{Color, Theme};
+
+// Gap in logical pixels around the edge of the window
+const MARGIN: f32 = 40.0;
+// Font sizes are in points, a point is 1/72 of an inch and there are 96 logical pixels to the inch
+const PIXELS_PER_POINT: f32 = 96.0 / 72.0;
+const CARET_WIDTH: f32 = 2.0; // logical pixels
+// Width of the sidebar in chars, it takes at most a third of the window
+const SIDEBAR_COLS: f32 = 28.0;
+
+pub struct View {
+    pub fonts: FontChain, // each char is drawn in the first of these fonts that has it
+    size: PhysicalSize<u32>,
+    text_scale: f32, // font size in physical pixels, with the zoom and scale factor applied
+    line_height: f32, // times the primary font's line spacing
+    scale_factor: f64, // physical pixels per logical pixel of the monitor the window is on
+    margin: f32, // MARGIN in physical pixels
+    theme: Theme,
+    panes: Vec<PaneLayout>, // layout of each pane in the last frame, in the order of the editor's panes
+    scrolls: HashMap<(u64, u64), usize>, // first row on screen by pane and doc id, so switching tabs comes back to the same place
+    tab_edges: Vec<f32>, // left edge of each tab in the last frame and the right edge of the last
+    tab_bar: f32, // height of the tab bar, 0 when it isn't shown
+    sidebar: Option<SidebarLayout>, // None when the sidebar isn't shown
+    sidebar_scroll: usize, // first section on screen in the sidebar
+    text_bottom: f32 // where the text area ends and the find bar or bottom margin starts
+}
+
+// Where a pane's doc was laid out in the last frame, for finding what is under the mouse
+struct PaneLayout {
+    area: (f32, f32, f32, f32), // x, y, width and height of the pane
+    doc: u64, // id of the doc shown
+    rows: Vec<Row>,
+    scroll: usize, // first row of the doc on screen
+    visible_rows: usize, // rows that fit in the pane
+    text_left: f32, // where the text starts, after the margin and the line numbers
+    text_top: f32 // where the text starts, below the top margin and the tab bar
+}
+
+// Where the book's sections were laid out in the sidebar in the last frame
+struct SidebarLayout {
+    area: (f32, f32, f32, f32),
+    rows_top: f32, // where the first section on screen starts, below the book's title
+    rows: usize, // sections in the book
+    visible_rows: usize
+}
+
+impl View {
+    pub fn new(fonts: FontChain, size: PhysicalSize<u32>, scale_factor: f64, font_settings: &FontSettings) -> Self {
+        Self {
+            fonts,
+            size,
+            text_scale: font_settings.size * PIXELS_PER_POINT * scale_factor as f32,
+            line_height: font_settings.line_height,
+            scale_factor,
+            margin: MARGIN * scale_factor as f32,
+            theme: Theme::default(),
+            panes: Vec::new(),
+            scrolls: HashMap::new(),
+            tab_edges: Vec::new(),
+            tab_bar: 0.0,
+            sidebar: None,
+            sidebar_scroll: 0,
+            text_bottom: 0.0
+        }
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width > 0 && size.height > 0 {
+            self.size = size;
+        }
+    }
+
+    // The window moved to a monitor with a different scale factor. Text and margins are sized in
+    // logical pixels, so they are scaled to look the same size as on the old monitor.
+    pub fn rescale(&mut self, scale_factor: f64, size: PhysicalSize<u32>) {
+        self.scale_factor = scale_factor;
+        self.margin = MARGIN * scale_factor as f32;
+        self.resize(size);
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    // Whether a point in the window is over the text rather than the tab bar, sidebar, find bar or bottom margin
+    pub fn over_text(&self, point: (f64, f64)) -> bool {
+        let sidebar_w = self.sidebar.as_ref().map_or(0.0, |sidebar| sidebar.area.2);
+        (point.1 as f32) < self.text_bottom && (point.1 as f32) >= self.tab_bar && (point.0 as f32) >= sidebar_w
+    }
+
+    // Row of the section under a point in the sidebar (see Book::rows), None when it isn't over one
+    pub fn sidebar_at(&self, point: (f64, f64)) -> Option<usize> {
+        let (_, cell_h) = self.cell_size();
+        let (x, y) = (point.0 as f32, point.1 as f32);
+        let sidebar = self.sidebar.as_ref()?;
+        let (left, top, w, h) = sidebar.area;
+        match x >= left && x < left + w && y >= sidebar.rows_top && y < top + h {
+            true => {
+                let offset = ((y - sidebar.rows_top) / cell_h) as usize;
+                Some(self.sidebar_scroll + offset).filter(|row| offset < sidebar.visible_rows && *row < sidebar.rows)
+            },
+            false => None
+        }
+    }
+
+    // Index of the tab under a point, None when it isn't over a tab
+    pub fn tab_at(&self, point: (f64, f64)) -> Option<usize> {
+        let (x, y) = (point.0 as f32, point.1 as f32);
+        match y < self.tab_bar {
+            true => self.tab_edges.windows(2).position(|edge| x >= edge[0] && x < edge[1]),
+            false => None
+        }
+    }
+
+    // Index of the pane under a point, None when it isn't over the text area
+    pub fn pane_at(&self, point: (f64, f64)) -> Option<usize> {
+        let (x, y) = (point.0 as f32, point.1 as f32);
+        match self.over_text(point) {
+            true => self.panes.iter().position(|pane| {
+                let (left, top, w, h) = pane.area;
+                x >= left && x < left + w && y >= top && y < top + h
+            }),
+            false => None
+        }
+    }
+
+    // Whether a point is above or below the rows on screen in a pane, where dragging scrolls the text
+    pub fn scrolls_at(&self, pane: usize, point: (f64, f64)) -> bool {
+        let (_, cell_h) = self.cell_size();
+        let y = point.1 as f32;
+        match self.panes.get(pane) {
+            Some(pane) => y < pane.text_top || y >= pane.text_top + pane.visible_rows as f32 * cell_h,
+            None => false
+        }
+    }
+
+    // Doc position under a point in the window, going by where the glyphs were laid out in a pane
+    // in the last frame. Points above or below the text give the row just off screen on that side,
+    // so dragging there moves the cursor off screen and the text scrolls to follow it.
+    pub fn position_at(&self, doc: &DocHandler, pane: usize, point: (f64, f64)) -> (usize, usize) {
+        // The doc changed since the last frame, e.g. another tab was picked
+        let pane = match self.panes.get(pane) {
+            Some(pane) if pane.doc == doc.id && pane.rows.last().is_some_and(|row| row.line + 1 == doc.text.len()) => pane,
+            _ => return (doc.ptr_y, doc.ptr_x)
+        };
+        let rows = &pane.rows;
+
+        let (_, cell_h) = self.cell_size();
+        let offset = ((point.1 as f32 - pane.text_top) / cell_h).floor() as isize;
+        let index = (pane.scroll as isize + offset.clamp(-1, pane.visible_rows as isize)).clamp(0, rows.len() as isize - 1);
+        let row = rows[index as usize];
+
+        // The nearest gap between chars to the point
+        let x = point.0 as f32;
+        let edges = self.char_edges(&doc.text[row.line], &row, pane.text_left);
+        let col = edges.windows(2).position(|edge| x < (edge[0] + edge[1]) / 2.0).unwrap_or(edges.len() - 1);
+
+        // The end of a wrapped row is drawn at the start of the next one, so stop just before it
+        let wrapped = row.end < doc.line_len(row.line);
+        match wrapped && col > 0 && col == row.end - row.start {
+            true => (row.line, row.end - 1),
+            false => (row.line, row.start + col)
+        }
+    }
+
+    // Lays out the open docs into a frame. With more than one doc open their tabs are drawn across the top,
+    // below them the book's sections down the left when the sidebar is open, then the rest of the text
+    // area is shared out between the panes. While the find bar is open it is drawn above the
+    // bottom margin. The status bar along the bottom starts with the mode, e.g. "-- INSERT --" for vim,
+    // or why the last command failed
+    pub fn frame(&mut self, editor: &Editor) -> Frame {
+        let doc = editor.doc();
+        let find_bar = &editor.find_bar;
+        let settings = &editor.settings;
+
+        // Font size in physical pixels, so text is the same size on every monitor
+        self.text_scale = settings.font.size * settings.zoom * PIXELS_PER_POINT * self.scale_factor as f32;
+        self.line_height = settings.font.line_height;
+
+        let (cell_w, cell_h) = self.cell_size();
+        let width = self.size.width as f32;
+        let height = self.size.height as f32;
+
+        // The tab bar is a row across the top, only there when there is more than one tab
+        let titles = editor.tabs.titles();
+        self.tab_bar = match titles.len() > 1 {
+            true => cell_h,
+            false => 0.0
+        };
+
+        // The bottom margin holds the status bar, it grows when zoomed in far enough to need it
+        let bottom = self.margin.max(cell_h);
+
+        // Lines of the find bar, see find.rs
+        let bar_lines = find_bar.lines(doc);
+        let bar_top = height - bottom - bar_lines.len() as f32 * cell_h;
+        self.text_bottom = bar_top;
+
+        // The sidebar reaches from the tab bar down to the find bar
+        let sidebar_w = match editor.sidebar.shown() {
+            true => (SIDEBAR_COLS * cell_w).min(width / 3.0).round(),
+            false => 0.0
+        };
+
+        // The panes fill the window right of the sidebar, between the tab bar and at least a row at the bottom for the find bar
+        let text_end = height - bottom - bar_lines.len().max(1) as f32 * cell_h;
+        let areas = editor.panes.areas((sidebar_w, self.tab_bar, width - sidebar_w, text_end - self.tab_bar));
+
+        // Each doc comes back scrolled to where it was left in each pane, forget closed panes and docs
+        self.scrolls.retain(|(pane, doc), _| {
+            editor.panes.panes().iter().any(|open| open.id == *pane) && editor.tabs.docs().iter().any(|open| open.id == *doc)
+        });
+
+        let mut rects = Vec::new();
+        let mut lines = Vec::new();
+        self.panes = areas.iter()
+            .enumerate()
+            .map(|(i, area)| self.lay_out_pane(editor, i, *area, text_end, &mut rects, &mut lines))
+            .collect();
+
+        // Lines between the panes, along the right and bottom edges of those that don't reach the edge
+        // of the text area. They are whole pixels wide and start on a pixel so they stay sharp.
+        let line_w = self.scale_factor.round().max(1.0) as f32;
+
+        self.sidebar = match editor.sidebar.book() {
+            Some(book) if sidebar_w > 0.0 => {
+                let area = (0.0, self.tab_bar, sidebar_w, bar_top - self.tab_bar);
+                let sidebar = self.lay_out_sidebar(editor, book, area, &mut rects, &mut lines);
+                rects.push(Rect { x: sidebar_w - line_w, y: self.tab_bar, w: line_w, h: bar_top - self.tab_bar, color: self.theme.status_bar });
+                Some(sidebar)
+            },
+            _ => None
+        };
+        for (x, y, w, h) in &areas {
+            if x + w < width - 0.5 {
+                rects.push(Rect { x: (x + w - line_w / 2.0).round(), y: *y, w: line_w, h: *h, color: self.theme.status_bar });
+            }
+            if y + h < text_end - 0.5 {
+                rects.push(Rect { x: *x, y: (y + h - line_w / 2.0).round(), w: *w, h: line_w, color: self.theme.status_bar });
+            }
+        }
+
+        // Background of the find bar
+        if find_bar.open {
+            rects.push(Rect { x: 0.0, y: bar_top, w: width, h: bar_lines.len() as f32 * cell_h, color: self.theme.find_bar });
+        }
+
+        // Background of the status bar
+        if settings.status_bar {
+            rects.push(Rect { x: 0.0, y: height - bottom, w: width, h: bottom, color: self.theme.status_bar });
+        }
+
+        // The tabs in order, the one being edited in the colours of the text. When they don't all fit
+        // they are shifted left far enough to show it.
+        self.tab_edges.clear();
+        if self.tab_bar > 0.0 {
+            rects.push(Rect { x: 0.0, y: 0.0, w: width, h: self.tab_bar, color: self.theme.status_bar });
+
+            let mut x = 0.0;
+            self.tab_edges.push(x);
+            for title in &titles {
+                x += self.text_width(title) + 2.0 * cell_w;
+                self.tab_edges.push(x);
+            }
+            let active = editor.tabs.active();
+            let shift = (self.tab_edges[active + 1] - width).max(0.0);
+            self.tab_edges.iter_mut().for_each(|edge| *edge -= shift);
+
+            for (i, title) in titles.iter().enumerate() {
+                let color = match i == active {
+                    true => {
+                        rects.push(Rect { x: self.tab_edges[i], y: 0.0, w: self.tab_edges[i + 1] - self.tab_edges[i], h: self.tab_bar, color: self.theme.background });
+                        self.theme.text
+                    },
+                    false => self.theme.status_text
+                };
+                lines.push(self.text_line(title, color, (self.tab_edges[i] + cell_w, 0.0)));
+            }
+        }
+
+        for (i, line) in bar_lines.iter().enumerate() {
+            lines.push(self.text_line(line, self.theme.text, (self.margin - cell_w, bar_top + i as f32 * cell_h)));
+        }
+
+        // The status bar fills the bottom margin, see status.rs
+        let (left_status, right_status) = editor.status.text(doc, &editor.mode_indicator(), settings.status_bar);
+        let left_status = editor.message.clone().unwrap_or(left_status);
+        let status_y = height - bottom + (bottom - cell_h) / 2.0;
+
+        lines.push(self.text_line(&left_status, self.theme.status_text, (self.margin, status_y)));
+        lines.push(self.text_line(&right_status, self.theme.status_text, (width - self.margin - self.text_width(&right_status), status_y)));
+
+        Frame {
+            width: self.size.width,
+            height: self.size.height,
+            background: self.theme.background,
+            rects,
+            lines
+        }
+    }
+
+    // Lays out the rows of a pane's doc that fit in its area, scrolling to keep the cursor on screen.
+    // The caret is only drawn in the focused pane, and the find bar's matches only in panes on the doc
+    // being edited. With line numbers on they are drawn in a gutter left of the text, on the first row
+    // of each line. Panes along the edges of the window keep the full margin there, between panes it is halved.
+    fn lay_out_pane(&mut self, editor: &Editor, i: usize, area: (f32, f32, f32, f32), text_end: f32, rects: &mut Vec<Rect>, lines: &mut Vec<TextLine>) -> PaneLayout {
+        let (doc, cursor) = editor.panes.view(&editor.tabs, i);
+        let focused = i == editor.panes.active();
+        let find_bar = &editor.find_bar;
+        let settings = &editor.settings;
+
+        let (cell_w, cell_h) = self.cell_size();
+        let (x, y, w, h) = area;
+        let width = self.size.width as f32;
+        let at_top = y < self.tab_bar + 0.5;
+        let at_bottom = y + h > text_end - 0.5;
+        let left = match x < 0.5 { true => self.margin, false => self.margin / 2.0 };
+        let right = match x + w > width - 0.5 { true => self.margin, false => self.margin / 2.0 };
+
+        let text_top = match at_top && self.tab_bar == 0.0 {
+            true => y + self.margin,
+            false => y + self.margin / 2.0
+        };
+
+        // The gutter is as wide as the last line number, plus a gap before the text
+        let digits = doc.text.len().to_string().len();
+        let gutter_w = match settings.line_numbers {
+            LineNumbers::Off => 0.0,
+            LineNumbers::Absolute | LineNumbers::Relative => (digits + 1) as f32 * cell_w
+        };
+        let text_left = x + left + gutter_w;
+
+        // The text fills the pane apart from the margins and the gutter. Panes along the bottom reach
+        // down to the find bar, their area leaves a row for it when it is closed.
+        let cols = ((x + w - right - text_left) / cell_w).max(1.0) as usize;
+        let visible_rows = ((y + h - text_top) / cell_h).max(1.0) as usize;
+        let rows = layout_rows(&doc.text, cols);
+        let pane_bottom = match at_bottom {
+            true => self.text_bottom,
+            false => y + h
+        };
+        let clip = Clip { x, y, w, h: pane_bottom - y };
+
+        // Scroll just far enough to bring the cursor's row on screen
+        let key = (editor.panes.panes()[i].id, doc.id);
+        let mut scroll = self.scrolls.get(&key).copied().unwrap_or(0);
+        let (cursor_row, cursor_col) = row_of(&rows, cursor.pos);
+        if cursor_row < scroll {
+            scroll = cursor_row;
+        } else if cursor_row >= scroll + visible_rows {
+            scroll = cursor_row + 1 - visible_rows;
+        }
+        scroll = scroll.min(rows.len() - 1);
+        self.scrolls.insert(key, scroll);
+
+        let pane = PaneLayout { area: (x, y, w, pane_bottom - y), doc: doc.id, rows, scroll, visible_rows, text_left, text_top };
+        let rows = &pane.rows;
+        let shown = &rows[scroll..(scroll + visible_rows).min(rows.len())];
+        let (first_line, last_line) = (shown[0].line, shown[shown.len() - 1].line);
+
+        // Background of the gutter
+        if gutter_w > 0.0 {
+            rects.push(Rect { x, y, w: text_left - cell_w / 2.0 - x, h: pane_bottom - y, color: self.theme.gutter });
+        }
+
+        // Highlight the selection on the lines that are on screen
+        if let Some(((start_y, start_x), (end_y, end_x))) = cursor.selection() {
+            for y in start_y.max(first_line)..=end_y.min(last_line) {
+                let from = match y == start_y { true => start_x, false => 0 };
+                let to = match y == end_y { true => end_x, false => doc.line_len(y) };
+                rects.extend(self.span_rects(doc, &pane, (y, from, to), self.theme.selection));
+            }
+        }
+
+        // Highlight the matches on the lines that are on screen
+        if find_bar.open && doc.id == editor.doc().id {
+            let matches = find_bar.matches();
+            let on_screen = &matches[matches.partition_point(|m| m.line < first_line)..matches.partition_point(|m| m.line <= last_line)];
+
+            for m in on_screen {
+                let color = match find_bar.current() == Some(*m) {
+                    true => self.theme.current_match,
+                    false => self.theme.search_match
+                };
+                rects.extend(self.span_rects(doc, &pane, (m.line, m.start, m.end), color));
+            }
+        }
+
+        // The caret, a thin bar before the char at the cursor
+        if focused && cursor_row >= scroll && cursor_row < scroll + shown.len() {
+            let edges = self.char_edges(&doc.text[cursor.pos.0], &rows[cursor_row], text_left);
+            rects.push(Rect {
+                x: edges[cursor_col.min(edges.len() - 1)],
+                y: text_top + (cursor_row - scroll) as f32 * cell_h,
+                w: CARET_WIDTH * self.scale_factor as f32,
+                h: cell_h,
+                color: self.theme.caret
+            });
+        }
+
+        // Each row on screen is a line of its own, rows never need wrapping again
+        for (i, row) in shown.iter().enumerate() {
+            let text: String = doc.text[row.line].chars().skip(row.start).take(row.end - row.start).collect();
+            lines.push(self.clipped_line(&text, self.theme.text, (text_left, text_top + i as f32 * cell_h), clip));
+        }
+
+        // Line numbers, right aligned and only on the first row of a line so they line up
+        // with the lines rather than the wrapped rows. Relative numbers count from the
+        // cursor's line, which shows its own number.
+        if gutter_w > 0.0 {
+            let cursor_line = cursor.pos.0;
+            for (i, row) in shown.iter().enumerate() {
+                let current = row.line == cursor_line;
+                let number = match (row.start == 0, settings.line_numbers, current) {
+                    (false, _, _) => continue,
+                    (true, LineNumbers::Relative, false) => row.line.abs_diff(cursor_line).to_string(),
+                    (true, _, _) => (row.line + 1).to_string()
+                };
+                let color = match current {
+                    true => self.theme.current_line_number,
+                    false => self.theme.line_number
+                };
+                let right = x + left + digits as f32 * cell_w;
+                lines.push(self.clipped_line(&number, color, (right - self.text_width(&number), text_top + i as f32 * cell_h), clip));
+            }
+        }
+
+        pane
+    }
+
+    // Lays out the book's title at the top of the sidebar and its sections below, each indented by how deep
+    // it is. The section being edited has the background of the text, and while the sidebar is focused the
+    // selected section is highlighted and scrolled to. Sections left out of the compiled book are dimmed.
+    fn lay_out_sidebar(&mut self, editor: &Editor, book: &Book, area: (f32, f32, f32, f32), rects: &mut Vec<Rect>, lines: &mut Vec<TextLine>) -> SidebarLayout {
+        let sidebar = &editor.sidebar;
+        let (cell_w, cell_h) = self.cell_size();
+        let (x, y, w, h) = area;
+        let clip = Clip { x, y, w, h };
+
+        rects.push(Rect { x, y, w, h, color: self.theme.gutter });
+
+        let title_top = y + self.margin / 2.0;
+        lines.push(self.clipped_line(&book.title, self.theme.current_line_number, (x + cell_w, title_top), clip));
+
+        let rows = book.rows();
+        let rows_top = title_top + 1.5 * cell_h;
+        let visible_rows = ((y + h - rows_top) / cell_h).max(1.0) as usize;
+
+        let mut scroll = self.sidebar_scroll;
+        let selected = sidebar.selected();
+        if sidebar.focused && selected < scroll {
+            scroll = selected;
+        } else if sidebar.focused && selected >= scroll + visible_rows {
+            scroll = selected + 1 - visible_rows;
+        }
+        scroll = scroll.min(rows.len().saturating_sub(visible_rows));
+        self.sidebar_scroll = scroll;
+
+        let current = sidebar.current(&editor.tabs);
+        for (i, (path, section)) in rows.iter().enumerate().skip(scroll).take(visible_rows) {
+            let top = rows_top + (i - scroll) as f32 * cell_h;
+            let highlight = match (sidebar.focused && i == selected, current == Some(i)) {
+                (true, _) => Some(self.theme.selection),
+                (false, true) => Some(self.theme.background),
+                (false, false) => None
+            };
+            if let Some(color) = highlight {
+                rects.push(Rect { x, y: top, w, h: cell_h, color });
+            }
+
+            // Sections left out of the compiled book are dimmed
+            let color = match section.include {
+                true => self.theme.text,
+                false => self.theme.line_number
+            };
+            let indent = (2 * path.len() - 1) as f32 * cell_w;
+            lines.push(self.clipped_line(&section.label(), color, (x + indent, top), clip));
+        }
+
+        SidebarLayout { area, rows_top, rows: rows.len(), visible_rows }
+    }
+
+    // Width of a char and height of a row of text in pixels, going by the primary font which is
+    // expected to be monospaced
+    fn cell_size(&self) -> (f32, f32) {
+        let font = self.fonts.primary().as_scaled(self.text_scale);
+        (font.h_advance(font.glyph_id('M')), (font.height() + font.line_gap()) * self.line_height)
+    }
+
+    // A line of text with its top left at position, split into runs by font.
+    // Taller rows than the font needs have the extra space split above and below the text.
+    fn text_line(&self, text: &str, color: Color, position: (f32, f32)) -> TextLine {
+        let font = self.fonts.primary().as_scaled(self.text_scale);
+        let padding = (font.height() + font.line_gap()) * (self.line_height - 1.0) / 2.0;
+
+        TextLine {
+            runs: self.fonts.runs(text).into_iter().map(|(run, font)| (run.to_string(), font)).collect(),
+            x: position.0,
+            y: position.1 + padding,
+            scale: self.text_scale,
+            color,
+            clip: None
+        }
+    }
+
+    // Width of a line of text in pixels, for right aligning it
+    fn text_width(&self, text: &str) -> f32 {
+        text.chars()
+            .map(|c| {
+                let font = self.fonts.fonts[self.fonts.font_for(c)].as_scaled(self.text_scale);
+                font.h_advance(font.glyph_id(c))
+            })
+            .sum()
+    }
+
+    // A line of text drawn only inside a pane
+    fn clipped_line(&self, text: &str, color: Color, position: (f32, f32), clip: Clip) -> TextLine {
+        TextLine { clip: Some(clip), ..self.text_line(text, color, position) }
+    }
+
+    // Rects covering the chars start..end of a line, one for each row of the line on screen in a pane
+    fn span_rects(&self, doc: &DocHandler, pane: &PaneLayout, span: (usize, usize, usize), color: Color) -> Vec<Rect> {
+        let (line, start, end) = span;
+        let (_, cell_h) = self.cell_size();
+
+        row_spans(&pane.rows, line, start, end).into_iter()
+            .filter(|(row, _, _)| *row >= pane.scroll && *row < pane.scroll + pane.visible_rows)
+            .map(|(row, from, to)| {
+                let edges = self.char_edges(&doc.text[line], &pane.rows[row], pane.text_left);
+                Rect {
+                    x: edges[from],
+                    y: pane.text_top + (row - pane.scroll) as f32 * cell_h,
+                    w: edges[to] - edges[from],
+                    h: cell_h,
+                    color
+                }
+            })
+            .collect()
+    }
+
+    // Pixel x of the left edge of each char on a row and of the end of the row, for text starting at left
+    fn char_edges(&self, line: &str, row: &Row, left: f32) -> Vec<f32> {
+        let mut x = left;
+        let mut edges = vec![x];
+
+        for c in line.chars().skip(row.start).take(row.end - row.start) {
+            let font = self.fonts.fonts[self.fonts.font_for(c)].as_scaled(self.text_scale);
+            x += font.h_advance(font.glyph_id(c));
+            edges.push(x);
+        }
+
+        edges
+    }
+}
@@ -0,0 +1,1179 @@
+//Vim
+//Optional modal editing layer over DocHandler, turned on with profile = "vim" in
+//keymap.toml. In normal mode keys are collected until they form a complete
+//command, e.g. "d2w", "\"ayy" or "3p", which is then run against the doc.
+//
+//Supported: normal/insert/visual/visual line modes, the d c y operators with
+//h j k l w b e 0 ^ $ gg G f t F T motions, counts, registers ("a-"z, "A-"Z to
+//append, "0 for the last yank), p P x X D C s Y r u ctrl+r, dot repeat and the
+//:w :q :q! :wq :x :<line> commands.
+
+use std::collections::HashMap;
+
+use winit::event::{
+    ElementState,
+    KeyboardInput,
+    ModifiersState,
+    VirtualKeyCode
+};
+
+use crate::command::Outcome;
+use crate::doc_handler::DocHandler;
+use crate::keymap::Mode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    CommandLine
+}
+
+//A key as vim sees it. Printable keys arrive as Char, keys held with ctrl as Ctrl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VimKey {
+    Char(char),
+    Ctrl(char),
+    Escape,
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VimMotion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordBack,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    FindForward(char),
+    TillForward(char),
+    FindBack(char),
+    TillBack(char)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Motion(VimMotion),
+    Operate(Operator, VimMotion),
+    OperateLines(Operator), //dd, cc, yy
+    Simple(char),           //i a I A o O p P u v V .
+    Replace(char),          //r{char}
+    Redo                    //ctrl + r
+}
+
+//a complete normal mode command
+#[derive(Debug, Clone, Copy)]
+struct Cmd {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action
+}
+
+enum Parse<T> {
+    Complete(T),
+    Incomplete,
+    Invalid
+}
+
+//how the text covered by a motion is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Exclusive, //up to but not including the target
+    Inclusive, //including the char under the target
+    Linewise   //whole lines from the cursor line to the target line
+}
+
+#[derive(Debug, Clone)]
+struct Register {
+    text: String,
+    linewise: bool
+}
+
+//the last change, kept for dot repeat
+#[derive(Debug, Clone)]
+struct Change {
+    cmd: Cmd,
+    inserted: Vec<VimKey> //keys typed in insert mode if the change entered it
+}
+
+//what a char counts as for the w b e motions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Blank,
+    EmptyLine,
+    Word,
+    Punctuation
+}
+
+pub struct Vim {
+    pub mode: VimMode,
+    pending: Vec<VimKey>,   //normal/visual mode keys of an unfinished command
+    registers: HashMap<char, Register>,
+    command_line: String,   //text typed after :
+    last_change: Option<Change>,
+    recording: Option<Change>, //change being typed in insert mode
+    insert_count: usize,    //times to repeat the inserted text, e.g. 3ihi<Esc>
+    pub unsaved: Vec<String> //names of the other open docs with unsaved changes, kept up to date by the editor for :q
+}
+
+impl Vim {
+    pub fn new() -> Self {
+        Self {
+            mode: VimMode::Normal,
+            pending: Vec::new(),
+            registers: HashMap::new(),
+            command_line: String::new(),
+            last_change: None,
+            recording: None,
+            insert_count: 1,
+            unsaved: Vec::new()
+        }
+    }
+
+    //keymap section whose bindings apply in the current mode
+    pub fn keymap_mode(&self) -> Mode {
+        match self.mode {
+            VimMode::Insert => Mode::Insert,
+            VimMode::Visual | VimMode::VisualLine => Mode::Visual,
+            VimMode::Normal | VimMode::CommandLine => Mode::Normal
+        }
+    }
+
+    //text for the mode indicator, e.g. "-- INSERT --", or the keys typed so far
+    pub fn mode_indicator(&self) -> String {
+        match self.mode {
+            VimMode::Insert => String::from("-- INSERT --"),
+            VimMode::Visual => String::from("-- VISUAL --"),
+            VimMode::VisualLine => String::from("-- VISUAL LINE --"),
+            VimMode::CommandLine => format!(":{}", self.command_line),
+            VimMode::Normal => self.pending.iter().map(|key| key_name(*key)).collect()
+        }
+    }
+
+    //contents of a register, the unnamed register is '"'
+    pub fn register(&self, name : char) -> Option<&str> {
+        self.registers.get(&name).map(|register| register.text.as_str())
+    }
+
+    //handle a single key press
+    pub fn feed(&mut self, doc : &mut DocHandler, key : VimKey) -> Outcome {
+        match self.mode {
+            VimMode::Normal => self.feed_normal(doc, key),
+            VimMode::Insert => self.feed_insert(doc, key),
+            VimMode::Visual | VimMode::VisualLine => self.feed_visual(doc, key),
+            VimMode::CommandLine => self.feed_command_line(doc, key)
+        }
+    }
+
+    //handle each key of a sequence such as "d2w" or "ihello<Esc>", see parse_keys
+    pub fn feed_keys(&mut self, doc : &mut DocHandler, keys : &str) -> Outcome {
+        let mut outcome = Outcome::Unchanged;
+
+        for key in parse_keys(keys) {
+            outcome = self.feed(doc, key);
+            if outcome == Outcome::Quit {
+                break
+            }
+        }
+
+        outcome
+    }
+
+    fn feed_normal(&mut self, doc : &mut DocHandler, key : VimKey) -> Outcome {
+        if key == VimKey::Escape {
+            self.pending.clear();
+            return Outcome::Unchanged
+        }
+
+        if key == VimKey::Char(':') && self.pending.is_empty() {
+            self.mode = VimMode::CommandLine;
+            self.command_line.clear();
+            return Outcome::Unchanged
+        }
+
+        self.pending.push(key);
+
+        match parse(&self.pending) {
+            Parse::Complete(cmd) => {
+                self.pending.clear();
+                let outcome = self.execute(doc, cmd);
+
+                //remember changes for dot repeat, ones that enter insert mode
+                //are finished off when insert mode is left
+                if is_change(cmd.action) && outcome != Outcome::Unchanged {
+                    let change = Change { cmd, inserted: Vec::new() };
+                    match self.mode {
+                        VimMode::Insert => self.recording = Some(change),
+                        _ => self.last_change = Some(change)
+                    }
+                }
+
+                outcome
+            },
+            Parse::Incomplete => Outcome::Unchanged,
+            Parse::Invalid => {
+                self.pending.clear();
+                Outcome::Unchanged
+            }
+        }
+    }
+
+    fn feed_insert(&mut self, doc : &mut DocHandler, key : VimKey) -> Outcome {
+        if key == VimKey::Escape {
+            //repeat the inserted text for a count, e.g. 3ihi<Esc>
+            let inserted = self.recording.as_ref().map(|change| change.inserted.clone()).unwrap_or_default();
+            for _ in 1..self.insert_count {
+                for key in &inserted {
+                    insert_key(doc, *key);
+                }
+            }
+
+            if let Some(change) = self.recording.take() {
+                self.last_change = Some(change);
+            }
+
+            self.mode = VimMode::Normal;
+            self.insert_count = 1;
+            let (y, x) = doc.left_pos();
+            if y == doc.ptr_y {
+                doc.set_cursor(y, x);
+            }
+            return Outcome::Applied
+        }
+
+        if let Some(change) = self.recording.as_mut() {
+            change.inserted.push(key);
+        }
+
+        insert_key(doc, key);
+        Outcome::Applied
+    }
+
+    fn feed_visual(&mut self, doc : &mut DocHandler, key : VimKey) -> Outcome {
+        let linewise = self.mode == VimMode::VisualLine;
+
+        if self.pending.is_empty() {
+            let operator = match key {
+                VimKey::Char('d') | VimKey::Char('x') => Some(Operator::Delete),
+                VimKey::Char('c') | VimKey::Char('s') => Some(Operator::Change),
+                VimKey::Char('y') => Some(Operator::Yank),
+                _ => None
+            };
+
+            if let Some(operator) = operator {
+                let (start, end) = doc.selection().unwrap_or(((doc.ptr_y, doc.ptr_x), (doc.ptr_y, doc.ptr_x)));
+                let kind = if linewise { Kind::Linewise } else { Kind::Inclusive };
+
+                self.exit_visual(doc);
+                doc.set_cursor(start.0, start.1);
+                return self.operate(doc, operator, end, kind, None)
+            }
+
+            match key {
+                VimKey::Escape => {
+                    self.exit_visual(doc);
+                    return Outcome::Unchanged
+                },
+                VimKey::Char('v') | VimKey::Char('V') => {
+                    let switch_to = if key == VimKey::Char('v') { VimMode::Visual } else { VimMode::VisualLine };
+                    match self.mode == switch_to {
+                        true => self.exit_visual(doc),
+                        false => self.mode = switch_to
+                    };
+                    return Outcome::Unchanged
+                },
+                VimKey::Char('o') => {
+                    if let Some((y, x)) = doc.anchor {
+                        doc.anchor = Some((doc.ptr_y, doc.ptr_x));
+                        doc.set_cursor(y, x);
+                    }
+                    return Outcome::Applied
+                },
+                _ => {}
+            }
+        }
+
+        if key == VimKey::Escape {
+            self.pending.clear();
+            return Outcome::Unchanged
+        }
+
+        self.pending.push(key);
+
+        match parse(&self.pending) {
+            Parse::Incomplete => Outcome::Unchanged,
+            Parse::Complete(Cmd { count, action: Action::Motion(motion), .. }) => {
+                self.pending.clear();
+                self.move_cursor(doc, motion, count)
+            },
+            _ => {
+                self.pending.clear();
+                Outcome::Unchanged
+            }
+        }
+    }
+
+    fn feed_command_line(&mut self, doc : &mut DocHandler, key : VimKey) -> Outcome {
+        match key {
+            VimKey::Char(c) => {
+                self.command_line.push(c);
+                Outcome::Unchanged
+            },
+            VimKey::Backspace => {
+                if self.command_line.pop().is_none() {
+                    self.mode = VimMode::Normal;
+                }
+                Outcome::Unchanged
+            },
+            VimKey::Enter => {
+                self.mode = VimMode::Normal;
+                let command = std::mem::take(&mut self.command_line);
+                run_ex_command(doc, command.trim(), &self.unsaved)
+            },
+            VimKey::Escape => {
+                self.mode = VimMode::Normal;
+                self.command_line.clear();
+                Outcome::Unchanged
+            },
+            _ => Outcome::Unchanged
+        }
+    }
+
+    fn execute(&mut self, doc : &mut DocHandler, cmd : Cmd) -> Outcome {
+        let before = (doc.revision, doc.ptr_y, doc.ptr_x);
+        let count = cmd.count.unwrap_or(1);
+
+        let outcome = match cmd.action {
+            Action::Motion(motion) => self.move_cursor(doc, motion, cmd.count),
+
+            Action::Operate(operator, motion) => {
+                //cw on a word changes to the end of the word, like ce. On the last char of
+                //a word that is the char itself, where ce would go on to the next word.
+                let class = char_class(doc, (doc.ptr_y, doc.ptr_x));
+                let on_word = !matches!(class, Class::Blank | Class::EmptyLine);
+                let word_ends = char_class(doc, (doc.ptr_y, doc.ptr_x + 1)) != class;
+                let (motion, count) = match (operator, motion) {
+                    (Operator::Change, VimMotion::WordStart) if on_word && word_ends => (VimMotion::WordEnd, Some(count - 1)),
+                    (Operator::Change, VimMotion::WordStart) if on_word => (VimMotion::WordEnd, cmd.count),
+                    _ => (motion, cmd.count)
+                };
+
+                match motion_target(doc, motion, count, true) {
+                    Some((target, kind)) => self.operate(doc, operator, target, kind, cmd.register),
+                    None => Outcome::Unchanged
+                }
+            },
+
+            Action::OperateLines(operator) => {
+                let last = (doc.ptr_y + count - 1).min(doc.text.len() - 1);
+                self.operate(doc, operator, (last, 0), Kind::Linewise, cmd.register)
+            },
+
+            Action::Replace(c) => {
+                let (y, x) = (doc.ptr_y, doc.ptr_x);
+                if x + count > doc.line_len(y) {
+                    return Outcome::Unchanged
+                }
+
+                doc.checkpoint(false);
+                doc.delete_range((y, x), (y, x + count));
+                doc.insert_text(&c.to_string().repeat(count));
+                doc.set_cursor(y, x + count - 1);
+                Outcome::Applied
+            },
+
+            Action::Redo => {
+                for _ in 0..count {
+                    if !doc.redo() {
+                        break
+                    }
+                }
+                Outcome::Applied
+            },
+
+            Action::Simple(c) => self.simple(doc, c, cmd)
+        };
+
+        if self.mode == VimMode::Normal {
+            clamp_to_char(doc);
+        }
+
+        match outcome {
+            Outcome::Applied if before == (doc.revision, doc.ptr_y, doc.ptr_x) && self.mode == VimMode::Normal => Outcome::Unchanged,
+            outcome => outcome
+        }
+    }
+
+    fn simple(&mut self, doc : &mut DocHandler, c : char, cmd : Cmd) -> Outcome {
+        let count = cmd.count.unwrap_or(1);
+        let (y, x) = (doc.ptr_y, doc.ptr_x);
+
+        match c {
+            'i' => self.enter_insert(doc, count, (y, x)),
+            'a' => self.enter_insert(doc, count, (y, (x + 1).min(doc.line_len(y)))),
+            'I' => self.enter_insert(doc, count, (y, first_non_blank(doc, y))),
+            'A' => self.enter_insert(doc, count, (y, doc.line_len(y))),
+
+            'o' | 'O' => {
+                let at = if c == 'o' { y + 1 } else { y };
+                doc.checkpoint(false);
+                doc.insert_lines(at, &[String::new()]);
+                self.mode = VimMode::Insert;
+                self.insert_count = 1;
+                doc.set_cursor(at, 0);
+                Outcome::Applied
+            },
+
+            'p' | 'P' => self.paste(doc, cmd.register.unwrap_or('"'), count, c == 'P'),
+
+            'u' => {
+                for _ in 0..count {
+                    if !doc.undo() {
+                        break
+                    }
+                }
+                Outcome::Applied
+            },
+
+            'v' | 'V' => {
+                doc.anchor = Some((y, x));
+                self.mode = if c == 'v' { VimMode::Visual } else { VimMode::VisualLine };
+                Outcome::Applied
+            },
+
+            '.' => self.repeat(doc, cmd.count),
+
+            _ => Outcome::Unchanged
+        }
+    }
+
+    fn enter_insert(&mut self, doc : &mut DocHandler, count : usize, at : (usize, usize)) -> Outcome {
+        doc.checkpoint(false);
+        doc.set_cursor(at.0, at.1);
+        self.mode = VimMode::Insert;
+        self.insert_count = count;
+        Outcome::Applied
+    }
+
+    fn exit_visual(&mut self, doc : &mut DocHandler) {
+        doc.anchor = None;
+        self.mode = VimMode::Normal;
+        self.pending.clear();
+        clamp_to_char(doc);
+    }
+
+    fn move_cursor(&mut self, doc : &mut DocHandler, motion : VimMotion, count : Option<usize>) -> Outcome {
+        match motion_target(doc, motion, count, false) {
+            Some(((y, x), _)) => {
+                doc.set_cursor(y, x);
+                clamp_to_char(doc);
+                Outcome::Applied
+            },
+            None => Outcome::Unchanged
+        }
+    }
+
+    //apply an operator to the text between the cursor and target
+    fn operate(&mut self, doc : &mut DocHandler, operator : Operator, target : (usize, usize), kind : Kind, register : Option<char>) -> Outcome {
+        let cursor = (doc.ptr_y, doc.ptr_x);
+        let (start, end) = if cursor <= target { (cursor, target) } else { (target, cursor) };
+
+        if kind == Kind::Linewise {
+            let lines = doc.text[start.0..=end.0].to_vec();
+            self.store(register, lines.join("\n"), true, operator == Operator::Yank);
+
+            match operator {
+                Operator::Yank => doc.set_cursor(start.0, cursor.1),
+                Operator::Delete => {
+                    doc.checkpoint(false);
+                    doc.delete_lines(start.0, end.0);
+                    let y = doc.ptr_y;
+                    doc.set_cursor(y, first_non_blank(doc, y));
+                },
+                Operator::Change => {
+                    doc.checkpoint(false);
+                    doc.delete_lines(start.0, end.0);
+                    doc.insert_lines(start.0, &[String::new()]);
+                    doc.set_cursor(start.0, 0);
+                    self.mode = VimMode::Insert;
+                    self.insert_count = 1;
+                }
+            };
+            return Outcome::Applied
+        }
+
+        let end = match kind {
+            Kind::Inclusive => (end.0, (end.1 + 1).min(doc.line_len(end.0))),
+            _ => end
+        };
+
+        if start == end && operator != Operator::Change {
+            return Outcome::Unchanged
+        }
+
+        let text = doc.get_range(start, end);
+        self.store(register, text, false, operator == Operator::Yank);
+
+        match operator {
+            Operator::Yank => doc.set_cursor(start.0, start.1),
+            Operator::Delete => {
+                doc.checkpoint(false);
+                doc.delete_range(start, end);
+            },
+            Operator::Change => {
+                doc.checkpoint(false);
+                doc.delete_range(start, end);
+                self.mode = VimMode::Insert;
+                self.insert_count = 1;
+            }
+        };
+
+        Outcome::Applied
+    }
+
+    fn paste(&mut self, doc : &mut DocHandler, name : char, count : usize, before : bool) -> Outcome {
+        let register = match self.registers.get(&name.to_ascii_lowercase()) {
+            Some(register) => register.clone(),
+            None => return Outcome::Failed(format!("E353: Nothing in register {}", name))
+        };
+
+        doc.checkpoint(false);
+        let (y, x) = (doc.ptr_y, doc.ptr_x);
+
+        if register.linewise {
+            let lines : Vec<String> = (0..count)
+                .flat_map(|_| register.text.split('\n').map(String::from))
+                .collect();
+            let at = if before { y } else { y + 1 };
+            doc.insert_lines(at, &lines);
+            doc.set_cursor(at, first_non_blank(doc, at));
+        }
+        else {
+            let at = if before || doc.line_len(y) == 0 { x } else { x + 1 };
+            doc.set_cursor(y, at);
+            doc.insert_text(&register.text.repeat(count));
+            //leave the cursor on the last pasted char
+            let (y, x) = doc.left_pos();
+            doc.set_cursor(y, x);
+        }
+
+        Outcome::Applied
+    }
+
+    //dot repeat, a count replaces the count of the original change
+    fn repeat(&mut self, doc : &mut DocHandler, count : Option<usize>) -> Outcome {
+        let change = match self.last_change.clone() {
+            Some(change) => change,
+            None => return Outcome::Unchanged
+        };
+
+        let mut cmd = change.cmd;
+        if count.is_some() {
+            cmd.count = count;
+        }
+
+        let outcome = self.execute(doc, cmd);
+
+        //replay the typed text, leaving insert mode repeats it for the count
+        if self.mode == VimMode::Insert {
+            for key in &change.inserted {
+                insert_key(doc, *key);
+            }
+            self.recording = Some(Change { cmd, inserted: change.inserted });
+            self.feed_insert(doc, VimKey::Escape);
+        }
+        else {
+            self.last_change = Some(Change { cmd, inserted: Vec::new() });
+        }
+
+        outcome
+    }
+
+    //store deleted or yanked text in a register.
+    //Uppercase names append to the lowercase register, yanks also go in "0
+    //and everything also goes in the unnamed register ".
+    fn store(&mut self, name : Option<char>, text : String, linewise : bool, yank : bool) {
+        let register = match name {
+            Some(c) if c.is_ascii_uppercase() => {
+                let existing = self.registers.entry(c.to_ascii_lowercase()).or_insert(Register { text: String::new(), linewise });
+                if !existing.text.is_empty() && (linewise || existing.linewise) {
+                    existing.text.push('\n');
+                }
+                existing.text.push_str(&text);
+                existing.linewise |= linewise;
+                existing.clone()
+            },
+            Some(c) if c != '"' => {
+                let register = Register { text, linewise };
+                self.registers.insert(c, register.clone());
+                register
+            },
+            _ => {
+                let register = Register { text, linewise };
+                if yank {
+                    self.registers.insert('0', register.clone());
+                }
+                register
+            }
+        };
+
+        self.registers.insert('"', register);
+    }
+}
+
+impl Default for Vim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//vim key for a winit key press. Printable keys come through ReceivedCharacter
+//instead, see char_key, so only special keys and ctrl chords are handled here.
+pub fn vim_key(input : &KeyboardInput, modifiers : ModifiersState) -> Option<VimKey> {
+    match input {
+        KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. } => key_code_vim_key(*key, modifiers),
+        _ => None
+    }
+}
+
+//vim key for a pressed key, for frontends without winit's keyboard events
+pub fn key_code_vim_key(key : VirtualKeyCode, modifiers : ModifiersState) -> Option<VimKey> {
+    let vim_key = match key {
+        VirtualKeyCode::Escape => VimKey::Escape,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => VimKey::Enter,
+        VirtualKeyCode::Back => VimKey::Backspace,
+        VirtualKeyCode::Delete => VimKey::Delete,
+        VirtualKeyCode::Left => VimKey::Left,
+        VirtualKeyCode::Right => VimKey::Right,
+        VirtualKeyCode::Up => VimKey::Up,
+        VirtualKeyCode::Down => VimKey::Down,
+        VirtualKeyCode::R if modifiers.ctrl() => VimKey::Ctrl('r'),
+        _ => return None
+    };
+
+    Some(vim_key)
+}
+
+//vim key for a char of typed text
+pub fn char_key(c : char, modifiers : ModifiersState) -> Option<VimKey> {
+    match c.is_control() || modifiers.ctrl() || modifiers.logo() {
+        true => None,
+        false => Some(VimKey::Char(c))
+    }
+}
+
+//turn a string such as "d2w" or "ihi<Esc>" into keys.
+//Special keys are written <Esc> <CR> <BS> <Del> <Left> <Right> <Up> <Down> <C-r>.
+pub fn parse_keys(text : &str) -> Vec<VimKey> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let special = match c {
+            '<' => rest.find('>').and_then(|end| special_key(&rest[1..end]).map(|key| (key, end + 1))),
+            _ => None
+        };
+
+        match special {
+            Some((key, used)) => {
+                keys.push(key);
+                rest = &rest[used..];
+            },
+            None => {
+                keys.push(VimKey::Char(c));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    keys
+}
+
+fn special_key(name : &str) -> Option<VimKey> {
+    let key = match name.to_lowercase().as_str() {
+        "esc" => VimKey::Escape,
+        "cr" | "enter" => VimKey::Enter,
+        "bs" => VimKey::Backspace,
+        "del" => VimKey::Delete,
+        "left" => VimKey::Left,
+        "right" => VimKey::Right,
+        "up" => VimKey::Up,
+        "down" => VimKey::Down,
+        "lt" => VimKey::Char('<'),
+        other => match other.strip_prefix("c-") {
+            Some(c) if c.chars().count() == 1 => VimKey::Ctrl(c.chars().next().unwrap_or('?')),
+            _ => return None
+        }
+    };
+
+    Some(key)
+}
+
+//inverse of parse_keys for a single key
+fn key_name(key : VimKey) -> String {
+    match key {
+        VimKey::Char('<') => String::from("<lt>"),
+        VimKey::Char(c) => c.to_string(),
+        VimKey::Ctrl(c) => format!("<C-{}>", c),
+        VimKey::Escape => String::from("<Esc>"),
+        VimKey::Enter => String::from("<CR>"),
+        VimKey::Backspace => String::from("<BS>"),
+        VimKey::Delete => String::from("<Del>"),
+        VimKey::Left => String::from("<Left>"),
+        VimKey::Right => String::from("<Right>"),
+        VimKey::Up => String::from("<Up>"),
+        VimKey::Down => String::from("<Down>")
+    }
+}
+
+//Parsing normal mode commands: ["x][count]operator[count]motion, ["x][count]cmd or [count]motion
+
+fn parse(keys : &[VimKey]) -> Parse<Cmd> {
+    let mut i = 0;
+    let mut register = None;
+
+    if keys.first() == Some(&VimKey::Char('"')) {
+        match keys.get(1) {
+            None => return Parse::Incomplete,
+            Some(VimKey::Char(c)) if c.is_ascii_alphabetic() || *c == '"' || *c == '0' => {
+                register = Some(*c);
+                i = 2;
+            },
+            Some(_) => return Parse::Invalid
+        }
+    }
+
+    let (count, used) = parse_count(&keys[i..]);
+    i += used;
+
+    let key = match keys.get(i) {
+        Some(key) => *key,
+        None => return Parse::Incomplete
+    };
+    let rest = &keys[i + 1..];
+
+    let operator = match key {
+        VimKey::Char('d') => Some(Operator::Delete),
+        VimKey::Char('c') => Some(Operator::Change),
+        VimKey::Char('y') => Some(Operator::Yank),
+        _ => None
+    };
+
+    if let Some(operator) = operator {
+        let (motion_count, used) = parse_count(rest);
+        let rest = &rest[used..];
+
+        //2d3w deletes 6 words
+        let count = match (count, motion_count) {
+            (Some(a), Some(b)) => Some(a * b),
+            (a, b) => a.or(b)
+        };
+
+        return match rest.first() {
+            None => Parse::Incomplete,
+            Some(next) if *next == key => Parse::Complete(Cmd { register, count, action: Action::OperateLines(operator) }),
+            Some(_) => match parse_motion(rest) {
+                Parse::Complete(motion) => Parse::Complete(Cmd { register, count, action: Action::Operate(operator, motion) }),
+                Parse::Incomplete => Parse::Incomplete,
+                Parse::Invalid => Parse::Invalid
+            }
+        }
+    }
+
+    let action = match key {
+        //shorthands for operator + motion
+        VimKey::Char('x') => Action::Operate(Operator::Delete, VimMotion::Right),
+        VimKey::Char('X') => Action::Operate(Operator::Delete, VimMotion::Left),
+        VimKey::Char('D') => Action::Operate(Operator::Delete, VimMotion::LineEnd),
+        VimKey::Char('C') => Action::Operate(Operator::Change, VimMotion::LineEnd),
+        VimKey::Char('s') => Action::Operate(Operator::Change, VimMotion::Right),
+        VimKey::Char('Y') => Action::OperateLines(Operator::Yank),
+
+        VimKey::Char('r') => match rest.first() {
+            None => return Parse::Incomplete,
+            Some(VimKey::Char(c)) => Action::Replace(*c),
+            Some(_) => return Parse::Invalid
+        },
+
+        VimKey::Ctrl('r') => Action::Redo,
+
+        VimKey::Char(c @ ('i' | 'a' | 'I' | 'A' | 'o' | 'O' | 'p' | 'P' | 'u' | 'v' | 'V' | '.')) => Action::Simple(c),
+
+        _ => match parse_motion(&keys[i..]) {
+            Parse::Complete(motion) => Action::Motion(motion),
+            Parse::Incomplete => return Parse::Incomplete,
+            Parse::Invalid => return Parse::Invalid
+        }
+    };
+
+    Parse::Complete(Cmd { register, count, action })
+}
+
+//a count is a run of digits not starting with 0, since 0 on its own is a motion
+fn parse_count(keys : &[VimKey]) -> (Option<usize>, usize) {
+    let mut count : Option<usize> = None;
+    let mut used = 0;
+
+    for key in keys {
+        match key {
+            VimKey::Char(c @ '0'..='9') if count.is_some() || *c != '0' => {
+                let digit = c.to_digit(10).unwrap_or(0) as usize;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                used += 1;
+            },
+            _ => break
+        }
+    }
+
+    (count, used)
+}
+
+fn parse_motion(keys : &[VimKey]) -> Parse<VimMotion> {
+    let motion = match keys.first() {
+        None => return Parse::Incomplete,
+        Some(VimKey::Char('h') | VimKey::Left | VimKey::Backspace) => VimMotion::Left,
+        Some(VimKey::Char('l') | VimKey::Char(' ') | VimKey::Right) => VimMotion::Right,
+        Some(VimKey::Char('j') | VimKey::Down | VimKey::Enter) => VimMotion::Down,
+        Some(VimKey::Char('k') | VimKey::Up) => VimMotion::Up,
+        Some(VimKey::Char('w')) => VimMotion::WordStart,
+        Some(VimKey::Char('b')) => VimMotion::WordBack,
+        Some(VimKey::Char('e')) => VimMotion::WordEnd,
+        Some(VimKey::Char('0')) => VimMotion::LineStart,
+        Some(VimKey::Char('^')) => VimMotion::FirstNonBlank,
+        Some(VimKey::Char('$')) => VimMotion::LineEnd,
+        Some(VimKey::Char('G')) => VimMotion::LastLine,
+
+        Some(VimKey::Char('g')) => match keys.get(1) {
+            None => return Parse::Incomplete,
+            Some(VimKey::Char('g')) => VimMotion::FirstLine,
+            Some(_) => return Parse::Invalid
+        },
+
+        Some(VimKey::Char(find @ ('f' | 't' | 'F' | 'T'))) => match keys.get(1) {
+            None => return Parse::Incomplete,
+            Some(VimKey::Char(c)) => match find {
+                'f' => VimMotion::FindForward(*c),
+                't' => VimMotion::TillForward(*c),
+                'F' => VimMotion::FindBack(*c),
+                _ => VimMotion::TillBack(*c)
+            },
+            Some(_) => return Parse::Invalid
+        },
+
+        Some(_) => return Parse::Invalid
+    };
+
+    Parse::Complete(motion)
+}
+
+//true for commands that change the text and can be dot repeated
+fn is_change(action : Action) -> bool {
+    match action {
+        Action::Operate(operator, _) | Action::OperateLines(operator) => operator != Operator::Yank,
+        Action::Replace(_) => true,
+        Action::Simple(c) => matches!(c, 'i' | 'a' | 'I' | 'A' | 'o' | 'O' | 'p' | 'P'),
+        Action::Motion(_) | Action::Redo => false
+    }
+}
+
+//Motions
+
+//where a motion lands and how the text up to it is measured, None if it cannot move.
+//for_operator allows landing one past the last char of a line, e.g. for dl.
+fn motion_target(doc : &DocHandler, motion : VimMotion, count : Option<usize>, for_operator : bool) -> Option<((usize, usize), Kind)> {
+    let (y, x) = (doc.ptr_y, doc.ptr_x);
+    let n = count.unwrap_or(1);
+    let len = doc.line_len(y);
+    let last_line = doc.text.len() - 1;
+
+    let target = match motion {
+        VimMotion::Left => match x {
+            0 => return None,
+            _ => ((y, x.saturating_sub(n)), Kind::Exclusive)
+        },
+
+        VimMotion::Right => {
+            let max = if for_operator { len } else { len.saturating_sub(1) };
+            match x < max {
+                true => ((y, (x + n).min(max)), Kind::Exclusive),
+                false => return None
+            }
+        },
+
+        VimMotion::Up => match y {
+            0 => return None,
+            _ => ((y.saturating_sub(n), x), Kind::Linewise)
+        },
+
+        VimMotion::Down => match y < last_line {
+            true => (((y + n).min(last_line), x), Kind::Linewise),
+            false => return None
+        },
+
+        VimMotion::WordStart => {
+            let mut pos = (y, x);
+            let mut from_line = y;
+            for _ in 0..n {
+                from_line = pos.0;
+                pos = word_forward(doc, pos);
+            }
+
+            //dw on the last word of a line stops at the end of that line
+            if for_operator && pos.0 > from_line {
+                pos = (from_line, doc.line_len(from_line));
+            }
+            (pos, Kind::Exclusive)
+        },
+
+        VimMotion::WordBack => ((0..n).fold((y, x), |pos, _| word_back(doc, pos)), Kind::Exclusive),
+
+        VimMotion::WordEnd => ((0..n).fold((y, x), |pos, _| word_end(doc, pos)), Kind::Inclusive),
+
+        VimMotion::LineStart => ((y, 0), Kind::Exclusive),
+
+        VimMotion::FirstNonBlank => ((y, first_non_blank(doc, y)), Kind::Exclusive),
+
+        VimMotion::LineEnd => {
+            let end_line = (y + n - 1).min(last_line);
+            ((end_line, doc.line_len(end_line).saturating_sub(1)), Kind::Inclusive)
+        },
+
+        VimMotion::FirstLine => {
+            let line = count.map_or(0, |n| n - 1).min(last_line);
+            ((line, first_non_blank(doc, line)), Kind::Linewise)
+        },
+
+        VimMotion::LastLine => {
+            let line = count.map_or(last_line, |n| n - 1).min(last_line);
+            ((line, first_non_blank(doc, line)), Kind::Linewise)
+        },
+
+        VimMotion::FindForward(c) | VimMotion::TillForward(c) => {
+            let found = doc.text[y].chars().enumerate()
+                .skip(x + 1)
+                .filter(|(_, ch)| *ch == c)
+                .nth(n - 1)
+                .map(|(i, _)| i)?;
+
+            match motion {
+                VimMotion::TillForward(_) if found - 1 == x => return None,
+                VimMotion::TillForward(_) => ((y, found - 1), Kind::Inclusive),
+                _ => ((y, found), Kind::Inclusive)
+            }
+        },
+
+        VimMotion::FindBack(c) | VimMotion::TillBack(c) => {
+            let chars : Vec<char> = doc.text[y].chars().take(x).collect();
+            let found = chars.iter().enumerate().rev()
+                .filter(|(_, ch)| **ch == c)
+                .nth(n - 1)
+                .map(|(i, _)| i)?;
+
+            match motion {
+                VimMotion::TillBack(_) if found + 1 == x => return None,
+                VimMotion::TillBack(_) => ((y, found + 1), Kind::Exclusive),
+                _ => ((y, found), Kind::Exclusive)
+            }
+        }
+    };
+
+    //an operator takes the char under the cursor with an inclusive motion that doesn't move, e.g. D on the last char
+    match target.0 == (y, x) && target.1 != Kind::Linewise && !(for_operator && target.1 == Kind::Inclusive) {
+        true => None,
+        false => Some(target)
+    }
+}
+
+fn char_class(doc : &DocHandler, (y, x) : (usize, usize)) -> Class {
+    match doc.text[y].chars().nth(x) {
+        None if doc.line_len(y) == 0 => Class::EmptyLine,
+        None => Class::Blank,
+        Some(c) if c.is_whitespace() => Class::Blank,
+        Some(c) if c.is_alphanumeric() || c == '_' => Class::Word,
+        Some(_) => Class::Punctuation
+    }
+}
+
+//next char position, moving onto the next line after the last char
+fn next_pos(doc : &DocHandler, (y, x) : (usize, usize)) -> Option<(usize, usize)> {
+    match x + 1 < doc.line_len(y) {
+        true => Some((y, x + 1)),
+        false if y + 1 < doc.text.len() => Some((y + 1, 0)),
+        false => None
+    }
+}
+
+//previous char position, moving onto the last char of the line above
+fn prev_pos(doc : &DocHandler, (y, x) : (usize, usize)) -> Option<(usize, usize)> {
+    match (y, x) {
+        (0, 0) => None,
+        (y, 0) => Some((y - 1, doc.line_len(y - 1).saturating_sub(1))),
+        (y, x) => Some((y, x - 1))
+    }
+}
+
+//w: start of the next word, an empty line counts as a word
+fn word_forward(doc : &DocHandler, pos : (usize, usize)) -> (usize, usize) {
+    let last_line = doc.text.len() - 1;
+    let end_of_doc = (last_line, doc.line_len(last_line));
+    let start_class = char_class(doc, pos);
+    let mut pos = pos;
+
+    //leave the current word
+    if start_class != Class::Blank {
+        loop {
+            match next_pos(doc, pos) {
+                None => return end_of_doc,
+                Some(next) => {
+                    let new_line = next.0 != pos.0;
+                    pos = next;
+                    if new_line || char_class(doc, pos) != start_class {
+                        break
+                    }
+                }
+            }
+        }
+    }
+
+    //skip blanks up to the next word
+    while char_class(doc, pos) == Class::Blank {
+        match next_pos(doc, pos) {
+            Some(next) => pos = next,
+            None => return end_of_doc
+        }
+    }
+
+    pos
+}
+
+//e: last char of the current or next word
+fn word_end(doc : &DocHandler, pos : (usize, usize)) -> (usize, usize) {
+    let mut pos = match next_pos(doc, pos) {
+        Some(next) => next,
+        None => return pos
+    };
+
+    while matches!(char_class(doc, pos), Class::Blank | Class::EmptyLine) {
+        match next_pos(doc, pos) {
+            Some(next) => pos = next,
+            None => return pos
+        }
+    }
+
+    let class = char_class(doc, pos);
+    while let Some(next) = next_pos(doc, pos) {
+        if next.0 != pos.0 || char_class(doc, next) != class {
+            break
+        }
+        pos = next;
+    }
+
+    pos
+}
+
+//b: first char of the current or previous word
+fn word_back(doc : &DocHandler, pos : (usize, usize)) -> (usize, usize) {
+    let mut pos = match prev_pos(doc, pos) {
+        Some(prev) => prev,
+        None => return pos
+    };
+
+    while char_class(doc, pos) == Class::Blank {
+        match prev_pos(doc, pos) {
+            Some(prev) => pos = prev,
+            None => return pos
+        }
+    }
+
+    let class = char_class(doc, pos);
+    if class == Class::EmptyLine {
+        return pos
+    }
+
+    while let Some(prev) = prev_pos(doc, pos) {
+        if prev.0 != pos.0 || char_class(doc, prev) != class {
+            break
+        }
+        pos = prev;
+    }
+
+    pos
+}
+
+fn first_non_blank(doc : &DocHandler, y : usize) -> usize {
+    doc.text[y].chars().position(|c| !c.is_whitespace()).unwrap_or(0)
+}
+
+//in normal mode the cursor sits on a char so it cannot be past the last one
+fn clamp_to_char(doc : &mut DocHandler) {
+    let max = doc.line_len(doc.ptr_y).saturating_sub(1);
+    if doc.ptr_x > max {
+        doc.ptr_x = max;
+    }
+}
+
+//a key typed in insert mode
+fn insert_key(doc : &mut DocHandler, key : VimKey) {
+    match key {
+        VimKey::Char(c) => doc.update(c),
+        VimKey::Enter => doc.newline(),
+        VimKey::Backspace => doc.backspace(),
+        VimKey::Delete => doc.delete(),
+        VimKey::Left => { let (y, x) = doc.left_pos(); doc.set_cursor(y, x) },
+        VimKey::Right => { let (y, x) = doc.right_pos(); doc.set_cursor(y, x) },
+        VimKey::Up => { let (y, x) = doc.up_pos(); doc.set_cursor(y, x) },
+        VimKey::Down => { let (y, x) = doc.down_pos(); doc.set_cursor(y, x) },
+        VimKey::Ctrl(_) | VimKey::Escape => {}
+    }
+}
+
+//commands typed after :, quitting is refused while this or another open doc has unsaved changes
+fn run_ex_command(doc : &mut DocHandler, command : &str, unsaved : &[String]) -> Outcome {
+    let save = |doc : &mut DocHandler| doc.save().map_err(|e| format!("Save failed: {}", e));
+    let others = || Outcome::Failed(format!("E162: No write since last change for {} (add ! to override)", unsaved.join(", ")));
+
+    match command {
+        "" => Outcome::Unchanged,
+        "w" => match save(doc) {
+            Ok(_) => Outcome::Notice(format!("Saved {}", doc.name())),
+            Err(e) => Outcome::Failed(e)
+        },
+        "q" if doc.is_dirty() => Outcome::Failed(String::from("E37: No write since last change (add ! to override)")),
+        "q" if !unsaved.is_empty() => others(),
+        "q" | "q!" => Outcome::Quit,
+        "wq" | "x" => match (save(doc), unsaved.is_empty()) {
+            (Err(e), _) => Outcome::Failed(e),
+            (Ok(_), false) => others(),
+            (Ok(_), true) => Outcome::Quit
+        },
+        _ => match command.parse::<usize>() {
+            Ok(line) => {
+                let y = line.saturating_sub(1).min(doc.text.len() - 1);
+                doc.set_cursor(y, first_non_blank(doc, y));
+                Outcome::Applied
+            },
+            Err(_) => Outcome::Failed(format!("E492: Not an editor command: {}", command))
+        }
+    }
+}
@@ -1,31 +1,45 @@
-use winit::{
-    event::*,
-    event_loop::{EventLoop, ControlFlow},
-    window::{WindowBuilder, Window},
-};
+use winit::window::Window;
+
+use wgpu::util::DeviceExt;
 
 use wgpu_glyph::{
-    ab_glyph,
+    FontId,
     GlyphBrushBuilder,
     GlyphBrush,
+    Layout,
+    Region,
     Section,
-    Text
+    Text,
+    orthographic_projection
 };
 
+use crate::fonts::FontChain;
+use crate::renderer::{Frame, Rect, RenderError, Renderer};
+use crate::theme::{self, Color};
+
+// Where frames are drawn
+enum Target {
+    // The surface of a window, each frame is presented on screen
+    Window(wgpu::Surface),
+    // A texture that frames can be read back from, for rendering without a window e.g. in tests
+    Offscreen(wgpu::Texture)
+}
+
+// Draws frames on the GPU, see renderer.rs
 pub struct WgpuHandler {
-    surface: wgpu::Surface,
+    target: Target,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration, 
+    config: wgpu::SurfaceConfiguration, // also describes the offscreen texture
     pub size: winit::dpi::PhysicalSize<u32>,
-    window: Window,
     staging_belt: wgpu::util::StagingBelt,
-    glyph_brush: GlyphBrush<()>
+    glyph_brush: GlyphBrush<()>,
+    rect_pipeline: wgpu::RenderPipeline
 }
 
 impl WgpuHandler {
-    // Constructor method
-    pub async fn new(window: Window) -> Self {
+    // Constructor method, None when there is no graphics adapter to draw to the window with
+    pub async fn new(window: &Window, fonts: &FontChain) -> Option<Self> {
         // Fetching the size of the window from the winit Window obj
         let size = window.inner_size();
 
@@ -35,15 +49,15 @@ impl WgpuHandler {
 
         // # Safety
         // The surface needs to live as long as the window that created it.
-        // WgpuHandler owns the window so this should be safe
-        let surface = unsafe { instance.create_surface(&window) };
+        // Screen owns both and drops the renderer first so this should be safe
+        let surface = unsafe { instance.create_surface(window) };
 
         // Use the instance to obtain an adapter obj with the desired parameters
         let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions{
             power_preference: wgpu::PowerPreference::LowPower,
             compatible_surface: Some(&surface),
             force_fallback_adapter: false,
-        }).await.unwrap();
+        }).await?;
 
         // Use the adapter to obtain the device and queue objs
         let (device, queue) = adapter.request_device(
@@ -69,12 +83,12 @@ impl WgpuHandler {
             // Some(&std::path::Path::new("trace")), // Trace path
             None
 
-            ).await.unwrap();
+            ).await.ok()?;
 
         //
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,      // How SurfaceTextures will be used
-            format: surface.get_supported_formats(&adapter)[0], // How SurfaceTextures will be stored on gpu
+            format: *surface.get_supported_formats(&adapter).first()?, // How SurfaceTextures will be stored on gpu
             width: size.width,                                  // Width of SurfaceTexture
             height: size.height,                                // Height of SurfaceTexture
             present_mode: wgpu::PresentMode::Fifo,              // How to sync surface with display
@@ -84,75 +98,166 @@ impl WgpuHandler {
         //
         surface.configure(&device, &config);
 
+        Some(Self::build(Target::Window(surface), device, queue, config, fonts))
+
+    } //end new() def
+
+    // Constructor for drawing into an offscreen texture instead of a window, on the fallback
+    // adapter (a software renderer such as llvmpipe or WARP) so frames look the same on any
+    // machine. None when there is no fallback adapter.
+    pub async fn headless(size: winit::dpi::PhysicalSize<u32>, fonts: &FontChain) -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::Backends::all());
+
+        // No surface to be compatible with
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions{
+            power_preference: wgpu::PowerPreference::LowPower,
+            compatible_surface: None,
+            force_fallback_adapter: true,
+        }).await?;
+
+        // Software adapters may not reach the default limits
+        let (device, queue) = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            },
+            None
+            ).await.ok()?;
+
+        // The texture is rgba so read back pixels need no swizzling, and can be copied from
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        };
+        let texture = offscreen_texture(&device, &config);
+
+        Some(Self::build(Target::Offscreen(texture), device, queue, config, fonts))
+    } //end headless() def
+
+    // The parts of the constructors that don't depend on where frames are drawn
+    fn build(target: Target, device: wgpu::Device, queue: wgpu::Queue, config: wgpu::SurfaceConfiguration, fonts: &FontChain) -> Self {
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+
         // Create staging belt
-        let mut staging_belt = wgpu::util::StagingBelt::new(1024);
+        let staging_belt = wgpu::util::StagingBelt::new(1024);
 
-        // Fetch font and store as FontArc obj for use initializing GlyphBrush obj
-        // Font sourced from Google Fonts, font name = Bungee Shade, designer = David Jonathan Ross
-        let font = ab_glyph::FontArc::try_from_slice(include_bytes!("RobotoMono-Regular.ttf")).unwrap(); // must unwrap since returns as Result otherwise
+        // Initialize our GlyphBrush obj for use later, a text's font_id is its index in the chain
+        let glyph_brush = GlyphBrushBuilder::using_fonts(fonts.fonts.clone()).build(&device, config.format); // want to retreive the value found in config's format field
 
-        // Initialize our GlyphBrush obj for use later
-        let mut glyph_brush = GlyphBrushBuilder::using_font(font).build(&device, config.format); // want to retreive the value found in config's format field
+        // Pipeline for the flat coloured rectangles drawn under the text, e.g. search highlights
+        let rect_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Rect Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("rect.wgsl").into()),
+        });
 
-        // 
+        let rect_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Rect Pipeline Layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        let rect_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Rect Pipeline"),
+            layout: Some(&rect_pipeline_layout),
+
+            // Each vertex is a clip space position followed by an rgba colour
+            vertex: wgpu::VertexState {
+                module: &rect_shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: (6 * std::mem::size_of::<f32>()) as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+                }],
+            },
+
+            fragment: Some(wgpu::FragmentState {
+                module: &rect_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
 
         // Return value
         // Self is a handler for whatever obj that the impl block is associated with
         // i.e. here Self {...} == WgpuHandler {...} 
         Self {
-            surface,
+            target,
             device,
             queue,
             config,
             size,
-            window,
             staging_belt,
-            glyph_brush
+            glyph_brush,
+            rect_pipeline
         }
 
-    } //end new() def
+    } //end build() def
 
-    // Function to fetch window associated with wgpu instance
-    pub fn window(&self) -> &Window {
-        &self.window
-    } //end window() def
+    // Theme colours are srgb, as on the web, but an srgb surface takes linear colours
+    // and converts them itself
+    fn color(&self, color: Color) -> Color {
+        match self.config.format.describe().srgb {
+            true => theme::to_linear(color),
+            false => color
+        }
+    } //end color() def
+
+}
+
+impl Renderer for WgpuHandler {
+    fn size(&self) -> winit::dpi::PhysicalSize<u32> {
+        self.size
+    }
 
     // Function to handle window scaling and resizing
-    pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
+    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;                               // Update size field
             self.config.width = new_size.width;                 // Need to reconfigure surface so
             self.config.height = new_size.height;               // we update config w & h
-            self.surface.configure(&self.device, &self.config); // Reconfigure surface
+            match &mut self.target {
+                Target::Window(surface) => surface.configure(&self.device, &self.config), // Reconfigure surface
+                Target::Offscreen(texture) => *texture = offscreen_texture(&self.device, &self.config)
+            }
         }
     } //end resize() def
 
-    // Function to handle specific window events within the main render loop
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
-        //input() returns a bool to indicate whether an event has been fully processed. 
-        //If the method returns true, the main loop won't process the event any further
-
-        false
-    } //end input() def
-
-    //
-    pub fn update(&mut self) {
-        //not in use
-    } //end update() def
-
-    //
-    pub fn render(&mut self, text: &Vec<String>) -> Result<(), wgpu::SurfaceError> {
+    // Draws the rects of the frame over its background, then its text on top
+    fn render(&mut self, frame: &Frame) -> Result<(), RenderError> {
 
         // get_current_surface_texture waits for a new SurfaceTexture obj to be
         // supplied by the surface. We will render to this SurfaceTexture obj.
-        // For now we store it in output.
-        let output = self.surface.get_current_texture()?; // for notes on ? syntax see 
-                                                          // https://stackoverflow.com/questions/42917566/what-is-this-question-mark-operator-about
-
+        // For now we store it in output. Offscreen there is nothing to present.
         // [The view variable def line] creates a TextureView with default settings. 
         // We need to do this because we want to control how the render code 
         // interacts with the texture.                                                 
-        let view = output.texture.create_view( &wgpu::TextureViewDescriptor::default() );
+        let (output, view) = match &self.target {
+            Target::Window(surface) => {
+                let output = surface.get_current_texture().map_err(|e| match e {
+                    wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => RenderError::Lost,
+                    wgpu::SurfaceError::OutOfMemory => RenderError::OutOfMemory,
+                    wgpu::SurfaceError::Timeout => RenderError::Timeout
+                })?;
+                let view = output.texture.create_view( &wgpu::TextureViewDescriptor::default() );
+                (Some(output), view)
+            },
+            Target::Offscreen(texture) => (None, texture.create_view( &wgpu::TextureViewDescriptor::default() ))
+        };
 
         // We also need to create a CommandEncoder to create the actual commands to send to the gpu.
         // Most modern graphics frameworks expect commands to be stored in a command buffer 
@@ -162,10 +267,23 @@ impl WgpuHandler {
             label: Some("Render Encoder"), //debug label, presumably
         });
 
+        // Vertex buffer with two triangles per rect, there is nothing to draw without any rects
+        let rects: Vec<Rect> = frame.rects.iter().map(|rect| Rect { color: self.color(rect.color), ..*rect }).collect();
+        let rect_vertices = rect_vertices(&rects, self.size.width as f32, self.size.height as f32);
+        let rect_buffer = match rect_vertices.is_empty() {
+            true => None,
+            false => Some(self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Rect Vertex Buffer"),
+                contents: bytemuck::cast_slice(&rect_vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            }))
+        };
+        let background = self.color(frame.background);
+
         //[To clear the screen] We need to use the encoder to create a RenderPass.
         // The RenderPass has all the methods for the actual drawing.
         { // not a typo
-        let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor { //start RenderPassDescriptor args
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor { //start RenderPassDescriptor args
             
             label: Some("Render Pass"),
 
@@ -188,17 +306,17 @@ impl WgpuHandler {
                 // The ops field takes a wpgu::Operations object.
                 // This tells wgpu what to do with the colors on the screen (specified by view).
                 // The load field tells wgpu how to handle colors stored from the previous frame.
-                // Currently, we are clearing the screen with white.
+                // The screen is cleared with the theme's background colour.
                 // The store field tells wgpu whether we want to store the rendered results
                 // to the Texture behind our TextureView (in this case it's the SurfaceTexture).
                 // We use true as we do want to store our render results.
                 ops: wgpu::Operations { //start Operations args
 
                     load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 1.0,
-                        g: 1.0,
-                        b: 1.0,
-                        a: 1.0,
+                        r: background[0] as f64,
+                        g: background[1] as f64,
+                        b: background[2] as f64,
+                        a: background[3] as f64,
                     }), //close Clear()
 
                     store: true, 
@@ -212,40 +330,67 @@ impl WgpuHandler {
             depth_stencil_attachment: None,
 
         } //end RenderPassDescriptor args
-        ); //end _render_pass def 
-        } //end not a typo
-
-        let mut render_text = String::new();
-        for line in text {
-            render_text.push_str(&line);
-            render_text.push_str("\n");
-        };
-
-        println!("{:?}", render_text);
+        ); //end render_pass def 
 
-        // Prepare and configure the text you want to display
-        // we are adding this Section obj to the GlyphBrush queue
-        self.glyph_brush.queue(Section {
-
-            screen_position: (40.0, 40.0),
-
-            bounds: (self.size.width as f32 , self.size.height as f32),
-
-            text: vec![Text::new( &render_text )
-                                    .with_color([0.0, 0.0, 0.0, 1.0])
-                                    .with_scale(25.0), ],
-
-            ..Section::default()
-        });
+        // Draw the rects on the cleared screen, the text is drawn over them afterwards
+        if let Some(buffer) = &rect_buffer {
+            render_pass.set_pipeline(&self.rect_pipeline);
+            render_pass.set_vertex_buffer(0, buffer.slice(..));
+            render_pass.draw(0..(rect_vertices.len() / 6) as u32, 0..1);
+        }
+        } //end not a typo
 
-        // Add the objects queued with GlyphBrush to the staging belt
-        self.glyph_brush.draw_queued(
-            &self.device,
-            &mut self.staging_belt,
-            &mut encoder,
-            &view,
-            self.size.width,
-            self.size.height).expect("Draw queued");
+        // Lines are drawn a clip at a time, so each pane's text is cut off at its edges
+        let mut start = 0;
+        while start < frame.lines.len() {
+            let clip = frame.lines[start].clip;
+            let end = start + frame.lines[start..].iter().take_while(|line| line.clip == clip).count();
+            let region = clip.map(|clip| clip.pixels(self.size.width, self.size.height));
+
+            // Nothing of these lines would show, a scissor rect can't be empty
+            if matches!(region, Some((left, top, right, bottom)) if left == right || top == bottom) {
+                start = end;
+                continue;
+            }
+
+            // Queue each line with GlyphBrush, in the fonts the view split it into
+            for line in &frame.lines[start..end] {
+                let color = self.color(line.color);
+                let texts: Vec<Text> = line.runs.iter()
+                    .map(|(run, font)| Text::new(run)
+                                            .with_color(color)
+                                            .with_scale(line.scale)
+                                            .with_font_id(FontId(*font)))
+                    .collect();
+
+                self.glyph_brush.queue(Section {
+                    screen_position: (line.x, line.y),
+                    bounds: (f32::INFINITY, f32::INFINITY),
+                    text: texts,
+                    layout: Layout::default_single_line(),
+                });
+            }
+
+            // Add the objects queued with GlyphBrush to the staging belt
+            match region {
+                Some((left, top, right, bottom)) => self.glyph_brush.draw_queued_with_transform_and_scissoring(
+                    &self.device,
+                    &mut self.staging_belt,
+                    &mut encoder,
+                    &view,
+                    orthographic_projection(self.size.width, self.size.height),
+                    Region { x: left, y: top, width: right - left, height: bottom - top }).map_err(RenderError::Other)?,
+                None => self.glyph_brush.draw_queued(
+                    &self.device,
+                    &mut self.staging_belt,
+                    &mut encoder,
+                    &view,
+                    self.size.width,
+                    self.size.height).map_err(RenderError::Other)?
+            }
+
+            start = end;
+        }
 
         // Lock the objs in the staging belt 
         // the staging belt can no longer be modified until the contents
@@ -254,7 +399,9 @@ impl WgpuHandler {
 
         // Tell wgpu to finish the command buffer and submit it to the gpu's render queue
         self.queue.submit( std::iter::once( encoder.finish() ) );
-        output.present();
+        if let Some(output) = output {
+            output.present();
+        }
 
         // Once the queue has been submitted we can recall
         // the staging belt and regain access to it
@@ -265,4 +412,86 @@ impl WgpuHandler {
 
     } //end render() def
 
-}
\ No newline at end of file
+    // The last frame drawn offscreen, also None if the texture could not be read
+    fn read_pixels(&self) -> Option<Vec<u8>> {
+        let texture = match &self.target {
+            Target::Offscreen(texture) => texture,
+            Target::Window(_) => return None
+        };
+
+        // Rows copied into a buffer have to be padded to a multiple of 256 bytes
+        let row_bytes = self.config.width * 4;
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row_bytes * self.config.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d { width: self.config.width, height: self.config.height, depth_or_array_layers: 1 });
+        self.queue.submit( std::iter::once( encoder.finish() ) );
+
+        // Wait for the copy, then drop the padding from each row
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| { let _ = sender.send(result); });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+
+        let data = slice.get_mapped_range();
+        let pixels = data.chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect();
+
+        Some(pixels)
+    } //end read_pixels() def
+}
+
+// A texture to draw into in place of a window's surface
+fn offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Texture"),
+        size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+    })
+}
+
+// Two triangles per rect as (x, y, r, g, b, a) vertices, converting from
+// pixels (origin top left) to clip space (origin in the middle, y up)
+fn rect_vertices(rects: &[Rect], width: f32, height: f32) -> Vec<f32> {
+    let mut vertices = Vec::with_capacity(rects.len() * 36);
+
+    for rect in rects {
+        let left = rect.x / width * 2.0 - 1.0;
+        let right = (rect.x + rect.w) / width * 2.0 - 1.0;
+        let top = 1.0 - rect.y / height * 2.0;
+        let bottom = 1.0 - (rect.y + rect.h) / height * 2.0;
+
+        for (x, y) in [(left, top), (left, bottom), (right, bottom), (left, top), (right, bottom), (right, top)] {
+            vertices.extend([x, y]);
+            vertices.extend(rect.color);
+        }
+    }
+
+    vertices
+}
@@ -0,0 +1,107 @@
+use garbanzo_book::book::{Book, Level, Section};
+
+// Two parts: A with chapters a1 (scenes s1, s2) and a2 (scene s3), B with chapter b1 (scene s4)
+fn book() -> Book {
+    let section = |level, title: &str, sections: Vec<Section>| Section { sections, ..Section::new(level, title, None) };
+    let scene = |title: &str| section(Level::Scene, title, Vec::new());
+
+    let mut book = Book::new("The Garbanzo", "A. Writer");
+    book.sections = vec![
+        section(Level::Part, "A", vec![
+            section(Level::Chapter, "a1", vec![scene("s1"), scene("s2")]),
+            section(Level::Chapter, "a2", vec![scene("s3")])
+        ]),
+        section(Level::Part, "B", vec![
+            section(Level::Chapter, "b1", vec![scene("s4")])
+        ])
+    ];
+    book
+}
+
+// The titles of the sections in reading order, each after the path of its parent
+fn outline(book: &Book) -> Vec<String> {
+    book.rows().iter()
+        .map(|(path, section)| format!("{:?} {}", &path[..path.len() - 1], section.title))
+        .collect()
+}
+
+#[test]
+fn sections_move_among_their_siblings() {
+    let mut book = book();
+    assert_eq!(book.move_down(&[0, 0, 0]), Some(vec![0, 0, 1]));
+    assert_eq!(book.section(&[0, 0, 0]).unwrap().title, "s2");
+    assert_eq!(book.section(&[0, 0, 1]).unwrap().title, "s1");
+
+    assert_eq!(book.move_up(&[0, 0, 1]), Some(vec![0, 0, 0]));
+    assert_eq!(book, self::book());
+
+    assert_eq!(book.move_down(&[0]), Some(vec![1]));
+    assert_eq!(outline(&book)[..3], ["[] B", "[0] b1", "[0, 0] s4"]);
+}
+
+#[test]
+fn sections_move_across_their_parent() {
+    // down from the last scene of a chapter to the start of the next one, and back
+    let mut book = book();
+    assert_eq!(book.move_down(&[0, 0, 1]), Some(vec![0, 1, 0]));
+    assert_eq!(outline(&book)[1..6], ["[0] a1", "[0, 0] s1", "[0] a2", "[0, 1] s2", "[0, 1] s3"]);
+    assert_eq!(book.move_up(&[0, 1, 0]), Some(vec![0, 0, 1]));
+    assert_eq!(book, self::book());
+
+    // a chapter goes from the end of one part to the start of the next
+    assert_eq!(book.move_down(&[0, 1]), Some(vec![1, 0]));
+    assert_eq!(outline(&book), ["[] A", "[0] a1", "[0, 0] s1", "[0, 0] s2", "[] B", "[1] a2", "[1, 0] s3", "[1] b1", "[1, 1] s4"]);
+    assert_eq!(book.move_up(&[1, 0]), Some(vec![0, 1]));
+    assert_eq!(book, self::book());
+
+    // the first scene of a part's first chapter stays, it has nowhere to go up to
+    assert_eq!(book.move_up(&[1, 0, 0]), None);
+    assert_eq!(book.move_up(&[0, 0, 0]), None);
+    assert_eq!(book.move_up(&[0]), None);
+    assert_eq!(book.move_down(&[1, 0, 0]), None);
+    assert_eq!(book.move_down(&[1]), None);
+    assert_eq!(book.move_down(&[2]), None);
+    assert_eq!(book, self::book());
+}
+
+#[test]
+fn dropped_sections_take_the_place_of_the_one_dropped_on() {
+    // dragged down it goes after the section, dragged up before it
+    let mut book = book();
+    assert_eq!(book.drop_onto(&[0, 0, 0], &[0, 1, 0]), Ok(vec![0, 1, 1]));
+    assert_eq!(outline(&book)[1..6], ["[0] a1", "[0, 0] s2", "[0] a2", "[0, 1] s3", "[0, 1] s1"]);
+
+    let mut book = self::book();
+    assert_eq!(book.drop_onto(&[1, 0, 0], &[0, 0, 1]), Ok(vec![0, 0, 1]));
+    assert_eq!(outline(&book)[1..5], ["[0] a1", "[0, 0] s1", "[0, 0] s4", "[0, 0] s2"]);
+    assert!(book.section(&[1, 0]).unwrap().sections.is_empty());
+
+    let mut book = self::book();
+    assert_eq!(book.drop_onto(&[1], &[0]), Ok(vec![0]));
+    assert_eq!(book.drop_onto(&[0], &[1]), Ok(vec![1]));
+    assert_eq!(book, self::book());
+}
+
+#[test]
+fn sections_dropped_on_the_level_above_go_first_in_it() {
+    let mut book = book();
+    assert_eq!(book.drop_onto(&[0, 0, 1], &[1, 0]), Ok(vec![1, 0, 0]));
+    assert_eq!(outline(&book)[5..], ["[] B", "[1] b1", "[1, 0] s2", "[1, 0] s4"]);
+
+    // dragged up out of a later part
+    assert_eq!(book.drop_onto(&[1, 0], &[0]), Ok(vec![0, 0]));
+    assert_eq!(outline(&book), ["[] A", "[0] b1", "[0, 0] s2", "[0, 0] s4", "[0] a1", "[0, 1] s1", "[0] a2", "[0, 2] s3", "[] B"]);
+}
+
+#[test]
+fn bad_drops_are_refused() {
+    let mut book = book();
+    assert_eq!(book.drop_onto(&[3], &[0]), Err(String::from("There is no such section")));
+    assert_eq!(book.drop_onto(&[0, 0], &[0, 5]), Err(String::from("There is no such section")));
+    assert_eq!(book.drop_onto(&[0], &[0, 1, 0]), Err(String::from("A part can't be moved into itself")));
+    assert_eq!(book.drop_onto(&[0, 1], &[0, 1]), Err(String::from("A chapter can't be moved into itself")));
+    assert_eq!(book.drop_onto(&[0, 0, 0], &[1]), Err(String::from("A scene can only be moved among scenes or into a chapter")));
+    assert_eq!(book.drop_onto(&[1, 0], &[0, 0, 0]), Err(String::from("A chapter can only be moved among chapters or into a part")));
+    assert_eq!(book.drop_onto(&[1], &[0, 0]), Err(String::from("A part can only be moved among parts or into a book")));
+    assert_eq!(book, self::book());
+}
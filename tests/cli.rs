@@ -0,0 +1,161 @@
+use serde_json::{json, Value};
+
+use garbanzo_book::cli::{execute, run, EXIT_FAILED, EXIT_OK, EXIT_PROBLEMS, EXIT_USAGE};
+
+mod common;
+use common::TempDir;
+
+// The JSON a subcommand prints and its exit code
+fn cli(args: &[&str]) -> (Value, i32) {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    execute(&args).expect("a subcommand")
+}
+
+fn text(path: &std::path::Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn only_subcommands_are_run() {
+    assert_eq!(run(&[]), None);
+    assert_eq!(run(&[String::from("notes.txt")]), None);
+    assert_eq!(run(&[String::from("stats")]), Some(EXIT_USAGE));
+
+    assert_eq!(cli(&["stats"]), (json!({ "error": "no file given" }), EXIT_USAGE));
+    assert_eq!(cli(&["stats", "a.txt", "b.txt"]), (json!({ "error": "unexpected argument \"b.txt\", only one file can be given" }), EXIT_USAGE));
+    assert_eq!(cli(&["stats", "--format", "html", "a.txt"]), (json!({ "error": "unknown option \"--format\"" }), EXIT_USAGE));
+    assert_eq!(cli(&["export", "a.txt", "--format"]), (json!({ "error": "--format needs a value" }), EXIT_USAGE));
+}
+
+#[test]
+fn stats_counts_the_file() {
+    let dir = TempDir::new("cli");
+    let file = dir.join("notes.txt");
+    std::fs::write(&file, "One two.\r\n\r\nThree\r\nfour").unwrap();
+
+    let (output, code) = cli(&["stats", text(&file)]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output, json!({
+        "file": text(&file),
+        "lines": 4,
+        "paragraphs": 2,
+        "words": 4,
+        "chars": 17,
+        "chars_without_spaces": 16,
+        "line_ending": "CRLF",
+        "encoding": "UTF-8"
+    }));
+
+    let (output, code) = cli(&["stats", text(&dir.join("missing.txt"))]);
+    assert_eq!(code, EXIT_FAILED);
+    assert!(output["error"].as_str().unwrap().starts_with(&format!("could not read {}: ", dir.join("missing.txt").display())), "{}", output);
+}
+
+#[test]
+fn export_writes_next_to_the_file_or_to_the_output() {
+    let dir = TempDir::new("cli");
+    let file = dir.join("notes.txt");
+    std::fs::write(&file, "Beans & rice").unwrap();
+
+    let (output, code) = cli(&["export", "--format", "html", text(&file)]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output["file"], text(&file));
+    assert_eq!(output["format"], "html");
+    assert_eq!(output["output"], text(&dir.join("notes.html")));
+    let html = std::fs::read_to_string(dir.join("notes.html")).unwrap();
+    assert_eq!(output["bytes"], html.len());
+    assert!(html.contains("Beans &amp; rice"), "{}", html);
+
+    let (output, code) = cli(&["export", "--format=pdf", "--output", text(&dir.join("out.pdf")), text(&file)]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output["output"], text(&dir.join("out.pdf")));
+    assert!(std::fs::read(dir.join("out.pdf")).unwrap().starts_with(b"%PDF-"));
+
+    assert_eq!(cli(&["export", text(&file)]), (json!({ "error": "--format is needed" }), EXIT_USAGE));
+    assert_eq!(
+        cli(&["export", "--format", "doc", text(&file)]),
+        (json!({ "error": "unknown export format \"doc\", expected txt, html, epub or pdf" }), EXIT_USAGE)
+    );
+    assert_eq!(
+        cli(&["export", "--format", "txt", text(&file)]),
+        (json!({ "error": format!("{} would be overwritten, give another --output", file.display()) }), EXIT_USAGE)
+    );
+}
+
+#[test]
+fn compile_writes_the_book_next_to_its_manifest() {
+    let dir = TempDir::new("cli");
+    std::fs::write(dir.join("book.toml"), concat!(
+        "title = \"The Garbanzo\"\n",
+        "author = \"A. Writer\"\n\n",
+        "[[chapter]]\ntitle = \"Sowing\"\nfile = \"field.txt\"\n\n",
+        "[[chapter]]\ntitle = \"Rain\"\nfile = \"rain.txt\"\n"
+    )).unwrap();
+    std::fs::write(dir.join("field.txt"), "The field was ploughed").unwrap();
+    std::fs::write(dir.join("rain.txt"), "It rained").unwrap();
+
+    // the folder of the book is enough
+    let (output, code) = cli(&["compile", text(dir.path())]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output["book"], text(&dir.join("book.toml")));
+    assert_eq!(output["format"], "txt");
+    assert_eq!(output["output"], text(&dir.join("book.txt")));
+    assert_eq!(output["bytes"], std::fs::read(dir.join("book.txt")).unwrap().len());
+    assert_eq!(output["chapters"], 2);
+    assert_eq!(output["words"], 6);
+
+    let (output, code) = cli(&["compile", "--format", "epub", text(&dir.join("book.toml"))]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output["output"], text(&dir.join("book.epub")));
+
+    let (output, code) = cli(&["compile", text(&dir.join("missing"))]);
+    assert_eq!(code, EXIT_FAILED);
+    assert!(output["error"].as_str().unwrap().starts_with(&format!("{}: could not read the book: ", dir.join("missing").display())), "{}", output);
+}
+
+#[test]
+fn lint_lists_the_problems_and_exits_with_3() {
+    let dir = TempDir::new("cli");
+    let file = dir.join("notes.txt");
+    std::fs::write(&file, "Fine.\nthe the beans ,").unwrap();
+
+    let (output, code) = cli(&["lint", text(&file)]);
+    assert_eq!(code, EXIT_PROBLEMS);
+    assert_eq!(output, json!({
+        "file": text(&file),
+        "problems": [
+            { "line": 2, "column": 5, "rule": "repeated_word", "message": "\"the\" is repeated" },
+            { "line": 2, "column": 14, "rule": "space_before_punctuation", "message": "space before \",\"" }
+        ]
+    }));
+
+    std::fs::write(&file, "Fine.").unwrap();
+    assert_eq!(cli(&["lint", text(&file)]), (json!({ "file": text(&file), "problems": [] }), EXIT_OK));
+}
+
+#[test]
+fn convert_rewrites_the_file_unless_chars_would_be_lost() {
+    let dir = TempDir::new("cli");
+    let file = dir.join("notes.txt");
+    std::fs::write(&file, "caf\u{e9}\r\nau lait").unwrap();
+
+    let (output, code) = cli(&["convert", "--eol", "lf", "--encoding", "latin-1", text(&file)]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output, json!({ "file": text(&file), "line_ending": "LF", "encoding": "Latin-1", "changed": true }));
+    assert_eq!(std::fs::read(&file).unwrap(), b"caf\xE9\nau lait");
+
+    // already like that
+    let (output, code) = cli(&["convert", "--eol=lf", text(&file)]);
+    assert_eq!(code, EXIT_OK);
+    assert_eq!(output["changed"], false);
+
+    std::fs::write(&file, "5 \u{20ac}").unwrap();
+    assert_eq!(cli(&["convert", "--encoding", "latin1", text(&file)]), (json!({ "error": "1 char cannot be stored in Latin-1" }), EXIT_FAILED));
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "5 \u{20ac}");
+
+    assert_eq!(cli(&["convert", text(&file)]), (json!({ "error": "--eol or --encoding is needed" }), EXIT_USAGE));
+    assert_eq!(
+        cli(&["convert", "--eol", "cr", text(&file)]),
+        (json!({ "error": "unknown line ending \"cr\", expected lf or crlf" }), EXIT_USAGE)
+    );
+}
@@ -0,0 +1,36 @@
+// Helpers shared by the integration tests, each test file that uses them has `mod common;`
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Told apart by a count so tests running in parallel in one binary never share a folder
+static DIRS: AtomicUsize = AtomicUsize::new(0);
+
+// A new empty folder in the temp dir, removed with everything in it when dropped, even when the test panics
+pub struct TempDir {
+    path: PathBuf
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("garbanzo_book_{}_{}_{}", name, std::process::id(), DIRS.fetch_add(1, Ordering::Relaxed)));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,51 @@
+use garbanzo_book::book::Level;
+use garbanzo_book::compile::compile;
+use garbanzo_book::export::Block;
+use garbanzo_book::iomod::load_manifest;
+
+mod common;
+use common::TempDir;
+
+// The headings of the book compiled from the manifest, with empty files for its sections
+fn compiled_headings(manifest: &str) -> Vec<(Level, String)> {
+    let dir = TempDir::new("compile");
+    std::fs::write(dir.join("book.toml"), manifest).unwrap();
+    let document = compile(&load_manifest(&dir.join("book.toml")).unwrap()).unwrap();
+
+    document.blocks.into_iter()
+        .filter_map(|block| match block {
+            Block::Heading(level, heading) => Some((level, heading)),
+            _ => None
+        })
+        .collect()
+}
+
+#[test]
+fn headings_drop_the_separator_only_without_a_title() {
+    let headings = compiled_headings(concat!(
+        "title = \"The Garbanzo\"\n\n",
+        "[[part]]\ntitle = \"\"\n\n",
+        "[[part.chapter]]\ntitle = \"Etc.\"\n\n",
+        "[[part.chapter]]\ntitle = \"\"\n\n",
+        "[[part.chapter]]\ntitle = \"Well, -\"\n"
+    ));
+    assert_eq!(headings, [
+        (Level::Part, String::from("Part 1")),
+        (Level::Chapter, String::from("Chapter 1: Etc.")),
+        (Level::Chapter, String::from("Chapter 2")),
+        (Level::Chapter, String::from("Chapter 3: Well, -"))
+    ]);
+
+    // the separator can come after the title too, and a heading of only the title is left out
+    let headings = compiled_headings(concat!(
+        "title = \"The Garbanzo\"\n\n",
+        "[compile]\npart_heading = \"{title} - Part {roman}\"\nchapter_heading = \"{title}\"\n\n",
+        "[[part]]\ntitle = \"\"\n\n",
+        "[[part.chapter]]\ntitle = \"\"\n\n",
+        "[[part.chapter]]\ntitle = \"Rain.\"\n"
+    ));
+    assert_eq!(headings, [
+        (Level::Part, String::from("Part I")),
+        (Level::Chapter, String::from("Rain."))
+    ]);
+}
@@ -0,0 +1,86 @@
+use garbanzo_book::doc_handler::DocHandler;
+
+// A doc over the text with the cursor at (line, column)
+fn at(text: &str, cursor: (usize, usize)) -> DocHandler {
+    let mut doc = DocHandler::from_text(text);
+    doc.set_cursor(cursor.0, cursor.1);
+    doc
+}
+
+#[test]
+fn word_motions_skip_spaces_and_punctuation() {
+    assert_eq!(at("Hello, world", (0, 12)).word_left_pos(), (0, 7));
+    assert_eq!(at("Hello, world", (0, 7)).word_left_pos(), (0, 0));
+    assert_eq!(at("Hello, world", (0, 0)).word_right_pos(), (0, 5));
+    assert_eq!(at("Hello, world", (0, 5)).word_right_pos(), (0, 12));
+
+    // contractions are one word, an em dash is not a word
+    assert_eq!(at("don't stop", (0, 10)).word_left_pos(), (0, 6));
+    assert_eq!(at("don't stop", (0, 6)).word_left_pos(), (0, 0));
+    assert_eq!(at("don’t stop", (0, 0)).word_right_pos(), (0, 5));
+    assert_eq!(at("one—two", (0, 3)).word_right_pos(), (0, 7));
+
+    // trailing punctuation, then the line ends
+    assert_eq!(at("Hello, world.", (0, 12)).word_right_pos(), (0, 13));
+    assert_eq!(at("  Hello", (0, 2)).word_left_pos(), (0, 0));
+}
+
+#[test]
+fn word_motions_cross_lines_at_the_ends() {
+    assert_eq!(at("one\ntwo", (1, 0)).word_left_pos(), (0, 3));
+    assert_eq!(at("one\ntwo", (0, 3)).word_right_pos(), (1, 0));
+    assert_eq!(at("one\ntwo", (0, 0)).word_left_pos(), (0, 0));
+    assert_eq!(at("one\ntwo", (1, 3)).word_right_pos(), (1, 3));
+}
+
+#[test]
+fn deleting_words() {
+    let mut doc = at("Hello, big world", (0, 11));
+    doc.delete_word_back();
+    assert_eq!(doc.text, vec!["Hello, world"]);
+    assert_eq!((doc.ptr_y, doc.ptr_x), (0, 7));
+
+    doc.delete_word_forward();
+    assert_eq!(doc.text, vec!["Hello, "]);
+
+    let mut doc = at("one\ntwo", (1, 0));
+    doc.delete_word_back();
+    assert_eq!(doc.text, vec!["onetwo"]);
+}
+
+#[test]
+fn sentence_motions() {
+    let text = "One. Two? Three!\nFour.";
+    assert_eq!(at(text, (0, 0)).sentence_forward_pos(), (0, 5));
+    assert_eq!(at(text, (0, 5)).sentence_forward_pos(), (0, 10));
+    assert_eq!(at(text, (0, 10)).sentence_forward_pos(), (1, 0));
+    assert_eq!(at(text, (1, 2)).sentence_forward_pos(), (1, 5));
+
+    // back goes to the start of the sentence, then to the one before
+    assert_eq!(at(text, (0, 12)).sentence_back_pos(), (0, 10));
+    assert_eq!(at(text, (0, 10)).sentence_back_pos(), (0, 5));
+    assert_eq!(at(text, (1, 0)).sentence_back_pos(), (0, 10));
+    assert_eq!(at(text, (0, 0)).sentence_back_pos(), (0, 0));
+
+    // a full stop inside a number doesn't end a sentence
+    assert_eq!(at("It cost 3.50 each. Fine.", (0, 0)).sentence_forward_pos(), (0, 19));
+}
+
+#[test]
+fn paragraph_motions() {
+    let text = "a\nb\n\nc\nd\n\n\ne";
+    assert_eq!(at(text, (0, 0)).paragraph_down_pos(), (3, 0));
+    assert_eq!(at(text, (3, 0)).paragraph_down_pos(), (7, 0));
+    assert_eq!(at(text, (5, 0)).paragraph_down_pos(), (7, 0));
+    assert_eq!(at(text, (7, 0)).paragraph_down_pos(), (7, 1));
+
+    // up goes to the first line of the paragraph, then to the one above
+    assert_eq!(at(text, (4, 1)).paragraph_up_pos(), (3, 0));
+    assert_eq!(at(text, (3, 0)).paragraph_up_pos(), (0, 0));
+    assert_eq!(at(text, (7, 0)).paragraph_up_pos(), (3, 0));
+    assert_eq!(at(text, (6, 0)).paragraph_up_pos(), (3, 0));
+    assert_eq!(at(text, (0, 0)).paragraph_up_pos(), (0, 0));
+
+    // lines of only spaces separate paragraphs too
+    assert_eq!(at("a\n  \nb", (0, 0)).paragraph_down_pos(), (2, 0));
+}
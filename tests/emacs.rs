@@ -0,0 +1,115 @@
+use garbanzo_book::command::{EditorCommand, Motion};
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::editor::Editor;
+use garbanzo_book::keymap::{Keymap, Profile};
+use garbanzo_book::macros::Macros;
+use garbanzo_book::settings::Settings;
+
+fn emacs_editor(text: &str) -> Editor {
+    Editor::new(DocHandler::from_text(text), Keymap::for_profile(Profile::Emacs), Settings::default(), Macros::new())
+}
+
+fn run(editor: &mut Editor, commands: &[EditorCommand]) {
+    for command in commands {
+        editor.run_command(command);
+    }
+}
+
+fn current_kill(editor: &Editor) -> Option<&str> {
+    editor.emacs.as_ref().unwrap().current_kill()
+}
+
+#[test]
+fn consecutive_kills_are_joined() {
+    let mut editor = emacs_editor("one two three\nfour");
+    run(&mut editor, &[EditorCommand::Kill(Motion::WordRight), EditorCommand::Kill(Motion::WordRight)]);
+    assert_eq!(editor.doc().text, [" three", "four"]);
+    assert_eq!(current_kill(&editor), Some("one two"));
+
+    // C-k twice takes the rest of the line and then the line break
+    run(&mut editor, &[EditorCommand::KillLine, EditorCommand::KillLine]);
+    assert_eq!(editor.doc().text, ["four"]);
+    assert_eq!(current_kill(&editor), Some("one two three\n"));
+
+    // anything in between starts a new kill
+    run(&mut editor, &[EditorCommand::Move(Motion::LineEnd), EditorCommand::Kill(Motion::WordLeft)]);
+    assert_eq!(current_kill(&editor), Some("four"));
+
+    // killing backwards puts the text in front
+    let mut editor = emacs_editor("one two three");
+    run(&mut editor, &[EditorCommand::Move(Motion::LineEnd), EditorCommand::Kill(Motion::WordLeft), EditorCommand::Kill(Motion::WordLeft)]);
+    assert_eq!(editor.doc().text, ["one "]);
+    assert_eq!(current_kill(&editor), Some("two three"));
+}
+
+#[test]
+fn yank_pop_cycles_through_the_kill_ring_after_a_yank() {
+    let mut editor = emacs_editor("a\nb\nc\n");
+    run(&mut editor, &[EditorCommand::Yank]);
+    assert_eq!(editor.message.as_deref(), Some("Kill ring is empty"));
+
+    // three separate kills, a move in between keeps them apart
+    for _ in 0..3 {
+        run(&mut editor, &[EditorCommand::KillLine, EditorCommand::Move(Motion::Down)]);
+    }
+    assert_eq!(editor.doc().text, ["", "", "", ""]);
+
+    run(&mut editor, &[EditorCommand::Move(Motion::DocStart), EditorCommand::YankPop]);
+    assert_eq!(editor.message.as_deref(), Some("Previous command was not a yank"));
+
+    run(&mut editor, &[EditorCommand::Yank]);
+    assert_eq!(editor.doc().text[0], "c");
+    run(&mut editor, &[EditorCommand::YankPop]);
+    assert_eq!(editor.doc().text[0], "b");
+    run(&mut editor, &[EditorCommand::YankPop, EditorCommand::YankPop]);
+    assert_eq!(editor.doc().text[0], "c");
+    assert_eq!((editor.doc().ptr_y, editor.doc().ptr_x), (0, 1));
+
+    // only straight after a yank
+    run(&mut editor, &[EditorCommand::InsertChar('!'), EditorCommand::YankPop]);
+    assert_eq!(editor.message.as_deref(), Some("Previous command was not a yank"));
+    assert_eq!(editor.doc().text[0], "c!");
+}
+
+#[test]
+fn the_kill_ring_keeps_the_last_60_kills() {
+    let text: Vec<String> = (0..61).map(|i| format!("kill {}", i)).collect();
+    let mut editor = emacs_editor(&text.join("\n"));
+    for _ in 0..61 {
+        run(&mut editor, &[EditorCommand::KillLine, EditorCommand::Move(Motion::Down)]);
+    }
+    assert_eq!(current_kill(&editor), Some("kill 60"));
+
+    // popping back 59 times reaches the oldest kept, once more wraps to the newest
+    run(&mut editor, &[EditorCommand::Move(Motion::DocStart), EditorCommand::Yank]);
+    for _ in 0..59 {
+        run(&mut editor, &[EditorCommand::YankPop]);
+    }
+    assert_eq!(editor.doc().text[0], "kill 1");
+    run(&mut editor, &[EditorCommand::YankPop]);
+    assert_eq!(editor.doc().text[0], "kill 60");
+}
+
+#[test]
+fn the_region_between_the_mark_and_the_cursor_is_killed_or_copied() {
+    let mut editor = emacs_editor("one two three");
+    run(&mut editor, &[EditorCommand::KillRegion]);
+    assert_eq!(editor.message.as_deref(), Some("The mark is not set now, so there is no region"));
+
+    run(&mut editor, &[EditorCommand::SetMark, EditorCommand::Move(Motion::WordRight), EditorCommand::Move(Motion::WordRight)]);
+    run(&mut editor, &[EditorCommand::KillRegion]);
+    assert_eq!(editor.doc().text, [" three"]);
+    assert_eq!(current_kill(&editor), Some("one two"));
+    assert_eq!(editor.doc().anchor, None);
+
+    // copying leaves the text and drops the mark, the cursor can be before the mark
+    run(&mut editor, &[EditorCommand::Move(Motion::LineEnd), EditorCommand::SetMark, EditorCommand::Move(Motion::WordLeft), EditorCommand::CopyRegion]);
+    assert_eq!(editor.doc().text, [" three"]);
+    assert_eq!(current_kill(&editor), Some("three"));
+    assert_eq!(editor.doc().anchor, None);
+    run(&mut editor, &[EditorCommand::CopyRegion]);
+    assert_eq!(editor.message.as_deref(), Some("The mark is not set now, so there is no region"));
+
+    run(&mut editor, &[EditorCommand::Move(Motion::LineStart), EditorCommand::Yank]);
+    assert_eq!(editor.doc().text, ["three three"]);
+}
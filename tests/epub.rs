@@ -0,0 +1,107 @@
+use std::io::{Cursor, Read};
+
+use regex::Regex;
+use zip::ZipArchive;
+
+use garbanzo_book::compile::compile;
+use garbanzo_book::epub::MIMETYPE;
+use garbanzo_book::export::{export, Document, Format};
+use garbanzo_book::iomod::load_manifest;
+
+mod common;
+use common::TempDir;
+
+// The text of a file in the archive
+fn read(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut text = String::new();
+    archive.by_name(name).unwrap_or_else(|_| panic!("{} is in the archive", name)).read_to_string(&mut text).unwrap();
+    text
+}
+
+#[test]
+fn compiled_book_is_a_valid_epub() {
+    let dir = TempDir::new("epub");
+    std::fs::write(dir.join("book.toml"), concat!(
+        "title = \"Beans & Rice\"\n",
+        "author = \"A. Writer\"\n",
+        "language = \"en-GB\"\n",
+        "identifier = \"urn:isbn:9780000000002\"\n",
+        "cover = \"cover.png\"\n\n",
+        "[[part]]\ntitle = \"Spring\"\n\n",
+        "[[part.chapter]]\ntitle = \"Sowing\"\nfile = \"field.txt\"\n\n",
+        "[[part.chapter]]\ntitle = \"Rain\"\nfile = \"rain.txt\"\n"
+    )).unwrap();
+    std::fs::write(dir.join("field.txt"), "The field\n\n#\n\nThe <seeds>").unwrap();
+    std::fs::write(dir.join("rain.txt"), "It rained").unwrap();
+    std::fs::write(dir.join("cover.png"), b"\x89PNG\r\n\x1a\n not really a png").unwrap();
+
+    let book = load_manifest(&dir.join("book.toml")).unwrap();
+    let bytes = export(&compile(&book).unwrap(), Format::Epub);
+
+    // the first local file header is the mimetype, stored without compression or extra
+    // fields so the bytes "application/epub+zip" sit at offset 38
+    assert_eq!(&bytes[0..4], b"PK\x03\x04");
+    assert_eq!(u16::from_le_bytes([bytes[8], bytes[9]]), 0);
+    assert_eq!(u16::from_le_bytes([bytes[26], bytes[27]]), 8);
+    assert_eq!(u16::from_le_bytes([bytes[28], bytes[29]]), 0);
+    assert_eq!(&bytes[30..38], b"mimetype");
+    assert_eq!(&bytes[38..58], MIMETYPE.as_bytes());
+
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+    let names: Vec<String> = archive.file_names().map(String::from).collect();
+    for name in ["META-INF/container.xml", "EPUB/package.opf", "EPUB/nav.xhtml", "EPUB/cover.xhtml", "EPUB/images/cover.png"] {
+        assert!(names.iter().any(|file| file == name), "{} is missing from {:?}", name, names);
+    }
+
+    let container = read(&mut archive, "META-INF/container.xml");
+    assert!(container.contains("full-path=\"EPUB/package.opf\" media-type=\"application/oebps-package+xml\""));
+
+    // the metadata comes from the manifest
+    let package = read(&mut archive, "EPUB/package.opf");
+    assert!(package.contains("version=\"3.0\" unique-identifier=\"book-id\""));
+    assert!(package.contains("<dc:identifier id=\"book-id\">urn:isbn:9780000000002</dc:identifier>"));
+    assert!(package.contains("<dc:title>Beans &amp; Rice</dc:title>"));
+    assert!(package.contains("<dc:creator>A. Writer</dc:creator>"));
+    assert!(package.contains("<dc:language>en-GB</dc:language>"));
+    assert!(Regex::new(r#"<meta property="dcterms:modified">\d{4}-\d\d-\d\dT\d\d:\d\d:\d\dZ</meta>"#).unwrap().is_match(&package));
+    assert!(package.contains("href=\"images/cover.png\" media-type=\"image/png\" properties=\"cover-image\""));
+    assert!(package.contains("href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\""));
+
+    // every file in the manifest is in the archive, and the spine is the cover, title page, part and chapters
+    let items = Regex::new(r#"<item id="([^"]+)" href="([^"]+)""#).unwrap();
+    let ids: Vec<String> = items.captures_iter(&package)
+        .map(|item| {
+            let href = format!("EPUB/{}", &item[2]);
+            assert!(names.contains(&href), "{} is missing from {:?}", href, names);
+            item[1].to_string()
+        })
+        .collect();
+    let spine: Vec<String> = Regex::new(r#"<itemref idref="([^"]+)""#).unwrap().captures_iter(&package).map(|itemref| itemref[1].to_string()).collect();
+    assert!(spine.iter().all(|idref| ids.contains(idref)));
+    assert_eq!(spine, ["cover", "section-001", "section-002", "section-003", "section-004"]);
+
+    // the chapters are listed in their part
+    let nav = read(&mut archive, "EPUB/nav.xhtml");
+    assert!(nav.contains("<nav epub:type=\"toc\" id=\"toc\">"));
+    assert!(nav.contains("<li><a href=\"section-002.xhtml\">Part 1: Spring</a>\n<ol>\n<li><a href=\"section-003.xhtml\">Chapter 1: Sowing</a></li>\n<li><a href=\"section-004.xhtml\">Chapter 2: Rain</a></li>\n</ol>\n</li>"));
+
+    let chapter = read(&mut archive, "EPUB/section-003.xhtml");
+    assert!(chapter.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(chapter.contains("xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"en-GB\""));
+    assert!(chapter.contains("<section epub:type=\"chapter\">\n<h2>Chapter 1: Sowing</h2>\n<p>The field</p>\n<p class=\"scene-break\">#</p>\n<p>The &lt;seeds&gt;</p>\n</section>"));
+}
+
+#[test]
+fn doc_without_headings_is_one_page() {
+    let text = vec![String::from("Just a line")];
+    let document = Document::from_text(&text, "notes");
+    let mut archive = ZipArchive::new(Cursor::new(export(&document, Format::Epub))).unwrap();
+
+    let package = read(&mut archive, "EPUB/package.opf");
+    assert!(package.contains("<dc:identifier id=\"book-id\">urn:garbanzo_book:"));
+    assert!(package.contains("<dc:language>en</dc:language>"));
+    assert!(!package.contains("cover"));
+    assert!(read(&mut archive, "EPUB/nav.xhtml").contains("<li><a href=\"section-001.xhtml\">notes</a></li>"));
+    assert!(read(&mut archive, "EPUB/section-001.xhtml").contains("<body>\n<p>Just a line</p>\n</body>"));
+}
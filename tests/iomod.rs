@@ -0,0 +1,102 @@
+use garbanzo_book::command::{dispatch, EditorCommand, Outcome};
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::iomod::{decode, encode, load_file_txt, save_file_txt, Encoding, FileFormat, LineEnding};
+
+mod common;
+use common::TempDir;
+
+const ENCODINGS: [Encoding; 5] = [Encoding::Utf8, Encoding::Utf8Bom, Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Latin1];
+
+#[test]
+fn files_are_saved_the_way_they_were_loaded() {
+    let dir = TempDir::new("iomod");
+    for encoding in ENCODINGS {
+        for line_ending in [LineEnding::Lf, LineEnding::CrLf] {
+            let format = FileFormat { line_ending, encoding };
+            let path = dir.join(&format!("{}_{}.txt", encoding, line_ending).replace(' ', "_"));
+
+            // café fits in every encoding, and a trailing empty line is a final line break
+            let lines = vec![String::from("Caf\u{e9} au lait"), String::new(), String::from("\u{bd} past"), String::new()];
+            save_file_txt(&path, &lines, format).unwrap();
+            let bytes = std::fs::read(&path).unwrap();
+            assert_eq!(bytes, encode(&lines.join(line_ending.as_str()), encoding), "{:?}", format);
+
+            let (loaded, loaded_format) = load_file_txt(&path).unwrap();
+            assert_eq!(loaded, lines, "{:?}", format);
+            assert_eq!(loaded_format, format);
+
+            // saving again writes the same bytes
+            save_file_txt(&path, &loaded, loaded_format).unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), bytes, "{:?}", format);
+        }
+    }
+}
+
+#[test]
+fn encodings_are_told_apart_by_their_bytes() {
+    assert_eq!(decode(b"plain"), (String::from("plain"), Encoding::Utf8));
+    assert_eq!(decode("caf\u{e9}".as_bytes()), (String::from("caf\u{e9}"), Encoding::Utf8));
+    assert_eq!(decode(b"\xEF\xBB\xBFcaf\xC3\xA9"), (String::from("caf\u{e9}"), Encoding::Utf8Bom));
+    assert_eq!(decode(b"\xFF\xFEh\x00i\x00"), (String::from("hi"), Encoding::Utf16Le));
+    assert_eq!(decode(b"\xFE\xFF\x00h\x00i"), (String::from("hi"), Encoding::Utf16Be));
+
+    // not valid utf-8, so every byte is a latin-1 char
+    assert_eq!(decode(b"caf\xE9"), (String::from("caf\u{e9}"), Encoding::Latin1));
+    assert_eq!(decode(b""), (String::new(), Encoding::Utf8));
+
+    // utf-16 keeps chars outside the basic plane as surrogate pairs
+    let bean = "\u{1fad8} beans";
+    for encoding in [Encoding::Utf16Le, Encoding::Utf16Be, Encoding::Utf8Bom] {
+        assert_eq!(decode(&encode(bean, encoding)), (String::from(bean), encoding));
+    }
+
+    // latin-1 has no room for them
+    assert_eq!(encode(bean, Encoding::Latin1), b"? beans");
+    assert!(!Encoding::Latin1.can_encode('\u{1fad8}'));
+}
+
+#[test]
+fn the_first_line_break_decides_the_line_ending() {
+    let dir = TempDir::new("iomod");
+    let path = dir.join("mixed.txt");
+
+    std::fs::write(&path, "one\r\ntwo\nthree\r\n").unwrap();
+    let (lines, format) = load_file_txt(&path).unwrap();
+    assert_eq!(format.line_ending, LineEnding::CrLf);
+    assert_eq!(lines, ["one", "two", "three", ""]);
+
+    // with LF a stray \r stays in the line, so it is written back as it was
+    std::fs::write(&path, "one\ntwo\r\n").unwrap();
+    let (lines, format) = load_file_txt(&path).unwrap();
+    assert_eq!(format.line_ending, LineEnding::Lf);
+    assert_eq!(lines, ["one", "two\r", ""]);
+    save_file_txt(&path, &lines, format).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"one\ntwo\r\n");
+
+    // a file of one line gets the usual line ending
+    std::fs::write(&path, "one").unwrap();
+    assert_eq!(load_file_txt(&path).unwrap().1.line_ending, LineEnding::native());
+}
+
+#[test]
+fn latin_1_files_are_not_saved_with_chars_it_cannot_hold() {
+    let dir = TempDir::new("iomod");
+    let path = dir.join("latin1.txt");
+    std::fs::write(&path, b"caf\xE9").unwrap();
+
+    let mut doc = DocHandler::open(&path).unwrap();
+    assert_eq!(doc.format.encoding, Encoding::Latin1);
+    doc.update('\u{20ac}');
+    let error = doc.save().unwrap_err();
+    assert_eq!(error.to_string(), "\"\u{20ac}\" cannot be stored in Latin-1, convert the file to UTF-8 to keep it");
+    assert!(doc.is_dirty());
+    assert_eq!(std::fs::read(&path).unwrap(), b"caf\xE9");
+
+    // the editor says why
+    assert_eq!(dispatch(&mut doc, &EditorCommand::Save), Outcome::Failed(format!("Save failed: {}", error)));
+
+    // once the doc is UTF-8 it saves
+    doc.format.encoding = Encoding::Utf8;
+    doc.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "caf\u{e9}\u{20ac}");
+}
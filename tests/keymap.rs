@@ -0,0 +1,109 @@
+use winit::event::{ModifiersState, VirtualKeyCode};
+
+use garbanzo_book::command::EditorCommand;
+use garbanzo_book::keymap::{parse_sequence, format_sequence, Keymap, Mode, Profile};
+
+mod common;
+use common::TempDir;
+
+// The error a keymap file is refused with
+fn error(text: &str) -> String {
+    match Keymap::from_toml(text) {
+        Ok(_) => panic!("{:?} was loaded", text),
+        Err(e) => e.to_string()
+    }
+}
+
+#[test]
+fn keymap_files_are_layered_over_the_profile() {
+    let mut keymap = Keymap::from_toml(concat!(
+        "[global]\n",
+        "\"Control+F9\" = \"save_all\"\n",
+        "\"ctrl+s\" = \"unbound\"\n\n",
+        "[insert]\n",
+        "\"alt+minus\" = \"insert:\u{2014}\"\n",
+        "\"ctrl+f10 f10\" = \"macro:tidy:3\"\n"
+    )).unwrap();
+    assert_eq!(keymap.profile, Profile::Default);
+
+    let ctrl = ModifiersState::CTRL;
+    assert_eq!(keymap.translate_key(VirtualKeyCode::F9, ctrl), Some(EditorCommand::SaveAll));
+    assert_eq!(keymap.translate_key(VirtualKeyCode::S, ctrl), None);
+    assert_eq!(keymap.translate_key(VirtualKeyCode::Minus, ModifiersState::ALT), Some(EditorCommand::InsertChar('\u{2014}')));
+    assert_eq!(keymap.translate_key(VirtualKeyCode::F10, ctrl), None);
+    assert!(keymap.is_pending());
+    assert_eq!(keymap.pending_keys(), "ctrl+f10");
+    assert_eq!(keymap.translate_key(VirtualKeyCode::F10, ModifiersState::empty()), Some("macro:tidy:3".parse().unwrap()));
+
+    // the insert section is not used in other modes, the defaults still are
+    keymap.mode = Mode::Normal;
+    assert_eq!(keymap.translate_key(VirtualKeyCode::Minus, ModifiersState::ALT), None);
+    assert_eq!(keymap.translate_key(VirtualKeyCode::Z, ctrl), Some(EditorCommand::Undo));
+
+    // the profile picks the defaults the file is layered over
+    let mut emacs = Keymap::from_toml("profile = \"emacs\"\n").unwrap();
+    assert_eq!(emacs.profile, Profile::Emacs);
+    assert_eq!(emacs.translate_key(VirtualKeyCode::K, ctrl), Some(EditorCommand::KillLine));
+}
+
+#[test]
+fn key_names_are_parsed_and_written_back() {
+    assert_eq!(format_sequence(&parse_sequence("Shift+Ctrl+Z").unwrap()), "ctrl+shift+z");
+    assert_eq!(format_sequence(&parse_sequence("meta+esc  cmd+return").unwrap()), "alt+escape logo+enter");
+    assert_eq!(format_sequence(&parse_sequence("ctrl+shift+equals").unwrap()), "ctrl+shift+equals");
+
+    assert_eq!(parse_sequence("ctrl+foo").unwrap_err(), "unknown key \"foo\"");
+    assert_eq!(parse_sequence("hyper+a").unwrap_err(), "unknown modifier \"hyper\", expected ctrl, alt, shift or logo");
+    assert_eq!(parse_sequence("ctrl++").unwrap_err(), "\"ctrl++\" has no key, write + as \"equals\" or \"shift+equals\"");
+    assert_eq!(parse_sequence(" ").unwrap_err(), "no keys given");
+}
+
+#[test]
+fn bad_keymap_files_are_refused_with_the_binding_at_fault() {
+    assert_eq!(error("[global]\n\"ctrl+s\" = \"sve\"\n"), "[global] \"ctrl+s\": unknown command \"sve\", did you mean \"save\"?");
+    assert_eq!(error("[find]\n\"ctrl+s\" = \"xyzzy_plugh\"\n"), "[find] \"ctrl+s\": unknown command \"xyzzy_plugh\", run with --dump-keymap to list the commands");
+    assert_eq!(error("[insert]\n\"alt+q\" = \"insert:ab\"\n"), "[insert] \"alt+q\": \"insert:ab\" must insert exactly one char");
+    assert_eq!(error("[insert]\n\"alt+q\" = \"macro:tidy:often\"\n"), "[insert] \"alt+q\": \"often\" is not a repeat count, use a number or until_fail");
+    assert_eq!(error("[normal]\n\"ctrl+foo\" = \"save\"\n"), "[normal] \"ctrl+foo\": unknown key \"foo\"");
+    assert_eq!(error("[visual]\n\"hyper+a\" = \"save\"\n"), "[visual] \"hyper+a\": unknown modifier \"hyper\", expected ctrl, alt, shift or logo");
+
+    // keys spelt two ways are the same binding
+    assert_eq!(error("[global]\n\"ctrl+s\" = \"save\"\n\"Ctrl+S\" = \"save_all\"\n"), "[global] \"ctrl+s\": same keys as \"Ctrl+S\" in this section");
+
+    // a binding that is the start of a longer one would hide it
+    assert_eq!(
+        error("[global]\n\"ctrl+f9\" = \"save\"\n\"ctrl+f9 f10\" = \"save_all\"\n"),
+        "[global] \"ctrl+f9\": bound to save but is also the start of \"ctrl+f9 f10\", unbind one of them with \"unbound\""
+    );
+
+    // mistakes in the toml itself
+    assert!(error("[global\n").starts_with("keymap is not valid toml: "));
+    assert!(error("[keys]\n").starts_with("keymap is not valid toml: "));
+    assert!(error("profile = \"nano\"\n").starts_with("keymap is not valid toml: "));
+}
+
+#[test]
+fn dumped_keymaps_load_back_the_same() {
+    let dir = TempDir::new("keymap");
+
+    // a missing file is the defaults
+    assert_eq!(Keymap::load(&dir.join("keymap.toml")).unwrap().to_toml(), Keymap::default().to_toml());
+
+    // what --dump-keymap writes
+    for keymap in [Keymap::default(), Keymap::for_profile(Profile::Vim), Keymap::for_profile(Profile::Emacs)] {
+        let path = dir.join("keymap.toml");
+        keymap.save(&path).unwrap();
+        let loaded = Keymap::load(&path).unwrap();
+        assert_eq!(loaded.profile, keymap.profile);
+        assert_eq!(loaded.to_toml(), keymap.to_toml());
+    }
+
+    // and so does a keymap loaded from a file
+    let custom = Keymap::from_toml("profile = \"vim\"\n\n[normal]\n\"ctrl+f9\" = \"save_all\"\n").unwrap();
+    custom.save(&dir.join("custom.toml")).unwrap();
+    let text = std::fs::read_to_string(dir.join("custom.toml")).unwrap();
+    assert!(text.starts_with("# garbanzo_book keymap\n# commands: "), "{}", text);
+    assert!(text.contains("profile = \"vim\"\n"), "{}", text);
+    assert!(text.contains("\"ctrl+f9\" = \"save_all\"\n"), "{}", text);
+    assert_eq!(Keymap::load(&dir.join("custom.toml")).unwrap().to_toml(), text);
+}
@@ -0,0 +1,132 @@
+use garbanzo_book::compile::compile;
+use garbanzo_book::export::{export, Block, Document, Format};
+use garbanzo_book::fonts::BUNDLED_FONT;
+use garbanzo_book::iomod::load_manifest;
+use garbanzo_book::pdf::{lay_out, Page};
+
+mod common;
+use common::TempDir;
+
+// The lines of the page between the margins, leaving out the header and footer
+fn body(page: &Page, document: &Document) -> Vec<String> {
+    let options = &document.pdf;
+    let top = options.page_size.height - options.margins.top.points;
+    page.lines.iter()
+        .filter(|line| line.y > options.margins.bottom.points && line.y < top)
+        .map(|line| line.text.clone())
+        .collect()
+}
+
+// A doc of one paragraph per list of lines, each line kept on a line of its own
+fn paragraphs(lengths: &[(&str, usize)]) -> Document {
+    let mut document = Document::from_text(&[], "lines");
+    document.blocks = lengths.iter()
+        .map(|(name, length)| Block::Paragraph((1..=*length).map(|i| format!("{}{}", name, i)).collect()))
+        .collect();
+    document
+}
+
+#[test]
+fn compiled_book_is_laid_out_on_pages() {
+    let dir = TempDir::new("pdf");
+    let manifest = concat!(
+        "title = \"The Garbanzo\"\n",
+        "author = \"A. Writer\"\n\n",
+        "[pdf]\n",
+        "page_size = \"6x9in\"\n",
+        "margins = { top = \"0.75in\", bottom = \"0.75in\", left = \"20mm\", right = \"20mm\" }\n",
+        "font_size = 10\n",
+        "line_spacing = 1\n",
+        "header = \"{author} / {title} / {page}\"\n",
+        "footer = \"{page} of {pages}\"\n\n",
+        "[[chapter]]\ntitle = \"Sowing\"\nfile = \"field.txt\"\n\n",
+        "[[chapter]]\ntitle = \"Rain\"\nfile = \"rain.txt\"\n"
+    );
+    std::fs::write(dir.join("book.toml"), manifest).unwrap();
+    let long: Vec<String> = (0..30).map(|i| format!("Paragraph {} of the field, long enough to wrap onto a second line at this size.", i)).collect();
+    std::fs::write(dir.join("field.txt"), long.join("\n\n")).unwrap();
+    std::fs::write(dir.join("rain.txt"), "It rained").unwrap();
+
+    let book = load_manifest(&dir.join("book.toml")).unwrap();
+    let document = compile(&book).unwrap();
+
+    // bad layouts are caught when the book is loaded
+    std::fs::write(dir.join("book.toml"), manifest.replace("6x9in", "6x9ft")).unwrap();
+    assert!(load_manifest(&dir.join("book.toml")).unwrap_err().to_string().contains("\"6x9ft\" is not a page size"));
+    std::fs::write(dir.join("book.toml"), manifest.replace("\"20mm\"", "\"3in\"")).unwrap();
+    assert!(load_manifest(&dir.join("book.toml")).unwrap_err().to_string().contains("margins leave no room"));
+
+    let pages = lay_out(&document);
+    let count = pages.len();
+    assert!(count >= 4, "{} pages", count);
+
+    // the title page has neither a header nor a footer
+    assert_eq!(body(&pages[0], &document), ["The Garbanzo", "by A. Writer"]);
+    assert_eq!(pages[0].lines.len(), 2);
+
+    // a chapter opens a page with a footer and no header, the pages after have both
+    assert_eq!(body(&pages[1], &document)[0], "Chapter 1: Sowing");
+    assert!(pages[1].lines.iter().all(|line| !line.text.starts_with("A. Writer")));
+    assert!(pages[1].lines.iter().any(|line| line.text == format!("2 of {}", count)));
+    assert!(pages[2].lines.iter().any(|line| line.text == "A. Writer / The Garbanzo / 3"));
+    assert!(pages[2].lines.iter().any(|line| line.text == format!("3 of {}", count)));
+    assert_eq!(body(&pages[count - 1], &document), ["Chapter 2: Rain", "It rained"]);
+
+    // long paragraphs are wrapped, and every line is inside the page
+    assert!(body(&pages[1], &document).iter().any(|line| line.starts_with("Paragraph 0 of the field") && !line.ends_with("size.")));
+    for line in pages.iter().flat_map(|page| page.lines.iter()) {
+        assert!(line.x >= 20.0 / 25.4 * 72.0 - 0.01 && line.x < 432.0, "{:?}", line);
+        assert!(line.y > 0.0 && line.y < 648.0, "{:?}", line);
+    }
+}
+
+#[test]
+fn paragraphs_keep_widows_and_orphans_off_page_breaks() {
+    // how many lines fit on a page
+    let document = paragraphs(&[("x", 200)]);
+    let fit = body(&lay_out(&document)[0], &document).len();
+
+    // a paragraph with only room for its first line goes to the next page
+    let document = paragraphs(&[("a", fit - 1), ("b", 3)]);
+    let pages = lay_out(&document);
+    assert_eq!(body(&pages[0], &document).last().unwrap(), &format!("a{}", fit - 1));
+    assert_eq!(body(&pages[1], &document), ["b1", "b2", "b3"]);
+
+    // one that would leave its last line alone at the top of a page leaves two
+    let document = paragraphs(&[("a", fit - 3), ("b", 4)]);
+    let pages = lay_out(&document);
+    assert_eq!(body(&pages[0], &document).last().unwrap(), "b2");
+    assert_eq!(body(&pages[1], &document), ["b3", "b4"]);
+
+    // without the rules the page is filled
+    let mut document = paragraphs(&[("a", fit - 3), ("b", 4)]);
+    document.pdf.widows = 1;
+    document.pdf.orphans = 1;
+    let pages = lay_out(&document);
+    assert_eq!(body(&pages[0], &document).last().unwrap(), "b3");
+    assert_eq!(body(&pages[1], &document), ["b4"]);
+}
+
+#[test]
+fn pdf_embeds_a_subset_of_the_font() {
+    let document = Document::from_text(&[String::from("Only a few glyphs")], "notes");
+    let bytes = export(&document, Format::Pdf);
+    let text = String::from_utf8_lossy(&bytes);
+
+    assert!(text.starts_with("%PDF-1.7"));
+    assert!(text.trim_end().ends_with("%%EOF"));
+    assert!(text.contains("/Count 1"));
+    assert!(text.contains("/MediaBox [0 0 595.28 841.89]"));
+    assert!(text.contains("/Encoding /Identity-H"));
+    assert!(text.contains("/ToUnicode 7 0 R"));
+    assert!(text.contains("+RobotoMono-Regular"));
+    assert!(text.contains("/FontFile2 6 0 R"));
+
+    let length = text.split("/Length1 ").nth(1).and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next()).unwrap();
+    let length: usize = length.parse().unwrap();
+    assert!(length < BUNDLED_FONT.len() / 2, "the embedded font is {} bytes of {}", length, BUNDLED_FONT.len());
+    assert!(bytes.len() < BUNDLED_FONT.len() / 4, "the pdf is {} bytes", bytes.len());
+
+    // an empty doc still has a page
+    assert_eq!(lay_out(&Document::from_text(&[], "empty")).len(), 1);
+}
@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::project_search::ProjectSearch;
+use garbanzo_book::search::{build_regex, SearchOptions};
+
+mod common;
+use common::TempDir;
+
+// A project search of the folder for the query, waited on until it is done
+fn search(dir: &std::path::Path, query: &str) -> ProjectSearch {
+    let mut project = ProjectSearch::new(dir);
+    project.start(build_regex(query, SearchOptions::default()).ok());
+    let start = Instant::now();
+    while project.is_searching() {
+        assert!(start.elapsed() < Duration::from_secs(10), "the search finished");
+        project.poll();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    project
+}
+
+#[test]
+fn replace_all_changes_open_docs_in_memory_and_the_rest_on_disk() {
+    let dir = TempDir::new("replace");
+    std::fs::write(dir.join("a.txt"), "Anna sowed").unwrap();
+    std::fs::write(dir.join("b.txt"), "Anna reaped").unwrap();
+    std::fs::write(dir.join("c.txt"), "Anna slept\r\nAnna woke").unwrap();
+
+    // a.txt is open in a tab with unsaved edits, b.txt is open in another
+    let mut a = DocHandler::open(&dir.join("a.txt")).unwrap();
+    a.update('>');
+    let b = DocHandler::open(&dir.join("b.txt")).unwrap();
+    let mut docs = vec![a, b];
+
+    let regex = build_regex("Anna", SearchOptions::default()).unwrap();
+    let mut project = search(dir.path(), "Anna");
+    assert_eq!(project.file_count(), 3);
+    assert_eq!(project.replace_all(&mut docs, &regex, "Bella", SearchOptions::default()).unwrap(), (4, 3));
+
+    // the open docs are changed as an undo step each and left for the user to save
+    assert_eq!(docs[0].text, ["Bella sowed>"]);
+    assert_eq!(docs[1].text, ["Bella reaped"]);
+    assert!(docs[1].is_dirty());
+    assert_eq!(std::fs::read_to_string(dir.join("a.txt")).unwrap(), "Anna sowed");
+    assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "Anna reaped");
+    assert!(docs[1].undo());
+    assert_eq!(docs[1].text, ["Anna reaped"]);
+    assert!(docs[0].undo());
+    assert_eq!(docs[0].text, ["Anna sowed>"]);
+
+    // the others are written back with their line endings
+    assert_eq!(std::fs::read_to_string(dir.join("c.txt")).unwrap(), "Bella slept\r\nBella woke");
+
+    // nothing is written when one of the files can't be read any more
+    std::fs::write(dir.join("d.txt"), "Bella rested").unwrap();
+    let mut project = search(dir.path(), "Bella");
+    std::fs::write(dir.join("d.txt"), b"Bella \xff").unwrap();
+    let error = project.replace_all(&mut [], &build_regex("Bella", SearchOptions::default()).unwrap(), "Cara", SearchOptions::default()).unwrap_err();
+    assert!(error.to_string().starts_with("d.txt: "), "{}", error);
+    assert_eq!(std::fs::read_to_string(dir.join("c.txt")).unwrap(), "Bella slept\r\nBella woke");
+
+}
@@ -0,0 +1,200 @@
+// Golden image tests, each renders a doc offscreen, on the fallback adapter or with the CPU
+// renderer, and compares the frame with a png in tests/golden. Run with UPDATE_GOLDEN=1 to
+// write the pngs again after a change to how things look, then check the new images before
+// committing them.
+use std::env;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use garbanzo_book::command::EditorCommand;
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::cpu_renderer::CpuRenderer;
+use garbanzo_book::editor::Editor;
+use garbanzo_book::fonts::FontChain;
+use garbanzo_book::keymap::Keymap;
+use garbanzo_book::macros::Macros;
+use garbanzo_book::renderer::Renderer;
+use garbanzo_book::settings::{LineNumbers, Settings};
+use garbanzo_book::theme::Theme;
+use garbanzo_book::view::View;
+use garbanzo_book::wgpu_handler::WgpuHandler;
+use winit::dpi::PhysicalSize;
+
+const WIDTH: u32 = 960;
+const HEIGHT: u32 = 400;
+
+// Channels may differ by this much, and this share of pixels by more,
+// so small differences in antialiasing between drivers don't fail the tests
+const CHANNEL_TOLERANCE: u8 = 24;
+const PIXEL_TOLERANCE: f64 = 0.002;
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog.\n\nA second paragraph that is long enough to wrap onto another row of the window.\nLast line";
+
+#[derive(Clone, Copy)]
+enum Backend {
+    Gpu,
+    Cpu
+}
+
+struct Scene {
+    editor: Editor,
+    theme: Theme
+}
+
+impl Scene {
+    fn new(text: &str) -> Self {
+        let mut settings = Settings::default();
+        // Only the bundled font, so the images don't depend on the fonts installed
+        settings.font.fallback = Some(Vec::new());
+
+        Self {
+            editor: Editor::new(DocHandler::from_text(text), Keymap::default(), settings, Macros::new()),
+            theme: Theme::default()
+        }
+    }
+
+    // The frame as rgba, None when there is no fallback adapter to draw with
+    fn render(&mut self, backend: Backend) -> Option<Vec<u8>> {
+        let size = PhysicalSize::new(WIDTH, HEIGHT);
+        let fonts = FontChain::load(&self.editor.settings.font);
+        let mut renderer: Box<dyn Renderer> = match backend {
+            Backend::Gpu => Box::new(pollster::block_on(WgpuHandler::headless(size, &fonts))?),
+            Backend::Cpu => Box::new(CpuRenderer::headless(size, &fonts))
+        };
+
+        let mut view = View::new(fonts, size, 1.0, &self.editor.settings.font);
+        view.set_theme(self.theme);
+
+        self.editor.refresh();
+        let frame = view.frame(&self.editor);
+        renderer.render(&frame).expect("offscreen render");
+        renderer.read_pixels()
+    }
+}
+
+// Compare a frame with its golden image, or write the golden image with UPDATE_GOLDEN=1
+fn check(name: &str, scene: &mut Scene, backend: Backend) {
+    let pixels = match scene.render(backend) {
+        Some(pixels) => pixels,
+        None => {
+            eprintln!("Skipping {}, no fallback adapter to render with", name);
+            return
+        }
+    };
+    assert_eq!(pixels.len(), (WIDTH * HEIGHT * 4) as usize);
+
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(&golden, &pixels);
+        return
+    }
+
+    let expected = read_png(&golden);
+    let differing = pixels.chunks(4).zip(expected.chunks(4))
+        .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE))
+        .count();
+
+    // Keep the frame that failed to look at next to the golden one
+    if differing as f64 > PIXEL_TOLERANCE * (WIDTH * HEIGHT) as f64 {
+        let actual = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+        write_png(&actual, &pixels);
+        panic!("{} differs from {:?} in {} pixels, the frame is in {:?}", name, golden, differing, actual);
+    }
+}
+
+fn read_png(path: &Path) -> Vec<u8> {
+    let file = File::open(path).unwrap_or_else(|e| panic!("no golden image {:?} ({}), run with UPDATE_GOLDEN=1 to make it", path, e));
+    let mut reader = png::Decoder::new(std::io::BufReader::new(file)).read_info().expect("golden image is a png");
+    let mut pixels = vec![0; reader.output_buffer_size().expect("golden image fits in memory")];
+    let info = reader.next_frame(&mut pixels).expect("golden image decodes");
+
+    assert_eq!((info.width, info.height, info.color_type), (WIDTH, HEIGHT, png::ColorType::Rgba), "golden image {:?} is the wrong size or format", path);
+    pixels.truncate(info.buffer_size());
+    pixels
+}
+
+fn write_png(path: &Path, pixels: &[u8]) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut encoder = png::Encoder::new(File::create(path).unwrap(), WIDTH, HEIGHT);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header().unwrap().write_image_data(pixels).unwrap();
+}
+
+#[test]
+fn document() {
+    check("document", &mut Scene::new(TEXT), Backend::Gpu);
+}
+
+#[test]
+fn cursor() {
+    let mut scene = Scene::new(TEXT);
+    scene.editor.doc_mut().set_cursor(2, 9);
+    check("cursor", &mut scene, Backend::Gpu);
+}
+
+#[test]
+fn selection() {
+    let mut scene = Scene::new(TEXT);
+    scene.editor.doc_mut().set_cursor(0, 10);
+    scene.editor.doc_mut().anchor = Some((2, 8));
+    check("selection", &mut scene, Backend::Gpu);
+}
+
+#[test]
+fn line_numbers() {
+    let mut scene = Scene::new(TEXT);
+    scene.editor.settings.line_numbers = LineNumbers::Relative;
+    scene.editor.doc_mut().set_cursor(2, 0);
+    check("line_numbers", &mut scene, Backend::Gpu);
+}
+
+#[test]
+fn search_highlights() {
+    let mut scene = Scene::new(TEXT);
+    scene.editor.find_bar.run(&mut scene.editor.tabs, &EditorCommand::Find);
+    for c in "the".chars() {
+        scene.editor.find_bar.run(&mut scene.editor.tabs, &EditorCommand::InsertChar(c));
+    }
+    check("search_highlights", &mut scene, Backend::Gpu);
+}
+
+#[test]
+fn dark_theme() {
+    let mut scene = Scene::new(TEXT);
+    scene.theme = Theme::bundled("dark").unwrap();
+    scene.editor.doc_mut().set_cursor(3, 4);
+    check("dark_theme", &mut scene, Backend::Gpu);
+}
+
+#[test]
+fn cpu_document() {
+    check("cpu_document", &mut Scene::new(TEXT), Backend::Cpu);
+}
+
+#[test]
+fn cpu_selection() {
+    let mut scene = Scene::new(TEXT);
+    scene.editor.doc_mut().set_cursor(0, 10);
+    scene.editor.doc_mut().anchor = Some((2, 8));
+    check("cpu_selection", &mut scene, Backend::Cpu);
+}
+
+#[test]
+fn cpu_search_highlights() {
+    let mut scene = Scene::new(TEXT);
+    scene.editor.settings.line_numbers = LineNumbers::Absolute;
+    scene.editor.find_bar.run(&mut scene.editor.tabs, &EditorCommand::Find);
+    for c in "the".chars() {
+        scene.editor.find_bar.run(&mut scene.editor.tabs, &EditorCommand::InsertChar(c));
+    }
+    check("cpu_search_highlights", &mut scene, Backend::Cpu);
+}
+
+#[test]
+fn cpu_dark_theme() {
+    let mut scene = Scene::new(TEXT);
+    scene.theme = Theme::bundled("dark").unwrap();
+    scene.editor.doc_mut().set_cursor(3, 4);
+    check("cpu_dark_theme", &mut scene, Backend::Cpu);
+}
@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+
+use garbanzo_book::command::{EditorCommand, Motion};
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::editor::Editor;
+use garbanzo_book::keymap::Keymap;
+use garbanzo_book::macros::Macros;
+use garbanzo_book::search::{build_regex, find_all, preserve_case, replace_all, replacement, SearchOption, SearchOptions};
+use garbanzo_book::settings::Settings;
+
+mod common;
+use common::TempDir;
+
+fn regex_options() -> SearchOptions {
+    SearchOptions { regex: true, ..SearchOptions::default() }
+}
+
+// The text after replacing every match of the query
+fn replaced(text: &str, query: &str, template: &str, options: SearchOptions) -> Vec<String> {
+    let mut lines: Vec<String> = text.split('\n').map(String::from).collect();
+    let regex = build_regex(query, options).unwrap();
+    let matches = find_all(&lines, &regex);
+    assert_eq!(replace_all(&mut lines, &matches, &regex, template, options), matches.len());
+    lines
+}
+
+fn new_editor(doc: DocHandler) -> Editor {
+    Editor::new(doc, Keymap::default(), Settings::default(), Macros::new())
+}
+
+// Open the find bar with the replace field and fill in both fields
+fn start_replace(editor: &mut Editor, command: EditorCommand, query: &str, with: &str) {
+    editor.run_command(&command);
+    for c in query.chars() {
+        editor.run_command(&EditorCommand::InsertChar(c));
+    }
+    editor.find_bar.replacement = String::from(with);
+}
+
+#[test]
+fn captures_are_expanded_in_regex_replacements() {
+    let text = "Anna Smith\nBella Jones";
+    assert_eq!(replaced(text, r"(\w+) (\w+)", "$2, $1", regex_options()), ["Smith, Anna", "Jones, Bella"]);
+    assert_eq!(replaced(text, r"(?P<first>\w+) (?P<last>\w+)", "${last}_${first}", regex_options()), ["Smith_Anna", "Jones_Bella"]);
+    assert_eq!(replaced("10 beans", r"(\d+)", "$$$1", regex_options()), ["$10 beans"]);
+
+    // plain queries use the template as typed
+    assert_eq!(replaced(text, "Anna", "$1", SearchOptions::default()), ["$1 Smith", "Bella Jones"]);
+
+    // a single match is replaced the same way
+    let lines = vec![String::from("to Anna Smith")];
+    let regex = build_regex(r"(\w+) (\w+)$", regex_options()).unwrap();
+    let found = find_all(&lines, &regex)[0];
+    assert_eq!(replacement(&lines[0], &found, &regex, "$2", regex_options()), "Smith");
+}
+
+#[test]
+fn preserve_case_follows_the_case_of_each_match() {
+    let options = SearchOptions { preserve_case: true, ..SearchOptions::default() };
+    assert_eq!(replaced("Anna, ANNA and anna", "anna", "bella", options), ["Bella, BELLA and bella"]);
+
+    // mixed case and single letters take the replacement as typed
+    assert_eq!(preserve_case("aNNa", "bella"), "bella");
+    assert_eq!(preserve_case("A", "bella"), "Bella");
+    assert_eq!(preserve_case("ANNA", "van Dijk"), "VAN DIJK");
+
+    // without the option the replacement is always used as typed
+    assert_eq!(replaced("Anna, ANNA and anna", "anna", "bella", SearchOptions::default()), ["bella, bella and bella"]);
+}
+
+#[test]
+fn line_breaks_in_replacements_split_lines() {
+    assert_eq!(replaced("one, two, three", ", ", "\n", SearchOptions::default()), ["one", "two", "three"]);
+    assert_eq!(replaced("Anna\nBella", "Anna", "Anna\n", SearchOptions::default()), ["Anna", "", "Bella"]);
+
+    let mut editor = new_editor(DocHandler::from_text("one, two"));
+    start_replace(&mut editor, EditorCommand::Replace, ", ", "\n");
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(editor.doc().text, ["one", "two"]);
+}
+
+#[test]
+fn in_selection_only_replaces_in_the_selection_the_bar_was_opened_with() {
+    let mut doc = DocHandler::from_text("Anna Anna\nAnna Anna\nAnna");
+    doc.anchor = Some((0, 5));
+    doc.set_cursor(1, 4);
+    let mut editor = new_editor(doc);
+    start_replace(&mut editor, EditorCommand::Replace, "Anna", "Bellas");
+
+    editor.run_command(&EditorCommand::ToggleSearchOption(SearchOption::InSelection));
+    editor.refresh();
+    assert_eq!(editor.find_bar.matches().len(), 2);
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(editor.doc().text, ["Anna Bellas", "Bellas Anna", "Anna"]);
+    assert_eq!(editor.find_bar.status(), "Replaced 2 matches");
+
+    // the end of the selection moved with the longer replacement on its last line
+    for _ in 0.."Anna".len() {
+        editor.run_command(&EditorCommand::Delete(Motion::Left));
+    }
+    for c in "Bellas".chars() {
+        editor.run_command(&EditorCommand::InsertChar(c));
+    }
+    editor.find_bar.replacement = String::from("Cyd");
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(editor.doc().text, ["Anna Cyd", "Cyd Anna", "Anna"]);
+
+    // and it can only be turned on when there was a selection
+    let mut editor = new_editor(DocHandler::from_text("Anna"));
+    editor.run_command(&EditorCommand::Find);
+    editor.run_command(&EditorCommand::ToggleSearchOption(SearchOption::InSelection));
+    assert_eq!(editor.message.as_deref(), Some("There was no selection when the find bar was opened"));
+}
+
+#[test]
+fn replace_all_is_undone_in_one_step() {
+    let mut editor = new_editor(DocHandler::from_text("Anna\nAnna and Anna"));
+    editor.run_command(&EditorCommand::InsertChar('>'));
+    start_replace(&mut editor, EditorCommand::Replace, "Anna", "Bella");
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(editor.doc().text, [">Bella", "Bella and Bella"]);
+
+    editor.run_command(&EditorCommand::CloseFind);
+    editor.run_command(&EditorCommand::Undo);
+    assert_eq!(editor.doc().text, [">Anna", "Anna and Anna"]);
+
+    // the typing before it is a step of its own
+    editor.run_command(&EditorCommand::Undo);
+    assert_eq!(editor.doc().text, ["Anna", "Anna and Anna"]);
+}
+
+#[test]
+fn replace_all_in_the_project_asks_first() {
+    let dir = TempDir::new("search");
+    std::fs::write(dir.join("a.txt"), "Anna sowed").unwrap();
+    std::fs::write(dir.join("b.txt"), "Anna reaped\nAnna slept").unwrap();
+
+    let mut editor = new_editor(DocHandler::open(&dir.join("a.txt")).unwrap());
+    start_replace(&mut editor, EditorCommand::FindInProject, "Anna", "Bella");
+    let start = Instant::now();
+    while editor.find_bar.is_searching() {
+        assert!(start.elapsed() < Duration::from_secs(10), "the search finished");
+        editor.find_bar.poll();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    editor.find_bar.poll();
+
+    // the first time only shows what would change
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(editor.find_bar.status(), "Replace 3 matches in 2 files? Replace all again to confirm");
+    assert_eq!(editor.find_bar.panel().1, None);
+    assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "Anna reaped\nAnna slept");
+    assert_eq!(editor.doc().text, ["Anna sowed"]);
+
+    // anything else in between cancels it
+    editor.run_command(&EditorCommand::FindNext);
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "Anna reaped\nAnna slept");
+
+    // asked again it replaces in the open doc and on disk
+    editor.run_command(&EditorCommand::ReplaceAll);
+    assert_eq!(editor.find_bar.status(), "Replaced 3 matches in 2 files");
+    assert_eq!(editor.doc().text, ["Bella sowed"]);
+    assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "Bella reaped\nBella slept");
+}
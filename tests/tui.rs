@@ -0,0 +1,396 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use garbanzo_book::command::EditorCommand;
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::editor::Editor;
+use garbanzo_book::keymap::{Keymap, Profile};
+use garbanzo_book::macros::Macros;
+use garbanzo_book::settings::{LineNumbers, Settings};
+use garbanzo_book::tui::{Buffer, Style, Tui};
+
+mod common;
+use common::TempDir;
+
+fn editor(text: &str, settings: Settings) -> Editor {
+    Editor::new(DocHandler::from_text(text), Keymap::default(), settings, Macros::new())
+}
+
+fn vim_editor(text: &str) -> Editor {
+    Editor::new(DocHandler::from_text(text), Keymap::for_profile(Profile::Vim), Settings::default(), Macros::new())
+}
+
+// Draw the editor into a fresh terminal buffer, returning it with the cursor position
+fn draw(tui: &mut Tui, editor: &mut Editor, size: (u16, u16)) -> (Buffer, (u16, u16)) {
+    let mut buffer = Buffer::new(size.0, size.1);
+    editor.refresh();
+    let cursor = tui.draw(editor, &mut buffer);
+    (buffer, cursor)
+}
+
+fn press(tui: &mut Tui, editor: &mut Editor, code: KeyCode, modifiers: KeyModifiers) {
+    tui.key(editor, KeyEvent::new(code, modifiers));
+}
+
+fn type_text(tui: &mut Tui, editor: &mut Editor, text: &str) {
+    for c in text.chars() {
+        press(tui, editor, KeyCode::Char(c), KeyModifiers::NONE);
+    }
+}
+
+#[test]
+fn draws_text_and_status_line() {
+    let mut tui = Tui::new();
+    let mut editor = editor("Call me Ishmael.\nSome years ago", Settings::default());
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (60, 6));
+
+    assert_eq!(buffer.line(0), "Call me Ishmael.");
+    assert_eq!(buffer.line(1), "Some years ago");
+    assert_eq!(buffer.line(2), "");
+    assert_eq!(cursor, (0, 0));
+
+    let status = buffer.line(5);
+    assert!(status.starts_with(" untitled   Ln 1, Col 1"), "{:?}", status);
+    assert!(status.ends_with("6 words   30 chars   LF   UTF-8"), "{:?}", status);
+    assert_eq!(buffer.cell(0, 5).style, Style::Bar);
+    assert_eq!(buffer.cell(59, 5).style, Style::Bar);
+}
+
+#[test]
+fn status_line_drops_the_right_side_when_narrow() {
+    let mut tui = Tui::new();
+    let mut editor = editor("Call me Ishmael.", Settings::default());
+    let (buffer, _) = draw(&mut tui, &mut editor, (30, 4));
+
+    assert_eq!(buffer.line(3), " untitled   Ln 1, Col 1");
+}
+
+#[test]
+fn typing_edits_the_doc() {
+    let mut tui = Tui::new();
+    let mut editor = editor("", Settings::default());
+
+    type_text(&mut tui, &mut editor, "Hi there");
+    press(&mut tui, &mut editor, KeyCode::Enter, KeyModifiers::NONE);
+    type_text(&mut tui, &mut editor, "You");
+    press(&mut tui, &mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+    assert_eq!(editor.doc().text, vec!["Hi there", "Yo"]);
+
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (40, 5));
+    assert_eq!(buffer.line(0), "Hi there");
+    assert_eq!(buffer.line(1), "Yo");
+    assert_eq!(cursor, (2, 1));
+    assert!(buffer.line(4).contains("[modified]   Ln 2, Col 3"));
+}
+
+#[test]
+fn ctrl_chords_run_commands() {
+    let mut tui = Tui::new();
+    let mut editor = editor("one", Settings::default());
+
+    press(&mut tui, &mut editor, KeyCode::End, KeyModifiers::NONE);
+    type_text(&mut tui, &mut editor, "two");
+    assert_eq!(editor.doc().text, vec!["onetwo"]);
+    press(&mut tui, &mut editor, KeyCode::Char('z'), KeyModifiers::CONTROL);
+    assert_eq!(editor.doc().text, vec!["one"]);
+
+    press(&mut tui, &mut editor, KeyCode::Char('f'), KeyModifiers::CONTROL);
+    assert!(editor.find_bar.open);
+    type_text(&mut tui, &mut editor, "on");
+    assert_eq!(editor.find_bar.query, "on");
+    assert_eq!(editor.doc().text, vec!["one"]);
+
+    let (buffer, _) = draw(&mut tui, &mut editor, (40, 5));
+    assert!(buffer.line(3).starts_with(" Find: on   1 of 1"), "{:?}", buffer.line(3));
+    assert_eq!(buffer.cell(0, 3).style, Style::Bar);
+    assert_eq!(buffer.cell(0, 0).style, Style::CurrentMatch);
+    assert_eq!(buffer.cell(2, 0).style, Style::Text);
+}
+
+#[test]
+fn vim_keys_and_selection() {
+    let mut tui = Tui::new();
+    let mut editor = vim_editor("abcdef");
+
+    type_text(&mut tui, &mut editor, "lvll");
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (30, 3));
+    // The selection runs up to the cursor, which is on the char after it
+    let styles: Vec<Style> = (0..6).map(|x| buffer.cell(x, 0).style).collect();
+    assert_eq!(styles, [Style::Text, Style::Selection, Style::Selection, Style::Text, Style::Text, Style::Text]);
+    assert_eq!(cursor, (3, 0));
+    assert!(buffer.line(2).starts_with(" -- VISUAL --"), "{:?}", buffer.line(2));
+
+    press(&mut tui, &mut editor, KeyCode::Esc, KeyModifiers::NONE);
+    type_text(&mut tui, &mut editor, "x");
+    assert_eq!(editor.doc().text, vec!["abcef"]);
+}
+
+#[test]
+fn scrolls_to_keep_the_cursor_on_screen() {
+    let mut tui = Tui::new();
+    let text: Vec<String> = (1..=50).map(|i| format!("line {}", i)).collect();
+    let mut editor = editor(&text.join("\n"), Settings::default());
+
+    // Five rows of text above the status line
+    for _ in 0..20 {
+        press(&mut tui, &mut editor, KeyCode::Down, KeyModifiers::NONE);
+    }
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (20, 6));
+    assert_eq!(buffer.line(0), "line 17");
+    assert_eq!(buffer.line(4), "line 21");
+    assert_eq!(cursor, (0, 4));
+
+    // Going back up only scrolls once the cursor reaches the top row
+    for _ in 0..6 {
+        press(&mut tui, &mut editor, KeyCode::Up, KeyModifiers::NONE);
+    }
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (20, 6));
+    assert_eq!(buffer.line(0), "line 15");
+    assert_eq!(cursor, (0, 0));
+}
+
+#[test]
+fn resizing_rewraps_the_text() {
+    let mut tui = Tui::new();
+    let mut editor = editor("It was the best of times, it was the worst of times", Settings::default());
+
+    let (buffer, _) = draw(&mut tui, &mut editor, (60, 4));
+    assert_eq!(buffer.line(0), "It was the best of times, it was the worst of times");
+
+    let mut buffer = buffer;
+    buffer.resize(20, 6);
+    editor.refresh();
+    tui.draw(&editor, &mut buffer);
+    assert_eq!(buffer.line(0), "It was the best of");
+    assert_eq!(buffer.line(1), "times, it was the");
+    assert_eq!(buffer.line(2), "worst of times");
+}
+
+#[test]
+fn line_numbers_in_a_gutter() {
+    let mut tui = Tui::new();
+    let text: Vec<String> = (1..=10).map(|i| format!("line {}", i)).collect();
+    let settings = Settings { line_numbers: LineNumbers::Absolute, ..Settings::default() };
+    let mut editor = editor(&text.join("\n"), settings);
+
+    press(&mut tui, &mut editor, KeyCode::Down, KeyModifiers::NONE);
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (20, 12));
+    assert_eq!(buffer.line(0), " 1 line 1");
+    assert_eq!(buffer.line(9), "10 line 10");
+    assert_eq!(buffer.cell(1, 1).style, Style::CurrentLineNumber);
+    assert_eq!(buffer.cell(1, 0).style, Style::LineNumber);
+    assert_eq!(cursor, (3, 1));
+}
+
+#[test]
+fn tabs_row_and_closing_with_unsaved_changes() {
+    let mut tui = Tui::new();
+    let mut editor = editor("First doc", Settings::default());
+    press(&mut tui, &mut editor, KeyCode::Char('t'), KeyModifiers::CONTROL);
+    type_text(&mut tui, &mut editor, "hi");
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (40, 6));
+
+    assert_eq!(buffer.line(0), " untitled  untitled.txt*");
+    assert_eq!(buffer.cell(0, 0).style, Style::Bar);
+    assert_eq!(buffer.cell(11, 0).style, Style::Text);
+    assert_eq!(buffer.line(1), "hi");
+    assert_eq!(cursor, (2, 1));
+
+    //the first close is refused because of the unsaved changes, the second goes ahead
+    press(&mut tui, &mut editor, KeyCode::Char('w'), KeyModifiers::CONTROL);
+    let (buffer, _) = draw(&mut tui, &mut editor, (40, 6));
+    assert!(buffer.line(5).starts_with(" untitled.txt has unsaved changes"), "{:?}", buffer.line(5));
+    assert_eq!(editor.tabs.docs().len(), 2);
+
+    press(&mut tui, &mut editor, KeyCode::Char('w'), KeyModifiers::CONTROL);
+    let (buffer, _) = draw(&mut tui, &mut editor, (40, 6));
+    assert_eq!(editor.tabs.docs().len(), 1);
+    assert_eq!(buffer.line(0), "First doc");
+}
+
+#[test]
+fn split_panes_share_the_doc_with_their_own_cursors() {
+    let mut tui = Tui::new();
+    let mut editor = editor("abc\ndef", Settings::default());
+    press(&mut tui, &mut editor, KeyCode::Char('\\'), KeyModifiers::CONTROL);
+    press(&mut tui, &mut editor, KeyCode::Down, KeyModifiers::NONE);
+    type_text(&mut tui, &mut editor, "X");
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (21, 4));
+
+    //the new pane is on the right and focused, the edit shows in both
+    assert_eq!(buffer.line(0), "abc       \u{2502}abc");
+    assert_eq!(buffer.line(1), "Xdef      \u{2502}Xdef");
+    assert_eq!(buffer.cell(10, 2).style, Style::Bar);
+    assert_eq!(cursor, (12, 1));
+
+    //the left pane kept its cursor at the start
+    press(&mut tui, &mut editor, KeyCode::F(6), KeyModifiers::NONE);
+    type_text(&mut tui, &mut editor, "Y");
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (21, 4));
+    assert_eq!(buffer.line(0), "Yabc      \u{2502}Yabc");
+    assert_eq!(cursor, (1, 0));
+
+    press(&mut tui, &mut editor, KeyCode::F(6), KeyModifiers::NONE);
+    assert_eq!((editor.doc().ptr_y, editor.doc().ptr_x), (1, 1));
+}
+
+#[test]
+fn sidebar_lists_the_book_and_moves_sections() {
+    let dir = TempDir::new("sidebar");
+    std::fs::write(dir.join("book.toml"), concat!(
+        "title = \"The Garbanzo\"\n",
+        "author = \"A. Writer\"\n\n",
+        "[[chapter]]\ntitle = \"Sowing\"\n\n",
+        "[[chapter.scene]]\nfile = \"field.txt\"\n\n",
+        "[[chapter.scene]]\ntitle = \"Rain\"\nfile = \"rain.txt\"\n\n",
+        "[[chapter]]\ntitle = \"Harvest\"\n\n",
+        "[[chapter.scene]]\nfile = \"barn.txt\"\n"
+    )).unwrap();
+    std::fs::write(dir.join("field.txt"), "The field").unwrap();
+    std::fs::write(dir.join("rain.txt"), "It rained").unwrap();
+
+    //the book opens at its first scene, with the sidebar on the left
+    let mut tui = Tui::new();
+    let mut editor = Editor::load(dir.path());
+    let (buffer, _) = draw(&mut tui, &mut editor, (60, 7));
+    assert_eq!(buffer.line(0), "The Garbanzo       \u{2502}The field");
+    assert_eq!(buffer.line(1), " Sowing            \u{2502}");
+    assert_eq!(buffer.line(2), "   field           \u{2502}");
+    assert_eq!(buffer.line(5), "   barn            \u{2502}");
+    assert_eq!(buffer.cell(3, 2).style, Style::CurrentLineNumber);
+
+    //moving the scene down rewrites the manifest
+    press(&mut tui, &mut editor, KeyCode::Char('e'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+    press(&mut tui, &mut editor, KeyCode::Down, KeyModifiers::ALT);
+    let (buffer, cursor) = draw(&mut tui, &mut editor, (60, 7));
+    assert_eq!(buffer.line(2), "   Rain            \u{2502}");
+    assert_eq!(buffer.line(3), "   field           \u{2502}");
+    assert_eq!(buffer.cell(0, 3).style, Style::Selection);
+    assert_eq!(cursor, (0, 3));
+    let manifest = std::fs::read_to_string(dir.join("book.toml")).unwrap();
+    assert!(manifest.find("rain.txt").unwrap() < manifest.find("field.txt").unwrap(), "{}", manifest);
+
+    //a scene that isn't written yet opens as a new doc, and typing goes to it again
+    press(&mut tui, &mut editor, KeyCode::Down, KeyModifiers::NONE);
+    press(&mut tui, &mut editor, KeyCode::Down, KeyModifiers::NONE);
+    press(&mut tui, &mut editor, KeyCode::Enter, KeyModifiers::NONE);
+    type_text(&mut tui, &mut editor, "Hay");
+    assert_eq!(editor.doc().path, Some(dir.join("barn.txt")));
+    assert_eq!(editor.doc().text, vec!["Hay"]);
+    assert!(!editor.sidebar.focused);
+}
+
+#[test]
+fn saves_are_reported_in_the_status_line() {
+    let dir = TempDir::new("save");
+    std::fs::write(dir.join("a.txt"), "A").unwrap();
+    std::fs::write(dir.join("b.txt"), "B").unwrap();
+    let mut editor = editor("", Settings::default());
+    editor.tabs.open(&dir.join("a.txt")).unwrap();
+    editor.tabs.open(&dir.join("b.txt")).unwrap();
+
+    editor.doc_mut().update('x');
+    editor.run_command(&EditorCommand::Save);
+    assert_eq!(editor.message.as_deref(), Some("Saved b.txt"));
+    assert_eq!(std::fs::read_to_string(dir.join("b.txt")).unwrap(), "Bx");
+
+    //save all names every doc it wrote, and says nothing when there was nothing to write
+    editor.doc_mut().update('y');
+    editor.select_tab(1);
+    editor.doc_mut().update('z');
+    editor.run_command(&EditorCommand::SaveAll);
+    assert_eq!(editor.message.as_deref(), Some("Saved a.txt, b.txt"));
+    editor.run_command(&EditorCommand::SaveAll);
+    assert_eq!(editor.message, None);
+
+    //and so does :w
+    let mut tui = Tui::new();
+    let mut editor = vim_editor("");
+    editor.tabs.open(&dir.join("a.txt")).unwrap();
+    type_text(&mut tui, &mut editor, ":w");
+    press(&mut tui, &mut editor, KeyCode::Enter, KeyModifiers::NONE);
+    assert_eq!(editor.message.as_deref(), Some("Saved a.txt"));
+}
+
+#[test]
+fn export_pdf_asks_before_replacing_a_pdf() {
+    let dir = TempDir::new("export");
+    std::fs::write(dir.join("notes.txt"), "Beans").unwrap();
+
+    //an unsaved doc has nowhere to put it
+    let mut editor = editor("", Settings::default());
+    editor.run_command(&EditorCommand::ExportPdf);
+    assert_eq!(editor.message.as_deref(), Some("Save the doc before exporting it, the pdf is written next to it"));
+
+    editor.tabs.open(&dir.join("notes.txt")).unwrap();
+    editor.run_command(&EditorCommand::ExportPdf);
+    let pdf = dir.join("notes.pdf");
+    assert_eq!(editor.message, Some(format!("Exported {}", pdf.display())));
+    assert!(std::fs::read(&pdf).unwrap().starts_with(b"%PDF-"));
+
+    //the second time it asks, and anything in between means asking again
+    std::fs::write(&pdf, "kept").unwrap();
+    editor.run_command(&EditorCommand::ExportPdf);
+    assert_eq!(editor.message, Some(format!("{} already exists, export again to replace it", pdf.display())));
+    editor.run_command(&EditorCommand::InsertChar('x'));
+    editor.run_command(&EditorCommand::ExportPdf);
+    assert_eq!(std::fs::read(&pdf).unwrap(), b"kept");
+    editor.run_command(&EditorCommand::ExportPdf);
+    assert_eq!(editor.message, Some(format!("Exported {}", pdf.display())));
+    assert!(std::fs::read(&pdf).unwrap().starts_with(b"%PDF-"));
+
+    //with a book open the whole book goes next to book.toml
+    std::fs::write(dir.join("book.toml"), "title = \"The Garbanzo\"\n\n[[chapter]]\ntitle = \"Sowing\"\nfile = \"notes.txt\"\n").unwrap();
+    std::fs::write(dir.join("book.pdf"), "kept").unwrap();
+    let mut editor = Editor::load(dir.path());
+    editor.run_command(&EditorCommand::ExportPdf);
+    assert_eq!(std::fs::read(dir.join("book.pdf")).unwrap(), b"kept");
+    editor.run_command(&EditorCommand::ExportPdf);
+    assert_eq!(editor.message, Some(format!("Exported {}", dir.join("book.pdf").display())));
+    assert!(std::fs::read(dir.join("book.pdf")).unwrap().starts_with(b"%PDF-"));
+}
+
+#[test]
+fn vim_quit_is_refused_while_another_tab_has_unsaved_changes() {
+    let mut tui = Tui::new();
+    let mut editor = vim_editor("Saved");
+    let mut other = DocHandler::from_text("Other");
+    other.update('x');
+    editor.tabs.add(other);
+    editor.select_tab(0);
+
+    type_text(&mut tui, &mut editor, ":q");
+    press(&mut tui, &mut editor, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(!editor.quit);
+    assert!(editor.message.as_deref().is_some_and(|message| message.starts_with("E162")), "{:?}", editor.message);
+
+    //and so is closing the window, until it is asked again
+    editor.run_command(&EditorCommand::Quit);
+    assert!(!editor.quit);
+    editor.run_command(&EditorCommand::Quit);
+    assert!(editor.quit);
+
+    let mut editor = vim_editor("Saved");
+    let mut other = DocHandler::from_text("Other");
+    other.update('x');
+    editor.tabs.add(other);
+    editor.select_tab(0);
+    type_text(&mut tui, &mut editor, ":q!");
+    press(&mut tui, &mut editor, KeyCode::Enter, KeyModifiers::NONE);
+    assert!(editor.quit);
+}
+
+#[test]
+fn recording_a_macro_is_reported_in_the_status_line() {
+    let mut tui = Tui::new();
+    let mut editor = editor("", Settings::default());
+    editor.run_command(&EditorCommand::RecordMacro);
+    type_text(&mut tui, &mut editor, "ab");
+    editor.run_command(&EditorCommand::RecordMacro);
+    let (buffer, _) = draw(&mut tui, &mut editor, (40, 4));
+    assert!(buffer.line(3).starts_with(" Macro recorded, 2 commands"), "{:?}", buffer.line(3));
+
+    //the notice goes at the next command
+    type_text(&mut tui, &mut editor, "c");
+    let (buffer, _) = draw(&mut tui, &mut editor, (40, 4));
+    assert!(buffer.line(3).starts_with(" untitled [modified]"), "{:?}", buffer.line(3));
+}
@@ -0,0 +1,371 @@
+use garbanzo_book::command::Outcome;
+use garbanzo_book::doc_handler::DocHandler;
+use garbanzo_book::vim::{Vim, VimMode};
+
+// Feed keys to a fresh vim over text with the cursor at (line, column)
+fn run(text: &str, cursor: (usize, usize), keys: &str) -> (DocHandler, Vim) {
+    let mut doc = DocHandler::from_text(text);
+    doc.set_cursor(cursor.0, cursor.1);
+
+    let mut vim = Vim::new();
+    vim.feed_keys(&mut doc, keys);
+    (doc, vim)
+}
+
+fn cursor(doc: &DocHandler) -> (usize, usize) {
+    (doc.ptr_y, doc.ptr_x)
+}
+
+#[test]
+fn word_motions() {
+    let (doc, _) = run("one two.three  four", (0, 0), "w");
+    assert_eq!(cursor(&doc), (0, 4));
+
+    let (doc, _) = run("one two.three  four", (0, 0), "3w");
+    assert_eq!(cursor(&doc), (0, 8));
+
+    let (doc, _) = run("one two.three  four", (0, 18), "2b");
+    assert_eq!(cursor(&doc), (0, 8));
+
+    let (doc, _) = run("one two.three  four", (0, 0), "ee");
+    assert_eq!(cursor(&doc), (0, 6));
+}
+
+#[test]
+fn word_motion_stops_on_empty_line() {
+    let (doc, _) = run("one\n\ntwo", (0, 0), "w");
+    assert_eq!(cursor(&doc), (1, 0));
+
+    let (doc, _) = run("one\n\ntwo", (0, 0), "ww");
+    assert_eq!(cursor(&doc), (2, 0));
+}
+
+#[test]
+fn line_motions() {
+    let (doc, _) = run("  indented line", (0, 8), "0");
+    assert_eq!(cursor(&doc), (0, 0));
+
+    let (doc, _) = run("  indented line", (0, 8), "^");
+    assert_eq!(cursor(&doc), (0, 2));
+
+    let (doc, _) = run("  indented line", (0, 0), "$");
+    assert_eq!(cursor(&doc), (0, 14));
+}
+
+#[test]
+fn gg_and_g_with_counts() {
+    let (doc, _) = run("a\nb\nc\nd", (0, 0), "G");
+    assert_eq!(cursor(&doc), (3, 0));
+
+    let (doc, _) = run("a\nb\nc\nd", (3, 0), "gg");
+    assert_eq!(cursor(&doc), (0, 0));
+
+    let (doc, _) = run("a\nb\nc\nd", (0, 0), "3G");
+    assert_eq!(cursor(&doc), (2, 0));
+}
+
+#[test]
+fn find_and_till() {
+    let (doc, _) = run("a,b,c,d", (0, 0), "2f,");
+    assert_eq!(cursor(&doc), (0, 3));
+
+    let (doc, _) = run("a,b,c,d", (0, 0), "t,");
+    assert_eq!(cursor(&doc), (0, 0));
+
+    let (doc, _) = run("a,b,c,d", (0, 0), "tc");
+    assert_eq!(cursor(&doc), (0, 3));
+
+    let (doc, _) = run("a,b,c,d", (0, 6), "F,");
+    assert_eq!(cursor(&doc), (0, 5));
+
+    let (doc, _) = run("a,b,c,d", (0, 6), "Tb");
+    assert_eq!(cursor(&doc), (0, 3));
+}
+
+#[test]
+fn delete_with_motions() {
+    let (doc, _) = run("one two three", (0, 0), "dw");
+    assert_eq!(doc.text, vec!["two three"]);
+
+    let (doc, _) = run("one two three", (0, 0), "d2w");
+    assert_eq!(doc.text, vec!["three"]);
+
+    let (doc, _) = run("one two three", (0, 0), "2dw");
+    assert_eq!(doc.text, vec!["three"]);
+
+    let (doc, _) = run("one two three", (0, 4), "de");
+    assert_eq!(doc.text, vec!["one  three"]);
+
+    let (doc, _) = run("one two three", (0, 4), "d$");
+    assert_eq!(doc.text, vec!["one "]);
+    assert_eq!(cursor(&doc), (0, 3));
+
+    let (doc, _) = run("one two three", (0, 4), "d0");
+    assert_eq!(doc.text, vec!["two three"]);
+
+    let (doc, _) = run("say (hello) there", (0, 0), "dt(");
+    assert_eq!(doc.text, vec!["(hello) there"]);
+
+    let (doc, _) = run("say (hello) there", (0, 0), "df)");
+    assert_eq!(doc.text, vec![" there"]);
+}
+
+#[test]
+fn operators_to_line_end_take_the_last_char() {
+    let (doc, _) = run("one two", (0, 6), "D");
+    assert_eq!(doc.text, vec!["one tw"]);
+    assert_eq!(cursor(&doc), (0, 5));
+
+    let (doc, _) = run("one two", (0, 6), "d$");
+    assert_eq!(doc.text, vec!["one tw"]);
+
+    let (doc, vim) = run("one two", (0, 6), "Cx<Esc>");
+    assert_eq!(doc.text, vec!["one twx"]);
+    assert_eq!(vim.mode, VimMode::Normal);
+
+    let (doc, vim) = run("one two", (0, 6), "y$");
+    assert_eq!(doc.text, vec!["one two"]);
+    assert_eq!(vim.register('"'), Some("o"));
+
+    // an empty line has nothing to delete, but C still starts inserting
+    let (doc, _) = run("one\n\ntwo", (1, 0), "D");
+    assert_eq!(doc.text, vec!["one", "", "two"]);
+    let (doc, _) = run("one\n\ntwo", (1, 0), "Cx<Esc>");
+    assert_eq!(doc.text, vec!["one", "x", "two"]);
+}
+
+#[test]
+fn dw_on_last_word_does_not_join_lines() {
+    let (doc, _) = run("one two\nthree", (0, 4), "dw");
+    assert_eq!(doc.text, vec!["one ", "three"]);
+}
+
+#[test]
+fn linewise_operators() {
+    let (doc, _) = run("a\nb\nc\nd", (1, 0), "dd");
+    assert_eq!(doc.text, vec!["a", "c", "d"]);
+    assert_eq!(cursor(&doc), (1, 0));
+
+    let (doc, _) = run("a\nb\nc\nd", (1, 0), "2dd");
+    assert_eq!(doc.text, vec!["a", "d"]);
+
+    let (doc, _) = run("a\nb\nc\nd", (1, 0), "dj");
+    assert_eq!(doc.text, vec!["a", "d"]);
+
+    let (doc, _) = run("a\nb\nc\nd", (1, 0), "dG");
+    assert_eq!(doc.text, vec!["a"]);
+
+    let (doc, _) = run("a\nb", (0, 0), "3dd");
+    assert_eq!(doc.text, vec![""]);
+}
+
+#[test]
+fn change_enters_insert_mode() {
+    let (doc, vim) = run("one two three", (0, 4), "cwTWO");
+    assert_eq!(vim.mode, VimMode::Insert);
+    assert_eq!(doc.text, vec!["one TWO three"]);
+
+    let (doc, vim) = run("one two three", (0, 4), "cwTWO<Esc>");
+    assert_eq!(vim.mode, VimMode::Normal);
+    assert_eq!(cursor(&doc), (0, 6));
+
+    let (doc, _) = run("a\nold line\nc", (1, 3), "ccnew<Esc>");
+    assert_eq!(doc.text, vec!["a", "new", "c"]);
+
+    let (doc, _) = run("one two three", (0, 4), "Cend<Esc>");
+    assert_eq!(doc.text, vec!["one end"]);
+
+    // cw on the last char of a word only changes that char
+    let (doc, _) = run("a b c", (0, 0), "cwX<Esc>");
+    assert_eq!(doc.text, vec!["X b c"]);
+
+    let (doc, _) = run("one two", (0, 2), "cwX<Esc>");
+    assert_eq!(doc.text, vec!["onX two"]);
+
+    let (doc, _) = run("a b c", (0, 0), "2cwX<Esc>");
+    assert_eq!(doc.text, vec!["X c"]);
+}
+
+#[test]
+fn insert_commands() {
+    let (doc, _) = run("bc", (0, 0), "ia<Esc>");
+    assert_eq!(doc.text, vec!["abc"]);
+    assert_eq!(cursor(&doc), (0, 0));
+
+    let (doc, _) = run("ac", (0, 0), "ab<Esc>");
+    assert_eq!(doc.text, vec!["abc"]);
+
+    let (doc, _) = run("  b", (0, 2), "Ia<Esc>");
+    assert_eq!(doc.text, vec!["  ab"]);
+
+    let (doc, _) = run("a", (0, 0), "Ab<Esc>");
+    assert_eq!(doc.text, vec!["ab"]);
+
+    let (doc, _) = run("a\nc", (0, 0), "ob<Esc>");
+    assert_eq!(doc.text, vec!["a", "b", "c"]);
+
+    let (doc, _) = run("b", (0, 0), "Oa<Esc>");
+    assert_eq!(doc.text, vec!["a", "b"]);
+
+    let (doc, _) = run("", (0, 0), "3ihi <Esc>");
+    assert_eq!(doc.text, vec!["hi hi hi "]);
+}
+
+#[test]
+fn x_and_replace() {
+    let (doc, _) = run("abcdef", (0, 1), "3x");
+    assert_eq!(doc.text, vec!["aef"]);
+
+    let (doc, _) = run("abcdef", (0, 2), "X");
+    assert_eq!(doc.text, vec!["acdef"]);
+
+    let (doc, _) = run("abcdef", (0, 0), "3rz");
+    assert_eq!(doc.text, vec!["zzzdef"]);
+    assert_eq!(cursor(&doc), (0, 2));
+}
+
+#[test]
+fn yank_and_paste() {
+    let (doc, _) = run("one two", (0, 0), "yeP");
+    assert_eq!(doc.text, vec!["oneone two"]);
+
+    let (doc, _) = run("one two", (0, 0), "dwP");
+    assert_eq!(doc.text, vec!["one two"]);
+
+    let (doc, _) = run("ab", (0, 0), "xp");
+    assert_eq!(doc.text, vec!["ba"]);
+
+    let (doc, _) = run("a\nb", (0, 0), "yyjp");
+    assert_eq!(doc.text, vec!["a", "b", "a"]);
+    assert_eq!(cursor(&doc), (2, 0));
+
+    let (doc, _) = run("a\nb", (1, 0), "yyP");
+    assert_eq!(doc.text, vec!["a", "b", "b"]);
+
+    let (doc, _) = run("a", (0, 0), "yy3p");
+    assert_eq!(doc.text, vec!["a", "a", "a", "a"]);
+}
+
+#[test]
+fn named_registers() {
+    let (doc, vim) = run("one two", (0, 0), "\"ayw\"byew\"ap");
+    assert_eq!(vim.register('a'), Some("one "));
+    assert_eq!(vim.register('b'), Some("one"));
+    assert_eq!(doc.text, vec!["one tone wo"]);
+
+    let (_, vim) = run("a\nb", (0, 0), "\"qyyj\"Qyy");
+    assert_eq!(vim.register('q'), Some("a\nb"));
+
+    // deleting does not overwrite the yank register
+    let (doc, vim) = run("keep drop", (0, 0), "ywwdw\"0P");
+    assert_eq!(vim.register('0'), Some("keep "));
+    assert_eq!(doc.text, vec!["keepkeep  "]);
+}
+
+#[test]
+fn dot_repeat() {
+    let (doc, _) = run("a b c d e", (0, 0), "dw..");
+    assert_eq!(doc.text, vec!["d e"]);
+
+    let (doc, _) = run("a b c d e", (0, 0), "dw3.");
+    assert_eq!(doc.text, vec!["e"]);
+
+    let (doc, _) = run("one\ntwo", (0, 0), "A!<Esc>j.");
+    assert_eq!(doc.text, vec!["one!", "two!"]);
+
+    let (doc, _) = run("foo foo foo", (0, 0), "cwbar<Esc>w.w.");
+    assert_eq!(doc.text, vec!["bar bar bar"]);
+
+    let (doc, _) = run("abcd", (0, 0), "x.");
+    assert_eq!(doc.text, vec!["cd"]);
+}
+
+#[test]
+fn undo_and_redo() {
+    let (doc, _) = run("one two", (0, 0), "dwu");
+    assert_eq!(doc.text, vec!["one two"]);
+
+    let (doc, _) = run("one two", (0, 0), "dwu<C-r>");
+    assert_eq!(doc.text, vec!["two"]);
+
+    // a whole insert is one undo step
+    let (doc, _) = run("", (0, 0), "ihello world<Esc>u");
+    assert_eq!(doc.text, vec![""]);
+
+    let (doc, _) = run("one two", (0, 4), "cwthree<Esc>u");
+    assert_eq!(doc.text, vec!["one two"]);
+}
+
+#[test]
+fn visual_mode() {
+    let (doc, vim) = run("one two three", (0, 4), "v");
+    assert_eq!(vim.mode, VimMode::Visual);
+    assert_eq!(doc.anchor, Some((0, 4)));
+
+    let (doc, vim) = run("one two three", (0, 4), "ved");
+    assert_eq!(vim.mode, VimMode::Normal);
+    assert_eq!(doc.text, vec!["one  three"]);
+    assert_eq!(doc.anchor, None);
+
+    let (doc, vim) = run("one two three", (0, 4), "vey$p");
+    assert_eq!(vim.register('"'), Some("two"));
+    assert_eq!(doc.text, vec!["one two threetwo"]);
+
+    let (doc, _) = run("a\nb\nc", (0, 0), "Vjd");
+    assert_eq!(doc.text, vec!["c"]);
+
+    let (doc, _) = run("one two", (0, 0), "vecONE<Esc>");
+    assert_eq!(doc.text, vec!["ONE two"]);
+
+    let (doc, vim) = run("one", (0, 0), "vl<Esc>");
+    assert_eq!(vim.mode, VimMode::Normal);
+    assert_eq!(doc.anchor, None);
+}
+
+#[test]
+fn normal_mode_cursor_stays_on_a_char() {
+    let (doc, _) = run("abc", (0, 0), "$l");
+    assert_eq!(cursor(&doc), (0, 2));
+
+    let (doc, _) = run("abc\nx", (0, 2), "j");
+    assert_eq!(cursor(&doc), (1, 0));
+
+    let (doc, _) = run("abc", (0, 0), "Ad<Esc>");
+    assert_eq!(cursor(&doc), (0, 3));
+}
+
+#[test]
+fn mode_indicator() {
+    let (_, vim) = run("a", (0, 0), "i");
+    assert_eq!(vim.mode_indicator(), "-- INSERT --");
+
+    let (_, vim) = run("a", (0, 0), "V");
+    assert_eq!(vim.mode_indicator(), "-- VISUAL LINE --");
+
+    let (_, vim) = run("a", (0, 0), "\"a2d");
+    assert_eq!(vim.mode_indicator(), "\"a2d");
+
+    let (_, vim) = run("a", (0, 0), ":wq");
+    assert_eq!(vim.mode_indicator(), ":wq");
+}
+
+#[test]
+fn ex_commands() {
+    let mut doc = DocHandler::from_text("a\nb\nc");
+    let mut vim = Vim::new();
+
+    assert_eq!(vim.feed_keys(&mut doc, ":2<CR>"), Outcome::Applied);
+    assert_eq!(cursor(&doc), (1, 0));
+
+    assert_eq!(vim.feed_keys(&mut doc, ":q!<CR>"), Outcome::Quit);
+
+    vim.feed_keys(&mut doc, "x");
+    assert!(matches!(vim.feed_keys(&mut doc, ":q<CR>"), Outcome::Failed(_)));
+    assert!(matches!(vim.feed_keys(&mut doc, ":nonsense<CR>"), Outcome::Failed(_)));
+
+    // another open doc with unsaved changes stops :q but not :q!
+    let mut doc = DocHandler::from_text("a");
+    vim.unsaved = vec![String::from("other.txt")];
+    assert_eq!(vim.feed_keys(&mut doc, ":q<CR>"), Outcome::Failed(String::from("E162: No write since last change for other.txt (add ! to override)")));
+    assert_eq!(vim.feed_keys(&mut doc, ":q!<CR>"), Outcome::Quit);
+}